@@ -13,7 +13,7 @@ use solana_client::{
 use solana_lite_rpc_accounts::account_store_interface::{
     AccountLoadingError, AccountStorageInterface,
 };
-use solana_lite_rpc_cluster_endpoints::geyser_grpc_connector::GrpcSourceConfig;
+use solana_lite_rpc_cluster_endpoints::grpc_sources::GrpcSourcesReceiver;
 use solana_lite_rpc_core::{
     commitment_utils::Commitment,
     structures::{
@@ -48,7 +48,7 @@ pub struct AccountsOnDemand {
 impl AccountsOnDemand {
     pub fn new(
         rpc_client: Arc<RpcClient>,
-        grpc_sources: Vec<GrpcSourceConfig>,
+        grpc_sources: GrpcSourcesReceiver,
         accounts_storage: Arc<dyn AccountStorageInterface>,
         account_notification_sender: Sender<AccountNotificationMessage>,
     ) -> Self {
@@ -292,4 +292,8 @@ impl AccountStorageInterface for AccountsOnDemand {
             .process_slot_data(slot, commitment)
             .await
     }
+
+    async fn rollback_slot(&self, slot: Slot) -> Vec<AccountData> {
+        self.accounts_storage.rollback_slot(slot).await
+    }
 }
@@ -4,8 +4,10 @@ use itertools::Itertools;
 use prometheus::{opts, register_int_gauge, IntGauge};
 use solana_lite_rpc_accounts::account_store_interface::AccountStorageInterface;
 use solana_lite_rpc_cluster_endpoints::{
-    geyser_grpc_connector::GrpcSourceConfig,
-    grpc::grpc_accounts_streaming::start_account_streaming_tasks,
+    grpc::grpc_accounts_streaming::{
+        create_grpc_multiplex_account_task, start_account_streaming_tasks,
+    },
+    grpc_sources::GrpcSourcesReceiver,
 };
 use solana_lite_rpc_core::{
     structures::{
@@ -33,7 +35,7 @@ pub struct SubscriptionManger {
 
 impl SubscriptionManger {
     pub fn new(
-        grpc_sources: Vec<GrpcSourceConfig>,
+        grpc_sources: GrpcSourcesReceiver,
         accounts_storage: Arc<dyn AccountStorageInterface>,
         account_notification_sender: Sender<AccountNotificationMessage>,
     ) -> Self {
@@ -79,10 +81,13 @@ impl SubscriptionManger {
 }
 
 pub fn create_grpc_account_streaming_tasks(
-    grpc_sources: Vec<GrpcSourceConfig>,
+    mut grpc_sources: GrpcSourcesReceiver,
     mut account_filter_watch: watch::Receiver<AccountFilters>,
 ) -> (AnyhowJoinHandle, AccountStream) {
     let (account_sender, accounts_stream) = broadcast::channel::<AccountNotificationMessage>(128);
+    // old and new subscriptions overlap while restarting, the multiplexer drops the duplicates
+    let (account_update_sx, account_update_rx) = tokio::sync::mpsc::channel(1024);
+    let jh_multiplex = create_grpc_multiplex_account_task(account_update_rx, account_sender);
 
     let jh: AnyhowJoinHandle = tokio::spawn(async move {
         match account_filter_watch.changed().await {
@@ -99,39 +104,54 @@ pub fn create_grpc_account_streaming_tasks(
         let has_started = Arc::new(tokio::sync::Notify::new());
 
         let mut current_tasks = grpc_sources
-            .iter()
+            .current()
+            .into_iter()
             .map(|grpc_config| {
                 start_account_streaming_tasks(
-                    grpc_config.clone(),
+                    grpc_config,
                     accounts_filters.clone(),
-                    account_sender.clone(),
+                    account_update_sx.clone(),
                     has_started.clone(),
                 )
             })
             .collect_vec();
 
-        while account_filter_watch.changed().await.is_ok() {
-            ON_DEMAND_SUBSCRIPTION_RESTARTED.inc();
-            // wait for a second to get all the accounts to update
-            tokio::time::sleep(Duration::from_secs(1)).await;
+        loop {
+            tokio::select! {
+                changed = account_filter_watch.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    ON_DEMAND_SUBSCRIPTION_RESTARTED.inc();
+                    // wait for a second to get all the accounts to update
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                _ = grpc_sources.changed() => {
+                    log::info!("grpc sources changed, restarting the accounts on demand subscriptions");
+                }
+            }
             let accounts_filters = account_filter_watch.borrow_and_update().clone();
 
             let has_started = Arc::new(tokio::sync::Notify::new());
 
             let new_tasks = grpc_sources
-                .iter()
+                .current()
+                .into_iter()
                 .map(|grpc_config| {
                     start_account_streaming_tasks(
-                        grpc_config.clone(),
+                        grpc_config,
                         accounts_filters.clone(),
-                        account_sender.clone(),
+                        account_update_sx.clone(),
                         has_started.clone(),
                     )
                 })
                 .collect_vec();
 
-            if let Err(_elapsed) =
-                tokio::time::timeout(Duration::from_secs(60), has_started.notified()).await
+            // without sources there is nothing to wait for
+            if !new_tasks.is_empty()
+                && tokio::time::timeout(Duration::from_secs(60), has_started.notified())
+                    .await
+                    .is_err()
             {
                 // check if time elapsed during restart is greater than 60ms
                 log::error!("Tried to restart the accounts on demand task but failed");
@@ -145,6 +165,7 @@ pub fn create_grpc_account_streaming_tasks(
             current_tasks = new_tasks;
         }
         log::error!("Accounts on demand task stopped");
+        jh_multiplex.abort();
         anyhow::bail!("Accounts on demand task stopped");
     });
 
@@ -4,7 +4,7 @@ use anyhow::bail;
 use itertools::Itertools;
 use prometheus::{opts, register_int_gauge, IntGauge};
 use solana_account_decoder::{UiAccount, UiDataSliceConfig};
-use solana_lite_rpc_core::types::BlockInfoStream;
+use solana_lite_rpc_core::types::{BlockInfoStream, OrphanedBlockStream};
 use solana_lite_rpc_core::{
     commitment_utils::Commitment,
     structures::{
@@ -30,6 +30,8 @@ lazy_static::lazy_static! {
        register_int_gauge!(opts!("literpc_accounts_updates_confirmed", "Account Updates by lite-rpc service")).unwrap();
     static ref ACCOUNT_UPDATES_FINALIZED: IntGauge =
        register_int_gauge!(opts!("literpc_accounts_updates_finalized", "Account Updates by lite-rpc service")).unwrap();
+    static ref ACCOUNT_UPDATES_ROLLED_BACK: IntGauge =
+       register_int_gauge!(opts!("literpc_accounts_updates_rolled_back", "Account Updates rolled back because their block got orphaned")).unwrap();
 
     static ref GET_PROGRAM_ACCOUNT_CALLED: IntGauge =
        register_int_gauge!(opts!("literpc_gpa_called", "Account Updates by lite-rpc service")).unwrap();
@@ -152,6 +154,7 @@ impl AccountService {
         &self,
         mut account_stream: AccountStream,
         mut blockinfo_stream: BlockInfoStream,
+        mut orphaned_block_stream: OrphanedBlockStream,
     ) -> Vec<AnyhowJoinHandle> {
         let this = self.clone();
         let processed_task = tokio::spawn(async move {
@@ -224,7 +227,35 @@ impl AccountService {
             bail!("Account Block Stream Broken");
         });
 
-        vec![processed_task, block_processing_task]
+        let this = self.clone();
+        let orphaned_block_task = tokio::spawn(async move {
+            loop {
+                match orphaned_block_stream.recv().await {
+                    Ok(orphaned_block) => {
+                        let updated_accounts =
+                            this.account_store.rollback_slot(orphaned_block.slot).await;
+                        ACCOUNT_UPDATES_ROLLED_BACK.add(updated_accounts.len() as i64);
+                        for data in updated_accounts {
+                            let _ =
+                                this.account_notification_sender
+                                    .send(AccountNotificationMessage {
+                                        data,
+                                        commitment: Commitment::Processed,
+                                    });
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(e)) => {
+                        log::error!("Orphaned Block Stream Lagged to update accounts by {}", e);
+                        continue;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                        bail!("Orphaned Block Stream Broken");
+                    }
+                }
+            }
+        });
+
+        vec![processed_task, block_processing_task, orphaned_block_task]
     }
 
     pub fn convert_account_data_to_ui_account(
@@ -33,4 +33,7 @@ pub trait AccountStorageInterface: Send + Sync {
     ) -> Option<Vec<AccountData>>;
 
     async fn process_slot_data(&self, slot: Slot, commitment: Commitment) -> Vec<AccountData>;
+
+    // drop the processed data of an orphaned slot, returns the accounts whose processed state changed
+    async fn rollback_slot(&self, slot: Slot) -> Vec<AccountData>;
 }
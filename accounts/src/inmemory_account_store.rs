@@ -423,6 +423,28 @@ impl AccountStorageInterface for InmemoryAccountStore {
             .cloned()
             .collect_vec()
     }
+
+    async fn rollback_slot(&self, slot: Slot) -> Vec<AccountData> {
+        if self.confirmed_slots_map.contains(&slot) {
+            // the slot has a confirmed block, the data is not from the orphaned one
+            return vec![];
+        }
+        self.account_store
+            .iter_mut()
+            .filter_map(|mut acc| {
+                let was_latest = acc
+                    .processed_accounts
+                    .last_key_value()
+                    .is_some_and(|(processed_slot, _)| *processed_slot == slot);
+                acc.processed_accounts.remove(&slot)?;
+                if was_latest {
+                    acc.get_account_data(Commitment::Processed)
+                } else {
+                    None
+                }
+            })
+            .collect_vec()
+    }
 }
 
 impl Default for InmemoryAccountStore {
@@ -878,4 +900,38 @@ mod tests {
         assert_eq!(p_3, Some(vec![]));
         assert_eq!(p_4, Some(vec![account_processed.clone()]));
     }
+
+    #[tokio::test]
+    pub async fn test_rollback_orphaned_slot() {
+        let store = InmemoryAccountStore::default();
+        let mut rng = rand::thread_rng();
+        let program = Pubkey::new_unique();
+        let pk1 = Pubkey::new_unique();
+
+        let account_data_0 = create_random_account(&mut rng, 0, pk1, program);
+        store
+            .initilize_or_update_account(account_data_0.clone())
+            .await;
+
+        let account_data_1 = create_random_account(&mut rng, 1, pk1, program);
+        let account_data_2 = create_random_account(&mut rng, 2, pk1, program);
+        store
+            .update_account(account_data_1.clone(), Commitment::Processed)
+            .await;
+        store
+            .update_account(account_data_2.clone(), Commitment::Processed)
+            .await;
+        store.process_slot_data(1, Commitment::Confirmed).await;
+
+        // confirmed slots are not rolled back
+        assert!(store.rollback_slot(1).await.is_empty());
+
+        // the processed state goes back to the confirmed one
+        assert_eq!(store.rollback_slot(2).await, vec![account_data_1.clone()]);
+        assert_eq!(
+            store.get_account(pk1, Commitment::Processed).await,
+            Ok(Some(account_data_1.clone()))
+        );
+        assert!(store.rollback_slot(2).await.is_empty());
+    }
 }
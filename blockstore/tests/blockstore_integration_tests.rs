@@ -2,12 +2,12 @@ use log::{debug, error, info, warn};
 use solana_lite_rpc_blockstore::block_stores::postgres::postgres_block_store_query::PostgresQueryBlockStore;
 use solana_lite_rpc_blockstore::block_stores::postgres::postgres_block_store_writer::PostgresBlockStore;
 use solana_lite_rpc_blockstore::block_stores::postgres::PostgresSessionConfig;
-use solana_lite_rpc_cluster_endpoints::geyser_grpc_connector::{
-    GrpcConnectionTimeouts, GrpcSourceConfig,
-};
+use solana_lite_rpc_cluster_endpoints::geyser_grpc_connector::GrpcConnectionTimeouts;
 use solana_lite_rpc_cluster_endpoints::grpc_multiplex::{
     create_grpc_multiplex_blocks_subscription, create_grpc_multiplex_processed_slots_subscription,
 };
+use solana_lite_rpc_cluster_endpoints::grpc_source_health::GrpcSourcesHealth;
+use solana_lite_rpc_cluster_endpoints::grpc_sources::{GrpcSourceSpec, GrpcSources};
 use solana_lite_rpc_core::structures::epoch::{EpochCache, EpochRef};
 use solana_lite_rpc_core::structures::produced_block::ProducedBlock;
 use solana_lite_rpc_core::structures::slot_notification::SlotNotification;
@@ -64,14 +64,19 @@ async fn storage_test() {
         receive_timeout: Duration::from_secs(5),
     };
 
-    let grpc_config = GrpcSourceConfig::new(grpc_addr, grpc_x_token, None, timeouts.clone());
-    let grpc_sources = vec![grpc_config];
+    let grpc_sources = GrpcSources::new(
+        vec![GrpcSourceSpec::new(grpc_addr, grpc_x_token)],
+        timeouts,
+    );
 
     let (slot_notifier, _jh_multiplex_slotstream) =
-        create_grpc_multiplex_processed_slots_subscription(grpc_sources.clone());
+        create_grpc_multiplex_processed_slots_subscription(grpc_sources.subscribe_blocks());
 
     let (blocks_notifier, _blockmeta_output_stream, _jh_multiplex_blockstream) =
-        create_grpc_multiplex_blocks_subscription(grpc_sources);
+        create_grpc_multiplex_blocks_subscription(
+            grpc_sources.subscribe_blocks(),
+            GrpcSourcesHealth::new(&[]),
+        );
 
     let (epoch_cache, _) = EpochCache::bootstrap_epoch(&rpc_client).await.unwrap();
 
@@ -0,0 +1,321 @@
+// Tracks the forks of the processed blocks using their parent links
+// processed blocks which do not make it into the confirmed chain are reported as orphaned
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::bail;
+use log::{debug, warn};
+use prometheus::{opts, register_int_counter, IntCounter};
+use solana_lite_rpc_core::structures::orphaned_block::OrphanedBlock;
+use solana_lite_rpc_core::structures::produced_block::ProducedBlock;
+use solana_lite_rpc_core::types::{BlockStream, OrphanedBlockStream};
+use solana_lite_rpc_core::AnyhowJoinHandle;
+use solana_sdk::clock::Slot;
+use solana_sdk::hash::Hash;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+lazy_static::lazy_static! {
+    static ref ORPHANED_BLOCKS: IntCounter =
+        register_int_counter!(opts!("literpc_orphaned_blocks", "Number of processed blocks which were orphaned")).unwrap();
+}
+
+// confirmed chain and orphaned blocks kept to judge late processed blocks
+const MAX_TRACKED_SLOTS: u64 = 1024;
+
+#[derive(Clone, Copy)]
+struct ChainLink {
+    blockhash: Hash,
+    parent_slot: Slot,
+    previous_blockhash: Hash,
+}
+
+impl ChainLink {
+    fn from_block(block: &ProducedBlock) -> Self {
+        Self {
+            blockhash: block.blockhash,
+            parent_slot: block.parent_slot,
+            previous_blockhash: block.previous_blockhash,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ForkTracker {
+    // processed blocks above the confirmed tip, not decided yet
+    pending: BTreeMap<Slot, Vec<ProducedBlock>>,
+    // recent confirmed chain
+    confirmed: BTreeMap<Slot, ChainLink>,
+    orphaned: HashMap<Hash, Slot>,
+    confirmed_tip: Slot,
+}
+
+impl ForkTracker {
+    /// returns the processed blocks which got orphaned by this block
+    pub fn process_block(&mut self, block: &ProducedBlock) -> Vec<OrphanedBlock> {
+        if block.commitment_config.is_processed() {
+            self.process_processed_block(block)
+        } else {
+            self.process_confirmed_block(block)
+        }
+    }
+
+    fn process_processed_block(&mut self, block: &ProducedBlock) -> Vec<OrphanedBlock> {
+        if self.orphaned.contains_key(&block.previous_blockhash)
+            || self.is_off_confirmed_chain(block)
+        {
+            return vec![self.orphan(block)];
+        }
+        if block.slot <= self.confirmed_tip {
+            // part of the confirmed chain or too old to judge
+            return vec![];
+        }
+        let blocks = self.pending.entry(block.slot).or_default();
+        if !blocks.iter().any(|b| b.blockhash == block.blockhash) {
+            blocks.push(block.clone());
+        }
+        // without confirmed blocks nothing gets decided, keep the recent slots only
+        let keep_from = block.slot.saturating_sub(MAX_TRACKED_SLOTS);
+        while let Some(entry) = self.pending.first_entry() {
+            if *entry.key() >= keep_from {
+                break;
+            }
+            debug!(
+                "dropping undecided processed blocks at slot {}",
+                entry.key()
+            );
+            entry.remove();
+        }
+        vec![]
+    }
+
+    // late processed block which the confirmed chain passed by
+    fn is_off_confirmed_chain(&self, block: &ProducedBlock) -> bool {
+        if block.slot > self.confirmed_tip {
+            return false;
+        }
+        match self.confirmed.range(block.slot..).next() {
+            Some((slot, link)) if *slot == block.slot => link.blockhash != block.blockhash,
+            // the chain skipped the slot
+            Some((_, link)) => link.parent_slot < block.slot,
+            None => false,
+        }
+    }
+
+    fn process_confirmed_block(&mut self, block: &ProducedBlock) -> Vec<OrphanedBlock> {
+        if block.slot <= self.confirmed_tip {
+            // finalized after confirmed or out of order
+            self.confirmed
+                .entry(block.slot)
+                .or_insert_with(|| ChainLink::from_block(block));
+            return vec![];
+        }
+
+        // walk down the new chain to the confirmed tip, pending blocks of the known part are decided
+        let mut chain = vec![(block.slot, ChainLink::from_block(block))];
+        let mut chain_hashes = HashSet::from([block.blockhash]);
+        let (mut slot, mut blockhash) = (block.parent_slot, block.previous_blockhash);
+        let decided_from = loop {
+            if slot <= self.confirmed_tip {
+                break slot + 1;
+            }
+            chain_hashes.insert(blockhash);
+            let link = self
+                .confirmed
+                .get(&slot)
+                .filter(|link| link.blockhash == blockhash)
+                .copied()
+                .or_else(|| {
+                    self.pending
+                        .get(&slot)
+                        .and_then(|blocks| blocks.iter().find(|b| b.blockhash == blockhash))
+                        .map(ChainLink::from_block)
+                });
+            let Some(link) = link else {
+                // gap, the chain below is unknown
+                break slot;
+            };
+            chain.push((slot, link));
+            (slot, blockhash) = (link.parent_slot, link.previous_blockhash);
+        };
+
+        let mut orphaned_blocks = vec![];
+        let decided_slots = self
+            .pending
+            .range(..=block.slot)
+            .map(|(slot, _)| *slot)
+            .collect::<Vec<_>>();
+        for slot in decided_slots {
+            let blocks = self.pending.remove(&slot).unwrap_or_default();
+            if slot < decided_from {
+                debug!("cannot decide the fork of processed block at slot {slot}, chain has a gap");
+                continue;
+            }
+            for pending_block in blocks {
+                if !chain_hashes.contains(&pending_block.blockhash) {
+                    orphaned_blocks.push(self.orphan(&pending_block));
+                }
+            }
+        }
+
+        // pending blocks above the new tip which descend from an abandoned fork
+        let pending_slots = self.pending.keys().copied().collect::<Vec<_>>();
+        for slot in pending_slots {
+            let blocks = self.pending.remove(&slot).unwrap_or_default();
+            let mut kept = vec![];
+            for pending_block in blocks {
+                let parent_off_chain = (decided_from..=block.slot)
+                    .contains(&pending_block.parent_slot)
+                    && !chain_hashes.contains(&pending_block.previous_blockhash);
+                if parent_off_chain
+                    || self
+                        .orphaned
+                        .contains_key(&pending_block.previous_blockhash)
+                {
+                    orphaned_blocks.push(self.orphan(&pending_block));
+                } else {
+                    kept.push(pending_block);
+                }
+            }
+            if !kept.is_empty() {
+                self.pending.insert(slot, kept);
+            }
+        }
+
+        self.confirmed.extend(chain);
+        self.confirmed_tip = block.slot;
+        let keep_from = block.slot.saturating_sub(MAX_TRACKED_SLOTS);
+        self.confirmed = self.confirmed.split_off(&keep_from);
+        self.orphaned.retain(|_, slot| *slot >= keep_from);
+
+        orphaned_blocks
+    }
+
+    fn orphan(&mut self, block: &ProducedBlock) -> OrphanedBlock {
+        ORPHANED_BLOCKS.inc();
+        self.orphaned.insert(block.blockhash, block.slot);
+        OrphanedBlock {
+            slot: block.slot,
+            blockhash: block.blockhash,
+            parent_slot: block.parent_slot,
+            signatures: block.transactions.iter().map(|tx| tx.signature).collect(),
+        }
+    }
+}
+
+/// stream of the processed blocks which were abandoned for another fork
+pub fn create_fork_tracker_task(
+    mut block_stream: BlockStream,
+) -> (AnyhowJoinHandle, OrphanedBlockStream) {
+    let (orphaned_block_sender, orphaned_block_stream) = broadcast::channel(64);
+    let jh = tokio::spawn(async move {
+        let mut fork_tracker = ForkTracker::default();
+        loop {
+            let block = match block_stream.recv().await {
+                Ok(block) => block,
+                Err(RecvError::Lagged(blocks_lagged)) => {
+                    warn!("Fork tracker lagged {} blocks - continue", blocks_lagged);
+                    continue;
+                }
+                Err(RecvError::Closed) => {
+                    bail!("Block stream has been closed - abort");
+                }
+            };
+            for orphaned_block in fork_tracker.process_block(&block) {
+                warn!(
+                    "Processed block {}#{} got orphaned",
+                    orphaned_block.slot, orphaned_block.blockhash
+                );
+                // there might be no subscribers
+                let _ = orphaned_block_sender.send(orphaned_block);
+            }
+        }
+    });
+    (jh, orphaned_block_stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_lite_rpc_core::structures::produced_block::ProducedBlockInner;
+    use solana_sdk::commitment_config::CommitmentConfig;
+
+    fn block(slot: Slot, parent: &ProducedBlock) -> ProducedBlock {
+        produced_block(slot, parent.slot, parent.blockhash)
+    }
+
+    fn produced_block(slot: Slot, parent_slot: Slot, previous_blockhash: Hash) -> ProducedBlock {
+        let inner = ProducedBlockInner {
+            transactions: vec![],
+            leader_id: None,
+            blockhash: Hash::new_unique(),
+            block_height: slot,
+            slot,
+            parent_slot,
+            block_time: 0,
+            previous_blockhash,
+            rewards: None,
+        };
+        ProducedBlock::new(inner, CommitmentConfig::processed())
+    }
+
+    fn orphaned_slots(orphaned_blocks: Vec<OrphanedBlock>) -> Vec<Slot> {
+        orphaned_blocks.iter().map(|b| b.slot).collect()
+    }
+
+    #[test]
+    fn abandoned_fork_is_orphaned() {
+        let mut fork_tracker = ForkTracker::default();
+        let root = produced_block(10, 9, Hash::new_unique());
+        assert!(fork_tracker.process_block(&root).is_empty());
+        assert!(fork_tracker
+            .process_block(&root.to_confirmed_block())
+            .is_empty());
+
+        // fork a: 11 <- 13, fork b: 12
+        let a1 = block(11, &root);
+        let b1 = block(12, &root);
+        let a2 = block(13, &a1);
+        for processed in [&a1, &b1, &a2] {
+            assert!(fork_tracker.process_block(processed).is_empty());
+        }
+
+        // confirming 12 abandons 11 and its child 13
+        assert_eq!(
+            orphaned_slots(fork_tracker.process_block(&b1.to_confirmed_block())),
+            vec![11, 13]
+        );
+        // the finalized block does not change anything
+        assert!(fork_tracker
+            .process_block(&b1.to_finalized_block())
+            .is_empty());
+
+        // late blocks of the abandoned fork
+        assert_eq!(
+            orphaned_slots(fork_tracker.process_block(&block(14, &a2))),
+            vec![14]
+        );
+        assert_eq!(
+            orphaned_slots(fork_tracker.process_block(&block(11, &root))),
+            vec![11]
+        );
+
+        // the confirmed fork goes on
+        let b2 = block(13, &b1);
+        assert!(fork_tracker.process_block(&b2).is_empty());
+        assert!(fork_tracker
+            .process_block(&b2.to_confirmed_block())
+            .is_empty());
+    }
+
+    #[test]
+    fn undecided_blocks_are_capped_without_confirmed_blocks() {
+        let mut fork_tracker = ForkTracker::default();
+        let mut parent = produced_block(10, 9, Hash::new_unique());
+        for _ in 0..(2 * MAX_TRACKED_SLOTS) {
+            assert!(fork_tracker.process_block(&parent).is_empty());
+            parent = block(parent.slot + 1, &parent);
+        }
+        assert_eq!(fork_tracker.pending.len() as u64, MAX_TRACKED_SLOTS + 1);
+    }
+}
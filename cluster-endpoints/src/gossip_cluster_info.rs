@@ -0,0 +1,135 @@
+// Keeps the cluster info current by joining gossip as a spy node
+// contact infos are applied as soon as gossip delivers them instead of waiting for the next getClusterNodes poll
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::info;
+use prometheus::{opts, register_int_counter, register_int_gauge, IntCounter, IntGauge};
+use solana_client::connection_cache::Protocol;
+use solana_gossip::cluster_info::ClusterInfo as GossipClusterInfo;
+use solana_gossip::gossip_service::make_gossip_node;
+use solana_gossip::legacy_contact_info::LegacyContactInfo;
+use solana_lite_rpc_core::stores::cluster_info_store::ClusterInfo;
+use solana_lite_rpc_core::AnyhowJoinHandle;
+use solana_rpc_client_api::response::RpcContactInfo;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_streamer::socket::SocketAddrSpace;
+
+lazy_static::lazy_static! {
+    static ref GOSSIP_CLUSTER_NODES: IntGauge =
+        register_int_gauge!(opts!("literpc_gossip_cluster_nodes", "Number of cluster nodes seen in gossip")).unwrap();
+    static ref GOSSIP_CONTACT_INFO_UPDATES: IntCounter =
+        register_int_counter!(opts!("literpc_gossip_contact_info_updates", "Number of contact infos applied from gossip")).unwrap();
+}
+
+const GOSSIP_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// stops the gossip threads when the task is dropped or aborted
+struct ExitOnDrop(Arc<AtomicBool>);
+
+impl Drop for ExitOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// entrypoint is host:port of a gossip entrypoint of the cluster
+/// the shred version is fetched from the entrypoint if not given
+pub fn start_gossip_cluster_info_task(
+    entrypoint: String,
+    shred_version: Option<u16>,
+    cluster_info: ClusterInfo,
+) -> AnyhowJoinHandle {
+    tokio::spawn(async move {
+        let entrypoint_addr = solana_net_utils::parse_host_port(&entrypoint)
+            .map_err(|e| anyhow::anyhow!("invalid gossip entrypoint {entrypoint}: {e}"))?;
+        let shred_version = match shred_version {
+            Some(shred_version) => shred_version,
+            None => tokio::task::spawn_blocking(move || {
+                solana_net_utils::get_cluster_shred_version(&entrypoint_addr)
+            })
+            .await?
+            .map_err(|e| anyhow::anyhow!("failed to get shred version from {entrypoint}: {e}"))?,
+        };
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let _exit_on_drop = ExitOnDrop(exit.clone());
+        let (_gossip_service, _ip_echo, gossip) = make_gossip_node(
+            Keypair::new(),
+            Some(&entrypoint_addr),
+            exit,
+            None,
+            shred_version,
+            false,
+            SocketAddrSpace::Unspecified,
+        );
+        info!(
+            "Joined gossip at {} as spy node {} with shred version {}",
+            entrypoint,
+            gossip.id(),
+            shred_version
+        );
+
+        // gossip timestamp of the last applied contact info per node
+        let mut applied = HashMap::<Pubkey, u64>::new();
+        let mut interval = tokio::time::interval(GOSSIP_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let peers = gossip.all_peers();
+            let mut in_gossip = HashSet::with_capacity(peers.len());
+            for (contact_info, local_timestamp) in peers {
+                let pubkey = *contact_info.pubkey();
+                if pubkey == gossip.id() || contact_info.shred_version() != shred_version {
+                    continue;
+                }
+                in_gossip.insert(pubkey);
+                // unchanged contact infos only keep their precedence over the rpc snapshot
+                if applied.insert(pubkey, local_timestamp) == Some(local_timestamp)
+                    && cluster_info.refresh_from_gossip(&pubkey)
+                {
+                    continue;
+                }
+                cluster_info.update_from_gossip(
+                    pubkey,
+                    rpc_contact_info(&gossip, &contact_info),
+                    contact_info.tpu_forwards(Protocol::QUIC).ok(),
+                    contact_info.wallclock(),
+                );
+                GOSSIP_CONTACT_INFO_UPDATES.inc();
+            }
+            applied.retain(|pubkey, _| {
+                let still_in_gossip = in_gossip.contains(pubkey);
+                if !still_in_gossip {
+                    cluster_info.remove_from_gossip(pubkey);
+                }
+                still_in_gossip
+            });
+            GOSSIP_CLUSTER_NODES.set(in_gossip.len() as i64);
+        }
+    })
+}
+
+// same shape as the getClusterNodes response
+fn rpc_contact_info(
+    gossip: &GossipClusterInfo,
+    contact_info: &LegacyContactInfo,
+) -> RpcContactInfo {
+    let version = gossip.get_node_version(contact_info.pubkey());
+    RpcContactInfo {
+        pubkey: contact_info.pubkey().to_string(),
+        gossip: contact_info.gossip().ok(),
+        tpu: contact_info.tpu(Protocol::UDP).ok(),
+        tpu_quic: contact_info.tpu(Protocol::QUIC).ok(),
+        rpc: contact_info.rpc().ok(),
+        pubsub: contact_info.rpc_pubsub().ok(),
+        version: version
+            .as_ref()
+            .map(|v| format!("{}.{}.{}", v.major, v.minor, v.patch)),
+        feature_set: version.map(|v| v.feature_set),
+        shred_version: Some(contact_info.shred_version()),
+    }
+}
@@ -20,7 +20,12 @@ use solana_lite_rpc_core::{
     },
     AnyhowJoinHandle,
 };
-use solana_sdk::{account::Account, pubkey::Pubkey};
+use solana_sdk::{
+    account::Account,
+    hash::{hashv, Hash},
+    pubkey::Pubkey,
+    slot_history::Slot,
+};
 use tokio::sync::Notify;
 use yellowstone_grpc_proto::geyser::{
     subscribe_request_filter_accounts_filter::Filter,
@@ -30,13 +35,129 @@ use yellowstone_grpc_proto::geyser::{
 };
 use yellowstone_grpc_proto::tonic::service::Interceptor;
 
+use crate::grpc_sources::GrpcSourcesReceiver;
+
+// updates of a source lagging more than this behind the newest update are dropped
+const MAX_ACCOUNT_UPDATE_LAG_SLOTS: Slot = 512;
+
+/// account update as received from one of the grpc sources
+pub struct SourceAccountUpdate {
+    // grpc address of the source
+    pub source: Arc<str>,
+    pub notification: AccountNotificationMessage,
+    pub write_version: u64,
+}
+
+struct DeliveredUpdate {
+    slot: Slot,
+    source: Arc<str>,
+    write_version: u64,
+    // account states delivered in the slot
+    delivered: HashSet<Hash>,
+}
+
+/// keeps the last delivered version of each account to merge the updates of all sources
+#[derive(Default)]
+pub struct AccountUpdateDeduplicator {
+    latest: HashMap<Pubkey, DeliveredUpdate>,
+    newest_slot: Slot,
+}
+
+fn account_hash(account: &Account) -> Hash {
+    hashv(&[
+        &account.lamports.to_le_bytes(),
+        account.owner.as_ref(),
+        &[account.executable as u8],
+        &account.rent_epoch.to_le_bytes(),
+        &account.data,
+    ])
+}
+
+impl AccountUpdateDeduplicator {
+    /// true if the update is newer than the last delivered version of the account
+    /// write versions are counted per validator and only compared within one source,
+    /// within a slot the other sources deliver the account states not delivered yet
+    pub fn is_newer(&mut self, update: &SourceAccountUpdate) -> bool {
+        let AccountData {
+            pubkey,
+            account,
+            updated_slot,
+        } = &update.notification.data;
+        if updated_slot + MAX_ACCOUNT_UPDATE_LAG_SLOTS < self.newest_slot {
+            return false;
+        }
+        let hash = account_hash(account);
+        match self.latest.get_mut(pubkey) {
+            Some(delivered) if *updated_slot < delivered.slot => return false,
+            Some(delivered) if *updated_slot == delivered.slot => {
+                let is_newer = if delivered.source == update.source {
+                    update.write_version > delivered.write_version
+                } else {
+                    // a source missing a write of the slot is completed by the others
+                    !delivered.delivered.contains(&hash)
+                };
+                if !is_newer {
+                    return false;
+                }
+                delivered.source = update.source.clone();
+                delivered.write_version = update.write_version;
+                delivered.delivered.insert(hash);
+            }
+            _ => {
+                self.latest.insert(
+                    *pubkey,
+                    DeliveredUpdate {
+                        slot: *updated_slot,
+                        source: update.source.clone(),
+                        write_version: update.write_version,
+                        delivered: HashSet::from([hash]),
+                    },
+                );
+            }
+        }
+
+        if *updated_slot > self.newest_slot {
+            // forget the accounts which cannot be raced by a lagging source anymore
+            if updated_slot / MAX_ACCOUNT_UPDATE_LAG_SLOTS
+                > self.newest_slot / MAX_ACCOUNT_UPDATE_LAG_SLOTS
+            {
+                let oldest_slot = updated_slot.saturating_sub(MAX_ACCOUNT_UPDATE_LAG_SLOTS);
+                self.latest
+                    .retain(|_, delivered| delivered.slot >= oldest_slot);
+            }
+            self.newest_slot = *updated_slot;
+        }
+        true
+    }
+}
+
+/// merges the account updates of all sources, each update is delivered once and in order
+pub fn create_grpc_multiplex_account_task(
+    mut account_update_rx: tokio::sync::mpsc::Receiver<SourceAccountUpdate>,
+    account_stream_sx: tokio::sync::broadcast::Sender<AccountNotificationMessage>,
+) -> AnyhowJoinHandle {
+    tokio::spawn(async move {
+        let mut deduplicator = AccountUpdateDeduplicator::default();
+        while let Some(update) = account_update_rx.recv().await {
+            if !deduplicator.is_newer(&update) {
+                continue;
+            }
+            if account_stream_sx.send(update.notification).is_err() {
+                anyhow::bail!("Account stream broken");
+            }
+        }
+        anyhow::bail!("Account update channel closed")
+    })
+}
+
 pub fn start_account_streaming_tasks(
     grpc_config: GrpcSourceConfig,
     accounts_filters: AccountFilters,
-    account_stream_sx: tokio::sync::broadcast::Sender<AccountNotificationMessage>,
+    account_update_sx: tokio::sync::mpsc::Sender<SourceAccountUpdate>,
     has_started: Arc<Notify>,
 ) -> AnyhowJoinHandle {
     tokio::spawn(async move {
+        let source: Arc<str> = grpc_config.grpc_addr.as_str().into();
         'main_loop: loop {
             let processed_commitment = yellowstone_grpc_proto::geyser::CommitmentLevel::Processed;
 
@@ -109,9 +230,20 @@ pub fn start_account_streaming_tasks(
                 ..Default::default()
             };
 
-            let mut client = create_connection(&grpc_config).await?;
+            let mut client = match create_connection(&grpc_config).await {
+                Ok(client) => client,
+                Err(e) => {
+                    log::error!("{e:?} (retrying)");
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    continue 'main_loop;
+                }
+            };
 
-            let account_stream = client.subscribe_once(program_subscription).await.unwrap();
+            let Ok(account_stream) = client.subscribe_once(program_subscription).await else {
+                log::error!("Grpc account subscription failed (retrying)");
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                continue 'main_loop;
+            };
 
             // each account subscription batch will require individual stream
             let mut subscriptions = vec![account_stream];
@@ -131,8 +263,6 @@ pub fn start_account_streaming_tasks(
                         filters: vec![],
                     },
                 );
-                let mut client = create_connection(&grpc_config).await?;
-
                 let account_request = SubscribeRequest {
                     accounts: accounts_subscription,
                     accounts_data_slice: Default::default(),
@@ -140,7 +270,18 @@ pub fn start_account_streaming_tasks(
                     ..Default::default()
                 };
 
-                let account_stream = client.subscribe_once(account_request).await.unwrap();
+                let account_stream = match create_connection(&grpc_config).await {
+                    Ok(mut client) => client.subscribe_once(account_request).await.ok(),
+                    Err(e) => {
+                        log::error!("{e:?}");
+                        None
+                    }
+                };
+                let Some(account_stream) = account_stream else {
+                    log::error!("Grpc account subscription failed (retrying)");
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    continue 'main_loop;
+                };
                 subscriptions.push(account_stream);
             }
             let mut merged_stream = subscriptions.merge();
@@ -168,6 +309,7 @@ pub fn start_account_streaming_tasks(
                                 .owner
                                 .try_into()
                                 .expect("owner pubkey should be deserializable");
+                            let write_version = account_data.write_version;
                             let notification = AccountNotificationMessage {
                                 data: AccountData {
                                     pubkey: Pubkey::new_from_array(account_pk_bytes),
@@ -183,7 +325,12 @@ pub fn start_account_streaming_tasks(
                                 // TODO update with processed commitment / check above
                                 commitment: Commitment::Processed,
                             };
-                            if account_stream_sx.send(notification).is_err() {
+                            let update = SourceAccountUpdate {
+                                source: source.clone(),
+                                notification,
+                                write_version,
+                            };
+                            if account_update_sx.send(update).await.is_err() {
                                 // non recoverable, i.e the whole stream is being restarted
                                 log::error!("Account stream broken, breaking from main loop");
                                 break 'main_loop;
@@ -224,21 +371,30 @@ async fn create_connection(
     .map_err(|e| anyhow!("Failed to connect to grpc source: {e:?}"))
 }
 
+/// a change of the sources restarts the source tasks
 pub fn create_grpc_account_streaming(
-    grpc_sources: Vec<GrpcSourceConfig>,
+    mut grpc_sources: GrpcSourcesReceiver,
     accounts_filters: AccountFilters,
     account_stream_sx: tokio::sync::broadcast::Sender<AccountNotificationMessage>,
     notify_abort: Arc<Notify>,
 ) -> AnyhowJoinHandle {
     let jh: AnyhowJoinHandle = tokio::spawn(async move {
+        // outlives the restarts of the sources so that no update is delivered twice
+        let (account_update_sx, account_update_rx) = tokio::sync::mpsc::channel(1024);
+        let jh_multiplex =
+            create_grpc_multiplex_account_task(account_update_rx, account_stream_sx.clone());
         loop {
+            if jh_multiplex.is_finished() {
+                anyhow::bail!("Account multiplexer exited unexpectedly");
+            }
             let jhs = grpc_sources
-                .iter()
+                .current()
+                .into_iter()
                 .map(|grpc_config| {
                     start_account_streaming_tasks(
-                        grpc_config.clone(),
+                        grpc_config,
                         accounts_filters.clone(),
-                        account_stream_sx.clone(),
+                        account_update_sx.clone(),
                         Arc::new(Notify::new()),
                     )
                 })
@@ -266,6 +422,10 @@ pub fn create_grpc_account_streaming(
                         log::debug!("Account stream aborted");
                         break;
                     }
+                    _ = grpc_sources.changed() => {
+                        log::info!("grpc sources changed - restarting account subscription");
+                        break;
+                    }
                 }
             }
             jhs.iter().for_each(|x| x.abort());
@@ -274,3 +434,92 @@ pub fn create_grpc_account_streaming(
 
     jh
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(
+        source: &str,
+        pubkey: Pubkey,
+        slot: Slot,
+        write_version: u64,
+        lamports: u64,
+    ) -> SourceAccountUpdate {
+        SourceAccountUpdate {
+            source: source.into(),
+            notification: AccountNotificationMessage {
+                data: AccountData {
+                    pubkey,
+                    account: Arc::new(Account {
+                        lamports,
+                        ..Default::default()
+                    }),
+                    updated_slot: slot,
+                },
+                commitment: Commitment::Processed,
+            },
+            write_version,
+        }
+    }
+
+    #[test]
+    fn deduplicate_account_updates_of_all_sources() {
+        let mut deduplicator = AccountUpdateDeduplicator::default();
+        let pubkey = Pubkey::new_unique();
+
+        assert!(deduplicator.is_newer(&update("a", pubkey, 10, 100, 1)));
+        // same update from a second source with its own write version counter
+        assert!(!deduplicator.is_newer(&update("b", pubkey, 10, 250, 1)));
+        // later write in the same slot
+        assert!(deduplicator.is_newer(&update("a", pubkey, 10, 101, 2)));
+        // lagging source
+        assert!(!deduplicator.is_newer(&update("b", pubkey, 9, 300, 3)));
+        assert!(deduplicator.is_newer(&update("b", pubkey, 11, 1, 3)));
+
+        // other accounts are independent
+        let other = Pubkey::new_unique();
+        assert!(deduplicator.is_newer(&update("a", other, 9, 1, 1)));
+
+        // too far behind the newest update
+        assert!(deduplicator.is_newer(&update("a", pubkey, 2000, 1, 4)));
+        assert!(!deduplicator.is_newer(&update("a", other, 1000, 1, 2)));
+    }
+
+    #[test]
+    fn write_versions_of_sources_with_offset_counters_are_not_compared() {
+        let mut deduplicator = AccountUpdateDeduplicator::default();
+        let pubkey = Pubkey::new_unique();
+
+        // source b counts far ahead of source a
+        assert!(deduplicator.is_newer(&update("a", pubkey, 10, 100, 1)));
+        assert!(!deduplicator.is_newer(&update("b", pubkey, 10, 5_000, 1)));
+        assert!(deduplicator.is_newer(&update("a", pubkey, 10, 101, 2)));
+        // the later write of the slot as seen by b does not replace the one of a
+        assert!(!deduplicator.is_newer(&update("b", pubkey, 10, 5_001, 2)));
+
+        // b is first in the next slot, a lower write version of a does not go through
+        assert!(deduplicator.is_newer(&update("b", pubkey, 11, 5_002, 3)));
+        assert!(!deduplicator.is_newer(&update("a", pubkey, 11, 102, 3)));
+        assert!(deduplicator.is_newer(&update("b", pubkey, 11, 5_003, 4)));
+        assert!(!deduplicator.is_newer(&update("b", pubkey, 11, 5_002, 3)));
+    }
+
+    #[test]
+    fn writes_missed_by_the_first_source_come_from_the_others() {
+        let mut deduplicator = AccountUpdateDeduplicator::default();
+        let pubkey = Pubkey::new_unique();
+
+        assert!(deduplicator.is_newer(&update("a", pubkey, 10, 100, 1)));
+        assert!(!deduplicator.is_newer(&update("b", pubkey, 10, 5_000, 1)));
+        // a drops the second write of the slot, b delivers it
+        assert!(deduplicator.is_newer(&update("b", pubkey, 10, 5_001, 2)));
+        assert!(deduplicator.is_newer(&update("b", pubkey, 10, 5_002, 3)));
+        // a catching up does not repeat the writes delivered by b
+        assert!(!deduplicator.is_newer(&update("a", pubkey, 10, 102, 3)));
+        assert!(deduplicator.is_newer(&update("a", pubkey, 10, 103, 4)));
+        assert!(!deduplicator.is_newer(&update("b", pubkey, 10, 5_003, 4)));
+        // the source of the last delivery may go back to an earlier state of the slot
+        assert!(deduplicator.is_newer(&update("a", pubkey, 10, 104, 1)));
+    }
+}
@@ -20,31 +20,47 @@ use tracing::debug_span;
 use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
 use yellowstone_grpc_proto::geyser::SubscribeUpdate;
 
+use crate::grpc_source_health::GrpcSourcesHealth;
+use crate::grpc_sources::GrpcSourcesReceiver;
 use crate::grpc_subscription::from_grpc_block_update;
 
 /// connect to all sources provided using transparent autoconnection task
 /// shutdown handling:
 /// - task will shutdown of the receiver side of block_sender gets closed
 /// - will also shutdown the grpc autoconnection task(s)
+/// - blocks of sources dropped from the fast path by the health monitor are ignored
 fn create_grpc_multiplex_processed_block_task(
     grpc_sources: &Vec<GrpcSourceConfig>,
+    sources_health: GrpcSourcesHealth,
     block_sender: tokio::sync::mpsc::Sender<ProducedBlock>,
     mut exit_notify: broadcast::Receiver<()>,
 ) -> Vec<JoinHandle<()>> {
     const COMMITMENT_CONFIG: CommitmentConfig = CommitmentConfig::processed();
 
-    let (autoconnect_tx, mut blocks_rx) = tokio::sync::mpsc::channel(10);
+    // messages tagged with the index of their source
+    let (tagged_tx, mut blocks_rx) = tokio::sync::mpsc::channel::<(usize, Message)>(10);
     let mut tasks = vec![];
 
-    for grpc_source in grpc_sources {
+    for (source_index, grpc_source) in grpc_sources.iter().enumerate() {
+        let (autoconnect_tx, mut source_rx) = tokio::sync::mpsc::channel(10);
         let task = create_geyser_autoconnection_task_with_mpsc(
             grpc_source.clone(),
             GeyserFilter(COMMITMENT_CONFIG).blocks_and_txs(),
-            autoconnect_tx.clone(),
+            autoconnect_tx,
             exit_notify.resubscribe(),
         );
         tasks.push(task);
+
+        let tagged_tx = tagged_tx.clone();
+        tasks.push(tokio::task::spawn(async move {
+            while let Some(message) = source_rx.recv().await {
+                if tagged_tx.send((source_index, message)).await.is_err() {
+                    break;
+                }
+            }
+        }));
     }
+    drop(tagged_tx);
 
     let jh_merging_streams = tokio::task::spawn(async move {
         let mut slots_processed = BTreeSet::<u64>::new();
@@ -69,10 +85,13 @@ fn create_grpc_multiplex_processed_block_task(
                 }
             };
             match blocks_rx_result {
-                Some(Message::GeyserSubscribeUpdate(subscribe_update)) => {
+                Some((source_index, Message::GeyserSubscribeUpdate(subscribe_update))) => {
                     // note: avoid mapping of full block as long as possible
                     let extracted_slot = extract_slot_from_yellowstone_update(&subscribe_update);
                     if let Some(slot) = extracted_slot {
+                        if !sources_health.observe_block(source_index, slot) {
+                            continue 'recv_loop;
+                        }
                         // check if the slot is in the map, if not check if the container is half full and the slot in question is older than the lowest value
                         // it means that the slot is too old to process
                         if slots_processed.contains(&slot) {
@@ -111,8 +130,9 @@ fn create_grpc_multiplex_processed_block_task(
                         }
                     }
                 }
-                Some(Message::Connecting(attempt)) => {
+                Some((source_index, Message::Connecting(attempt))) => {
                     if attempt > 1 {
+                        sources_health.observe_reconnect(source_index);
                         warn!(
                             "Multiplexed geyser stream performs reconnect attempt {}",
                             attempt
@@ -228,24 +248,27 @@ fn create_grpc_multiplex_block_info_task(
     tasks
 }
 
+fn log_grpc_sources(stream: &str, grpc_sources: &[GrpcSourceConfig]) {
+    info!("Setup grpc multiplexed {} connection...", stream);
+    if grpc_sources.is_empty() {
+        info!("- no grpc connection configured");
+    }
+    for grpc_source in grpc_sources {
+        info!("- connection to {}", grpc_source);
+    }
+}
+
 /// connect to multiple grpc sources to consume processed blocks and block status update
 /// emits full blocks for commitment levels processed, confirmed, finalized in that order
-/// the channel must never be closed
+/// the channel must never be closed, a change of the sources restarts the multiplexer
 pub fn create_grpc_multiplex_blocks_subscription(
-    grpc_sources: Vec<GrpcSourceConfig>,
+    mut grpc_sources: GrpcSourcesReceiver,
+    sources_health: GrpcSourcesHealth,
 ) -> (
     Receiver<ProducedBlock>,
     Receiver<BlockInfo>,
     AnyhowJoinHandle,
 ) {
-    info!("Setup grpc multiplexed blocks connection...");
-    if grpc_sources.is_empty() {
-        info!("- no grpc connection configured");
-    }
-    for grpc_source in &grpc_sources {
-        info!("- connection to {}", grpc_source);
-    }
-
     // return value is the broadcast receiver
     // must NEVER be closed from inside this method
     let (producedblock_sender, blocks_output_stream) =
@@ -260,6 +283,10 @@ pub fn create_grpc_multiplex_blocks_subscription(
     // task MUST not terminate but might be aborted from outside
     let jh_block_emitter_task = tokio::task::spawn(async move {
         loop {
+            let current_sources = grpc_sources.current();
+            log_grpc_sources("blocks", &current_sources);
+            sources_health.set_sources(&current_sources);
+
             // channels must NEVER GET CLOSED (unless full restart of multiplexer)
             let (processed_block_sender, mut processed_block_reciever) =
                 tokio::sync::mpsc::channel::<ProducedBlock>(10); // experiemental
@@ -284,7 +311,8 @@ pub fn create_grpc_multiplex_blocks_subscription(
             let mut task_list: Vec<JoinHandle<()>> = vec![];
 
             let processed_blocks_tasks = create_grpc_multiplex_processed_block_task(
-                &grpc_sources,
+                &current_sources,
+                sources_health.clone(),
                 processed_block_sender.clone(),
                 exit_notify.resubscribe(),
             );
@@ -293,21 +321,21 @@ pub fn create_grpc_multiplex_blocks_subscription(
             // TODO apply same pattern as in create_grpc_multiplex_processed_block_task
 
             let jh_meta_task_processed = create_grpc_multiplex_block_info_task(
-                &grpc_sources,
+                &current_sources,
                 block_info_sender_processed.clone(),
                 CommitmentConfig::processed(),
                 exit_notify.resubscribe(),
             );
             task_list.extend(jh_meta_task_processed);
             let jh_meta_task_confirmed = create_grpc_multiplex_block_info_task(
-                &grpc_sources,
+                &current_sources,
                 block_info_sender_confirmed.clone(),
                 CommitmentConfig::confirmed(),
                 exit_notify.resubscribe(),
             );
             task_list.extend(jh_meta_task_confirmed);
             let jh_meta_task_finalized = create_grpc_multiplex_block_info_task(
-                &grpc_sources,
+                &current_sources,
                 block_info_sender_finalized.clone(),
                 CommitmentConfig::finalized(),
                 exit_notify,
@@ -423,6 +451,10 @@ pub fn create_grpc_multiplex_blocks_subscription(
                                 finalized_block_not_yet_processed.insert(blockhash);
                             }
                         },
+                    _ = grpc_sources.changed() => {
+                        info!("grpc sources changed - restarting blocks multiplexer");
+                        break 'recv_loop;
+                    },
                     _ = cleanup_tick.tick() => {
                          // timebased restart
                         if cleanup_without_recv_full_blocks > MAX_ALLOWED_CLEANUP_WITHOUT_RECV ||
@@ -464,17 +496,11 @@ pub fn create_grpc_multiplex_blocks_subscription(
     )
 }
 
+/// a change of the sources restarts the multiplexer
 pub fn create_grpc_multiplex_processed_slots_subscription(
-    grpc_sources: Vec<GrpcSourceConfig>,
+    mut grpc_sources: GrpcSourcesReceiver,
 ) -> (Receiver<SlotNotification>, AnyhowJoinHandle) {
     const COMMITMENT_CONFIG: CommitmentConfig = CommitmentConfig::processed();
-    info!("Setup grpc multiplexed slots connection...");
-    if grpc_sources.is_empty() {
-        info!("- no grpc connection configured");
-    }
-    for grpc_source in &grpc_sources {
-        info!("- connection to {}", grpc_source);
-    }
 
     // multiplexed_messages_sender must not be closed from inside this method
     let (multiplexed_messages_sender, multiplexed_messages_rx) =
@@ -483,11 +509,13 @@ pub fn create_grpc_multiplex_processed_slots_subscription(
     // task MUST not terminate but might be aborted from outside
     let jh_multiplex_task = tokio::spawn(async move {
         loop {
+            let current_sources = grpc_sources.current();
+            log_grpc_sources("slots", &current_sources);
+
             let (autoconnect_tx, mut slots_rx) = tokio::sync::mpsc::channel(10);
             let (exit_sender, exit_notify) = broadcast::channel(1);
 
-            let task_list = grpc_sources
-                .clone()
+            let task_list = current_sources
                 .iter()
                 .map(|grpc_source| {
                     create_geyser_autoconnection_task_with_mpsc(
@@ -501,7 +529,13 @@ pub fn create_grpc_multiplex_processed_slots_subscription(
 
             let mut last_slot = 0;
             'recv_loop: loop {
-                let next = tokio::time::timeout(Duration::from_secs(30), slots_rx.recv()).await;
+                let next = tokio::select! {
+                    next = tokio::time::timeout(Duration::from_secs(30), slots_rx.recv()) => next,
+                    _ = grpc_sources.changed() => {
+                        info!("grpc sources changed - restarting slots multiplexer");
+                        break 'recv_loop;
+                    }
+                };
                 match next {
                     Ok(Some(Message::GeyserSubscribeUpdate(slot_update))) => {
                         let mapfilter = map_slot_from_yellowstone_update(*slot_update);
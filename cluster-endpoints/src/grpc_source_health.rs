@@ -0,0 +1,379 @@
+// Health of the multiplexed grpc sources
+// measures for each source how often it delivers a block first, how late it is otherwise,
+// the slots it misses, its lag behind the best source and its reconnects
+// persistently bad sources are dropped from the fast path until they recover
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use geyser_grpc_connector::GrpcSourceConfig;
+use log::{info, warn};
+use prometheus::{opts, register_int_gauge_vec, IntGaugeVec};
+use serde::Serialize;
+use solana_lite_rpc_util::secrets::url_obfuscate_api_token;
+use solana_sdk::clock::Slot;
+
+lazy_static::lazy_static! {
+    static ref GRPC_SOURCE_BLOCKS: IntGaugeVec =
+        register_int_gauge_vec!(opts!("literpc_grpc_source_blocks", "Blocks received per grpc source"), &["source"]).unwrap();
+    static ref GRPC_SOURCE_FIRST_SEEN: IntGaugeVec =
+        register_int_gauge_vec!(opts!("literpc_grpc_source_first_seen", "Blocks a grpc source delivered before all other sources"), &["source"]).unwrap();
+    static ref GRPC_SOURCE_LATENCY_MS: IntGaugeVec =
+        register_int_gauge_vec!(opts!("literpc_grpc_source_latency_ms", "Average delay of a grpc source behind the first source delivering a block"), &["source"]).unwrap();
+    static ref GRPC_SOURCE_MISSING_SLOTS: IntGaugeVec =
+        register_int_gauge_vec!(opts!("literpc_grpc_source_missing_slots", "Blocks delivered by other sources but not by this grpc source"), &["source"]).unwrap();
+    static ref GRPC_SOURCE_LAG_SLOTS: IntGaugeVec =
+        register_int_gauge_vec!(opts!("literpc_grpc_source_lag_slots", "Slots a grpc source is behind the best source"), &["source"]).unwrap();
+    static ref GRPC_SOURCE_RECONNECTS: IntGaugeVec =
+        register_int_gauge_vec!(opts!("literpc_grpc_source_reconnects", "Reconnects of a grpc source"), &["source"]).unwrap();
+    static ref GRPC_SOURCE_ON_FAST_PATH: IntGaugeVec =
+        register_int_gauge_vec!(opts!("literpc_grpc_source_on_fast_path", "1 if blocks of the grpc source are used"), &["source"]).unwrap();
+}
+
+// slots kept to compare the sources
+const SLOT_WINDOW: u64 = 64;
+const EVALUATION_INTERVAL: Duration = Duration::from_secs(10);
+// a source is bad for an evaluation round if it lags or misses more than this
+const MAX_LAG_SLOTS: u64 = 8;
+const MAX_MISSING_SLOTS_PER_ROUND: u64 = 4;
+// consecutive rounds to drop a source from the fast path or to bring it back
+const BAD_ROUNDS_TO_DROP: u32 = 3;
+const GOOD_ROUNDS_TO_RESTORE: u32 = 3;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrpcSourceStatus {
+    pub source: String,
+    pub rank: usize,
+    pub score: f64,
+    pub on_fast_path: bool,
+    pub blocks: u64,
+    pub first_seen: u64,
+    pub avg_latency_ms: u64,
+    pub missing_slots: u64,
+    pub lag_slots: u64,
+    pub reconnects: u64,
+}
+
+struct SourceState {
+    addr: String,
+    // obfuscated address, used in metrics and rankings
+    label: String,
+    blocks: u64,
+    first_seen: u64,
+    // moving average of the delay behind the first source, None until sampled
+    avg_latency_ms: Option<f64>,
+    missing_slots: u64,
+    reconnects: u64,
+    last_slot: Slot,
+    on_fast_path: bool,
+    round_missing_slots: u64,
+    bad_rounds: u32,
+    good_rounds: u32,
+}
+
+struct HealthState {
+    sources: Vec<SourceState>,
+    // first seen time and the sources which delivered the slot
+    recent_slots: BTreeMap<Slot, (Instant, Vec<bool>)>,
+    best_slot: Slot,
+    last_evaluation: Instant,
+}
+
+#[derive(Clone)]
+pub struct GrpcSourcesHealth {
+    state: Arc<Mutex<HealthState>>,
+}
+
+impl SourceState {
+    fn new(addr: String) -> Self {
+        Self {
+            label: url_obfuscate_api_token(&addr).into_owned(),
+            addr,
+            blocks: 0,
+            first_seen: 0,
+            avg_latency_ms: None,
+            missing_slots: 0,
+            reconnects: 0,
+            last_slot: 0,
+            on_fast_path: true,
+            round_missing_slots: 0,
+            bad_rounds: 0,
+            good_rounds: 0,
+        }
+    }
+}
+
+impl GrpcSourcesHealth {
+    pub fn new(grpc_sources: &[GrpcSourceConfig]) -> Self {
+        let sources = grpc_sources
+            .iter()
+            .map(|grpc_source| SourceState::new(grpc_source.grpc_addr.clone()))
+            .collect();
+        Self {
+            state: Arc::new(Mutex::new(HealthState {
+                sources,
+                recent_slots: BTreeMap::new(),
+                best_slot: 0,
+                last_evaluation: Instant::now(),
+            })),
+        }
+    }
+
+    /// records a block of the source, returns false if the source is not on the fast path
+    pub fn observe_block(&self, source: usize, slot: Slot) -> bool {
+        self.observe_block_at(source, slot, Instant::now())
+    }
+
+    fn observe_block_at(&self, source: usize, slot: Slot, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let num_sources = state.sources.len();
+        let Some(source_state) = state.sources.get_mut(source) else {
+            return false;
+        };
+        source_state.blocks += 1;
+        source_state.last_slot = source_state.last_slot.max(slot);
+
+        if slot + SLOT_WINDOW >= state.best_slot {
+            let (first_seen_at, delivered_by) = state
+                .recent_slots
+                .entry(slot)
+                .or_insert_with(|| (now, vec![false; num_sources]));
+            if !delivered_by[source] {
+                if delivered_by.iter().all(|delivered| !delivered) {
+                    source_state.first_seen += 1;
+                }
+                delivered_by[source] = true;
+                let latency_ms = now.duration_since(*first_seen_at).as_secs_f64() * 1000.0;
+                source_state.avg_latency_ms = Some(match source_state.avg_latency_ms {
+                    Some(avg_latency_ms) => avg_latency_ms * 0.9 + latency_ms * 0.1,
+                    None => latency_ms,
+                });
+            }
+        }
+
+        state.best_slot = state.best_slot.max(slot);
+        while let Some(entry) = state.recent_slots.first_entry() {
+            if *entry.key() + SLOT_WINDOW >= state.best_slot {
+                break;
+            }
+            let (_, delivered_by) = entry.remove();
+            for (source_state, delivered) in state.sources.iter_mut().zip(delivered_by) {
+                if !delivered {
+                    source_state.missing_slots += 1;
+                    source_state.round_missing_slots += 1;
+                }
+            }
+        }
+
+        if now.duration_since(state.last_evaluation) >= EVALUATION_INTERVAL {
+            state.last_evaluation = now;
+            state.evaluate();
+        }
+
+        // never starve the multiplexer if all sources are bad
+        state.sources[source].on_fast_path || state.sources.iter().all(|s| !s.on_fast_path)
+    }
+
+    /// follows a change of the sources, keeps the state of the remaining ones
+    pub fn set_sources(&self, grpc_sources: &[GrpcSourceConfig]) {
+        let mut state = self.state.lock().unwrap();
+        let mut previous = std::mem::take(&mut state.sources);
+        for removed in previous
+            .iter()
+            .filter(|s| !grpc_sources.iter().any(|g| g.grpc_addr == s.addr))
+        {
+            let label = [removed.label.as_str()];
+            for metric in [
+                &*GRPC_SOURCE_BLOCKS,
+                &*GRPC_SOURCE_FIRST_SEEN,
+                &*GRPC_SOURCE_LATENCY_MS,
+                &*GRPC_SOURCE_MISSING_SLOTS,
+                &*GRPC_SOURCE_LAG_SLOTS,
+                &*GRPC_SOURCE_RECONNECTS,
+                &*GRPC_SOURCE_ON_FAST_PATH,
+            ] {
+                let _ = metric.remove_label_values(&label);
+            }
+        }
+        state.sources = grpc_sources
+            .iter()
+            .map(|grpc_source| {
+                match previous
+                    .iter()
+                    .position(|s| s.addr == grpc_source.grpc_addr)
+                {
+                    Some(index) => previous.swap_remove(index),
+                    None => SourceState::new(grpc_source.grpc_addr.clone()),
+                }
+            })
+            .collect();
+        // the slot window is indexed by the old positions
+        state.recent_slots.clear();
+    }
+
+    pub fn observe_reconnect(&self, source: usize) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(source_state) = state.sources.get_mut(source) {
+            source_state.reconnects += 1;
+            GRPC_SOURCE_RECONNECTS
+                .with_label_values(&[&source_state.label])
+                .set(source_state.reconnects as i64);
+        }
+    }
+
+    /// sources ordered from best to worst
+    pub fn rankings(&self) -> Vec<GrpcSourceStatus> {
+        let state = self.state.lock().unwrap();
+        let total_first_seen = state
+            .sources
+            .iter()
+            .map(|source_state| source_state.first_seen)
+            .sum::<u64>()
+            .max(1);
+        let mut rankings = state
+            .sources
+            .iter()
+            .map(|source_state| {
+                let lag_slots = state.best_slot.saturating_sub(source_state.last_slot);
+                let avg_latency_ms = source_state.avg_latency_ms.unwrap_or_default();
+                // share of first seen blocks minus penalties for latency, lag and missing slots
+                let score = 100.0 * source_state.first_seen as f64 / total_first_seen as f64
+                    - avg_latency_ms / 10.0
+                    - 10.0 * lag_slots as f64
+                    - 5.0 * source_state.round_missing_slots as f64;
+                GrpcSourceStatus {
+                    source: source_state.label.clone(),
+                    rank: 0,
+                    score,
+                    on_fast_path: source_state.on_fast_path,
+                    blocks: source_state.blocks,
+                    first_seen: source_state.first_seen,
+                    avg_latency_ms: avg_latency_ms as u64,
+                    missing_slots: source_state.missing_slots,
+                    lag_slots,
+                    reconnects: source_state.reconnects,
+                }
+            })
+            .collect::<Vec<_>>();
+        rankings.sort_by(|a, b| {
+            b.on_fast_path
+                .cmp(&a.on_fast_path)
+                .then(b.score.total_cmp(&a.score))
+        });
+        for (rank, status) in rankings.iter_mut().enumerate() {
+            status.rank = rank + 1;
+        }
+        rankings
+    }
+}
+
+impl HealthState {
+    fn evaluate(&mut self) {
+        for source_state in &mut self.sources {
+            let lag_slots = self.best_slot.saturating_sub(source_state.last_slot);
+            let is_bad = lag_slots > MAX_LAG_SLOTS
+                || source_state.round_missing_slots > MAX_MISSING_SLOTS_PER_ROUND;
+            if is_bad {
+                source_state.bad_rounds += 1;
+                source_state.good_rounds = 0;
+            } else {
+                source_state.good_rounds += 1;
+                source_state.bad_rounds = 0;
+            }
+
+            if source_state.on_fast_path && source_state.bad_rounds >= BAD_ROUNDS_TO_DROP {
+                warn!(
+                    "grpc source {} dropped from the fast path (lag {} slots, {} missing slots)",
+                    source_state.label, lag_slots, source_state.round_missing_slots
+                );
+                source_state.on_fast_path = false;
+            } else if !source_state.on_fast_path
+                && source_state.good_rounds >= GOOD_ROUNDS_TO_RESTORE
+            {
+                info!("grpc source {} back on the fast path", source_state.label);
+                source_state.on_fast_path = true;
+            }
+
+            let label = [source_state.label.as_str()];
+            GRPC_SOURCE_BLOCKS
+                .with_label_values(&label)
+                .set(source_state.blocks as i64);
+            GRPC_SOURCE_FIRST_SEEN
+                .with_label_values(&label)
+                .set(source_state.first_seen as i64);
+            GRPC_SOURCE_LATENCY_MS
+                .with_label_values(&label)
+                .set(source_state.avg_latency_ms.unwrap_or_default() as i64);
+            GRPC_SOURCE_MISSING_SLOTS
+                .with_label_values(&label)
+                .set(source_state.missing_slots as i64);
+            GRPC_SOURCE_LAG_SLOTS
+                .with_label_values(&label)
+                .set(lag_slots as i64);
+            GRPC_SOURCE_ON_FAST_PATH
+                .with_label_values(&label)
+                .set(source_state.on_fast_path as i64);
+
+            source_state.round_missing_slots = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geyser_grpc_connector::GrpcConnectionTimeouts;
+
+    fn grpc_source(addr: &str) -> GrpcSourceConfig {
+        let timeouts = GrpcConnectionTimeouts {
+            connect_timeout: Duration::from_secs(1),
+            request_timeout: Duration::from_secs(1),
+            subscribe_timeout: Duration::from_secs(1),
+            receive_timeout: Duration::from_secs(1),
+        };
+        GrpcSourceConfig::new(addr.to_string(), None, None, timeouts)
+    }
+
+    #[test]
+    fn api_tokens_are_not_exposed_in_rankings() {
+        let health = GrpcSourcesHealth::new(&[grpc_source("http://mango.rpcpool.com/a991fba00f")]);
+        assert_eq!(
+            health.rankings()[0].source,
+            "http://mango.rpcpool.com/omitted-secret"
+        );
+    }
+
+    #[test]
+    fn lagging_source_is_dropped_from_fast_path() {
+        let health =
+            GrpcSourcesHealth::new(&[grpc_source("http://fast"), grpc_source("http://slow")]);
+        let start = Instant::now();
+        let mut slot = 1000;
+        let mut now = start;
+        // the slow source stopped delivering blocks
+        for _ in 0..((BAD_ROUNDS_TO_DROP as u64 + 1) * 30) {
+            now += Duration::from_millis(400);
+            assert!(health.observe_block_at(0, slot, now));
+            slot += 1;
+        }
+        assert!(!health.observe_block_at(1, slot - 1, now));
+
+        let rankings = health.rankings();
+        assert_eq!(rankings[0].source, "http://fast");
+        assert!(rankings[0].on_fast_path);
+        assert!(!rankings[1].on_fast_path);
+        assert!(rankings[1].missing_slots > 0);
+
+        // the slow source catches up again, its missing slots have to leave the window first
+        for _ in 0..((GOOD_ROUNDS_TO_RESTORE as u64 + 3) * 30) {
+            now += Duration::from_millis(400);
+            health.observe_block_at(0, slot, now);
+            health.observe_block_at(1, slot, now + Duration::from_millis(20));
+            slot += 1;
+        }
+        assert!(health.observe_block_at(1, slot, now));
+    }
+}
@@ -0,0 +1,244 @@
+// List of grpc sources which can change at runtime
+// the multiplexers follow the changes by restarting their inner tasks, their output channels stay open
+
+use std::sync::Arc;
+
+use anyhow::bail;
+use geyser_grpc_connector::{GrpcConnectionTimeouts, GrpcSourceConfig};
+use serde::{Deserialize, Serialize};
+use solana_lite_rpc_util::secrets::url_obfuscate_api_token;
+use tokio::sync::watch;
+use yellowstone_grpc_proto::tonic::transport::ClientTlsConfig;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrpcSourceRole {
+    #[default]
+    All,
+    BlocksOnly,
+    AccountsOnly,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrpcSourceSpec {
+    pub addr: String,
+    #[serde(default)]
+    pub x_token: Option<String>,
+    #[serde(default)]
+    pub tls: bool,
+    #[serde(default)]
+    pub role: GrpcSourceRole,
+}
+
+impl GrpcSourceSpec {
+    pub fn new(addr: String, x_token: Option<String>) -> Self {
+        Self {
+            addr,
+            x_token,
+            tls: false,
+            role: GrpcSourceRole::All,
+        }
+    }
+
+    /// same source with the token and the api token of the url hidden, e.g. to list the sources
+    pub fn redacted(&self) -> Self {
+        Self {
+            addr: url_obfuscate_api_token(&self.addr).into_owned(),
+            x_token: self.x_token.as_ref().map(|_| "<redacted>".to_string()),
+            ..self.clone()
+        }
+    }
+
+    // by its address or the redacted address it is listed with
+    fn has_addr(&self, addr: &str) -> bool {
+        self.addr == addr || url_obfuscate_api_token(&self.addr) == addr
+    }
+
+    fn to_config(&self, timeouts: &GrpcConnectionTimeouts) -> GrpcSourceConfig {
+        let tls_config = self.tls.then(ClientTlsConfig::new);
+        GrpcSourceConfig::new(
+            self.addr.clone(),
+            self.x_token.clone(),
+            tls_config,
+            timeouts.clone(),
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SourceUse {
+    Blocks,
+    Accounts,
+}
+
+/// shared handle to add, remove or replace the grpc sources
+#[derive(Clone)]
+pub struct GrpcSources {
+    sources: Arc<watch::Sender<Vec<GrpcSourceSpec>>>,
+    timeouts: GrpcConnectionTimeouts,
+}
+
+impl GrpcSources {
+    pub fn new(sources: Vec<GrpcSourceSpec>, timeouts: GrpcConnectionTimeouts) -> Self {
+        let (sources, _) = watch::channel(sources);
+        Self {
+            sources: Arc::new(sources),
+            timeouts,
+        }
+    }
+
+    pub fn list(&self) -> Vec<GrpcSourceSpec> {
+        self.sources.borrow().clone()
+    }
+
+    pub fn add(&self, source: GrpcSourceSpec) -> anyhow::Result<()> {
+        if self.list().iter().any(|s| s.addr == source.addr) {
+            bail!("grpc source {} already exists", source.addr);
+        }
+        self.sources.send_modify(|sources| sources.push(source));
+        Ok(())
+    }
+
+    pub fn remove(&self, addr: &str) -> anyhow::Result<()> {
+        match self.list().iter().filter(|s| s.has_addr(addr)).count() {
+            0 => bail!("grpc source {} does not exist", addr),
+            1 => {}
+            _ => bail!("grpc source {} is ambiguous, use the full address", addr),
+        }
+        self.sources
+            .send_modify(|sources| sources.retain(|s| !s.has_addr(addr)));
+        Ok(())
+    }
+
+    pub fn replace(&self, sources: Vec<GrpcSourceSpec>) {
+        self.sources.send_if_modified(|current| {
+            if *current == sources {
+                return false;
+            }
+            *current = sources;
+            true
+        });
+    }
+
+    /// sources for blocks and slots
+    pub fn subscribe_blocks(&self) -> GrpcSourcesReceiver {
+        self.subscribe(SourceUse::Blocks)
+    }
+
+    /// sources for account updates
+    pub fn subscribe_accounts(&self) -> GrpcSourcesReceiver {
+        self.subscribe(SourceUse::Accounts)
+    }
+
+    fn subscribe(&self, source_use: SourceUse) -> GrpcSourcesReceiver {
+        GrpcSourcesReceiver {
+            sources: self.sources.subscribe(),
+            timeouts: self.timeouts.clone(),
+            source_use,
+            last: vec![],
+        }
+    }
+}
+
+/// sources of one kind of stream as seen by a multiplexer
+pub struct GrpcSourcesReceiver {
+    sources: watch::Receiver<Vec<GrpcSourceSpec>>,
+    timeouts: GrpcConnectionTimeouts,
+    source_use: SourceUse,
+    last: Vec<GrpcSourceSpec>,
+}
+
+fn filter_sources(sources: &[GrpcSourceSpec], source_use: SourceUse) -> Vec<GrpcSourceSpec> {
+    sources
+        .iter()
+        .filter(|source| match source.role {
+            GrpcSourceRole::All => true,
+            GrpcSourceRole::BlocksOnly => source_use == SourceUse::Blocks,
+            GrpcSourceRole::AccountsOnly => source_use == SourceUse::Accounts,
+        })
+        .cloned()
+        .collect()
+}
+
+impl GrpcSourcesReceiver {
+    /// current sources, changed() waits for changes after this call
+    pub fn current(&mut self) -> Vec<GrpcSourceConfig> {
+        let sources = filter_sources(&self.sources.borrow_and_update(), self.source_use);
+        let configs = sources
+            .iter()
+            .map(|source| source.to_config(&self.timeouts))
+            .collect();
+        self.last = sources;
+        configs
+    }
+
+    /// resolves when the sources differ from the last current(), never if they cannot change anymore
+    pub async fn changed(&mut self) {
+        loop {
+            if self.sources.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+            if filter_sources(&self.sources.borrow(), self.source_use) != self.last {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn grpc_sources(sources: Vec<GrpcSourceSpec>) -> GrpcSources {
+        let timeouts = GrpcConnectionTimeouts {
+            connect_timeout: Duration::from_secs(1),
+            request_timeout: Duration::from_secs(1),
+            subscribe_timeout: Duration::from_secs(1),
+            receive_timeout: Duration::from_secs(1),
+        };
+        GrpcSources::new(sources, timeouts)
+    }
+
+    #[tokio::test]
+    async fn receivers_follow_the_sources_of_their_role() {
+        let sources = grpc_sources(vec![GrpcSourceSpec::new("http://a".to_string(), None)]);
+        let mut blocks = sources.subscribe_blocks();
+        let mut accounts = sources.subscribe_accounts();
+        assert_eq!(blocks.current().len(), 1);
+        assert_eq!(accounts.current().len(), 1);
+
+        let accounts_only: GrpcSourceSpec =
+            serde_json::from_str(r#"{"addr": "http://b", "role": "accounts_only"}"#).unwrap();
+        sources.add(accounts_only.clone()).unwrap();
+        assert!(sources.add(accounts_only).is_err());
+
+        let wait = Duration::from_millis(100);
+        tokio::time::timeout(wait, accounts.changed())
+            .await
+            .unwrap();
+        assert_eq!(accounts.current().len(), 2);
+        // the blocks sources did not change
+        assert!(tokio::time::timeout(wait, blocks.changed()).await.is_err());
+
+        sources.remove("http://a").unwrap();
+        assert!(sources.remove("http://a").is_err());
+        tokio::time::timeout(wait, blocks.changed()).await.unwrap();
+        assert!(blocks.current().is_empty());
+    }
+
+    #[test]
+    fn sources_are_listed_and_removed_without_secrets() {
+        let source = GrpcSourceSpec::new(
+            "https://mango.rpcpool.com/a991fba00f".to_string(),
+            Some("token".to_string()),
+        );
+        let redacted = source.redacted();
+        assert_eq!(redacted.addr, "https://mango.rpcpool.com/omitted-secret");
+        assert_eq!(redacted.x_token.as_deref(), Some("<redacted>"));
+
+        let sources = grpc_sources(vec![source]);
+        sources.remove(&redacted.addr).unwrap();
+        assert!(sources.list().is_empty());
+    }
+}
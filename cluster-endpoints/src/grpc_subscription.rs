@@ -3,7 +3,8 @@ use crate::grpc::grpc_accounts_streaming::create_grpc_account_streaming;
 use crate::grpc_multiplex::{
     create_grpc_multiplex_blocks_subscription, create_grpc_multiplex_processed_slots_subscription,
 };
-use geyser_grpc_connector::GrpcSourceConfig;
+use crate::grpc_source_health::GrpcSourcesHealth;
+use crate::grpc_sources::GrpcSources;
 use itertools::Itertools;
 use log::trace;
 use solana_client::nonblocking::rpc_client::RpcClient;
@@ -244,18 +245,19 @@ fn map_compute_budget_instructions(message: &VersionedMessage) -> (Option<u32>,
 
 pub fn create_grpc_subscription(
     rpc_client: Arc<RpcClient>,
-    grpc_sources: Vec<GrpcSourceConfig>,
+    grpc_sources: GrpcSources,
     accounts_filter: AccountFilters,
+    sources_health: GrpcSourcesHealth,
 ) -> anyhow::Result<(EndpointStreaming, Vec<AnyhowJoinHandle>)> {
     let (cluster_info_sx, cluster_info_notifier) = tokio::sync::broadcast::channel(10);
     let (va_sx, vote_account_notifier) = tokio::sync::broadcast::channel(10);
 
     // processed slot is required to keep up with leader schedule
     let (slot_multiplex_channel, jh_multiplex_slotstream) =
-        create_grpc_multiplex_processed_slots_subscription(grpc_sources.clone());
+        create_grpc_multiplex_processed_slots_subscription(grpc_sources.subscribe_blocks());
 
     let (block_multiplex_channel, blockmeta_channel, jh_multiplex_blockstream) =
-        create_grpc_multiplex_blocks_subscription(grpc_sources.clone());
+        create_grpc_multiplex_blocks_subscription(grpc_sources.subscribe_blocks(), sources_health);
 
     let cluster_info_polling = poll_cluster_info(rpc_client.clone(), cluster_info_sx);
     let vote_accounts_polling = poll_vote_accounts(rpc_client.clone(), va_sx);
@@ -264,7 +266,7 @@ pub fn create_grpc_subscription(
         let (account_sender, accounts_stream) =
             tokio::sync::broadcast::channel::<AccountNotificationMessage>(1024);
         let account_jh = create_grpc_account_streaming(
-            grpc_sources,
+            grpc_sources.subscribe_accounts(),
             accounts_filter,
             account_sender,
             Arc::new(Notify::new()),
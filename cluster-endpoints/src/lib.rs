@@ -1,12 +1,18 @@
 pub mod endpoint_stremers;
+pub mod fork_tracker;
+pub mod gossip_cluster_info;
 pub mod grpc;
 pub mod grpc_inspect;
 pub mod grpc_leaders_getter;
 pub mod grpc_multiplex;
+pub mod grpc_source_health;
+pub mod grpc_sources;
 pub mod grpc_stream_utils;
 pub mod grpc_subscription;
 pub mod json_rpc_leaders_getter;
 pub mod json_rpc_subscription;
 pub mod rpc_polling;
+pub mod stream_recording;
+pub mod websocket_subscription;
 
 pub use geyser_grpc_connector;
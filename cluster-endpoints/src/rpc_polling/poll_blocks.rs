@@ -317,7 +317,7 @@ pub fn from_ui_block(
     ProducedBlock::new(inner, commitment_config)
 }
 
-fn map_block_info(produced_block: &ProducedBlock) -> BlockInfo {
+pub fn map_block_info(produced_block: &ProducedBlock) -> BlockInfo {
     BlockInfo {
         slot: produced_block.slot,
         block_height: produced_block.block_height,
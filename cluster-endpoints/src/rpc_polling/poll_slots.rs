@@ -44,13 +44,23 @@ pub fn poll_slots(
     sender: Sender<SlotNotification>,
 ) -> anyhow::Result<Vec<AnyhowJoinHandle>> {
     // processed slot update task
-    let (slot_update_sx, mut slot_update_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (slot_update_sx, slot_update_rx) = tokio::sync::mpsc::unbounded_channel();
     let task1 = tokio::spawn(poll_commitment_slots(
         rpc_client.clone(),
         commitment_config,
         slot_update_sx,
     ));
-    let task2 = tokio::spawn(async move {
+    let task2 = estimate_slots(rpc_client, slot_update_rx, sender);
+    Ok(vec![task1, task2])
+}
+
+/// slot notifications from the slot updates, estimates the slot while no update arrives
+pub fn estimate_slots(
+    rpc_client: Arc<RpcClient>,
+    mut slot_update_rx: tokio::sync::mpsc::UnboundedReceiver<Slot>,
+    sender: Sender<SlotNotification>,
+) -> AnyhowJoinHandle {
+    tokio::spawn(async move {
         let slot = rpc_client
             .get_slot_with_commitment(CommitmentConfig::confirmed())
             .await
@@ -95,6 +105,5 @@ pub fn poll_slots(
                 }
             }
         }
-    });
-    Ok(vec![task1, task2])
+    })
 }
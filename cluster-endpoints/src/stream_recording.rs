@@ -0,0 +1,591 @@
+// Record the streams of an EndpointStreaming to a file and replay them later
+// File layout: header, then length-prefixed bincode frames of events timestamped relative to the start
+// The epoch schedule and the leaders of the recorded slots are part of the recording, a replay does not need the rpc
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use solana_lite_rpc_core::commitment_utils::Commitment;
+use solana_lite_rpc_core::structures::account_data::{AccountData, AccountNotificationMessage};
+use solana_lite_rpc_core::structures::block_info::BlockInfo;
+use solana_lite_rpc_core::structures::epoch::EpochCache;
+use solana_lite_rpc_core::structures::leader_data::LeaderData;
+use solana_lite_rpc_core::structures::produced_block::{ProducedBlock, ProducedBlockInner};
+use solana_lite_rpc_core::structures::slot_notification::SlotNotification;
+use solana_lite_rpc_core::traits::leaders_fetcher_interface::LeaderFetcherInterface;
+use solana_lite_rpc_core::AnyhowJoinHandle;
+use solana_rpc_client_api::response::{RpcContactInfo, RpcVoteAccountStatus};
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::slot_history::Slot;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use tokio::sync::mpsc;
+
+use crate::endpoint_stremers::EndpointStreaming;
+
+const FILE_MAGIC: &[u8; 8] = b"LRPCSTRM";
+const FILE_FORMAT_VERSION: u16 = 2;
+// leaders are recorded this far ahead of the processed slot, covers the fanout of the tpu service
+const LEADER_LOOKAHEAD_SLOTS: u64 = 512;
+// the tpu service looks a few slots back for late leaders
+const LEADER_LOOKBEHIND_SLOTS: u64 = 32;
+// the file is flushed at least this often so that it is usable if lite-rpc gets killed
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+// replay may run faster than real time, keep enough buffered for slow subscribers
+const REPLAY_CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RecordingHeader {
+    format_version: u16,
+    // wall clock time of the start of the recording
+    started_at_unix_ms: u64,
+    has_account_stream: bool,
+    epoch_schedule: EpochSchedule,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordedAccount {
+    pubkey: Pubkey,
+    account: Account,
+    updated_slot: Slot,
+    commitment: Commitment,
+}
+
+// borrowed counterpart of StreamEvent for writing, variants must stay in the same order
+#[derive(Serialize)]
+enum StreamEventRef<'a> {
+    Block(&'a ProducedBlockInner, CommitmentConfig),
+    BlockInfo(&'a BlockInfo),
+    Slot(&'a SlotNotification),
+    ClusterInfo(&'a Vec<RpcContactInfo>),
+    VoteAccounts(&'a RpcVoteAccountStatus),
+    Account(RecordedAccount),
+    Leaders(&'a [(Slot, Pubkey)]),
+}
+
+#[derive(Deserialize)]
+enum StreamEvent {
+    Block(ProducedBlockInner, CommitmentConfig),
+    BlockInfo(BlockInfo),
+    Slot(SlotNotification),
+    ClusterInfo(Vec<RpcContactInfo>),
+    VoteAccounts(RpcVoteAccountStatus),
+    Account(RecordedAccount),
+    Leaders(Vec<(Slot, Pubkey)>),
+}
+
+struct RecordingWriter {
+    writer: BufWriter<File>,
+    started_at: Instant,
+    last_flush: Instant,
+    nb_events: u64,
+}
+
+impl RecordingWriter {
+    fn create(
+        path: &Path,
+        has_account_stream: bool,
+        epoch_schedule: EpochSchedule,
+    ) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("create recording file {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        let header = RecordingHeader {
+            format_version: FILE_FORMAT_VERSION,
+            started_at_unix_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            has_account_stream,
+            epoch_schedule,
+        };
+        writer.write_all(FILE_MAGIC)?;
+        bincode::serialize_into(&mut writer, &header)?;
+        Ok(Self {
+            writer,
+            started_at: Instant::now(),
+            last_flush: Instant::now(),
+            nb_events: 0,
+        })
+    }
+
+    fn write(&mut self, payload: &[u8]) -> anyhow::Result<()> {
+        self.writer
+            .write_all(&(payload.len() as u32).to_le_bytes())?;
+        self.writer.write_all(payload)?;
+        self.nb_events += 1;
+        if self.last_flush.elapsed() > FLUSH_INTERVAL {
+            self.writer.flush()?;
+            self.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+struct RecordingReader {
+    reader: BufReader<File>,
+    header: RecordingHeader,
+}
+
+impl RecordingReader {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("open recording file {}", path.display()))?;
+        let mut reader = BufReader::new(file);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != FILE_MAGIC {
+            bail!("{} is not a stream recording", path.display());
+        }
+        let header: RecordingHeader = bincode::deserialize_from(&mut reader)?;
+        if header.format_version != FILE_FORMAT_VERSION {
+            bail!(
+                "unsupported stream recording format version {}",
+                header.format_version
+            );
+        }
+        Ok(Self { reader, header })
+    }
+
+    // none at the end of the file
+    fn next_event(&mut self) -> anyhow::Result<Option<(u64, StreamEvent)>> {
+        let mut length = [0u8; 4];
+        match self.reader.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        let mut payload = vec![0u8; u32::from_le_bytes(length) as usize];
+        match self.reader.read_exact(&mut payload) {
+            Ok(()) => {}
+            // last frame cut off when the recording was killed
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        Ok(Some(bincode::deserialize(&payload)?))
+    }
+}
+
+// next message of a stream, none once the stream is closed
+async fn recv_or_closed<T: Clone>(receiver: &mut Option<Receiver<T>>, name: &str) -> Option<T> {
+    loop {
+        let stream = receiver.as_mut()?;
+        match stream.recv().await {
+            Ok(message) => return Some(message),
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("stream recorder lagged on {} - skipped {}", name, skipped);
+            }
+            Err(RecvError::Closed) => {
+                *receiver = None;
+                return None;
+            }
+        }
+    }
+}
+
+/// writes every message of the streams to the file until all of them are closed
+/// the leaders of the recorded slots are taken from `leader_schedule`
+pub fn record_endpoint_streams(
+    streaming: &EndpointStreaming,
+    epoch_cache: EpochCache,
+    leader_schedule: Arc<dyn LeaderFetcherInterface>,
+    path: PathBuf,
+) -> anyhow::Result<AnyhowJoinHandle> {
+    let mut recording = RecordingWriter::create(
+        &path,
+        streaming.processed_account_stream.is_some(),
+        epoch_cache.get_epoch_schedule().clone(),
+    )?;
+    let started_at = recording.started_at;
+    let mut blocks = Some(streaming.blocks_notifier.resubscribe());
+    let mut block_infos = Some(streaming.blockinfo_notifier.resubscribe());
+    let mut slots = Some(streaming.slot_notifier.resubscribe());
+    let mut cluster_infos = Some(streaming.cluster_info_notifier.resubscribe());
+    let mut vote_accounts = Some(streaming.vote_account_notifier.resubscribe());
+    let mut accounts = streaming
+        .processed_account_stream
+        .as_ref()
+        .map(|stream| stream.resubscribe());
+    info!("Recording endpoint streams to {}", path.display());
+
+    // the file is written on a blocking thread
+    let (frame_sx, mut frame_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+    let writer = tokio::task::spawn_blocking(move || {
+        while let Some(frame) = frame_rx.blocking_recv() {
+            recording.write(&frame)?;
+        }
+        recording.writer.flush()?;
+        anyhow::Ok(recording.nb_events)
+    });
+
+    Ok(tokio::spawn(async move {
+        let mut leaders_recorded_until = None;
+        loop {
+            let payload = tokio::select! {
+                Some(block) = recv_or_closed(&mut blocks, "blocks") => {
+                    frame(started_at, StreamEventRef::Block(&block, block.commitment_config))?
+                }
+                Some(block_info) = recv_or_closed(&mut block_infos, "block info") => {
+                    frame(started_at, StreamEventRef::BlockInfo(&block_info))?
+                }
+                Some(slot) = recv_or_closed(&mut slots, "slots") => {
+                    // the leaders go first so that they are known when the slot is replayed
+                    for leaders in record_leaders(leader_schedule.as_ref(), slot.processed_slot, &mut leaders_recorded_until).await {
+                        if frame_sx.send(frame(started_at, StreamEventRef::Leaders(&leaders))?).is_err() {
+                            break;
+                        }
+                    }
+                    frame(started_at, StreamEventRef::Slot(&slot))?
+                }
+                Some(cluster_info) = recv_or_closed(&mut cluster_infos, "cluster info") => {
+                    frame(started_at, StreamEventRef::ClusterInfo(&cluster_info))?
+                }
+                Some(vote_account_status) = recv_or_closed(&mut vote_accounts, "vote accounts") => {
+                    frame(started_at, StreamEventRef::VoteAccounts(&vote_account_status))?
+                }
+                Some(notification) = recv_or_closed(&mut accounts, "accounts") => {
+                    frame(started_at, StreamEventRef::Account(RecordedAccount {
+                        pubkey: notification.data.pubkey,
+                        account: (*notification.data.account).clone(),
+                        updated_slot: notification.data.updated_slot,
+                        commitment: notification.commitment,
+                    }))?
+                }
+                else => break,
+            };
+            // the writer failed, its error is returned below
+            if frame_sx.send(payload).is_err() {
+                break;
+            }
+        }
+        drop(frame_sx);
+        let nb_events = writer.await??;
+        info!(
+            "Recorded {} events of the endpoint streams to {}",
+            nb_events,
+            path.display()
+        );
+        Ok(())
+    }))
+}
+
+fn frame(started_at: Instant, event: StreamEventRef) -> anyhow::Result<Vec<u8>> {
+    let elapsed_micros = started_at.elapsed().as_micros() as u64;
+    Ok(bincode::serialize(&(elapsed_micros, event))?)
+}
+
+// leaders from a bit before the processed slot to the lookahead which are not recorded yet
+async fn record_leaders(
+    leader_schedule: &dyn LeaderFetcherInterface,
+    processed_slot: Slot,
+    recorded_until: &mut Option<Slot>,
+) -> Vec<Vec<(Slot, Pubkey)>> {
+    let mut batches = vec![];
+    loop {
+        let from = match *recorded_until {
+            Some(until) if until + LEADER_LOOKAHEAD_SLOTS >= processed_slot => until + 1,
+            _ => processed_slot.saturating_sub(LEADER_LOOKBEHIND_SLOTS),
+        };
+        if from > processed_slot + LEADER_LOOKAHEAD_SLOTS {
+            return batches;
+        }
+        let to = from + LEADER_LOOKAHEAD_SLOTS;
+        match leader_schedule.get_slot_leaders(from, to).await {
+            Ok(leaders) => {
+                batches.push(
+                    leaders
+                        .into_iter()
+                        .map(|leader| (leader.leader_slot, leader.pubkey))
+                        .collect(),
+                );
+                *recorded_until = Some(to);
+            }
+            Err(err) => {
+                // tried again on the next slot
+                warn!(
+                    "failed to get the leaders of slots {}-{} for the recording: {:#}",
+                    from, to, err
+                );
+                return batches;
+            }
+        }
+    }
+}
+
+/// leaders of the recorded slots, known once the replay reaches them
+#[derive(Clone, Default)]
+pub struct ReplayedLeaderSchedule {
+    leaders: Arc<RwLock<BTreeMap<Slot, Pubkey>>>,
+}
+
+impl ReplayedLeaderSchedule {
+    fn insert(&self, leaders: Vec<(Slot, Pubkey)>) {
+        let Some(first_slot) = leaders.first().map(|(slot, _)| *slot) else {
+            return;
+        };
+        let mut known_leaders = self.leaders.write().unwrap();
+        // slots far behind the replay are not asked for anymore
+        *known_leaders =
+            known_leaders.split_off(&first_slot.saturating_sub(2 * LEADER_LOOKAHEAD_SLOTS));
+        known_leaders.extend(leaders);
+    }
+}
+
+#[async_trait]
+impl LeaderFetcherInterface for ReplayedLeaderSchedule {
+    async fn get_slot_leaders(&self, from: Slot, to: Slot) -> anyhow::Result<Vec<LeaderData>> {
+        let leaders = self
+            .leaders
+            .read()
+            .unwrap()
+            .range(from..=to)
+            .map(|(slot, pubkey)| LeaderData {
+                leader_slot: *slot,
+                pubkey: *pubkey,
+            })
+            .collect::<Vec<_>>();
+        if leaders.is_empty() {
+            bail!("no recorded leaders for slots {}-{}", from, to);
+        }
+        Ok(leaders)
+    }
+}
+
+/// cluster data of a recording which lite-rpc otherwise takes from the rpc
+#[derive(Clone)]
+pub struct ReplayedCluster {
+    pub epoch_cache: EpochCache,
+    pub leader_schedule: ReplayedLeaderSchedule,
+}
+
+/// streams the events of a recording, speed 1.0 keeps the recorded timing and f64::INFINITY sends without delay
+/// note: the channels stay open after the end of the recording like those of a live endpoint
+pub fn create_replay_subscription(
+    path: PathBuf,
+    speed: f64,
+) -> anyhow::Result<(EndpointStreaming, Vec<AnyhowJoinHandle>, ReplayedCluster)> {
+    if speed.is_nan() || speed <= 0.0 {
+        bail!("replay speed must be positive");
+    }
+    let mut recording = RecordingReader::open(&path)?;
+    info!(
+        "Replaying endpoint streams from {} recorded at {} ms since epoch at speed {}",
+        path.display(),
+        recording.header.started_at_unix_ms,
+        speed
+    );
+    let replayed_cluster = ReplayedCluster {
+        epoch_cache: EpochCache::new(recording.header.epoch_schedule.clone()),
+        leader_schedule: ReplayedLeaderSchedule::default(),
+    };
+    let leader_schedule = replayed_cluster.leader_schedule.clone();
+
+    let (block_sx, blocks_notifier) = broadcast::channel(REPLAY_CHANNEL_CAPACITY);
+    let (blockinfo_sx, blockinfo_notifier) = broadcast::channel(REPLAY_CHANNEL_CAPACITY);
+    let (slot_sx, slot_notifier) = broadcast::channel(REPLAY_CHANNEL_CAPACITY);
+    let (cluster_info_sx, cluster_info_notifier) = broadcast::channel(REPLAY_CHANNEL_CAPACITY);
+    let (va_sx, vote_account_notifier) = broadcast::channel(REPLAY_CHANNEL_CAPACITY);
+    let (account_sx, account_notifier): (Sender<AccountNotificationMessage>, _) =
+        broadcast::channel(REPLAY_CHANNEL_CAPACITY);
+    let processed_account_stream = recording
+        .header
+        .has_account_stream
+        .then_some(account_notifier);
+
+    // the file is read on a blocking thread
+    let (event_sx, mut event_rx) = mpsc::channel(REPLAY_CHANNEL_CAPACITY);
+    let reader = tokio::task::spawn_blocking(move || {
+        while let Some(event) = recording.next_event()? {
+            if event_sx.blocking_send(event).is_err() {
+                break;
+            }
+        }
+        anyhow::Ok(())
+    });
+
+    let replay: AnyhowJoinHandle = tokio::spawn(async move {
+        let started_at = tokio::time::Instant::now();
+        let mut nb_events = 0u64;
+        while let Some((elapsed_micros, event)) = event_rx.recv().await {
+            if speed.is_finite() {
+                let delay = Duration::from_secs_f64(elapsed_micros as f64 / 1_000_000.0 / speed);
+                tokio::time::sleep_until(started_at + delay).await;
+            }
+            // no subscriber is not an error
+            match event {
+                StreamEvent::Block(block, commitment_config) => {
+                    let _ = block_sx.send(ProducedBlock::new(block, commitment_config));
+                }
+                StreamEvent::BlockInfo(block_info) => {
+                    let _ = blockinfo_sx.send(block_info);
+                }
+                StreamEvent::Slot(slot) => {
+                    let _ = slot_sx.send(slot);
+                }
+                StreamEvent::ClusterInfo(cluster_info) => {
+                    let _ = cluster_info_sx.send(cluster_info);
+                }
+                StreamEvent::VoteAccounts(vote_account_status) => {
+                    let _ = va_sx.send(vote_account_status);
+                }
+                StreamEvent::Account(account) => {
+                    let _ = account_sx.send(AccountNotificationMessage {
+                        data: AccountData {
+                            pubkey: account.pubkey,
+                            account: account.account.into(),
+                            updated_slot: account.updated_slot,
+                        },
+                        commitment: account.commitment,
+                    });
+                }
+                StreamEvent::Leaders(leaders) => leader_schedule.insert(leaders),
+            }
+            nb_events += 1;
+            if speed.is_infinite() {
+                // let the subscribers catch up
+                tokio::task::yield_now().await;
+            }
+        }
+        reader.await??;
+        info!("Replayed {} events from {}", nb_events, path.display());
+        let _senders = (
+            block_sx,
+            blockinfo_sx,
+            slot_sx,
+            cluster_info_sx,
+            va_sx,
+            account_sx,
+        );
+        std::future::pending::<()>().await;
+        Ok(())
+    });
+
+    let streamers = EndpointStreaming {
+        blocks_notifier,
+        blockinfo_notifier,
+        slot_notifier,
+        vote_account_notifier,
+        cluster_info_notifier,
+        processed_account_stream,
+    };
+    Ok((streamers, vec![replay], replayed_cluster))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+    use solana_sdk::signature::Signature;
+
+    use super::*;
+
+    struct SingleLeader(Pubkey);
+
+    #[async_trait]
+    impl LeaderFetcherInterface for SingleLeader {
+        async fn get_slot_leaders(&self, from: Slot, to: Slot) -> anyhow::Result<Vec<LeaderData>> {
+            Ok((from..=to)
+                .map(|leader_slot| LeaderData {
+                    leader_slot,
+                    pubkey: self.0,
+                })
+                .collect())
+        }
+    }
+
+    fn block(slot: Slot) -> ProducedBlock {
+        ProducedBlock::new(
+            ProducedBlockInner {
+                transactions: vec![],
+                leader_id: None,
+                blockhash: Hash::new_unique(),
+                block_height: slot,
+                slot,
+                parent_slot: slot - 1,
+                block_time: 0,
+                previous_blockhash: Hash::new_unique(),
+                rewards: None,
+            },
+            CommitmentConfig::confirmed(),
+        )
+    }
+
+    #[tokio::test]
+    async fn replay_recorded_streams() {
+        let path =
+            std::env::temp_dir().join(format!("lite-rpc-streams-{}", Signature::new_unique()));
+        let (block_sx, blocks_notifier) = broadcast::channel(16);
+        let (_blockinfo_sx, blockinfo_notifier) = broadcast::channel(16);
+        let (slot_sx, slot_notifier) = broadcast::channel(16);
+        let (_cluster_info_sx, cluster_info_notifier) = broadcast::channel(16);
+        let (_va_sx, vote_account_notifier) = broadcast::channel(16);
+        let streaming = EndpointStreaming {
+            blocks_notifier,
+            blockinfo_notifier,
+            slot_notifier,
+            vote_account_notifier,
+            cluster_info_notifier,
+            processed_account_stream: None,
+        };
+        let leader = Pubkey::new_unique();
+        let epoch_schedule = EpochSchedule::custom(1000, 1000, false);
+        let recorder = record_endpoint_streams(
+            &streaming,
+            EpochCache::new(epoch_schedule.clone()),
+            Arc::new(SingleLeader(leader)),
+            path.clone(),
+        )
+        .unwrap();
+        drop(streaming);
+
+        let blocks = [block(41), block(42)];
+        for block in &blocks {
+            slot_sx
+                .send(SlotNotification {
+                    processed_slot: block.slot,
+                    estimated_processed_slot: block.slot,
+                })
+                .unwrap();
+            block_sx.send(block.clone()).unwrap();
+        }
+        drop((block_sx, slot_sx, _blockinfo_sx, _cluster_info_sx, _va_sx));
+        recorder.await.unwrap().unwrap();
+
+        let (mut replay, _tasks, replayed_cluster) =
+            create_replay_subscription(path.clone(), f64::INFINITY).unwrap();
+        assert!(replay.processed_account_stream.is_none());
+        assert_eq!(
+            replayed_cluster.epoch_cache.get_epoch_schedule(),
+            &epoch_schedule
+        );
+        for block in &blocks {
+            let replayed = replay.blocks_notifier.recv().await.unwrap();
+            assert_eq!(replayed.slot, block.slot);
+            assert_eq!(replayed.blockhash, block.blockhash);
+            assert_eq!(replayed.commitment_config, block.commitment_config);
+            assert_eq!(
+                replay.slot_notifier.recv().await.unwrap().processed_slot,
+                block.slot
+            );
+            // recorded ahead of the slot
+            let leaders = replayed_cluster
+                .leader_schedule
+                .get_slot_leaders(block.slot, block.slot + LEADER_LOOKAHEAD_SLOTS)
+                .await
+                .unwrap();
+            assert_eq!(leaders.len() as u64, LEADER_LOOKAHEAD_SLOTS + 1);
+            assert!(leaders.iter().all(|x| x.pubkey == leader));
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,505 @@
+// Cluster endpoint on top of the standard solana pubsub websocket
+// slots, blocks and accounts come from the subscriptions, falls back to rpc polling
+// for blocks when blockSubscribe is not enabled on the node and for slots while disconnected
+
+use crate::endpoint_stremers::EndpointStreaming;
+use crate::rpc_polling::poll_blocks::{from_ui_block, map_block_info, poll_block, process_block};
+use crate::rpc_polling::poll_slots::{estimate_slots, poll_commitment_slots};
+use crate::rpc_polling::vote_accounts_and_cluster_info_polling::{
+    poll_cluster_info, poll_vote_accounts,
+};
+use anyhow::{bail, Context};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use log::{debug, info, warn};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_lite_rpc_core::commitment_utils::Commitment;
+use solana_lite_rpc_core::structures::account_data::{AccountData, AccountNotificationMessage};
+use solana_lite_rpc_core::structures::account_filter::{AccountFilter, AccountFilters};
+use solana_lite_rpc_core::structures::block_info::BlockInfo;
+use solana_lite_rpc_core::structures::produced_block::ProducedBlock;
+use solana_lite_rpc_core::structures::slot_notification::SlotNotification;
+use solana_lite_rpc_core::AnyhowJoinHandle;
+use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
+use solana_rpc_client_api::config::{
+    RpcAccountInfoConfig, RpcBlockSubscribeConfig, RpcBlockSubscribeFilter,
+    RpcProgramAccountsConfig,
+};
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::slot_history::Slot;
+use solana_transaction_status::{TransactionDetails, UiTransactionEncoding};
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{Receiver, Sender};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+// missed blocks older than this are not fetched after a reconnect
+const MAX_BLOCK_GAP: u64 = 128;
+
+pub fn create_websocket_subscription(
+    rpc_client: Arc<RpcClient>,
+    ws_url: String,
+    accounts_filter: AccountFilters,
+    num_parallel_tasks: usize,
+) -> anyhow::Result<(EndpointStreaming, Vec<AnyhowJoinHandle>)> {
+    let (slot_sx, slot_notifier) = tokio::sync::broadcast::channel(16);
+    let (block_sx, blocks_notifier) = tokio::sync::broadcast::channel(16);
+    let (blockinfo_sx, blockinfo_notifier) = tokio::sync::broadcast::channel(16);
+    let (cluster_info_sx, cluster_info_notifier) = tokio::sync::broadcast::channel(16);
+    let (va_sx, vote_account_notifier) = tokio::sync::broadcast::channel(16);
+
+    let (slot_update_sx, slot_update_rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut endpoint_tasks = vec![
+        tokio::spawn(subscribe_slots(
+            rpc_client.clone(),
+            ws_url.clone(),
+            slot_update_sx,
+        )),
+        estimate_slots(rpc_client.clone(), slot_update_rx, slot_sx),
+        tokio::spawn(subscribe_blocks(
+            rpc_client.clone(),
+            ws_url.clone(),
+            block_sx,
+            blockinfo_sx,
+            slot_notifier.resubscribe(),
+            num_parallel_tasks,
+        )),
+        poll_cluster_info(rpc_client.clone(), cluster_info_sx),
+        poll_vote_accounts(rpc_client.clone(), va_sx),
+    ];
+
+    let processed_account_stream = if accounts_filter.is_empty() {
+        None
+    } else {
+        let (account_sx, account_stream) =
+            tokio::sync::broadcast::channel::<AccountNotificationMessage>(1024);
+        for account_filter in accounts_filter {
+            endpoint_tasks.push(tokio::spawn(subscribe_accounts(
+                ws_url.clone(),
+                account_filter,
+                account_sx.clone(),
+            )));
+        }
+        Some(account_stream)
+    };
+
+    let streamers = EndpointStreaming {
+        blocks_notifier,
+        blockinfo_notifier,
+        slot_notifier,
+        cluster_info_notifier,
+        vote_account_notifier,
+        processed_account_stream,
+    };
+    Ok((streamers, endpoint_tasks))
+}
+
+// slotSubscribe, polls the processed slot while the websocket is down
+async fn subscribe_slots(
+    rpc_client: Arc<RpcClient>,
+    ws_url: String,
+    slot_tx: tokio::sync::mpsc::UnboundedSender<Slot>,
+) -> anyhow::Result<()> {
+    loop {
+        match PubsubClient::new(&ws_url).await {
+            Ok(pubsub_client) => match pubsub_client.slot_subscribe().await {
+                Ok((mut slot_stream, _unsubscribe)) => {
+                    info!("slot subscription on {} started", ws_url);
+                    while let Some(slot_info) = slot_stream.next().await {
+                        slot_tx.send(slot_info.slot).context("Error sending slot")?;
+                    }
+                    warn!("slot subscription on {} closed", ws_url);
+                }
+                Err(err) => warn!("slot subscribe failed: {}", err),
+            },
+            Err(err) => warn!("failed to connect to {}: {}", ws_url, err),
+        }
+
+        // keep the slots going until the next reconnect attempt
+        if let Ok(res) = tokio::time::timeout(
+            RECONNECT_DELAY,
+            poll_commitment_slots(
+                rpc_client.clone(),
+                CommitmentConfig::processed(),
+                slot_tx.clone(),
+            ),
+        )
+        .await
+        {
+            res?;
+        }
+    }
+}
+
+// blockSubscribe for confirmed and finalized blocks
+// falls back to block polling if the node does not support the subscription
+async fn subscribe_blocks(
+    rpc_client: Arc<RpcClient>,
+    ws_url: String,
+    block_sx: Sender<ProducedBlock>,
+    blockinfo_sx: Sender<BlockInfo>,
+    slot_notifier: Receiver<SlotNotification>,
+    num_parallel_tasks: usize,
+) -> anyhow::Result<()> {
+    let block_subscribe_supported = match PubsubClient::new(&ws_url).await {
+        Ok(pubsub_client) => {
+            let supported = pubsub_client
+                .block_subscribe(
+                    RpcBlockSubscribeFilter::All,
+                    Some(block_subscribe_config(CommitmentConfig::confirmed())),
+                )
+                .await
+                .map(|_| ())
+                .context("block subscribe");
+            let _ = pubsub_client.shutdown().await;
+            supported
+        }
+        Err(err) => Err(err).context("connect"),
+    };
+    if let Err(err) = block_subscribe_supported {
+        warn!(
+            "blockSubscribe not available on {} ({:#}), polling blocks instead",
+            ws_url, err
+        );
+        let tasks = poll_block(
+            rpc_client,
+            block_sx,
+            blockinfo_sx,
+            slot_notifier,
+            num_parallel_tasks,
+        );
+        let (res, _, _) = futures::future::select_all(tasks).await;
+        bail!("block polling exited unexpectedly {res:?}");
+    }
+
+    let confirmed = tokio::spawn(subscribe_blocks_with_commitment(
+        rpc_client.clone(),
+        ws_url.clone(),
+        CommitmentConfig::confirmed(),
+        block_sx.clone(),
+        blockinfo_sx.clone(),
+    ));
+    let finalized = tokio::spawn(subscribe_blocks_with_commitment(
+        rpc_client,
+        ws_url,
+        CommitmentConfig::finalized(),
+        block_sx,
+        blockinfo_sx,
+    ));
+    tokio::select! {
+        res = confirmed => bail!("confirmed block subscription exited unexpectedly {res:?}"),
+        res = finalized => bail!("finalized block subscription exited unexpectedly {res:?}"),
+    }
+}
+
+fn block_subscribe_config(commitment_config: CommitmentConfig) -> RpcBlockSubscribeConfig {
+    RpcBlockSubscribeConfig {
+        commitment: Some(commitment_config),
+        encoding: Some(UiTransactionEncoding::Base64),
+        transaction_details: Some(TransactionDetails::Full),
+        show_rewards: Some(true),
+        max_supported_transaction_version: Some(0),
+    }
+}
+
+async fn subscribe_blocks_with_commitment(
+    rpc_client: Arc<RpcClient>,
+    ws_url: String,
+    commitment_config: CommitmentConfig,
+    block_sx: Sender<ProducedBlock>,
+    blockinfo_sx: Sender<BlockInfo>,
+) -> anyhow::Result<()> {
+    let (gap_sx, gap_rx) = tokio::sync::mpsc::unbounded_channel();
+    let gap_filler = tokio::spawn(fill_block_gaps(
+        rpc_client,
+        commitment_config,
+        gap_rx,
+        block_sx.clone(),
+        blockinfo_sx.clone(),
+    ));
+
+    let subscription = async move {
+        let mut last_slot: Option<Slot> = None;
+        loop {
+            match PubsubClient::new(&ws_url).await {
+                Ok(pubsub_client) => match pubsub_client
+                    .block_subscribe(
+                        RpcBlockSubscribeFilter::All,
+                        Some(block_subscribe_config(commitment_config)),
+                    )
+                    .await
+                {
+                    Ok((mut block_stream, _unsubscribe)) => {
+                        info!(
+                            "{} block subscription on {} started",
+                            commitment_config.commitment, ws_url
+                        );
+                        // only the first block after a reconnect can follow missed blocks
+                        let mut resumed_after = last_slot;
+                        while let Some(update) = block_stream.next().await {
+                            let slot = update.value.slot;
+                            let Some(block) = update.value.block else {
+                                debug!("block update for slot {} without block", slot);
+                                continue;
+                            };
+                            if last_slot.is_some_and(|last_slot| slot <= last_slot) {
+                                continue;
+                            }
+                            // the blocks missed while disconnected are fetched separately
+                            if let Some(resumed_after) = resumed_after.take() {
+                                let missed_slots = missed_slots(resumed_after, slot);
+                                if !missed_slots.is_empty() {
+                                    gap_sx
+                                        .send(missed_slots)
+                                        .context("Block gap filler stopped")?;
+                                }
+                            }
+                            send_block(
+                                &block_sx,
+                                &blockinfo_sx,
+                                from_ui_block(block, slot, commitment_config),
+                            )?;
+                            last_slot = Some(slot);
+                        }
+                        warn!(
+                            "{} block subscription on {} closed",
+                            commitment_config.commitment, ws_url
+                        );
+                    }
+                    Err(err) => warn!("block subscribe failed: {}", err),
+                },
+                Err(err) => warn!("failed to connect to {}: {}", ws_url, err),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    };
+
+    tokio::select! {
+        res = subscription => res,
+        res = gap_filler => bail!("block gap filler exited unexpectedly {res:?}"),
+    }
+}
+
+// slots between the last block and a new one, limited to the MAX_BLOCK_GAP before the new one
+fn missed_slots(last_slot: Slot, slot: Slot) -> Range<Slot> {
+    (last_slot + 1).max(slot.saturating_sub(MAX_BLOCK_GAP))..slot
+}
+
+fn send_block(
+    block_sx: &Sender<ProducedBlock>,
+    blockinfo_sx: &Sender<BlockInfo>,
+    produced_block: ProducedBlock,
+) -> anyhow::Result<()> {
+    let block_info = map_block_info(&produced_block);
+    block_sx
+        .send(produced_block)
+        .context("Block should be sent")?;
+    blockinfo_sx
+        .send(block_info)
+        .context("Block info should be sent")?;
+    Ok(())
+}
+
+// fetches the missed blocks without holding up the subscription, they arrive after the newer block
+async fn fill_block_gaps(
+    rpc_client: Arc<RpcClient>,
+    commitment_config: CommitmentConfig,
+    mut gaps: UnboundedReceiver<Range<Slot>>,
+    block_sx: Sender<ProducedBlock>,
+    blockinfo_sx: Sender<BlockInfo>,
+) -> anyhow::Result<()> {
+    while let Some(missed_slots) = gaps.recv().await {
+        debug!(
+            "fetching {} {} blocks missed while disconnected",
+            missed_slots.end - missed_slots.start,
+            commitment_config.commitment
+        );
+        // skipped slots have no block, only the produced ones are fetched
+        let produced_slots = match rpc_client
+            .get_blocks_with_commitment(
+                missed_slots.start,
+                Some(missed_slots.end - 1),
+                commitment_config,
+            )
+            .await
+        {
+            Ok(produced_slots) => produced_slots,
+            Err(err) => {
+                warn!("getBlocks for the missed slots failed: {}", err);
+                missed_slots.collect()
+            }
+        };
+        for missed_slot in produced_slots {
+            if let Some(produced_block) =
+                process_block(&rpc_client, missed_slot, commitment_config).await
+            {
+                send_block(&block_sx, &blockinfo_sx, produced_block)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// accountSubscribe for the listed accounts and programSubscribe for the program of the filter
+async fn subscribe_accounts(
+    ws_url: String,
+    account_filter: AccountFilter,
+    account_sx: Sender<AccountNotificationMessage>,
+) -> anyhow::Result<()> {
+    let accounts = account_filter
+        .accounts
+        .iter()
+        .map(|account| Pubkey::from_str(account).context("invalid account in filter"))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let program_id = account_filter
+        .program_id
+        .as_ref()
+        .map(|program_id| Pubkey::from_str(program_id).context("invalid program id in filter"))
+        .transpose()?;
+    let account_config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(CommitmentConfig::processed()),
+        ..Default::default()
+    };
+
+    loop {
+        let pubsub_client = match PubsubClient::new(&ws_url).await {
+            Ok(pubsub_client) => pubsub_client,
+            Err(err) => {
+                warn!("failed to connect to {}: {}", ws_url, err);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+
+        let mut streams: Vec<BoxStream<AccountNotificationMessage>> = vec![];
+        let mut all_subscribed = true;
+        for pubkey in &accounts {
+            match pubsub_client
+                .account_subscribe(pubkey, Some(account_config.clone()))
+                .await
+            {
+                Ok((stream, _unsubscribe)) => {
+                    let pubkey = *pubkey;
+                    streams.push(
+                        stream
+                            .filter_map(move |update| async move {
+                                account_notification(pubkey, update.value, update.context.slot)
+                            })
+                            .boxed(),
+                    );
+                }
+                Err(err) => {
+                    warn!("account subscribe for {} failed: {}", pubkey, err);
+                    all_subscribed = false;
+                    break;
+                }
+            }
+        }
+        if let Some(program_id) = program_id.as_ref().filter(|_| all_subscribed) {
+            let program_config = RpcProgramAccountsConfig {
+                filters: account_filter.get_rpc_filter(),
+                account_config: account_config.clone(),
+                ..Default::default()
+            };
+            match pubsub_client
+                .program_subscribe(program_id, Some(program_config))
+                .await
+            {
+                Ok((stream, _unsubscribe)) => {
+                    streams.push(
+                        stream
+                            .filter_map(|update| async move {
+                                let pubkey = Pubkey::from_str(&update.value.pubkey).ok()?;
+                                account_notification(
+                                    pubkey,
+                                    update.value.account,
+                                    update.context.slot,
+                                )
+                            })
+                            .boxed(),
+                    );
+                }
+                Err(err) => {
+                    warn!("program subscribe for {} failed: {}", program_id, err);
+                    all_subscribed = false;
+                }
+            }
+        }
+
+        // a partial set of subscriptions would miss updates, all of them are renewed
+        if all_subscribed && !streams.is_empty() {
+            let mut account_stream = until_any_closed(streams);
+            while let Some(notification) = account_stream.next().await {
+                if account_sx.send(notification).is_err() {
+                    bail!("Account stream broken");
+                }
+            }
+            warn!("account subscription on {} closed - resubscribing", ws_url);
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+// items of all the streams, ends as soon as one of them ends
+fn until_any_closed<'a, T: Send + 'a>(streams: Vec<BoxStream<'a, T>>) -> BoxStream<'a, T> {
+    let streams = streams.into_iter().map(|stream| {
+        stream
+            .map(Some)
+            .chain(futures::stream::once(async { None }))
+            .boxed()
+    });
+    futures::stream::select_all(streams)
+        .take_while(|item| futures::future::ready(item.is_some()))
+        .filter_map(futures::future::ready)
+        .boxed()
+}
+
+fn account_notification(
+    pubkey: Pubkey,
+    ui_account: UiAccount,
+    slot: Slot,
+) -> Option<AccountNotificationMessage> {
+    let account: Account = ui_account.decode()?;
+    Some(AccountNotificationMessage {
+        data: AccountData {
+            pubkey,
+            account: Arc::new(account),
+            updated_slot: slot,
+        },
+        commitment: Commitment::Processed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaps_are_limited_to_the_recent_slots() {
+        assert!(missed_slots(10, 11).is_empty());
+        assert_eq!(missed_slots(10, 15), 11..15);
+        assert_eq!(missed_slots(10, 1000), 1000 - MAX_BLOCK_GAP..1000);
+    }
+
+    #[tokio::test]
+    async fn account_streams_end_with_the_first_closed_subscription() {
+        let (open_sx, open_rx) = futures::channel::mpsc::unbounded::<u64>();
+        let (closed_sx, closed_rx) = futures::channel::mpsc::unbounded::<u64>();
+        closed_sx.unbounded_send(1).unwrap();
+        drop(closed_sx);
+
+        let items = until_any_closed(vec![open_rx.boxed(), closed_rx.boxed()])
+            .collect::<Vec<_>>()
+            .await;
+        // the open subscription does not keep the stream alive
+        assert_eq!(items, vec![1]);
+        drop(open_sx);
+    }
+}
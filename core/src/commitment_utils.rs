@@ -1,6 +1,7 @@
+use serde::{Deserialize, Serialize};
 use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[repr(C)]
 pub enum Commitment {
     Processed = 0,
@@ -15,10 +15,20 @@ pub async fn load_identity_keypair(
         return Ok(None);
     };
 
-    let identity_bytes: Vec<u8> = serde_json::from_str(&identity_jsonarray_str)
+    Ok(Some(parse_identity_keypair(&identity_jsonarray_str)?))
+}
+
+/// loads a keypair file, ignoring the IDENTITY environment variable
+pub async fn load_keypair_file(keyfile_path: &str) -> anyhow::Result<Keypair> {
+    let identity_jsonarray_str = tokio::fs::read_to_string(keyfile_path)
+        .await
+        .context("Cannot find the identity file provided")?;
+    parse_identity_keypair(&identity_jsonarray_str)
+}
+
+fn parse_identity_keypair(identity_jsonarray_str: &str) -> anyhow::Result<Keypair> {
+    let identity_bytes: Vec<u8> = serde_json::from_str(identity_jsonarray_str)
         .context("Invalid identity format expected Vec<u8>")?;
 
-    Ok(Some(
-        Keypair::from_bytes(identity_bytes.as_slice()).context("Invalid identity")?,
-    ))
+    Keypair::from_bytes(identity_bytes.as_slice()).context("Invalid identity")
 }
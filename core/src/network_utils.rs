@@ -1,4 +1,8 @@
+use std::net::SocketAddr;
+
 use quinn::{Connection, TransportConfig};
+use solana_rpc_client_api::response::RpcContactInfo;
+use solana_sdk::quic::QUIC_PORT_OFFSET;
 
 // connection for sending proxy request: FrameStats {
 // ACK: 2, CONNECTION_CLOSE: 0, CRYPTO: 3, DATA_BLOCKED: 0, DATAGRAM: 0, HANDSHAKE_DONE: 1,
@@ -35,3 +39,66 @@ fn disable_gso() -> bool {
         .parse::<bool>()
         .expect("flag must be true or false")
 }
+
+/// quic tpu addresses of a node in order of preference
+/// the tpu forwards address is only known from gossip
+pub fn tpu_quic_addresses(
+    contact_info: &RpcContactInfo,
+    tpu_forwards_quic: Option<SocketAddr>,
+) -> Vec<SocketAddr> {
+    let mut addresses = vec![];
+    // advertised directly by the nodes with the newer gossip contact info
+    addresses.extend(contact_info.tpu_quic);
+    // legacy convention of a quic port at a fixed offset of the udp port
+    if let Some(tpu) = contact_info.tpu {
+        if let Some(port) = tpu.port().checked_add(QUIC_PORT_OFFSET) {
+            addresses.push(SocketAddr::new(tpu.ip(), port));
+        }
+    }
+    addresses.extend(tpu_forwards_quic);
+    let mut unique = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        if !unique.contains(&address) {
+            unique.push(address);
+        }
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    #[test]
+    fn tpu_quic_addresses_in_order_of_preference() {
+        let contact_info = RpcContactInfo {
+            pubkey: Pubkey::new_unique().to_string(),
+            gossip: None,
+            tpu: Some(SocketAddr::from_str("10.0.0.1:8003").unwrap()),
+            tpu_quic: Some(SocketAddr::from_str("10.0.0.1:8009").unwrap()),
+            rpc: None,
+            pubsub: None,
+            version: None,
+            feature_set: None,
+            shred_version: None,
+        };
+        assert_eq!(
+            tpu_quic_addresses(&contact_info, None),
+            vec![SocketAddr::from_str("10.0.0.1:8009").unwrap()]
+        );
+        assert_eq!(
+            tpu_quic_addresses(
+                &contact_info,
+                Some(SocketAddr::from_str("10.0.0.1:8010").unwrap()),
+            ),
+            vec![
+                SocketAddr::from_str("10.0.0.1:8009").unwrap(),
+                SocketAddr::from_str("10.0.0.1:8010").unwrap(),
+            ]
+        );
+    }
+}
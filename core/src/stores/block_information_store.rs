@@ -159,6 +159,15 @@ impl BlockInformationStore {
         }
     }
 
+    /// forget a processed block which got orphaned, blocks of higher commitment are kept
+    pub fn remove_processed_block(&self, blockhash: &Hash) -> bool {
+        self.blocks
+            .remove_if(blockhash, |_, block_info| {
+                block_info.commitment_config.is_processed()
+            })
+            .is_some()
+    }
+
     pub async fn clean(&self) {
         let finalized_block_information = self
             .get_latest_block_information(CommitmentConfig::finalized())
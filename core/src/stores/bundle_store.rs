@@ -0,0 +1,101 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{signature::Signature, slot_history::Slot, transaction::TransactionError};
+use solana_transaction_status::TransactionConfirmationStatus;
+use std::sync::Arc;
+
+use super::tx_store::TxStore;
+
+/// Bundle Properties
+#[derive(Debug, Clone)]
+pub struct BundleProps {
+    pub signatures: Vec<Signature>,
+    pub last_valid_blockheight: u64,
+}
+
+/// Combined status of all the transactions of a bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleStatus {
+    pub bundle_id: String,
+    pub transactions: Vec<String>,
+    // highest slot in which a transaction of the bundle landed
+    pub slot: Option<Slot>,
+    // set only when all the transactions of the bundle landed, lowest commitment of them
+    pub confirmation_status: Option<TransactionConfirmationStatus>,
+    pub err: Option<TransactionError>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BundleStore {
+    pub store: Arc<DashMap<Signature, BundleProps>>,
+}
+
+impl BundleStore {
+    pub fn insert(&self, bundle_id: Signature, props: BundleProps) -> Option<BundleProps> {
+        self.store.insert(bundle_id, props)
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    pub fn get_status(&self, bundle_id: &Signature, txs: &TxStore) -> Option<BundleStatus> {
+        let props = self.store.get(bundle_id)?.value().clone();
+        let statuses = props
+            .signatures
+            .iter()
+            .map(|signature| txs.get(signature).and_then(|props| props.status))
+            .collect::<Vec<_>>();
+
+        let slot = statuses.iter().flatten().map(|status| status.slot).max();
+        let err = statuses
+            .iter()
+            .flatten()
+            .find_map(|status| status.err.clone());
+        let confirmation_status = if statuses.iter().all(|status| status.is_some()) {
+            statuses
+                .iter()
+                .flatten()
+                .map(|status| {
+                    status
+                        .confirmation_status
+                        .clone()
+                        .unwrap_or(TransactionConfirmationStatus::Processed)
+                })
+                .min_by_key(confirmation_rank)
+        } else {
+            None
+        };
+
+        Some(BundleStatus {
+            bundle_id: bundle_id.to_string(),
+            transactions: props.signatures.iter().map(|s| s.to_string()).collect(),
+            slot,
+            confirmation_status,
+            err,
+        })
+    }
+
+    pub fn clean(&self, current_finalized_blockheight: u64) {
+        let length_before = self.store.len();
+        self.store
+            .retain(|_k, v| v.last_valid_blockheight >= current_finalized_blockheight);
+        log::info!(
+            "Cleaned {} bundles",
+            length_before.saturating_sub(self.store.len())
+        );
+    }
+}
+
+fn confirmation_rank(status: &TransactionConfirmationStatus) -> u8 {
+    match status {
+        TransactionConfirmationStatus::Processed => 0,
+        TransactionConfirmationStatus::Confirmed => 1,
+        TransactionConfirmationStatus::Finalized => 2,
+    }
+}
@@ -1,13 +1,46 @@
 use dashmap::DashMap;
+use serde::Serialize;
 use solana_rpc_client_api::response::RpcContactInfo;
 use solana_sdk::pubkey::Pubkey;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{str::FromStr, sync::Arc};
 
 use crate::types::ClusterInfoStream;
 
+// contact infos seen in gossip are not overwritten by the rpc snapshots for this long
+const GOSSIP_PRECEDENCE: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ContactInfoSource {
+    Rpc,
+    Gossip,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactInfoSeen {
+    pub source: ContactInfoSource,
+    // unix timestamp in ms when lite-rpc received the contact info
+    pub last_seen_ms: u64,
+    // unix timestamp in ms the node signed the contact info with, only from gossip
+    pub wallclock_ms: Option<u64>,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct ClusterInfo {
     pub cluster_nodes: Arc<DashMap<Pubkey, Arc<RpcContactInfo>>>,
+    pub last_seen: Arc<DashMap<Pubkey, ContactInfoSeen>>,
+    // not part of the rpc contact info, only known from gossip
+    pub tpu_forwards_quic: Arc<DashMap<Pubkey, SocketAddr>>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
 }
 
 impl ClusterInfo {
@@ -19,11 +52,175 @@ impl ClusterInfo {
             .recv()
             .await
             .expect("Failed to recieve on broadcast channel");
+        let now = now_ms();
+        let gossip_precedence_from = now.saturating_sub(GOSSIP_PRECEDENCE.as_millis() as u64);
         cluster_nodes.iter().for_each(|x| {
             if let Ok(pubkey) = Pubkey::from_str(x.pubkey.as_str()) {
+                let fresh_from_gossip = self.last_seen.get(&pubkey).is_some_and(|seen| {
+                    seen.source == ContactInfoSource::Gossip
+                        && seen.last_seen_ms >= gossip_precedence_from
+                });
+                if fresh_from_gossip {
+                    return;
+                }
                 self.cluster_nodes.insert(pubkey, Arc::new(x.clone()));
+                self.last_seen.insert(
+                    pubkey,
+                    ContactInfoSeen {
+                        source: ContactInfoSource::Rpc,
+                        last_seen_ms: now,
+                        wallclock_ms: None,
+                    },
+                );
             }
         });
         Ok(())
     }
+
+    pub fn update_from_gossip(
+        &self,
+        pubkey: Pubkey,
+        contact_info: RpcContactInfo,
+        tpu_forwards_quic: Option<SocketAddr>,
+        wallclock: u64,
+    ) {
+        self.cluster_nodes.insert(pubkey, Arc::new(contact_info));
+        if let Some(tpu_forwards_quic) = tpu_forwards_quic {
+            self.tpu_forwards_quic.insert(pubkey, tpu_forwards_quic);
+        } else {
+            self.tpu_forwards_quic.remove(&pubkey);
+        }
+        self.last_seen.insert(
+            pubkey,
+            ContactInfoSeen {
+                source: ContactInfoSource::Gossip,
+                last_seen_ms: now_ms(),
+                wallclock_ms: Some(wallclock),
+            },
+        );
+    }
+
+    /// the contact info applied from gossip is still there unchanged
+    /// false if the node has no contact info from gossip anymore
+    pub fn refresh_from_gossip(&self, pubkey: &Pubkey) -> bool {
+        match self.last_seen.get_mut(pubkey) {
+            Some(mut seen) if seen.source == ContactInfoSource::Gossip => {
+                seen.last_seen_ms = now_ms();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// the node left gossip, the contact infos of the rpc snapshot are kept
+    pub fn remove_from_gossip(&self, pubkey: &Pubkey) {
+        if self
+            .last_seen
+            .remove_if(pubkey, |_, seen| seen.source == ContactInfoSource::Gossip)
+            .is_some()
+        {
+            self.cluster_nodes.remove(pubkey);
+            self.tpu_forwards_quic.remove(pubkey);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact_info(pubkey: &Pubkey, version: &str) -> RpcContactInfo {
+        RpcContactInfo {
+            pubkey: pubkey.to_string(),
+            gossip: None,
+            tpu: None,
+            tpu_quic: None,
+            rpc: None,
+            pubsub: None,
+            version: Some(version.to_string()),
+            feature_set: None,
+            shred_version: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn rpc_snapshot_does_not_overwrite_fresh_gossip() {
+        let cluster_info = ClusterInfo::default();
+        let (from_gossip, from_rpc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        cluster_info.update_from_gossip(
+            from_gossip,
+            contact_info(&from_gossip, "gossip"),
+            None,
+            42,
+        );
+
+        let (sender, mut receiver) = tokio::sync::broadcast::channel(1);
+        sender
+            .send(vec![
+                contact_info(&from_gossip, "rpc"),
+                contact_info(&from_rpc, "rpc"),
+            ])
+            .unwrap();
+        cluster_info.load_cluster_info(&mut receiver).await.unwrap();
+
+        let version = |pubkey| {
+            cluster_info
+                .cluster_nodes
+                .get(pubkey)
+                .unwrap()
+                .version
+                .clone()
+        };
+        assert_eq!(version(&from_gossip).as_deref(), Some("gossip"));
+        assert_eq!(version(&from_rpc).as_deref(), Some("rpc"));
+        let seen = cluster_info.last_seen.get(&from_gossip).unwrap().clone();
+        assert_eq!(seen.source, ContactInfoSource::Gossip);
+        assert_eq!(seen.wallclock_ms, Some(42));
+        assert_eq!(
+            cluster_info.last_seen.get(&from_rpc).unwrap().source,
+            ContactInfoSource::Rpc
+        );
+    }
+
+    #[test]
+    fn gossip_nodes_are_refreshed_and_removed() {
+        let cluster_info = ClusterInfo::default();
+        let (from_gossip, from_rpc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(!cluster_info.refresh_from_gossip(&from_gossip));
+        cluster_info.update_from_gossip(
+            from_gossip,
+            contact_info(&from_gossip, "gossip"),
+            Some(SocketAddr::from_str("10.0.0.1:8010").unwrap()),
+            42,
+        );
+        cluster_info
+            .last_seen
+            .get_mut(&from_gossip)
+            .unwrap()
+            .last_seen_ms = 0;
+        assert!(cluster_info.refresh_from_gossip(&from_gossip));
+        let seen = cluster_info.last_seen.get(&from_gossip).unwrap().clone();
+        assert!(seen.last_seen_ms > 0);
+        assert_eq!(seen.wallclock_ms, Some(42));
+
+        cluster_info
+            .cluster_nodes
+            .insert(from_rpc, Arc::new(contact_info(&from_rpc, "rpc")));
+        cluster_info.last_seen.insert(
+            from_rpc,
+            ContactInfoSeen {
+                source: ContactInfoSource::Rpc,
+                last_seen_ms: 1,
+                wallclock_ms: None,
+            },
+        );
+        assert!(!cluster_info.refresh_from_gossip(&from_rpc));
+
+        cluster_info.remove_from_gossip(&from_gossip);
+        cluster_info.remove_from_gossip(&from_rpc);
+        assert!(!cluster_info.cluster_nodes.contains_key(&from_gossip));
+        assert!(!cluster_info.last_seen.contains_key(&from_gossip));
+        assert!(cluster_info.tpu_forwards_quic.is_empty());
+        assert!(cluster_info.cluster_nodes.contains_key(&from_rpc));
+    }
 }
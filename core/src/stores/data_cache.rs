@@ -8,14 +8,14 @@ use tokio::sync::RwLock;
 
 use crate::{
     stores::{
-        block_information_store::BlockInformationStore, cluster_info_store::ClusterInfo,
-        subscription_store::SubscriptionStore, tx_store::TxStore,
+        block_information_store::BlockInformationStore, bundle_store::BundleStore,
+        cluster_info_store::ClusterInfo, subscription_store::SubscriptionStore, tx_store::TxStore,
     },
     structures::{
         epoch::{Epoch, EpochCache},
         identity_stakes::IdentityStakes,
         slot_notification::{AtomicSlot, SlotNotification},
-        transaction_sent_info::SentTransactionInfo,
+        transaction_sent_info::{SentTransactionInfo, TransactionToSend},
     },
 };
 
@@ -33,6 +33,7 @@ pub struct SlotCache {
 pub struct DataCache {
     pub block_information_store: BlockInformationStore,
     pub txs: TxStore,
+    pub bundles: BundleStore,
     pub tx_subs: SubscriptionStore,
     pub slot_cache: SlotCache,
     pub identity_stakes: IdentityStakes,
@@ -49,6 +50,7 @@ impl DataCache {
             .await;
         self.block_information_store.clean().await;
         self.txs.clean(block_info.block_height);
+        self.bundles.clean(block_info.block_height);
 
         self.tx_subs.clean(ttl_duration);
     }
@@ -64,6 +66,34 @@ impl DataCache {
                 .is_transaction_confirmed(&sent_transaction_info.signature)
     }
 
+    // a bundle is done when it expired or when all of its transactions are confirmed
+    pub fn check_if_confirmed_or_expired(&self, transaction: &TransactionToSend) -> bool {
+        match transaction {
+            TransactionToSend::Single(tx) => self.check_if_confirmed_or_expired_blockheight(tx),
+            TransactionToSend::Bundle(bundle) => {
+                let last_block_height = self.block_information_store.get_last_blockheight();
+                last_block_height > transaction.last_valid_block_height()
+                    || bundle
+                        .transactions
+                        .iter()
+                        .all(|tx| self.txs.is_transaction_confirmed(&tx.signature))
+            }
+        }
+    }
+
+    // like check_if_confirmed_or_expired but processed transactions are not done yet
+    pub fn check_if_confirmed_or_finalized_or_expired(
+        &self,
+        transaction: &TransactionToSend,
+    ) -> bool {
+        let last_block_height = self.block_information_store.get_last_blockheight();
+        last_block_height > transaction.last_valid_block_height()
+            || transaction.transactions().iter().all(|tx| {
+                self.txs
+                    .is_transaction_confirmed_or_finalized(&tx.signature)
+            })
+    }
+
     pub async fn get_current_epoch(&self, commitment: CommitmentConfig) -> Epoch {
         let BlockInformation { slot, .. } = self
             .block_information_store
@@ -90,6 +120,7 @@ impl DataCache {
             txs: TxStore {
                 store: Arc::new(DashMap::new()),
             },
+            bundles: BundleStore::default(),
             epoch_data: EpochCache::new_for_tests(),
             leader_schedule: Arc::new(RwLock::new(CalculatedSchedule::default())),
         }
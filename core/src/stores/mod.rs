@@ -1,6 +1,7 @@
 // this mod will contain all the different stores that are used by lite-rpc
 
 pub mod block_information_store;
+pub mod bundle_store;
 pub mod cluster_info_store;
 pub mod data_cache;
 pub mod subscription_store;
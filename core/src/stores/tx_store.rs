@@ -1,6 +1,7 @@
+use crate::structures::orphaned_block::OrphanedBlock;
 use dashmap::DashMap;
 use solana_sdk::signature::Signature;
-use solana_transaction_status::TransactionStatus;
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
 use std::sync::Arc;
 
 /// Transaction Properties
@@ -70,10 +71,53 @@ impl TxStore {
         );
     }
 
+    /// drop the processed status the transactions got from an orphaned block, returns the number of rolled back transactions
+    pub fn rollback_processed(&self, orphaned_block: &OrphanedBlock) -> usize {
+        let mut rolled_back = 0;
+        for signature in &orphaned_block.signatures {
+            let Some(mut props) = self.store.get_mut(signature) else {
+                continue;
+            };
+            let from_orphaned_block = props.status.as_ref().is_some_and(|status| {
+                status.slot == orphaned_block.slot
+                    && matches!(
+                        status.confirmation_status,
+                        Some(TransactionConfirmationStatus::Processed)
+                    )
+            });
+            if !from_orphaned_block {
+                continue;
+            }
+            rolled_back += 1;
+            if props.sent_by_lite_rpc {
+                props.status = None;
+            } else {
+                // only known from the orphaned block
+                drop(props);
+                self.store.remove(signature);
+            }
+        }
+        rolled_back
+    }
+
     pub fn is_transaction_confirmed(&self, signature: &Signature) -> bool {
         match self.store.get(signature) {
             Some(props) => props.status.is_some(),
             None => false,
         }
     }
+
+    // processed is not enough
+    pub fn is_transaction_confirmed_or_finalized(&self, signature: &Signature) -> bool {
+        match self.store.get(signature) {
+            Some(props) => props.status.as_ref().is_some_and(|status| {
+                matches!(
+                    status.confirmation_status,
+                    Some(TransactionConfirmationStatus::Confirmed)
+                        | Some(TransactionConfirmationStatus::Finalized)
+                )
+            }),
+            None => false,
+        }
+    }
 }
@@ -1,7 +1,8 @@
+use serde::{Deserialize, Serialize};
 use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::hash::Hash;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BlockInfo {
     pub slot: u64,
     pub parent: u64,
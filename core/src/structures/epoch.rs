@@ -63,6 +63,12 @@ pub struct EpochCache {
 }
 
 impl EpochCache {
+    pub fn new(epoch_schedule: EpochSchedule) -> Self {
+        Self {
+            epoch_schedule: Arc::new(epoch_schedule),
+        }
+    }
+
     pub fn get_epoch_at_slot(&self, slot: Slot) -> Epoch {
         let (epoch, slot_index) = self.epoch_schedule.get_epoch_and_slot_index(slot);
         let slots_in_epoch = self.epoch_schedule.get_slots_in_epoch(epoch);
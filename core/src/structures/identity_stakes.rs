@@ -29,15 +29,18 @@ impl Default for IdentityStakesData {
 
 #[derive(Debug, Clone)]
 pub struct IdentityStakes {
-    identity: Pubkey,
+    identity: Arc<RwLock<Pubkey>>,
     stakes_data: Arc<RwLock<IdentityStakesData>>,
+    // kept to compute the stakes when the identity changes
+    vote_accounts: Arc<RwLock<Option<RpcVoteAccountStatus>>>,
 }
 
 impl IdentityStakes {
     pub fn new(identity: Pubkey) -> Self {
         Self {
-            identity,
+            identity: Arc::new(RwLock::new(identity)),
             stakes_data: Arc::new(RwLock::new(IdentityStakesData::default())),
+            vote_accounts: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -45,7 +48,14 @@ impl IdentityStakes {
         *self.stakes_data.read().await
     }
 
-    pub async fn update_stakes_for_identity(&self, vote_accounts: RpcVoteAccountStatus) {
+    pub async fn get_identity(&self) -> Pubkey {
+        *self.identity.read().await
+    }
+
+    fn compute_stakes(
+        identity: &Pubkey,
+        vote_accounts: &RpcVoteAccountStatus,
+    ) -> Option<IdentityStakesData> {
         let map_of_stakes: HashMap<String, u64> = vote_accounts
             .current
             .iter()
@@ -53,16 +63,20 @@ impl IdentityStakes {
             .map(|x| (x.node_pubkey.clone(), x.activated_stake))
             .collect();
 
-        if let Some(stakes) = map_of_stakes.get(&self.identity.to_string()) {
-            let only_stakes = map_of_stakes.iter().map(|x| *x.1).collect_vec();
-            let identity_stakes = IdentityStakesData {
-                peer_type: ConnectionPeerType::Staked(*stakes), // Staked -> Staked(*stakes) .. not sure if that is correct
-                stakes: *stakes,
-                min_stakes: only_stakes.iter().min().map_or(0, |x| *x),
-                max_stakes: only_stakes.iter().max().map_or(0, |x| *x),
-                total_stakes: only_stakes.iter().sum(),
-            };
+        let stakes = map_of_stakes.get(&identity.to_string())?;
+        let only_stakes = map_of_stakes.iter().map(|x| *x.1).collect_vec();
+        Some(IdentityStakesData {
+            peer_type: ConnectionPeerType::Staked(*stakes), // Staked -> Staked(*stakes) .. not sure if that is correct
+            stakes: *stakes,
+            min_stakes: only_stakes.iter().min().map_or(0, |x| *x),
+            max_stakes: only_stakes.iter().max().map_or(0, |x| *x),
+            total_stakes: only_stakes.iter().sum(),
+        })
+    }
 
+    pub async fn update_stakes_for_identity(&self, vote_accounts: RpcVoteAccountStatus) {
+        let identity = self.get_identity().await;
+        if let Some(identity_stakes) = Self::compute_stakes(&identity, &vote_accounts) {
             log::info!(
                 "Identity stakes {}, {}, {}, {}",
                 identity_stakes.total_stakes,
@@ -72,5 +86,34 @@ impl IdentityStakes {
             );
             *self.stakes_data.write().await = identity_stakes;
         }
+        *self.vote_accounts.write().await = Some(vote_accounts);
+    }
+
+    /// stakes of any identity in the last vote accounts, 0 if not found
+    pub async fn stakes_of(&self, identity: &Pubkey) -> u64 {
+        self.vote_accounts
+            .read()
+            .await
+            .as_ref()
+            .and_then(|vote_accounts| Self::compute_stakes(identity, vote_accounts))
+            .map_or(0, |stakes| stakes.stakes)
+    }
+
+    /// switch to another identity, it is unstaked if not found in the last vote accounts
+    pub async fn set_identity(&self, identity: Pubkey) -> IdentityStakesData {
+        *self.identity.write().await = identity;
+        let identity_stakes = self
+            .vote_accounts
+            .read()
+            .await
+            .as_ref()
+            .and_then(|vote_accounts| Self::compute_stakes(&identity, vote_accounts))
+            .unwrap_or_default();
+        log::info!(
+            "Identity changed to {identity} with stakes {}",
+            identity_stakes.stakes
+        );
+        *self.stakes_data.write().await = identity_stakes;
+        identity_stakes
     }
 }
@@ -37,23 +37,36 @@ impl TryFrom<RpcGetVoteAccountsConfig> for GetVoteAccountsConfig {
 
 #[derive(Clone, Default, Debug)]
 pub struct CalculatedSchedule {
+    pub previous: Option<LeaderScheduleData>,
     pub current: Option<LeaderScheduleData>,
     pub next: Option<LeaderScheduleData>,
 }
 
 impl CalculatedSchedule {
+    /// the next epoch becomes current, the current one previous
+    pub fn rotate_to_epoch(&mut self, epoch: u64) -> bool {
+        if !self.next.as_ref().is_some_and(|next| next.epoch == epoch) {
+            return false;
+        }
+        self.previous = self.current.take();
+        self.current = self.next.take();
+        true
+    }
+
+    pub fn get_schedule_for_epoch(&self, epoch: u64) -> Option<&LeaderScheduleData> {
+        [&self.previous, &self.current, &self.next]
+            .into_iter()
+            .flatten()
+            .find(|schedule_data| schedule_data.epoch == epoch)
+    }
+
     pub async fn get_leader_schedule_for_slot(
         &self,
         slot: Option<u64>,
         commitment: Option<CommitmentConfig>,
+        identity: Option<&Pubkey>,
         data_cache: &DataCache,
     ) -> Option<HashMap<String, Vec<usize>>> {
-        log::debug!(
-            "get_leader_schedule_for_slot current:{:?} next:{:?} ",
-            self.current.clone().unwrap_or_default(),
-            self.next.clone().unwrap_or_default()
-        );
-
         let commitment = commitment.unwrap_or_default();
         let slot = match slot {
             Some(slot) => slot,
@@ -66,15 +79,37 @@ impl CalculatedSchedule {
             }
         };
         let epoch = data_cache.epoch_data.get_epoch_at_slot(slot);
+        self.get_leader_schedule_for_epoch(epoch.epoch, identity)
+    }
 
-        let get_schedule = |schedule_data: Option<&LeaderScheduleData>| {
-            schedule_data.and_then(|current| {
-                (current.epoch == epoch.epoch).then_some(current.schedule_by_node.clone())
-            })
+    // same as the validator, an unknown identity gives an empty schedule
+    pub fn get_leader_schedule_for_epoch(
+        &self,
+        epoch: u64,
+        identity: Option<&Pubkey>,
+    ) -> Option<HashMap<String, Vec<usize>>> {
+        log::debug!(
+            "get_leader_schedule_for_epoch {epoch} previous:{:?} current:{:?} next:{:?}",
+            self.previous.as_ref().map(|data| data.epoch),
+            self.current.as_ref().map(|data| data.epoch),
+            self.next.as_ref().map(|data| data.epoch)
+        );
+        let schedule_data = self.get_schedule_for_epoch(epoch)?;
+        let schedule = match identity {
+            Some(identity) => {
+                let identity = identity.to_string();
+                schedule_data
+                    .schedule_by_node
+                    .get(&identity)
+                    .map(|slots| HashMap::from([(identity, slots.clone())]))
+                    .unwrap_or_default()
+            }
+            None => schedule_data.schedule_by_node.clone(),
         };
-        get_schedule(self.current.as_ref()).or_else(|| get_schedule(self.next.as_ref()))
+        Some(schedule)
     }
 
+    /// the errors are the invalid params messages of the validator
     pub async fn get_slot_leaders(
         &self,
         start_slot: Slot,
@@ -89,51 +124,32 @@ impl CalculatedSchedule {
         pub const MAX_GET_SLOT_LEADERS: usize =
             solana_rpc_client_api::request::MAX_GET_SLOT_LEADERS;
 
-        let mut limit = limit as usize;
+        let limit = limit as usize;
         if limit > MAX_GET_SLOT_LEADERS {
-            return Err(format!(
-                "Invalid Params: Invalid limit; max {MAX_GET_SLOT_LEADERS}"
-            ));
+            return Err(format!("Invalid limit; max {MAX_GET_SLOT_LEADERS}"));
         }
 
-        let (epoch, slot_index) = epock_schedule.get_epoch_and_slot_index(start_slot);
+        // the range continues in the following epochs
+        let (mut epoch, mut slot_index) = epock_schedule.get_epoch_and_slot_index(start_slot);
         let mut slot_leaders = Vec::with_capacity(limit);
-
-        let mut extend_slot_from_epoch = |leader_schedule: &[Pubkey], slot_index: usize| {
-            let take = limit.saturating_sub(slot_leaders.len());
-            slot_leaders.extend(leader_schedule.iter().skip(slot_index).take(take));
-            limit -= slot_leaders.len();
-        };
-
-        // log::info!(
-        //     "get_slot_leaders  epoch:{epoch} current:{:?} next:{:?} ",
-        //     self.current.clone().unwrap_or_default(),
-        //     self.next.clone().unwrap_or_default()
-        // );
-
-        //TODO manage more leader schedule data in storage.
-        //Here  only search  on current and next epoch
-        let res = [
-            (&self.current, slot_index as usize, epoch),
-            (&self.next, slot_index as usize, epoch),
-            (&self.next, 0, epoch + 1),
-        ]
-        .into_iter()
-        .filter_map(|(epoch_data, slot_index, epoch)| {
-            epoch_data.as_ref().and_then(|epoch_data| {
-                (epoch_data.epoch == epoch).then_some((epoch_data, slot_index))
-            })
-        })
-        .map(|(epoch_data, slot_index)| {
-            extend_slot_from_epoch(&epoch_data.schedule_by_slot, slot_index);
-        })
-        .collect::<Vec<()>>();
-        match res.is_empty()  {
-            true => Err(format!(
-                "Invalid Params: Invalid slot range: leader schedule for epoch {epoch} is unavailable"
-            )),
-            false => Ok(slot_leaders),
+        while slot_leaders.len() < limit {
+            let Some(schedule_data) = self.get_schedule_for_epoch(epoch) else {
+                return Err(format!(
+                    "Invalid slot range: leader schedule for epoch {epoch} is unavailable"
+                ));
+            };
+            let take = limit - slot_leaders.len();
+            slot_leaders.extend(
+                schedule_data
+                    .schedule_by_slot
+                    .iter()
+                    .skip(slot_index as usize)
+                    .take(take),
+            );
+            epoch += 1;
+            slot_index = 0;
         }
+        Ok(slot_leaders)
     }
 }
 
@@ -143,3 +159,97 @@ pub struct LeaderScheduleData {
     pub schedule_by_slot: Vec<Pubkey>,
     pub epoch: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule_data(epoch: u64, slots_in_epoch: usize) -> LeaderScheduleData {
+        let leaders = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let schedule_by_slot = (0..slots_in_epoch)
+            .map(|slot_index| leaders[slot_index / 4 % 2])
+            .collect::<Vec<_>>();
+        let mut schedule_by_node = HashMap::<String, Vec<usize>>::new();
+        for (slot_index, leader) in schedule_by_slot.iter().enumerate() {
+            schedule_by_node
+                .entry(leader.to_string())
+                .or_default()
+                .push(slot_index);
+        }
+        LeaderScheduleData {
+            schedule_by_node,
+            schedule_by_slot,
+            epoch,
+        }
+    }
+
+    #[tokio::test]
+    async fn slot_leaders_across_epochs() {
+        let epoch_schedule = EpochSchedule::without_warmup();
+        let slots_in_epoch = epoch_schedule.slots_per_epoch as usize;
+        let mut schedule = CalculatedSchedule {
+            previous: None,
+            current: Some(schedule_data(4, slots_in_epoch)),
+            next: Some(schedule_data(5, slots_in_epoch)),
+        };
+        assert!(schedule.rotate_to_epoch(5));
+        assert!(!schedule.rotate_to_epoch(6));
+        schedule.next = Some(schedule_data(6, slots_in_epoch));
+
+        // from the last 10 slots of epoch 4 into epoch 5
+        let start_slot = epoch_schedule.get_first_slot_in_epoch(5) - 10;
+        let slot_leaders = schedule
+            .get_slot_leaders(start_slot, 5000, &epoch_schedule)
+            .await
+            .unwrap();
+        assert_eq!(slot_leaders.len(), 5000);
+        let previous = schedule.previous.as_ref().unwrap();
+        let current = schedule.current.as_ref().unwrap();
+        assert_eq!(
+            slot_leaders[..10],
+            previous.schedule_by_slot[slots_in_epoch - 10..]
+        );
+        assert_eq!(slot_leaders[10..], current.schedule_by_slot[..4990]);
+
+        assert_eq!(
+            schedule
+                .get_slot_leaders(start_slot, 5001, &epoch_schedule)
+                .await,
+            Err("Invalid limit; max 5000".to_string())
+        );
+        let last_known_slot = epoch_schedule.get_last_slot_in_epoch(6);
+        assert_eq!(
+            schedule
+                .get_slot_leaders(last_known_slot, 2, &epoch_schedule)
+                .await,
+            Err("Invalid slot range: leader schedule for epoch 7 is unavailable".to_string())
+        );
+        assert_eq!(
+            schedule
+                .get_slot_leaders(last_known_slot, 1, &epoch_schedule)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn leader_schedule_identity_filter() {
+        let schedule = CalculatedSchedule {
+            current: Some(schedule_data(4, 64)),
+            ..CalculatedSchedule::default()
+        };
+        let leader = schedule.current.as_ref().unwrap().schedule_by_slot[0];
+        let filtered = schedule
+            .get_leader_schedule_for_epoch(4, Some(&leader))
+            .unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[&leader.to_string()].len(), 32);
+        assert_eq!(
+            schedule.get_leader_schedule_for_epoch(4, Some(&Pubkey::new_unique())),
+            Some(HashMap::new())
+        );
+        assert_eq!(schedule.get_leader_schedule_for_epoch(5, None), None);
+    }
+}
@@ -8,9 +8,11 @@ pub mod identity_stakes;
 pub mod leader_data;
 pub mod leaderschedule;
 pub mod notifications;
+pub mod orphaned_block;
 pub mod prioritization_fee_heap;
 pub mod produced_block;
 pub mod proxy_request_format;
 pub mod rotating_queue;
 pub mod slot_notification;
+pub mod transaction_priority;
 pub mod transaction_sent_info;
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::Hash;
+use solana_sdk::signature::Signature;
+use solana_sdk::slot_history::Slot;
+
+/// processed block which did not make it into the confirmed chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanedBlock {
+    pub slot: Slot,
+    pub blockhash: Hash,
+    pub parent_slot: Slot,
+    // transactions which got the processed status from this block
+    pub signatures: Vec<Signature>,
+}
@@ -1,147 +1,194 @@
 use std::{
-    collections::{BTreeMap, HashSet, VecDeque},
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap},
     sync::Arc,
 };
 
-use solana_sdk::signature::Signature;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
 use tokio::sync::Mutex;
 
-use super::transaction_sent_info::SentTransactionInfo;
+use super::transaction_sent_info::TransactionToSend;
+
+// ordered by priority score, then first inserted first popped among equal scores
+type HeapKey = (u64, Reverse<u64>);
 
 #[derive(Default)]
 struct PrioFeeHeapData {
-    map: BTreeMap<u64, VecDeque<SentTransactionInfo>>,
-    signatures: HashSet<Signature>,
+    map: BTreeMap<HeapKey, TransactionToSend>,
+    keys: HashMap<Signature, HeapKey>,
+    by_sender: HashMap<Pubkey, BTreeSet<HeapKey>>,
+    // senders ordered by the number of transactions they hold
+    sender_counts: BTreeSet<(usize, Pubkey)>,
+    next_sequence: u64,
+}
+
+impl PrioFeeHeapData {
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn sender_count(&self, sender: &Pubkey) -> usize {
+        self.by_sender.get(sender).map_or(0, |keys| keys.len())
+    }
+
+    fn push(&mut self, tx: TransactionToSend) {
+        let key = (tx.priority_score(), Reverse(self.next_sequence));
+        self.next_sequence += 1;
+        let sender = tx.fee_payer();
+        let count = self.sender_count(&sender);
+        self.sender_counts.remove(&(count, sender));
+        self.sender_counts.insert((count + 1, sender));
+        self.by_sender.entry(sender).or_default().insert(key);
+        self.keys.insert(tx.id(), key);
+        self.map.insert(key, tx);
+    }
+
+    fn remove(&mut self, key: &HeapKey) -> Option<TransactionToSend> {
+        let tx = self.map.remove(key)?;
+        self.keys.remove(&tx.id());
+        let sender = tx.fee_payer();
+        let count = self.sender_count(&sender);
+        self.sender_counts.remove(&(count, sender));
+        if count > 1 {
+            self.sender_counts.insert((count - 1, sender));
+            self.by_sender.get_mut(&sender).unwrap().remove(key);
+        } else {
+            self.by_sender.remove(&sender);
+        }
+        Some(tx)
+    }
+
+    // lowest priority transaction, optionally only among the ones of a sender
+    fn lowest(&self, sender: Option<&Pubkey>) -> Option<HeapKey> {
+        match sender {
+            Some(sender) => self.by_sender.get(sender)?.first().copied(),
+            None => self.map.keys().next().copied(),
+        }
+    }
+
+    // sender holding the most transactions if it holds more than the cap
+    fn most_over_cap_sender(&self, max_transactions_per_sender: usize) -> Option<Pubkey> {
+        self.sender_counts
+            .last()
+            .filter(|(count, _)| *count > max_transactions_per_sender)
+            .map(|(_, sender)| *sender)
+    }
 }
 
+/// Transactions waiting to be sent, highest priority first
+///
+/// Once full a fee payer holds at most `max_transactions_per_sender` transactions as long as others
+/// compete for the space. The cap is per fee payer which clients can rotate freely, so it stops a
+/// single busy sender from filling the heap but is no defense against one spreading over many fee payers.
 #[derive(Default, Clone)]
 pub struct PrioritizationFeesHeap {
     map: Arc<Mutex<PrioFeeHeapData>>,
     max_number_of_transactions: usize,
+    max_transactions_per_sender: usize,
 }
 
 impl PrioritizationFeesHeap {
-    pub fn new(max_number_of_transactions: usize) -> Self {
+    pub fn new(max_number_of_transactions: usize, max_transactions_per_sender: usize) -> Self {
         Self {
             map: Arc::new(Mutex::new(PrioFeeHeapData::default())),
             max_number_of_transactions,
+            max_transactions_per_sender: max_transactions_per_sender.max(1),
         }
     }
 
-    pub async fn pop(&self) -> Option<SentTransactionInfo> {
+    pub async fn pop(&self) -> Option<TransactionToSend> {
         let mut write_lock = self.map.lock().await;
-        if let Some(mut entry) = write_lock.map.last_entry() {
-            let element = entry.get_mut().pop_front().unwrap();
-            if entry.get().is_empty() {
-                entry.remove();
-            }
-            write_lock.signatures.remove(&element.signature);
-            return Some(element);
-        }
-        None
+        let key = *write_lock.map.keys().next_back()?;
+        write_lock.remove(&key)
     }
 
-    pub async fn insert(&self, tx: SentTransactionInfo) {
+    pub async fn insert(&self, tx: TransactionToSend) {
         let mut write_lock = self.map.lock().await;
+        let priority_score = tx.priority_score();
 
-        if write_lock.signatures.contains(&tx.signature) {
+        if write_lock.keys.contains_key(&tx.id()) {
             // signature already in the list
             return;
         }
 
-        if write_lock.signatures.len() >= self.max_number_of_transactions {
-            // check if prioritization is more than prioritization in the map
-            if tx.prioritization_fee <= *write_lock.map.first_entry().unwrap().key() {
-                return;
-            }
-        }
-
-        write_lock.signatures.insert(tx.signature);
-        match write_lock.map.get_mut(&tx.prioritization_fee) {
-            Some(value) => {
-                value.push_back(tx);
-            }
-            None => {
-                let mut vec_d = VecDeque::new();
-                let prioritization_fee = tx.prioritization_fee;
-                vec_d.push_back(tx);
-                write_lock.map.insert(prioritization_fee, vec_d);
-            }
-        }
-        if write_lock.signatures.len() > self.max_number_of_transactions {
-            match write_lock.map.first_entry() {
-                Some(mut first_entry) => {
-                    let tx_info = first_entry.get_mut().pop_front().unwrap();
-                    if first_entry.get().is_empty() {
-                        first_entry.remove();
+        if write_lock.len() >= self.max_number_of_transactions {
+            let sender = tx.fee_payer();
+            if write_lock.sender_count(&sender) >= self.max_transactions_per_sender {
+                // a sender at its share can only push out its own transactions
+                match write_lock.lowest(Some(&sender)) {
+                    Some(lowest) if priority_score > lowest.0 => {
+                        write_lock.remove(&lowest);
                     }
-
-                    write_lock.signatures.remove(&tx_info.signature);
+                    _ => return,
                 }
-                None => {
-                    panic!("Should not happen");
+            } else if let Some(over_cap_sender) =
+                write_lock.most_over_cap_sender(self.max_transactions_per_sender)
+            {
+                // the space taken above the cap is given back first, whatever the priority
+                let lowest = write_lock.lowest(Some(&over_cap_sender)).unwrap();
+                write_lock.remove(&lowest);
+            } else {
+                match write_lock.lowest(None) {
+                    Some(lowest) if priority_score > lowest.0 => {
+                        write_lock.remove(&lowest);
+                    }
+                    _ => return,
                 }
             }
         }
+
+        write_lock.push(tx);
     }
 
     pub async fn remove_expired_transactions(&self, current_blockheight: u64) -> usize {
         let mut write_lock = self.map.lock().await;
-        let mut cells_to_remove = vec![];
-        let mut signatures_to_remove = vec![];
-        for (p, entry) in write_lock.map.iter_mut() {
-            entry.retain(|x| {
-                let retain = x.last_valid_block_height > current_blockheight;
-                if !retain {
-                    signatures_to_remove.push(x.signature);
-                }
-                retain
-            });
-            if entry.is_empty() {
-                cells_to_remove.push(*p);
-            }
-        }
-        for p in cells_to_remove {
-            write_lock.map.remove(&p);
+        let expired = write_lock
+            .map
+            .iter()
+            .filter(|(_, tx)| tx.last_valid_block_height() <= current_blockheight)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in &expired {
+            write_lock.remove(key);
         }
-        let signatures_len = signatures_to_remove.len();
-        for sig in signatures_to_remove {
-            write_lock.signatures.remove(&sig);
-        }
-        signatures_len
+        expired.len()
     }
 
     pub async fn size(&self) -> usize {
-        self.map.lock().await.signatures.len()
+        self.map.lock().await.len()
     }
 
     pub async fn clear(&self) -> usize {
         let mut lk = self.map.lock().await;
-        lk.map.clear();
-        let size = lk.signatures.len();
-        lk.signatures.clear();
+        let size = lk.len();
+        *lk = PrioFeeHeapData::default();
         size
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use solana_sdk::signature::Signature;
+    use solana_sdk::{pubkey::Pubkey, signature::Signature};
     use std::{sync::Arc, time::Duration};
 
     use crate::structures::{
-        prioritization_fee_heap::PrioritizationFeesHeap, transaction_sent_info::SentTransactionInfo,
+        prioritization_fee_heap::PrioritizationFeesHeap,
+        transaction_sent_info::{SentBundleInfo, SentTransactionInfo, TransactionToSend},
     };
 
     #[tokio::test]
     pub async fn test_prioritization_heap() {
-        let p_heap = PrioritizationFeesHeap::new(4);
-        let tx_creator = |signature, prioritization_fee| SentTransactionInfo {
-            signature,
-            slot: 0,
-            transaction: Arc::new(vec![]),
-            last_valid_block_height: 0,
-            prioritization_fee,
+        let p_heap = PrioritizationFeesHeap::new(4, 4);
+        let tx_creator = |signature, prioritization_fee| {
+            TransactionToSend::Single(SentTransactionInfo {
+                signature,
+                slot: 0,
+                transaction: Arc::new(vec![]),
+                last_valid_block_height: 0,
+                prioritization_fee,
+                priority_score: prioritization_fee,
+                fee_payer: Pubkey::default(),
+            })
         };
 
         let tx_0 = tx_creator(Signature::new_unique(), 0);
@@ -188,9 +235,86 @@ mod tests {
         assert_eq!(p_heap.pop().await, None);
     }
 
+    #[tokio::test]
+    pub async fn test_prioritization_heap_keeps_bundles_together() {
+        let p_heap = PrioritizationFeesHeap::new(4, 4);
+        let tx_creator = |prioritization_fee, last_valid_block_height| SentTransactionInfo {
+            signature: Signature::new_unique(),
+            slot: 0,
+            transaction: Arc::new(vec![]),
+            last_valid_block_height,
+            prioritization_fee,
+            priority_score: prioritization_fee,
+            fee_payer: Pubkey::default(),
+        };
+
+        let single = TransactionToSend::Single(tx_creator(50, 100));
+        let bundle = TransactionToSend::Bundle(SentBundleInfo::new(vec![
+            tx_creator(0, 100),
+            tx_creator(0, 90),
+            tx_creator(200, 100),
+        ]));
+        p_heap.insert(single.clone()).await;
+        p_heap.insert(bundle.clone()).await;
+        p_heap.insert(bundle.clone()).await;
+        assert_eq!(p_heap.size().await, 2);
+
+        // bundle is ordered by its tip transaction and stays in one piece
+        assert_eq!(p_heap.pop().await, Some(bundle.clone()));
+        assert_eq!(p_heap.pop().await, Some(single));
+        assert_eq!(p_heap.pop().await, None);
+
+        // bundle expires with its oldest transaction
+        p_heap.insert(bundle).await;
+        assert_eq!(p_heap.remove_expired_transactions(95).await, 1);
+        assert_eq!(p_heap.size().await, 0);
+    }
+
+    #[tokio::test]
+    pub async fn test_prioritization_heap_sender_cap() {
+        let p_heap = PrioritizationFeesHeap::new(4, 2);
+        let tx_creator = |fee_payer, priority_score| {
+            TransactionToSend::Single(SentTransactionInfo {
+                signature: Signature::new_unique(),
+                slot: 0,
+                transaction: Arc::new(vec![]),
+                last_valid_block_height: 0,
+                prioritization_fee: 0,
+                priority_score,
+                fee_payer,
+            })
+        };
+        let spammer = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+
+        let spam_1 = tx_creator(spammer, 10);
+        let spam_2 = tx_creator(spammer, 20);
+        let spam_3 = tx_creator(spammer, 30);
+        let spam_4 = tx_creator(spammer, 40);
+        let other_1 = tx_creator(other, 15);
+        let other_2 = tx_creator(other, 5);
+        let spam_5 = tx_creator(spammer, 50);
+        // alone the spammer can fill the heap
+        for tx in [&spam_1, &spam_2, &spam_3, &spam_4] {
+            p_heap.insert(tx.clone()).await;
+        }
+        // other sender gets in by pushing out the spammer above its cap, even with a lower priority
+        p_heap.insert(other_1.clone()).await;
+        p_heap.insert(other_2.clone()).await;
+        assert_eq!(p_heap.size().await, 4);
+        // spammer at its share only replaces its own transactions
+        p_heap.insert(spam_5.clone()).await;
+
+        assert_eq!(p_heap.pop().await, Some(spam_5));
+        assert_eq!(p_heap.pop().await, Some(spam_4));
+        assert_eq!(p_heap.pop().await, Some(other_1));
+        assert_eq!(p_heap.pop().await, Some(other_2));
+        assert_eq!(p_heap.pop().await, None);
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     pub async fn test_prioritization_bench() {
-        let p_heap = PrioritizationFeesHeap::new(4096);
+        let p_heap = PrioritizationFeesHeap::new(4096, 4096);
 
         let jh = {
             let p_heap = p_heap.clone();
@@ -208,8 +332,10 @@ mod tests {
                             transaction: Arc::new(vec![]),
                             last_valid_block_height: height + 10,
                             prioritization_fee,
+                            priority_score: prioritization_fee,
+                            fee_payer: Pubkey::default(),
                         };
-                        p_heap.insert(info).await;
+                        p_heap.insert(info.into()).await;
                     }
                     tokio::time::sleep(Duration::from_millis(1)).await;
                     p_heap.remove_expired_transactions(height).await;
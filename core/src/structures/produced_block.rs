@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::hash::Hash;
 use solana_sdk::message::v0::MessageAddressTableLookup;
@@ -10,7 +11,7 @@ use std::fmt::Debug;
 use std::ops::Deref;
 use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionInfo {
     pub signature: Signature,
     pub is_vote: bool,
@@ -60,6 +61,7 @@ impl Deref for ProducedBlock {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ProducedBlockInner {
     pub transactions: Vec<TransactionInfo>,
     pub leader_id: Option<String>,
@@ -12,6 +12,10 @@ use std::net::SocketAddr;
 /// compat info: non-public format ATM
 /// initial version
 const FORMAT_VERSION1: u16 = 2500;
+/// adds bundles; only used if the request contains bundles so older proxies keep working
+const FORMAT_VERSION2: u16 = 2501;
+/// same layout as version 2; the proxy answers with a delivery report on the same bidirectional stream
+const FORMAT_VERSION3: u16 = 2502;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TxData(Signature, Vec<u8>);
@@ -28,11 +32,34 @@ pub struct TpuNode {
     pub identity_tpunode: Pubkey, // note: this is only used for debugging
 }
 
+/// transactions which must be forwarded in order over the same connection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BundleData(Vec<TxData>);
+
+impl BundleData {
+    pub fn new(transactions: Vec<TxData>) -> Self {
+        BundleData(transactions)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TpuForwardingRequest {
     format_version: u16,
     tpu_nodes: Vec<TpuNode>,
     transactions: Vec<TxData>,
+    // note: not part of version 1 of the wire format
+    #[serde(skip)]
+    bundles: Vec<BundleData>,
+    #[serde(skip)]
+    delivery_report: bool,
+}
+
+#[derive(Serialize)]
+struct TpuForwardingRequestV2<'a> {
+    format_version: u16,
+    tpu_nodes: &'a Vec<TpuNode>,
+    transactions: &'a Vec<TxData>,
+    bundles: &'a Vec<BundleData>,
 }
 
 impl Display for TpuForwardingRequest {
@@ -57,16 +84,122 @@ impl TpuForwardingRequest {
                 })
                 .collect_vec(),
             transactions: transactions.to_vec(),
+            bundles: vec![],
+            delivery_report: false,
         }
     }
 
+    pub fn new_with_bundles(
+        tpu_fanout_nodes: &[(SocketAddr, Pubkey)],
+        transactions: &[TxData],
+        bundles: &[BundleData],
+    ) -> Self {
+        let mut request = Self::new(tpu_fanout_nodes, transactions);
+        request.bundles = bundles.to_vec();
+        request
+    }
+
+    /// asks the proxy to report the delivery of the transactions to each tpu node
+    pub fn with_delivery_report(mut self) -> Self {
+        self.delivery_report = true;
+        self
+    }
+
     pub fn try_serialize_wire_format(&self) -> anyhow::Result<Vec<u8>> {
-        bincode::serialize(&self)
-            .context("serialize proxy request")
-            .map_err(anyhow::Error::from)
+        if self.bundles.is_empty() && !self.delivery_report {
+            bincode::serialize(&self)
+        } else {
+            bincode::serialize(&TpuForwardingRequestV2 {
+                format_version: if self.delivery_report {
+                    FORMAT_VERSION3
+                } else {
+                    FORMAT_VERSION2
+                },
+                tpu_nodes: &self.tpu_nodes,
+                transactions: &self.transactions,
+                bundles: &self.bundles,
+            })
+        }
+        .context("serialize proxy request")
+        .map_err(anyhow::Error::from)
     }
 
     pub fn get_tpu_nodes(&self) -> &Vec<TpuNode> {
         &self.tpu_nodes
     }
 }
+
+/// outcome of forwarding a transaction to a tpu node
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DeliveryStatus {
+    // written to a stream of the tpu node
+    Sent,
+    // the connection to the tpu node failed
+    SendFailed,
+    // not forwarded before the proxy had to report
+    TimedOut,
+    // refused by the proxy, e.g. over the rate limit of the client
+    Dropped,
+    // given up by the proxy, e.g. its connection to the tpu node is dead
+    Skipped,
+}
+
+impl DeliveryStatus {
+    /// value recorded in the quic_response of the transaction notifications, 0 is unknown
+    pub fn quic_response_code(&self) -> i16 {
+        match self {
+            DeliveryStatus::Sent => 1,
+            DeliveryStatus::SendFailed => 2,
+            DeliveryStatus::TimedOut => 3,
+            DeliveryStatus::Dropped => 4,
+            DeliveryStatus::Skipped => 5,
+        }
+    }
+}
+
+impl Display for DeliveryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            DeliveryStatus::Sent => "sent",
+            DeliveryStatus::SendFailed => "send_failed",
+            DeliveryStatus::TimedOut => "timed_out",
+            DeliveryStatus::Dropped => "dropped",
+            DeliveryStatus::Skipped => "skipped",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TxDeliveryResult {
+    pub signature: Signature,
+    pub tpu_node: TpuNode,
+    pub status: DeliveryStatus,
+}
+
+/// proxy to lite-rpc answer to a request of format version 3
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliveryReport {
+    format_version: u16,
+    pub results: Vec<TxDeliveryResult>,
+}
+
+impl DeliveryReport {
+    pub fn new(results: Vec<TxDeliveryResult>) -> Self {
+        DeliveryReport {
+            format_version: FORMAT_VERSION3,
+            results,
+        }
+    }
+
+    pub fn try_deserialize_from_wire_format(raw_report: &[u8]) -> anyhow::Result<DeliveryReport> {
+        let report = bincode::deserialize::<DeliveryReport>(raw_report)
+            .context("deserialize delivery report")?;
+        anyhow::ensure!(
+            report.format_version == FORMAT_VERSION3,
+            "unsupported delivery report format version {}",
+            report.format_version
+        );
+        Ok(report)
+    }
+}
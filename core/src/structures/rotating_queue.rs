@@ -20,6 +20,13 @@ impl<T: Clone> RotatingQueue<T> {
         }
     }
 
+    pub fn from_vec(elements: Vec<T>) -> Self {
+        Self {
+            elements,
+            current: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
     pub fn get(&self) -> Option<T> {
         if !self.elements.is_empty() {
             let current = self.current.fetch_add(1, Ordering::Relaxed);
@@ -1,10 +1,11 @@
 use std::sync::{atomic::AtomicU64, Arc};
 
+use serde::{Deserialize, Serialize};
 use solana_sdk::slot_history::Slot;
 
 pub type AtomicSlot = Arc<AtomicU64>;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SlotNotification {
     pub processed_slot: Slot,
     pub estimated_processed_slot: Slot,
@@ -0,0 +1,167 @@
+use std::{collections::HashSet, str::FromStr, sync::OnceLock};
+
+use solana_sdk::{
+    compute_budget::{self, ComputeBudgetInstruction},
+    pubkey::Pubkey,
+    system_instruction::SystemInstruction,
+    system_program,
+    transaction::VersionedTransaction,
+};
+
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+// default compute unit limit per instruction when the transaction does not request one
+pub const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+// accounts receiving the tips of the jito block engine
+const TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+];
+
+fn is_tip_account(pubkey: &Pubkey) -> bool {
+    static TIP_ACCOUNT_KEYS: OnceLock<HashSet<Pubkey>> = OnceLock::new();
+    TIP_ACCOUNT_KEYS
+        .get_or_init(|| {
+            TIP_ACCOUNTS
+                .iter()
+                .map(|key| Pubkey::from_str(key).expect("valid tip account"))
+                .collect()
+        })
+        .contains(pubkey)
+}
+
+/// Fees a transaction pays to get included
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TransactionPriority {
+    // micro lamports per compute unit
+    pub cu_price: u64,
+    pub cu_limit: u32,
+    pub base_fee: u64,
+    pub priority_fee: u64,
+    pub tips: u64,
+}
+
+impl TransactionPriority {
+    pub fn from_transaction(tx: &VersionedTransaction) -> Self {
+        let account_keys = tx.message.static_account_keys();
+        let mut cu_price = 0;
+        let mut cu_limit = None;
+        let mut tips = 0u64;
+        let mut nb_instructions = 0u32;
+
+        for ix in tx.message.instructions() {
+            let program_id = ix.program_id(account_keys);
+            if program_id.eq(&compute_budget::id()) {
+                match solana_sdk::borsh1::try_from_slice_unchecked::<ComputeBudgetInstruction>(
+                    ix.data.as_slice(),
+                ) {
+                    Ok(ComputeBudgetInstruction::SetComputeUnitPrice(price)) => cu_price = price,
+                    Ok(ComputeBudgetInstruction::SetComputeUnitLimit(limit)) => {
+                        cu_limit = Some(limit)
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            nb_instructions += 1;
+
+            if program_id.eq(&system_program::id()) {
+                if let Ok(SystemInstruction::Transfer { lamports }) =
+                    bincode::deserialize::<SystemInstruction>(&ix.data)
+                {
+                    // destination may be in a lookup table, those tips are not detected
+                    let is_tip = ix
+                        .accounts
+                        .get(1)
+                        .and_then(|index| account_keys.get(*index as usize))
+                        .is_some_and(is_tip_account);
+                    if is_tip {
+                        tips = tips.saturating_add(lamports);
+                    }
+                }
+            }
+        }
+
+        let cu_limit = cu_limit
+            .unwrap_or(nb_instructions.saturating_mul(DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT))
+            .min(MAX_COMPUTE_UNIT_LIMIT);
+        let priority_fee = (cu_price as u128 * cu_limit as u128)
+            .div_ceil(MICRO_LAMPORTS_PER_LAMPORT)
+            .min(u64::MAX as u128) as u64;
+
+        Self {
+            cu_price,
+            cu_limit,
+            base_fee: LAMPORTS_PER_SIGNATURE * tx.signatures.len() as u64,
+            priority_fee,
+            tips,
+        }
+    }
+
+    pub fn total_fee(&self) -> u64 {
+        self.base_fee
+            .saturating_add(self.priority_fee)
+            .saturating_add(self.tips)
+    }
+
+    /// total fee in micro lamports per requested compute unit
+    pub fn score(&self) -> u64 {
+        (self.total_fee() as u128 * MICRO_LAMPORTS_PER_LAMPORT / self.cu_limit.max(1) as u128)
+            .min(u64::MAX as u128) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        compute_budget::ComputeBudgetInstruction, hash::Hash, message::Message, signature::Keypair,
+        signer::Signer, system_instruction, transaction::Transaction,
+    };
+
+    use super::*;
+
+    fn transaction(
+        instructions: impl Fn(&Pubkey) -> Vec<solana_sdk::instruction::Instruction>,
+    ) -> VersionedTransaction {
+        let payer = Keypair::new();
+        let message = Message::new(&instructions(&payer.pubkey()), Some(&payer.pubkey()));
+        Transaction::new(&[&payer], message, Hash::default()).into()
+    }
+
+    #[test]
+    fn score_includes_tips_and_requested_cus() {
+        let tip_account = Pubkey::from_str(TIP_ACCOUNTS[0]).unwrap();
+
+        let plain = TransactionPriority::from_transaction(&transaction(|payer| {
+            vec![system_instruction::transfer(
+                payer,
+                &Pubkey::new_unique(),
+                1_000_000,
+            )]
+        }));
+        assert_eq!(plain.cu_limit, DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT);
+        assert_eq!(plain.tips, 0);
+        assert_eq!(plain.score(), 5000 * 1_000_000 / 200_000);
+
+        let tipped = TransactionPriority::from_transaction(&transaction(|payer| {
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(10_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1_000),
+                system_instruction::transfer(payer, &tip_account, 100_000),
+            ]
+        }));
+        assert_eq!(tipped.cu_limit, 10_000);
+        assert_eq!(tipped.priority_fee, 10);
+        assert_eq!(tipped.tips, 100_000);
+        assert_eq!(tipped.total_fee(), 5000 + 10 + 100_000);
+        assert!(tipped.score() > plain.score());
+    }
+}
@@ -1,10 +1,14 @@
 use std::sync::Arc;
 
+use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
 use solana_sdk::slot_history::Slot;
 
 pub type WireTransaction = Vec<u8>;
 
+/// maximum number of transactions accepted in one bundle
+pub const MAX_TRANSACTIONS_PER_BUNDLE: usize = 5;
+
 #[derive(Clone, Debug, PartialEq, PartialOrd, Eq)]
 pub struct SentTransactionInfo {
     pub signature: Signature,
@@ -12,4 +16,107 @@ pub struct SentTransactionInfo {
     pub transaction: Arc<WireTransaction>,
     pub last_valid_block_height: u64,
     pub prioritization_fee: u64,
+    // total fee per requested compute unit, see TransactionPriority::score
+    pub priority_score: u64,
+    pub fee_payer: Pubkey,
+}
+
+/// An ordered group of transactions which is forwarded as a whole to the same leader
+/// The bundle is identified by the signature of its first transaction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SentBundleInfo {
+    pub bundle_id: Signature,
+    pub transactions: Vec<SentTransactionInfo>,
+}
+
+impl SentBundleInfo {
+    pub fn new(transactions: Vec<SentTransactionInfo>) -> Self {
+        assert!(!transactions.is_empty(), "bundle must not be empty");
+        Self {
+            bundle_id: transactions[0].signature,
+            transactions,
+        }
+    }
+}
+
+/// Unit which travels through the tpu service, the priority heap and the quic streams
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransactionToSend {
+    Single(SentTransactionInfo),
+    Bundle(SentBundleInfo),
+}
+
+impl TransactionToSend {
+    /// signature of the transaction or id of the bundle
+    pub fn id(&self) -> Signature {
+        match self {
+            TransactionToSend::Single(tx) => tx.signature,
+            TransactionToSend::Bundle(bundle) => bundle.bundle_id,
+        }
+    }
+
+    pub fn transactions(&self) -> &[SentTransactionInfo] {
+        match self {
+            TransactionToSend::Single(tx) => std::slice::from_ref(tx),
+            TransactionToSend::Bundle(bundle) => &bundle.transactions,
+        }
+    }
+
+    pub fn is_bundle(&self) -> bool {
+        matches!(self, TransactionToSend::Bundle(_))
+    }
+
+    pub fn slot(&self) -> Slot {
+        self.transactions()
+            .iter()
+            .map(|tx| tx.slot)
+            .min()
+            .unwrap_or_default()
+    }
+
+    // a bundle expires with its oldest blockhash
+    pub fn last_valid_block_height(&self) -> u64 {
+        self.transactions()
+            .iter()
+            .map(|tx| tx.last_valid_block_height)
+            .min()
+            .unwrap_or_default()
+    }
+
+    pub fn prioritization_fee(&self) -> u64 {
+        self.transactions()
+            .iter()
+            .map(|tx| tx.prioritization_fee)
+            .max()
+            .unwrap_or_default()
+    }
+
+    // a bundle is as urgent as its most urgent transaction (usually the one paying the tip)
+    pub fn priority_score(&self) -> u64 {
+        self.transactions()
+            .iter()
+            .map(|tx| tx.priority_score)
+            .max()
+            .unwrap_or_default()
+    }
+
+    // a bundle is accounted to the payer of its first transaction
+    pub fn fee_payer(&self) -> Pubkey {
+        self.transactions()
+            .first()
+            .map(|tx| tx.fee_payer)
+            .unwrap_or_default()
+    }
+}
+
+impl From<SentTransactionInfo> for TransactionToSend {
+    fn from(transaction: SentTransactionInfo) -> Self {
+        TransactionToSend::Single(transaction)
+    }
+}
+
+impl From<SentBundleInfo> for TransactionToSend {
+    fn from(bundle: SentBundleInfo) -> Self {
+        TransactionToSend::Bundle(bundle)
+    }
 }
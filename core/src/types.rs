@@ -4,6 +4,7 @@ use solana_rpc_client_api::response::{RpcContactInfo, RpcVoteAccountStatus};
 use tokio::sync::broadcast::Receiver;
 
 use crate::structures::block_info::BlockInfo;
+use crate::structures::orphaned_block::OrphanedBlock;
 use crate::{
     structures::{produced_block::ProducedBlock, slot_notification::SlotNotification},
     traits::subscription_sink::SubscriptionSink,
@@ -18,6 +19,8 @@ pub type BlockStream = Receiver<ProducedBlock>;
 // note: there is no guarantee about the order wrt commitment level
 pub type BlockInfoStream = Receiver<BlockInfo>;
 pub type SlotStream = Receiver<SlotNotification>;
+// processed blocks which were abandoned for another fork
+pub type OrphanedBlockStream = Receiver<OrphanedBlock>;
 
 pub type VoteAccountStream = Receiver<RpcVoteAccountStatus>;
 pub type ClusterInfoStream = Receiver<Vec<RpcContactInfo>>;
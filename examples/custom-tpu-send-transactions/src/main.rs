@@ -8,7 +8,10 @@ use rand::{
     SeedableRng,
 };
 use solana_lite_rpc_cluster_endpoints::{
-    geyser_grpc_connector::{GrpcConnectionTimeouts, GrpcSourceConfig},
+    fork_tracker::create_fork_tracker_task,
+    geyser_grpc_connector::GrpcConnectionTimeouts,
+    grpc_source_health::GrpcSourcesHealth,
+    grpc_sources::{GrpcSourceSpec, GrpcSources},
     grpc_subscription::create_grpc_subscription,
     json_rpc_leaders_getter::JsonRpcLeaderGetter,
     json_rpc_subscription::create_json_rpc_polling_subscription,
@@ -17,6 +20,7 @@ use solana_lite_rpc_core::{
     keypair_loader::load_identity_keypair,
     stores::{
         block_information_store::{BlockInformation, BlockInformationStore},
+        bundle_store::BundleStore,
         cluster_info_store::ClusterInfo,
         data_cache::{DataCache, SlotCache},
         subscription_store::SubscriptionStore,
@@ -24,7 +28,7 @@ use solana_lite_rpc_core::{
     },
     structures::{
         epoch::EpochCache, identity_stakes::IdentityStakes, leaderschedule::CalculatedSchedule,
-        transaction_sent_info::SentTransactionInfo,
+        transaction_priority::TransactionPriority, transaction_sent_info::SentTransactionInfo,
     },
     utils::wait_till_block_of_commitment_is_recieved,
 };
@@ -268,13 +272,12 @@ async fn main() -> anyhow::Result<()> {
         };
         create_grpc_subscription(
             rpc_client.clone(),
-            vec![GrpcSourceConfig::new(
-                grpc_addr,
-                args.x_token.clone(),
-                None,
+            GrpcSources::new(
+                vec![GrpcSourceSpec::new(grpc_addr, args.x_token.clone())],
                 timeouts,
-            )],
+            ),
             vec![],
+            GrpcSourcesHealth::new(&[]),
         )?
     } else {
         create_json_rpc_polling_subscription(rpc_client.clone(), 100)?
@@ -326,6 +329,7 @@ async fn main() -> anyhow::Result<()> {
         txs: TxStore {
             store: Arc::new(DashMap::new()),
         },
+        bundles: BundleStore::default(),
         epoch_data: EpochCache::new_for_tests(),
         leader_schedule: Arc::new(RwLock::new(CalculatedSchedule::default())),
     };
@@ -335,6 +339,9 @@ async fn main() -> anyhow::Result<()> {
         clean_duration: Duration::from_secs(120),
     };
 
+    let (_fork_tracker_task, orphaned_block_notifier) =
+        create_fork_tracker_task(endpoints.blocks_notifier.resubscribe());
+
     // start listning the cluster data and filling the cache
     data_cache_service.listen(
         endpoints.blocks_notifier.resubscribe(),
@@ -342,6 +349,7 @@ async fn main() -> anyhow::Result<()> {
         endpoints.slot_notifier.resubscribe(),
         endpoints.cluster_info_notifier,
         endpoints.vote_account_notifier,
+        orphaned_block_notifier,
     );
 
     let count = args.transaction_count.unwrap_or(10);
@@ -360,8 +368,11 @@ async fn main() -> anyhow::Result<()> {
             number_of_transactions_per_unistream: 1,
             unistreams_to_create_new_connection_in_percentage: 5,
             prioritization_heap_size,
+            prioritization_heap_share_per_sender_in_percentage: 100,
+            prewarm_slots: 8,
         },
         tpu_connection_path: TpuConnectionPath::QuicDirectPath,
+        quic_bind_config: None,
     };
 
     let tpu_service: TpuService = TpuService::new(
@@ -455,10 +466,12 @@ async fn main() -> anyhow::Result<()> {
                 slot,
                 transaction: Arc::new(raw_tx),
                 prioritization_fee: priority_fee,
+                priority_score: TransactionPriority::from_transaction(&transaction).score(),
+                fee_payer: transaction.message.static_account_keys()[0],
             };
             let _ = transaction_service
                 .transaction_channel
-                .send(transaction_info.clone())
+                .send(transaction_info.clone().into())
                 .await;
             current_txs.push(signature);
         }
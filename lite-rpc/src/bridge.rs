@@ -12,8 +12,8 @@ use solana_rpc_client_api::response::{OptionalContext, RpcKeyedAccount};
 use solana_rpc_client_api::{
     config::{
         RpcBlocksConfigWrapper, RpcContextConfig, RpcGetVoteAccountsConfig,
-        RpcLeaderScheduleConfig, RpcProgramAccountsConfig, RpcRequestAirdropConfig,
-        RpcSignatureStatusConfig, RpcSignaturesForAddressConfig,
+        RpcLeaderScheduleConfig, RpcLeaderScheduleConfigWrapper, RpcProgramAccountsConfig,
+        RpcRequestAirdropConfig, RpcSignatureStatusConfig, RpcSignaturesForAddressConfig,
     },
     response::{
         Response as RpcResponse, RpcBlockhash, RpcConfirmedTransactionStatusWithSignature,
@@ -35,20 +35,32 @@ use std::sync::Arc;
 use solana_lite_rpc_blockstore::history::History;
 use solana_lite_rpc_core::solana_utils::hash_from_str;
 use solana_lite_rpc_core::stores::{
-    block_information_store::BlockInformation, data_cache::DataCache,
+    block_information_store::BlockInformation, bundle_store::BundleStatus, data_cache::DataCache,
 };
+use solana_lite_rpc_core::structures::transaction_sent_info::MAX_TRANSACTIONS_PER_BUNDLE;
 use solana_lite_rpc_services::{
-    transaction_service::TransactionService, tx_sender::TXS_IN_CHANNEL,
+    tpu_utils::slot_clock::SlotTiming,
+    transaction_replayer::ReplayStrategy,
+    transaction_service::{InvalidTransaction, TransactionService},
+    tx_sender::TXS_IN_CHANNEL,
 };
 
 use crate::rpc_errors::RpcErrors;
-use crate::{configs::IsBlockHashValidConfig, rpc::LiteRpcServer};
+use crate::{
+    client_quotas::max_retries_ceiling,
+    configs::{IsBlockHashValidConfig, LiteSendTransactionConfig},
+    rpc::LiteRpcServer,
+};
 use solana_lite_rpc_prioritization_fees::rpc_data::{AccountPrioFeesStats, PrioFeesStats};
 use solana_lite_rpc_prioritization_fees::PrioFeesService;
 
 lazy_static::lazy_static! {
     static ref RPC_SEND_TX: IntCounter =
     register_int_counter!(opts!("literpc_rpc_send_tx", "RPC call send transaction")).unwrap();
+    static ref RPC_SEND_BUNDLE: IntCounter =
+    register_int_counter!(opts!("literpc_rpc_send_bundle", "RPC call send bundle")).unwrap();
+    static ref RPC_GET_BUNDLE_STATUSES: IntCounter =
+    register_int_counter!(opts!("literpc_rpc_get_bundle_statuses", "RPC call to get bundle statuses")).unwrap();
     static ref RPC_GET_LATEST_BLOCKHASH: IntCounter =
     register_int_counter!(opts!("literpc_rpc_get_latest_blockhash", "RPC call to get latest block hash")).unwrap();
     static ref RPC_IS_BLOCKHASH_VALID: IntCounter =
@@ -59,6 +71,8 @@ lazy_static::lazy_static! {
     register_int_counter!(opts!("literpc_rpc_get_version", "RPC call to version")).unwrap();
     static ref RPC_REQUEST_AIRDROP: IntCounter =
     register_int_counter!(opts!("literpc_rpc_airdrop", "RPC call to request airdrop")).unwrap();
+    static ref RPC_GET_SLOT_TIMING: IntCounter =
+    register_int_counter!(opts!("literpc_rpc_get_slot_timing", "RPC call to get slot timing")).unwrap();
 }
 
 /// A bridge between clients and tpu
@@ -347,70 +361,108 @@ impl LiteRpcServer for LiteBridge {
     async fn send_transaction(
         &self,
         tx: String,
-        send_transaction_config: Option<RpcSendTransactionConfig>,
+        send_transaction_config: Option<LiteSendTransactionConfig>,
     ) -> RpcResult<String> {
         RPC_SEND_TX.inc();
 
-        // Copied these constants from solana labs code
-        const MAX_BASE58_SIZE: usize = 1683;
-        const MAX_BASE64_SIZE: usize = 1644;
-
-        let RpcSendTransactionConfig {
-            encoding,
-            max_retries,
-            ..
+        let LiteSendTransactionConfig {
+            rpc_config:
+                RpcSendTransactionConfig {
+                    encoding,
+                    max_retries,
+                    ..
+                },
+            replay_strategy,
         } = send_transaction_config.unwrap_or_default();
 
-        let encoding = encoding.unwrap_or(UiTransactionEncoding::Base58);
-        let expected_size = match encoding {
-            UiTransactionEncoding::Base58 => MAX_BASE58_SIZE,
-            UiTransactionEncoding::Base64 => MAX_BASE64_SIZE,
-            _ => usize::MAX,
-        };
-        if tx.len() > expected_size {
-            return Err(jsonrpsee::types::error::ErrorCode::OversizedRequest.into());
+        let (max_retries, replay_strategy) = replay_settings(max_retries, replay_strategy)?;
+        let wire_output = decode_wire_transaction(tx, encoding)?;
+        match self
+            .transaction_service
+            .send_wire_transaction(wire_output, max_retries, replay_strategy)
+            .await
+        {
+            Ok(sig) => {
+                TXS_IN_CHANNEL.inc();
+
+                Ok(sig)
+            }
+            Err(e) => Err(send_error(e)),
         }
+    }
 
-        let binary_encoding = encoding
-            .into_binary_encoding()
-            .ok_or(jsonrpsee::types::error::ErrorCode::InvalidParams)?;
+    async fn send_bundle(
+        &self,
+        txs: Vec<String>,
+        send_transaction_config: Option<LiteSendTransactionConfig>,
+    ) -> RpcResult<String> {
+        RPC_SEND_BUNDLE.inc();
+
+        let LiteSendTransactionConfig {
+            rpc_config:
+                RpcSendTransactionConfig {
+                    encoding,
+                    max_retries,
+                    ..
+                },
+            replay_strategy,
+        } = send_transaction_config.unwrap_or_default();
 
-        let wire_output = match binary_encoding {
-            TransactionBinaryEncoding::Base58 => {
-                if tx.len() > MAX_BASE58_SIZE {
-                    return Err(jsonrpsee::types::error::ErrorCode::OversizedRequest.into());
-                }
-                BASE58
-                    .decode(tx)
-                    .map_err(|_| jsonrpsee::types::error::ErrorCode::InvalidParams)?
-            }
-            TransactionBinaryEncoding::Base64 => {
-                if tx.len() > MAX_BASE64_SIZE {
-                    return Err(jsonrpsee::types::error::ErrorCode::OversizedRequest.into());
-                }
-                BASE64
-                    .decode(tx)
-                    .map_err(|_| jsonrpsee::types::error::ErrorCode::InvalidParams)?
-            }
-        };
-        if wire_output.len() > PACKET_DATA_SIZE {
-            return Err(jsonrpsee::types::error::ErrorCode::OversizedRequest.into());
+        if txs.is_empty() || txs.len() > MAX_TRANSACTIONS_PER_BUNDLE {
+            return Err(jsonrpsee::types::error::ErrorCode::InvalidParams.into());
         }
-        let max_retries = max_retries.map(|x| x as u16);
+
+        let wire_outputs = txs
+            .into_iter()
+            .map(|tx| decode_wire_transaction(tx, encoding))
+            .collect::<RpcResult<Vec<_>>>()?;
+        let nb_txs = wire_outputs.len();
+        let (max_retries, replay_strategy) = replay_settings(max_retries, replay_strategy)?;
         match self
             .transaction_service
-            .send_wire_transaction(wire_output, max_retries)
+            .send_wire_bundle(wire_outputs, max_retries, replay_strategy)
             .await
         {
-            Ok(sig) => {
-                TXS_IN_CHANNEL.inc();
+            Ok(bundle_id) => {
+                TXS_IN_CHANNEL.add(nb_txs as i64);
 
-                Ok(sig)
+                Ok(bundle_id)
             }
-            Err(_) => Err(jsonrpsee::types::error::ErrorCode::InternalError.into()),
+            Err(e) => Err(send_error(e)),
         }
     }
 
+    async fn get_bundle_statuses(
+        &self,
+        bundle_ids: Vec<String>,
+    ) -> RpcResult<RpcResponse<Vec<Option<BundleStatus>>>> {
+        RPC_GET_BUNDLE_STATUSES.inc();
+
+        let bundle_ids = bundle_ids
+            .iter()
+            .map(|id| Signature::from_str(id))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| jsonrpsee::types::error::ErrorCode::InvalidParams)?;
+
+        let bundle_statuses = bundle_ids
+            .iter()
+            .map(|id| self.data_cache.bundles.get_status(id, &self.data_cache.txs))
+            .collect();
+
+        Ok(RpcResponse {
+            context: RpcResponseContext {
+                slot: self
+                    .data_cache
+                    .block_information_store
+                    .get_latest_block_information(CommitmentConfig::finalized())
+                    .await
+                    .slot,
+                api_version: None,
+            },
+            value: bundle_statuses,
+        })
+    }
+
     fn get_version(&self) -> RpcResult<RpcVersionInfo> {
         RPC_GET_VERSION.inc();
 
@@ -433,16 +485,28 @@ impl LiteRpcServer for LiteBridge {
 
     async fn get_leader_schedule(
         &self,
-        slot: Option<u64>,
+        options: Option<RpcLeaderScheduleConfigWrapper>,
         config: Option<RpcLeaderScheduleConfig>,
     ) -> RpcResult<Option<HashMap<String, Vec<usize>>>> {
-        //TODO verify leader identity.
+        // the config can be the first param, as accepted by the validator
+        let (slot, maybe_config) = options.map(|options| options.unzip()).unwrap_or_default();
+        let config = maybe_config.or(config).unwrap_or_default();
+        let identity = config
+            .identity
+            .map(|identity| Pubkey::from_str(&identity))
+            .transpose()
+            .map_err(|err| invalid_params(format!("Invalid param: {err:?}")))?;
         let schedule = self
             .data_cache
             .leader_schedule
             .read()
             .await
-            .get_leader_schedule_for_slot(slot, config.and_then(|c| c.commitment), &self.data_cache)
+            .get_leader_schedule_for_slot(
+                slot,
+                config.commitment,
+                identity.as_ref(),
+                &self.data_cache,
+            )
             .await;
         Ok(schedule)
     }
@@ -456,10 +520,7 @@ impl LiteRpcServer for LiteBridge {
             .await
             .get_slot_leaders(start_slot, limit, epock_schedule)
             .await
-            .map_err(|err| {
-                log::error!("Error processing get leader schedule : {err:?}");
-                jsonrpsee::types::error::ErrorCode::InternalError.into()
-            })
+            .map_err(invalid_params)
     }
 
     async fn get_vote_accounts(
@@ -531,6 +592,30 @@ impl LiteRpcServer for LiteBridge {
         }
     }
 
+    async fn get_slot_timing(&self, limit: Option<usize>) -> RpcResult<SlotTiming> {
+        RPC_GET_SLOT_TIMING.inc();
+
+        const DEFAULT_LEADERS: usize = 16;
+        const MAX_LEADERS: usize = 256;
+        let limit = limit.unwrap_or(DEFAULT_LEADERS);
+        if limit > MAX_LEADERS {
+            return Err(jsonrpsee::types::error::ErrorCode::InvalidParams.into());
+        }
+        match self
+            .transaction_service
+            .tx_replayer
+            .tpu_service
+            .get_slot_timing(limit)
+            .await
+        {
+            Ok(timing) => Ok(timing),
+            Err(e) => {
+                log::error!("Error getting slot timing: {e:?}");
+                Err(jsonrpsee::types::error::ErrorCode::InternalError.into())
+            }
+        }
+    }
+
     async fn get_account_info(
         &self,
         pubkey_str: String,
@@ -726,3 +811,84 @@ impl LiteRpcServer for LiteBridge {
         }
     }
 }
+
+fn decode_wire_transaction(
+    tx: String,
+    encoding: Option<UiTransactionEncoding>,
+) -> RpcResult<Vec<u8>> {
+    // Copied these constants from solana labs code
+    const MAX_BASE58_SIZE: usize = 1683;
+    const MAX_BASE64_SIZE: usize = 1644;
+
+    let encoding = encoding.unwrap_or(UiTransactionEncoding::Base58);
+    let expected_size = match encoding {
+        UiTransactionEncoding::Base58 => MAX_BASE58_SIZE,
+        UiTransactionEncoding::Base64 => MAX_BASE64_SIZE,
+        _ => usize::MAX,
+    };
+    if tx.len() > expected_size {
+        return Err(jsonrpsee::types::error::ErrorCode::OversizedRequest.into());
+    }
+
+    let binary_encoding = encoding
+        .into_binary_encoding()
+        .ok_or(jsonrpsee::types::error::ErrorCode::InvalidParams)?;
+
+    let wire_output = match binary_encoding {
+        TransactionBinaryEncoding::Base58 => {
+            if tx.len() > MAX_BASE58_SIZE {
+                return Err(jsonrpsee::types::error::ErrorCode::OversizedRequest.into());
+            }
+            BASE58
+                .decode(tx)
+                .map_err(|_| jsonrpsee::types::error::ErrorCode::InvalidParams)?
+        }
+        TransactionBinaryEncoding::Base64 => {
+            if tx.len() > MAX_BASE64_SIZE {
+                return Err(jsonrpsee::types::error::ErrorCode::OversizedRequest.into());
+            }
+            BASE64
+                .decode(tx)
+                .map_err(|_| jsonrpsee::types::error::ErrorCode::InvalidParams)?
+        }
+    };
+    if wire_output.len() > PACKET_DATA_SIZE {
+        return Err(jsonrpsee::types::error::ErrorCode::OversizedRequest.into());
+    }
+    Ok(wire_output)
+}
+
+// applies the maxRetries ceiling of the api key, replaying every slot is not bounded by maxRetries
+fn replay_settings(
+    max_retries: Option<usize>,
+    replay_strategy: Option<ReplayStrategy>,
+) -> RpcResult<(Option<u16>, ReplayStrategy)> {
+    let replay_strategy = replay_strategy.unwrap_or_default();
+    let Some(ceiling) = max_retries_ceiling() else {
+        return Ok((max_retries.map(|x| x as u16), replay_strategy));
+    };
+    if replay_strategy == ReplayStrategy::EverySlot {
+        return Err(invalid_params(
+            "replay strategy everySlot is not allowed for this api key".to_string(),
+        ));
+    }
+    let max_retries = max_retries.map_or(ceiling, |x| x.min(ceiling));
+    Ok((Some(max_retries as u16), replay_strategy))
+}
+
+// invalid params error with the message of the validator
+fn invalid_params(message: String) -> jsonrpsee::types::ErrorObjectOwned {
+    jsonrpsee::types::ErrorObject::owned(
+        jsonrpsee::types::error::ErrorCode::InvalidParams.code(),
+        message,
+        None::<()>,
+    )
+}
+
+// malformed transactions are the fault of the client
+fn send_error(e: anyhow::Error) -> jsonrpsee::types::ErrorObjectOwned {
+    match e.downcast_ref::<InvalidTransaction>() {
+        Some(invalid_transaction) => invalid_params(invalid_transaction.to_string()),
+        None => jsonrpsee::types::error::ErrorCode::InternalError.into(),
+    }
+}
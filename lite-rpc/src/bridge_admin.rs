@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use jsonrpsee::core::RpcResult;
+use prometheus::{opts, register_int_counter, IntCounter};
+use solana_lite_rpc_cluster_endpoints::grpc_source_health::{GrpcSourceStatus, GrpcSourcesHealth};
+use solana_lite_rpc_cluster_endpoints::grpc_sources::{GrpcSourceSpec, GrpcSources};
+use solana_lite_rpc_core::stores::cluster_info_store::{ClusterInfo, ContactInfoSeen};
+use solana_lite_rpc_services::{
+    tpu_utils::leader_scores::LeaderScore, transaction_service::TransactionService,
+};
+
+use crate::{identity_rotation::IdentityRotation, rpc_admin::LiteRpcAdminServer};
+
+lazy_static::lazy_static! {
+    static ref RPC_GET_LEADER_SCORES: IntCounter =
+    register_int_counter!(opts!("literpc_rpc_get_leader_scores", "RPC call to get leader scores")).unwrap();
+    static ref RPC_GET_GRPC_SOURCE_RANKINGS: IntCounter =
+    register_int_counter!(opts!("literpc_rpc_get_grpc_source_rankings", "RPC call to get grpc source rankings")).unwrap();
+    static ref RPC_GET_GRPC_SOURCES: IntCounter =
+    register_int_counter!(opts!("literpc_rpc_get_grpc_sources", "RPC call to get grpc sources")).unwrap();
+    static ref RPC_ADD_GRPC_SOURCE: IntCounter =
+    register_int_counter!(opts!("literpc_rpc_add_grpc_source", "RPC call to add a grpc source")).unwrap();
+    static ref RPC_REMOVE_GRPC_SOURCE: IntCounter =
+    register_int_counter!(opts!("literpc_rpc_remove_grpc_source", "RPC call to remove a grpc source")).unwrap();
+    static ref RPC_RELOAD_IDENTITY: IntCounter =
+    register_int_counter!(opts!("literpc_rpc_reload_identity", "RPC call to reload the identity")).unwrap();
+    static ref RPC_GET_CLUSTER_NODES_LAST_SEEN: IntCounter =
+    register_int_counter!(opts!("literpc_rpc_get_cluster_nodes_last_seen", "RPC call to get when cluster nodes were last seen")).unwrap();
+}
+
+/// Operator methods of lite-rpc
+pub struct LiteAdminBridge {
+    transaction_service: TransactionService,
+    grpc_sources_health: Option<GrpcSourcesHealth>,
+    grpc_sources: Option<GrpcSources>,
+    identity_rotation: IdentityRotation,
+    cluster_info: ClusterInfo,
+}
+
+impl LiteAdminBridge {
+    pub fn new(
+        transaction_service: TransactionService,
+        grpc_sources_health: Option<GrpcSourcesHealth>,
+        grpc_sources: Option<GrpcSources>,
+        identity_rotation: IdentityRotation,
+        cluster_info: ClusterInfo,
+    ) -> Self {
+        Self {
+            transaction_service,
+            grpc_sources_health,
+            grpc_sources,
+            identity_rotation,
+            cluster_info,
+        }
+    }
+}
+
+#[jsonrpsee::core::async_trait]
+impl LiteRpcAdminServer for LiteAdminBridge {
+    async fn get_leader_scores(&self) -> RpcResult<Vec<LeaderScore>> {
+        RPC_GET_LEADER_SCORES.inc();
+
+        Ok(self
+            .transaction_service
+            .tx_replayer
+            .tpu_service
+            .get_leader_scores())
+    }
+
+    async fn get_grpc_source_rankings(&self) -> RpcResult<Vec<GrpcSourceStatus>> {
+        RPC_GET_GRPC_SOURCE_RANKINGS.inc();
+
+        Ok(self
+            .grpc_sources_health
+            .as_ref()
+            .map(|grpc_sources_health| grpc_sources_health.rankings())
+            .unwrap_or_default())
+    }
+
+    async fn get_grpc_sources(&self) -> RpcResult<Vec<GrpcSourceSpec>> {
+        RPC_GET_GRPC_SOURCES.inc();
+
+        Ok(self
+            .grpc_sources
+            .as_ref()
+            .map(|grpc_sources| {
+                grpc_sources
+                    .list()
+                    .iter()
+                    .map(GrpcSourceSpec::redacted)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn add_grpc_source(&self, source: GrpcSourceSpec) -> RpcResult<Vec<GrpcSourceSpec>> {
+        RPC_ADD_GRPC_SOURCE.inc();
+
+        let Some(grpc_sources) = &self.grpc_sources else {
+            return Err(jsonrpsee::types::error::ErrorCode::MethodNotFound.into());
+        };
+        if let Err(e) = grpc_sources.add(source) {
+            log::error!("Error adding grpc source: {e:?}");
+            return Err(jsonrpsee::types::error::ErrorCode::InvalidParams.into());
+        }
+        self.get_grpc_sources().await
+    }
+
+    async fn remove_grpc_source(&self, addr: String) -> RpcResult<Vec<GrpcSourceSpec>> {
+        RPC_REMOVE_GRPC_SOURCE.inc();
+
+        let Some(grpc_sources) = &self.grpc_sources else {
+            return Err(jsonrpsee::types::error::ErrorCode::MethodNotFound.into());
+        };
+        if let Err(e) = grpc_sources.remove(&addr) {
+            log::error!("Error removing grpc source: {e:?}");
+            return Err(jsonrpsee::types::error::ErrorCode::InvalidParams.into());
+        }
+        self.get_grpc_sources().await
+    }
+
+    async fn reload_identity(&self) -> RpcResult<String> {
+        RPC_RELOAD_IDENTITY.inc();
+
+        match self.identity_rotation.rotate().await {
+            Ok(identity) => Ok(identity.to_string()),
+            Err(e) => {
+                log::error!("Error reloading identity: {e:?}");
+                Err(jsonrpsee::types::error::ErrorCode::InternalError.into())
+            }
+        }
+    }
+
+    async fn get_cluster_nodes_last_seen(&self) -> RpcResult<HashMap<String, ContactInfoSeen>> {
+        RPC_GET_CLUSTER_NODES_LAST_SEEN.inc();
+
+        Ok(self
+            .cluster_info
+            .last_seen
+            .iter()
+            .map(|entry| (entry.key().to_string(), entry.value().clone()))
+            .collect())
+    }
+}
@@ -4,7 +4,7 @@ use solana_lite_rpc_core::{
     commitment_utils::Commitment,
     stores::data_cache::DataCache,
     structures::account_data::AccountNotificationMessage,
-    types::{BlockInfoStream, BlockStream},
+    types::{BlockInfoStream, BlockStream, OrphanedBlockStream},
 };
 use std::{str::FromStr, sync::Arc, time::Duration};
 use tokio::sync::broadcast::error::RecvError::{Closed, Lagged};
@@ -17,6 +17,7 @@ use jsonrpsee::{
     core::{StringError, SubscriptionResult},
     DisconnectError, PendingSubscriptionSink,
 };
+use serde::Serialize;
 use solana_lite_rpc_prioritization_fees::{
     account_prio_service::AccountPrioService,
     rpc_data::{AccountPrioFeesUpdateMessage, PrioFeesUpdateMessage},
@@ -32,6 +33,7 @@ use solana_rpc_client_api::{
 };
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
+use solana_sdk::slot_history::Slot;
 
 lazy_static::lazy_static! {
     static ref RPC_SIGNATURE_SUBSCRIBE: IntCounter =
@@ -44,6 +46,17 @@ lazy_static::lazy_static! {
     register_int_counter!(opts!("literpc_rpc_account_subscribe", "RPC call to subscribe to account")).unwrap();
     static ref RPC_PROGRAM_ACCOUNT_SUBSCRIBE: IntCounter =
     register_int_counter!(opts!("literpc_rpc_program_account_subscribe", "RPC call to subscribe to program account")).unwrap();
+    static ref RPC_ORPHANED_BLOCK_SUBSCRIBE: IntCounter =
+    register_int_counter!(opts!("literpc_rpc_orphaned_block_subscribe", "RPC call to subscribe to orphaned blocks")).unwrap();
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcOrphanedBlock {
+    slot: Slot,
+    blockhash: String,
+    parent_slot: Slot,
+    signatures: Vec<String>,
 }
 
 pub struct LitePubSubBridge {
@@ -52,6 +65,7 @@ pub struct LitePubSubBridge {
     account_priofees_service: AccountPrioService,
     _block_stream: BlockStream,
     block_info_stream: BlockInfoStream,
+    orphaned_block_stream: OrphanedBlockStream,
     accounts_service: Option<AccountService>,
 }
 
@@ -62,6 +76,7 @@ impl LitePubSubBridge {
         account_priofees_service: AccountPrioService,
         block_stream: BlockStream,
         block_info_stream: BlockInfoStream,
+        orphaned_block_stream: OrphanedBlockStream,
         accounts_service: Option<AccountService>,
     ) -> Self {
         Self {
@@ -70,6 +85,7 @@ impl LitePubSubBridge {
             account_priofees_service,
             _block_stream: block_stream,
             block_info_stream,
+            orphaned_block_stream,
             accounts_service,
         }
     }
@@ -293,6 +309,56 @@ impl LiteRpcPubSubServer for LitePubSubBridge {
         Ok(())
     }
 
+    async fn orphaned_block_subscribe(
+        &self,
+        pending: PendingSubscriptionSink,
+    ) -> SubscriptionResult {
+        RPC_ORPHANED_BLOCK_SUBSCRIBE.inc();
+        let sink = pending.accept().await?;
+        let mut orphaned_block_stream = self.orphaned_block_stream.resubscribe();
+        tokio::spawn(async move {
+            loop {
+                match orphaned_block_stream.recv().await {
+                    Ok(orphaned_block) => {
+                        let orphaned_block = RpcOrphanedBlock {
+                            slot: orphaned_block.slot,
+                            blockhash: orphaned_block.blockhash.to_string(),
+                            parent_slot: orphaned_block.parent_slot,
+                            signatures: orphaned_block
+                                .signatures
+                                .iter()
+                                .map(|signature| signature.to_string())
+                                .collect(),
+                        };
+                        let result_message =
+                            jsonrpsee::SubscriptionMessage::from_json(&orphaned_block);
+
+                        match sink.send(result_message.unwrap()).await {
+                            Ok(()) => {
+                                // success
+                                continue;
+                            }
+                            Err(DisconnectError(_subscription_message)) => {
+                                log::debug!("Stopping subscription task on disconnect");
+                                return;
+                            }
+                        };
+                    }
+                    Err(e) => match e {
+                        Closed => {
+                            break;
+                        }
+                        Lagged(_) => {
+                            log::error!("Orphaned block subscription stream lagged");
+                            continue;
+                        }
+                    },
+                }
+            }
+        });
+        Ok(())
+    }
+
     async fn account_subscribe(
         &self,
         pending: PendingSubscriptionSink,
@@ -1,9 +1,9 @@
-use std::borrow::Cow;
 use std::fmt::{Debug, Display, Formatter};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 use std::{env, time::Duration};
 
+use crate::client_quotas::ClientQuotasConfig;
 use crate::postgres_logger::{self, PostgresSessionConfig};
 use crate::{
     DEFAULT_FANOUT_SIZE, DEFAULT_GRPC_ADDR, DEFAULT_RETRY_TIMEOUT, DEFAULT_RPC_ADDR,
@@ -12,8 +12,10 @@ use crate::{
 use anyhow::Context;
 use clap::Parser;
 use dotenv::dotenv;
-use solana_lite_rpc_services::quic_connection_utils::QuicConnectionParameters;
-use solana_rpc_client_api::client_error::reqwest::Url;
+use solana_lite_rpc_cluster_endpoints::grpc_sources::GrpcSourceSpec;
+use solana_lite_rpc_services::quic_connection_utils::{QuicBindConfig, QuicConnectionParameters};
+use solana_lite_rpc_services::tpu_utils::tpu_connection_path::ProxyBalancing;
+use solana_lite_rpc_util::secrets::url_obfuscate_api_token;
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -33,11 +35,17 @@ pub struct Config {
     pub lite_rpc_http_addr: String,
     #[serde(default = "Config::default_lite_rpc_ws_addr")]
     pub lite_rpc_ws_addr: String,
+    // operator methods, keep it on a loopback or private interface
+    #[serde(default = "Config::default_lite_rpc_admin_addr")]
+    pub lite_rpc_admin_addr: String,
     #[serde(default = "Config::default_fanout_size")]
     pub fanout_size: u64,
     // Identity keypair path
     #[serde(default)]
     pub identity_keypair: Option<String>,
+    // keypair files tried in order by reloadIdentity when the identity keypair is not staked
+    #[serde(default)]
+    pub fallback_identity_keypairs: Vec<String>,
     #[serde(default = "Config::default_prometheus_addr")]
     pub prometheus_addr: String,
     #[serde(default = "Config::default_maximum_retries_per_tx")]
@@ -46,8 +54,28 @@ pub struct Config {
     pub transaction_retry_after_secs: u64,
     #[serde(default)]
     pub quic_proxy_addr: Option<String>,
+    // identity pubkey of the quic proxy, its certificate is not verified if not set
+    #[serde(default)]
+    pub quic_proxy_identity: Option<String>,
+    // ask the quic proxy to report the delivery of the transactions, requires a proxy speaking format version 2502
+    #[serde(default)]
+    pub quic_proxy_delivery_reports: bool,
+    // further proxies of the group next to quic_proxy_addr
+    #[serde(default)]
+    pub quic_proxies: Vec<QuicProxyConfig>,
+    #[serde(default)]
+    pub quic_proxy_balancing: ProxyBalancing,
+    // send directly to the tpus while no proxy is healthy
+    #[serde(default)]
+    pub quic_proxy_fallback_to_direct: bool,
+    // write-ahead log of the pending transactions, disabled if not set
+    #[serde(default)]
+    pub transaction_wal_path: Option<String>,
     #[serde(default)]
     pub use_grpc: bool,
+    // slots, blocks and accounts from the websocket subscriptions of ws_addr, ignored with use_grpc
+    #[serde(default)]
+    pub use_websocket: bool,
     #[serde(default)]
     pub calculate_leader_schedule_from_geyser: bool,
     #[serde(default = "Config::default_grpc_addr")]
@@ -70,9 +98,34 @@ pub struct Config {
     #[serde(default)]
     pub grpc_x_token4: Option<String>,
 
+    // replaces the numbered grpc sources if set, can be reloaded with SIGHUP
+    #[serde(default)]
+    pub grpc_sources: Vec<GrpcSourceSpec>,
+
     #[serde(default)]
     pub enable_grpc_stream_inspection: bool,
 
+    // file to record the cluster endpoint streams to, disabled if not set
+    #[serde(default)]
+    pub record_endpoint_streams: Option<String>,
+    // file with recorded streams used instead of grpc or rpc polling
+    #[serde(default)]
+    pub replay_endpoint_streams: Option<String>,
+    // 1.0 replays at the recorded pace, "inf" as fast as possible
+    #[serde(default = "Config::default_replay_speed")]
+    pub replay_speed: f64,
+
+    // host:port of a gossip entrypoint, cluster info is taken from gossip in addition to getClusterNodes if set
+    #[serde(default)]
+    pub gossip_entrypoint: Option<String>,
+    // fetched from the gossip entrypoint if not set
+    #[serde(default)]
+    pub gossip_shred_version: Option<u16>,
+
+    // compute the next epoch leader schedule from the vote account stakes
+    #[serde(default)]
+    pub local_leader_schedule: bool,
+
     /// postgres config
     #[serde(default)]
     pub postgres: Option<postgres_logger::PostgresSessionConfig>,
@@ -94,6 +147,31 @@ pub struct Config {
 
     #[serde(default)]
     pub quic_connection_parameters: Option<QuicConnectionParameters>,
+
+    // local addresses and port range of the outgoing quic endpoints
+    #[serde(default)]
+    pub quic_bind_config: Option<QuicBindConfig>,
+
+    // api keys and per-client rate limits, no limit if not set
+    #[serde(default)]
+    pub client_quotas: Option<ClientQuotasConfig>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct QuicProxyConfig {
+    // e.g. "127.0.0.1:11111" or "localhost:11111"
+    pub address: String,
+    // identity pubkey of the proxy, its certificate is not verified if not set
+    #[serde(default)]
+    pub identity: Option<String>,
+    #[serde(default = "QuicProxyConfig::default_weight")]
+    pub weight: u32,
+}
+
+impl QuicProxyConfig {
+    pub fn default_weight() -> u32 {
+        1
+    }
 }
 
 impl Config {
@@ -135,8 +213,12 @@ impl Config {
 
         config.lite_rpc_ws_addr = env::var("LITE_RPC_WS_ADDR").unwrap_or(config.lite_rpc_ws_addr);
 
+        config.lite_rpc_admin_addr =
+            env::var("LITE_RPC_ADMIN_ADDR").unwrap_or(config.lite_rpc_admin_addr);
+
         SocketAddr::from_str(&config.lite_rpc_http_addr).expect("invalid LITE_RPC_HTTP_ADDR");
         SocketAddr::from_str(&config.lite_rpc_ws_addr).expect("invalid LITE_RPC_WS_ADDR");
+        SocketAddr::from_str(&config.lite_rpc_admin_addr).expect("invalid LITE_RPC_ADMIN_ADDR");
 
         config.fanout_size = env::var("FANOUT_SIZE")
             .map(|size| size.parse().unwrap())
@@ -145,6 +227,13 @@ impl Config {
         // note: identity config is handled in load_identity_keypair
         // the behavior is different from the other config values as it does either take a file path or the keypair as json array
 
+        if let Ok(paths) = env::var("FALLBACK_IDENTITY_KEYPAIRS") {
+            config.fallback_identity_keypairs = paths
+                .split(',')
+                .map(|path| path.trim().to_string())
+                .collect();
+        }
+
         config.prometheus_addr = env::var("PROMETHEUS_ADDR").unwrap_or(config.prometheus_addr);
 
         config.maximum_retries_per_tx = env::var("MAX_RETRIES")
@@ -157,10 +246,43 @@ impl Config {
 
         config.quic_proxy_addr = env::var("QUIC_PROXY_ADDR").ok();
 
+        config.quic_proxy_identity = env::var("QUIC_PROXY_IDENTITY")
+            .ok()
+            .or(config.quic_proxy_identity);
+
+        config.quic_proxy_delivery_reports = env::var("QUIC_PROXY_DELIVERY_REPORTS")
+            .map(|value| value.parse::<bool>().unwrap())
+            .unwrap_or(config.quic_proxy_delivery_reports);
+
+        // json array, e.g. [{"address": "127.0.0.1:11112", "identity": null, "weight": 2}]
+        if let Ok(quic_proxies) = env::var("QUIC_PROXIES") {
+            config.quic_proxies =
+                serde_json::from_str(&quic_proxies).context("invalid QUIC_PROXIES")?;
+        }
+
+        // "weightedRoundRobin" or "shardByLeader"
+        if let Ok(balancing) = env::var("QUIC_PROXY_BALANCING") {
+            config.quic_proxy_balancing =
+                serde_json::from_value(serde_json::Value::String(balancing))
+                    .context("invalid QUIC_PROXY_BALANCING")?;
+        }
+
+        config.quic_proxy_fallback_to_direct = env::var("QUIC_PROXY_FALLBACK_TO_DIRECT")
+            .map(|value| value.parse::<bool>().unwrap())
+            .unwrap_or(config.quic_proxy_fallback_to_direct);
+
+        config.transaction_wal_path = env::var("TRANSACTION_WAL_PATH")
+            .ok()
+            .or(config.transaction_wal_path);
+
         config.use_grpc = env::var("USE_GRPC")
             .map(|value| value.parse::<bool>().unwrap())
             .unwrap_or(config.use_grpc);
 
+        config.use_websocket = env::var("USE_WEBSOCKET")
+            .map(|value| value.parse::<bool>().unwrap())
+            .unwrap_or(config.use_websocket);
+
         // source 1
         config.grpc_addr = env::var("GRPC_ADDR").unwrap_or(config.grpc_addr);
         config.grpc_x_token = env::var("GRPC_X_TOKEN")
@@ -200,10 +322,40 @@ impl Config {
             .map(Some)
             .unwrap_or(config.grpc_x_token4);
 
+        if let Ok(grpc_sources) = env::var("GRPC_SOURCES") {
+            config.grpc_sources =
+                serde_json::from_str(&grpc_sources).context("invalid GRPC_SOURCES")?;
+        }
+
         config.enable_grpc_stream_inspection = env::var("ENABLE_GRPC_STREAM_INSPECTION")
             .map(|value| value.parse::<bool>().expect("bool value"))
             .unwrap_or(config.enable_grpc_stream_inspection);
 
+        config.record_endpoint_streams = env::var("RECORD_ENDPOINT_STREAMS")
+            .ok()
+            .or(config.record_endpoint_streams);
+        config.replay_endpoint_streams = env::var("REPLAY_ENDPOINT_STREAMS")
+            .ok()
+            .or(config.replay_endpoint_streams);
+        if let Ok(replay_speed) = env::var("REPLAY_SPEED") {
+            config.replay_speed = replay_speed.parse().context("invalid REPLAY_SPEED")?;
+        }
+
+        config.gossip_entrypoint = env::var("GOSSIP_ENTRYPOINT")
+            .ok()
+            .or(config.gossip_entrypoint);
+        if let Ok(shred_version) = env::var("GOSSIP_SHRED_VERSION") {
+            config.gossip_shred_version = Some(
+                shred_version
+                    .parse()
+                    .context("invalid GOSSIP_SHRED_VERSION")?,
+            );
+        }
+
+        config.local_leader_schedule = env::var("LOCAL_LEADER_SCHEDULE")
+            .map(|value| value.parse::<bool>().unwrap())
+            .unwrap_or(config.local_leader_schedule);
+
         config.max_number_of_connection = env::var("MAX_NB_OF_CONNECTIONS_WITH_LEADERS")
             .map(|x| x.parse().ok())
             .unwrap_or(config.max_number_of_connection);
@@ -228,6 +380,41 @@ impl Config {
         config.quic_connection_parameters = config
             .quic_connection_parameters
             .or(quic_params_from_environment());
+
+        // comma separated, e.g. "0.0.0.0,::"
+        if let Ok(addresses) = env::var("QUIC_BIND_ADDRESSES") {
+            config
+                .quic_bind_config
+                .get_or_insert_with(QuicBindConfig::default)
+                .addresses = addresses
+                .split(',')
+                .map(|address| IpAddr::from_str(address.trim()))
+                .collect::<Result<_, _>>()
+                .context("invalid QUIC_BIND_ADDRESSES")?;
+        }
+        // e.g. "8000-10000"
+        if let Ok(port_range) = env::var("QUIC_BIND_PORT_RANGE") {
+            let (start, end) = port_range
+                .split_once('-')
+                .context("QUIC_BIND_PORT_RANGE expected as start-end")?;
+            config
+                .quic_bind_config
+                .get_or_insert_with(QuicBindConfig::default)
+                .port_range = (
+                start
+                    .trim()
+                    .parse()
+                    .context("invalid QUIC_BIND_PORT_RANGE")?,
+                end.trim().parse().context("invalid QUIC_BIND_PORT_RANGE")?,
+            );
+        }
+        if config
+            .quic_bind_config
+            .as_ref()
+            .is_some_and(|bind_config| bind_config.addresses.is_empty())
+        {
+            anyhow::bail!("at least one quic bind address is required");
+        }
         Ok(config)
     }
 
@@ -251,6 +438,10 @@ impl Config {
         "[::]:8891".to_string()
     }
 
+    pub fn default_lite_rpc_admin_addr() -> String {
+        "127.0.0.1:8892".to_string()
+    }
+
     pub const fn default_fanout_size() -> u64 {
         DEFAULT_FANOUT_SIZE
     }
@@ -267,80 +458,73 @@ impl Config {
         DEFAULT_RETRY_TIMEOUT
     }
 
+    pub const fn default_replay_speed() -> f64 {
+        1.0
+    }
+
     pub fn default_grpc_addr() -> String {
         DEFAULT_GRPC_ADDR.to_string()
     }
 
-    pub fn get_grpc_sources(&self) -> Vec<GrpcSource> {
-        let mut sources: Vec<GrpcSource> = vec![];
+    /// grpc_sources replaces grpc_addr..grpc_addr4 if set
+    pub fn get_grpc_sources(&self) -> Vec<GrpcSourceSpec> {
+        if !self.grpc_sources.is_empty() {
+            return self.grpc_sources.clone();
+        }
 
-        sources.push(GrpcSource {
-            addr: self.grpc_addr.clone(),
-            x_token: self.grpc_x_token.clone(),
-        });
+        let mut sources: Vec<GrpcSourceSpec> = vec![];
+
+        sources.push(GrpcSourceSpec::new(
+            self.grpc_addr.clone(),
+            self.grpc_x_token.clone(),
+        ));
 
         if self.grpc_addr2.is_some() {
-            sources.push(GrpcSource {
-                addr: self.grpc_addr2.clone().unwrap(),
-                x_token: self.grpc_x_token2.clone(),
-            });
+            sources.push(GrpcSourceSpec::new(
+                self.grpc_addr2.clone().unwrap(),
+                self.grpc_x_token2.clone(),
+            ));
         }
 
         if self.grpc_addr3.is_some() {
-            sources.push(GrpcSource {
-                addr: self.grpc_addr3.clone().unwrap(),
-                x_token: self.grpc_x_token3.clone(),
-            });
+            sources.push(GrpcSourceSpec::new(
+                self.grpc_addr3.clone().unwrap(),
+                self.grpc_x_token3.clone(),
+            ));
         }
 
         if self.grpc_addr4.is_some() {
-            sources.push(GrpcSource {
-                addr: self.grpc_addr4.clone().unwrap(),
-                x_token: self.grpc_x_token4.clone(),
-            });
+            sources.push(GrpcSourceSpec::new(
+                self.grpc_addr4.clone().unwrap(),
+                self.grpc_x_token4.clone(),
+            ));
         }
 
         sources
     }
 }
 
-#[derive(Clone)]
-pub struct GrpcSource {
-    pub addr: String,
-    pub x_token: Option<String>,
-}
+/// grpc source for logging, without secrets
+pub struct GrpcSourceDisplay<'a>(pub &'a GrpcSourceSpec);
 
-impl Display for GrpcSource {
+impl Display for GrpcSourceDisplay<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "GrpcSource {} (x-token {})",
-            url_obfuscate_api_token(&self.addr),
-            obfuscate_token(&self.x_token)
+            "GrpcSource {} (x-token {}, role {:?})",
+            url_obfuscate_api_token(&self.0.addr),
+            obfuscate_token(&self.0.x_token),
+            self.0.role
         )
     }
 }
 
-impl Debug for GrpcSource {
+impl Debug for GrpcSourceDisplay<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         Display::fmt(self, f)
     }
 }
 
-/// obfuscate urls with api token like http://mango.rpcpool.com/a991fba00fagbad
-fn url_obfuscate_api_token(url: &str) -> Cow<str> {
-    if let Ok(mut parsed) = Url::parse(url) {
-        if parsed.path() == "/" {
-            return Cow::Borrowed(url);
-        } else {
-            parsed.set_path("omitted-secret");
-            Cow::Owned(parsed.to_string())
-        }
-    } else {
-        Cow::Borrowed(url)
-    }
-}
-
 fn obfuscate_token(token: &Option<String>) -> String {
     match token {
         None => "n/a".to_string(),
@@ -393,5 +577,16 @@ fn quic_params_from_environment() -> Option<QuicConnectionParameters> {
                 quic_connection_parameters.unistreams_to_create_new_connection_in_percentage,
             );
 
+    quic_connection_parameters.prioritization_heap_share_per_sender_in_percentage =
+        env::var("QUIC_PRIORITIZATION_HEAP_SHARE_PER_SENDER_PERCENTAGE")
+            .map(|percentage| percentage.parse().unwrap())
+            .unwrap_or(
+                quic_connection_parameters.prioritization_heap_share_per_sender_in_percentage,
+            );
+
+    quic_connection_parameters.prewarm_slots = env::var("QUIC_PREWARM_SLOTS")
+        .map(|slots| slots.parse().unwrap())
+        .unwrap_or(quic_connection_parameters.prewarm_slots);
+
     Some(quic_connection_parameters)
 }
@@ -0,0 +1,488 @@
+// Authentication and per-client quotas of the json rpc servers
+// Clients are identified by an api key passed in the `x-api-key` header or, if enabled, as url path (http://host:port/<api key>)
+
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use hyper::{
+    body::HttpBody, header::HeaderValue, http::uri::PathAndQuery, Body, Method, Request, Response,
+    StatusCode, Uri,
+};
+use jsonrpsee::types::error::ErrorCode;
+use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use serde_json::{json, Value};
+use tower::{Layer, Service};
+
+lazy_static::lazy_static! {
+    static ref CLIENT_REQUESTS: IntCounterVec =
+        register_int_counter_vec!(opts!("literpc_client_requests", "Number of rpc calls per client"), &["client", "kind"]).unwrap();
+    static ref CLIENT_RATE_LIMITED: IntCounterVec =
+        register_int_counter_vec!(opts!("literpc_client_rate_limited", "Number of rpc calls rejected by the quota of the client"), &["client", "kind"]).unwrap();
+    static ref CLIENT_UNAUTHORIZED: IntCounterVec =
+        register_int_counter_vec!(opts!("literpc_client_unauthorized", "Number of requests with a missing or unknown api key"), &["reason"]).unwrap();
+}
+
+tokio::task_local! {
+    // ceiling of the maxRetries of the client whose http request is served
+    static MAX_RETRIES_CEILING: Option<usize>;
+}
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+const ANONYMOUS_CLIENT: &str = "anonymous";
+
+/// ceiling of the maxRetries of the client of the current request, if its quota sets one
+pub fn max_retries_ceiling() -> Option<usize> {
+    MAX_RETRIES_CEILING
+        .try_with(|ceiling| *ceiling)
+        .ok()
+        .flatten()
+}
+
+/// Limits applied to a client, unset limits are not enforced
+#[derive(Debug, Clone, Copy, Default, serde::Deserialize)]
+pub struct ClientQuota {
+    #[serde(default)]
+    pub send_transactions_per_second: Option<u32>,
+    // defaults to send_transactions_per_second
+    #[serde(default)]
+    pub send_transactions_burst: Option<u32>,
+    #[serde(default)]
+    pub read_requests_per_second: Option<u32>,
+    // defaults to read_requests_per_second
+    #[serde(default)]
+    pub read_requests_burst: Option<u32>,
+    // ceiling of the maxRetries of sendTransaction and sendBundle
+    #[serde(default)]
+    pub max_retries: Option<usize>,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct ApiKeyConfig {
+    // used as label of the metrics so the key itself is never exposed
+    pub name: String,
+    pub key: String,
+    #[serde(flatten)]
+    pub quota: ClientQuota,
+}
+
+impl Debug for ApiKeyConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKeyConfig")
+            .field("name", &self.name)
+            .field("quota", &self.quota)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct ClientQuotasConfig {
+    // reject the requests without api key instead of applying the anonymous quota
+    #[serde(default)]
+    pub require_api_key: bool,
+    // also read the api key from the first segment of the url path
+    #[serde(default)]
+    pub api_key_in_path: bool,
+    #[serde(default)]
+    pub anonymous_quota: ClientQuota,
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_second: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(per_second: Option<u32>, burst: Option<u32>) -> Option<Mutex<Self>> {
+        let per_second = per_second?;
+        let capacity = burst.unwrap_or(per_second).max(1) as f64;
+        Some(Mutex::new(Self {
+            capacity,
+            tokens: capacity,
+            refill_per_second: per_second as f64,
+            last_refill: Instant::now(),
+        }))
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        self.last_refill = now;
+    }
+}
+
+struct ClientState {
+    name: String,
+    send_bucket: Option<Mutex<TokenBucket>>,
+    read_bucket: Option<Mutex<TokenBucket>>,
+    max_retries: Option<usize>,
+}
+
+impl ClientState {
+    fn new(name: String, quota: &ClientQuota) -> Self {
+        Self {
+            name,
+            send_bucket: TokenBucket::new(
+                quota.send_transactions_per_second,
+                quota.send_transactions_burst,
+            ),
+            read_bucket: TokenBucket::new(
+                quota.read_requests_per_second,
+                quota.read_requests_burst,
+            ),
+            max_retries: quota.max_retries,
+        }
+    }
+
+    // takes the tokens from both buckets only if both have enough of them
+    fn try_acquire(&self, send_count: u32, read_count: u32) -> bool {
+        let counts = [("send", send_count), ("read", read_count)];
+        for (kind, count) in counts {
+            if count > 0 {
+                CLIENT_REQUESTS
+                    .with_label_values(&[&self.name, kind])
+                    .inc_by(count as u64);
+            }
+        }
+
+        let now = Instant::now();
+        // always locked in the same order
+        let mut send_bucket = self.send_bucket.as_ref().map(|b| b.lock().unwrap());
+        let mut read_bucket = self.read_bucket.as_ref().map(|b| b.lock().unwrap());
+        let mut buckets = [
+            (send_bucket.as_deref_mut(), send_count),
+            (read_bucket.as_deref_mut(), read_count),
+        ];
+        let acquired = buckets.iter_mut().all(|(bucket, count)| match bucket {
+            Some(bucket) if *count > 0 => {
+                bucket.refill(now);
+                bucket.tokens >= *count as f64
+            }
+            _ => true,
+        });
+        if acquired {
+            for (bucket, count) in buckets {
+                if let Some(bucket) = bucket {
+                    bucket.tokens -= count as f64;
+                }
+            }
+        } else {
+            for (kind, count) in counts {
+                if count > 0 {
+                    CLIENT_RATE_LIMITED
+                        .with_label_values(&[&self.name, kind])
+                        .inc_by(count as u64);
+                }
+            }
+        }
+        acquired
+    }
+}
+
+pub struct ClientQuotas {
+    require_api_key: bool,
+    api_key_in_path: bool,
+    anonymous: ClientState,
+    clients: HashMap<String, ClientState>,
+    // same limit as the one of the server behind
+    max_request_body_size: u32,
+}
+
+impl ClientQuotas {
+    pub fn new(config: ClientQuotasConfig, max_request_body_size: u32) -> Self {
+        let clients = config
+            .api_keys
+            .iter()
+            .map(|api_key| {
+                (
+                    api_key.key.clone(),
+                    ClientState::new(api_key.name.clone(), &api_key.quota),
+                )
+            })
+            .collect();
+        Self {
+            require_api_key: config.require_api_key,
+            api_key_in_path: config.api_key_in_path,
+            anonymous: ClientState::new(ANONYMOUS_CLIENT.to_string(), &config.anonymous_quota),
+            clients,
+            max_request_body_size,
+        }
+    }
+
+    // takes the api key from the header or the path, the path is reset so jsonrpsee serves the request
+    fn authenticate(&self, request: &mut Request<Body>) -> Result<&ClientState, Rejection> {
+        let path_key = self
+            .api_key_in_path
+            .then(|| request.uri().path().trim_matches('/').split('/').next())
+            .flatten()
+            .filter(|key| !key.is_empty())
+            .map(str::to_string);
+        let api_key = request
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|key| key.to_str().ok())
+            .map(str::to_string)
+            .or(path_key.clone());
+
+        if path_key.is_some() {
+            let mut parts = request.uri().clone().into_parts();
+            parts.path_and_query = Some(PathAndQuery::from_static("/"));
+            *request.uri_mut() = Uri::from_parts(parts).unwrap_or_default();
+        }
+
+        match api_key {
+            Some(api_key) => self.clients.get(&api_key).ok_or_else(|| {
+                CLIENT_UNAUTHORIZED.with_label_values(&["unknown"]).inc();
+                Rejection::new(StatusCode::UNAUTHORIZED, -32001, "unknown api key")
+            }),
+            None if self.require_api_key => {
+                CLIENT_UNAUTHORIZED.with_label_values(&["missing"]).inc();
+                Err(Rejection::new(
+                    StatusCode::UNAUTHORIZED,
+                    -32001,
+                    "missing api key",
+                ))
+            }
+            None => Ok(&self.anonymous),
+        }
+    }
+
+    // returns the request to serve and the maxRetries ceiling of its client
+    async fn admit(
+        &self,
+        mut request: Request<Body>,
+    ) -> Result<(Request<Body>, Option<usize>), Rejection> {
+        let client = self.authenticate(&mut request)?;
+        if request.method() != Method::POST {
+            // the calls over a websocket do not go through the middleware, opening it costs a read request
+            if is_websocket_upgrade(&request) && !client.try_acquire(0, 1) {
+                return Err(Rejection::rate_limited());
+            }
+            // preflight requests only need to be authenticated
+            return Ok((request, client.max_retries));
+        }
+
+        let (parts, body) = request.into_parts();
+        let body = read_body(body, self.max_request_body_size).await?;
+        let (send_count, read_count) = match serde_json::from_slice::<Value>(&body) {
+            Ok(calls) => count_calls(&calls),
+            // let jsonrpsee answer with a parse error
+            Err(_) => (0, 1),
+        };
+        if !client.try_acquire(send_count, read_count) {
+            return Err(Rejection::rate_limited());
+        }
+        Ok((
+            Request::from_parts(parts, Body::from(body)),
+            client.max_retries,
+        ))
+    }
+}
+
+fn is_websocket_upgrade(request: &Request<Body>) -> bool {
+    request
+        .headers()
+        .get(hyper::header::UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+}
+
+async fn read_body(mut body: Body, max_size: u32) -> Result<Vec<u8>, Rejection> {
+    let mut buf = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| {
+            Rejection::new(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::ParseError.code(),
+                "invalid body",
+            )
+        })?;
+        if buf.len() + chunk.len() > max_size as usize {
+            return Err(Rejection::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                ErrorCode::OversizedRequest.code(),
+                "request body too large",
+            ));
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf)
+}
+
+fn calls_of(calls: &Value) -> Vec<&Value> {
+    match calls {
+        Value::Array(batch) => batch.iter().collect(),
+        call => vec![call],
+    }
+}
+
+// returns the number of transactions sent and the number of read calls
+fn count_calls(calls: &Value) -> (u32, u32) {
+    calls_of(calls)
+        .into_iter()
+        .fold((0, 0), |(send, read), call| match call["method"].as_str() {
+            Some("sendBundle") => {
+                let nb_txs = call["params"][0].as_array().map_or(1, Vec::len) as u32;
+                (send + nb_txs, read)
+            }
+            Some("sendTransaction") => (send + 1, read),
+            _ => (send, read + 1),
+        })
+}
+
+// request refused before reaching jsonrpsee
+struct Rejection {
+    status: StatusCode,
+    code: i32,
+    message: &'static str,
+}
+
+impl Rejection {
+    fn new(status: StatusCode, code: i32, message: &'static str) -> Self {
+        Self {
+            status,
+            code,
+            message,
+        }
+    }
+
+    fn rate_limited() -> Self {
+        Self::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::ServerIsBusy.code(),
+            "rate limit exceeded for this api key",
+        )
+    }
+
+    fn into_response(self) -> Response<Body> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "error": { "code": self.code, "message": self.message },
+            "id": null,
+        });
+        let mut response = Response::new(Body::from(body.to_string()));
+        *response.status_mut() = self.status;
+        response.headers_mut().insert(
+            hyper::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        response
+    }
+}
+
+#[derive(Clone)]
+pub struct ClientQuotaLayer {
+    quotas: Arc<ClientQuotas>,
+}
+
+impl ClientQuotaLayer {
+    pub fn new(quotas: Arc<ClientQuotas>) -> Self {
+        Self { quotas }
+    }
+}
+
+impl<S> Layer<S> for ClientQuotaLayer {
+    type Service = ClientQuotaService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientQuotaService {
+            inner,
+            quotas: self.quotas.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ClientQuotaService<S> {
+    inner: S,
+    quotas: Arc<ClientQuotas>,
+}
+
+impl<S> Service<Request<Body>> for ClientQuotaService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // the service which was polled ready handles the request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let quotas = self.quotas.clone();
+        Box::pin(async move {
+            match quotas.admit(request).await {
+                // the methods are run within the call so they can read the ceiling of the client
+                Ok((request, max_retries)) => {
+                    MAX_RETRIES_CEILING
+                        .scope(max_retries, inner.call(request))
+                        .await
+                }
+                Err(rejection) => Ok(rejection.into_response()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quotas(api_key_in_path: bool) -> ClientQuotas {
+        let quota = ClientQuota {
+            send_transactions_per_second: Some(1),
+            read_requests_per_second: Some(1),
+            ..ClientQuota::default()
+        };
+        ClientQuotas::new(
+            ClientQuotasConfig {
+                require_api_key: true,
+                api_key_in_path,
+                anonymous_quota: ClientQuota::default(),
+                api_keys: vec![ApiKeyConfig {
+                    name: "client".to_string(),
+                    key: "key".to_string(),
+                    quota,
+                }],
+            },
+            1024,
+        )
+    }
+
+    #[test]
+    fn rejected_calls_take_no_tokens() {
+        let quotas = quotas(false);
+        let client = &quotas.clients["key"];
+        assert!(client.try_acquire(0, 1));
+        // the send bucket is not debited when the read bucket is empty
+        assert!(!client.try_acquire(1, 1));
+        assert!(client.try_acquire(1, 0));
+    }
+
+    #[test]
+    fn path_key_only_when_enabled() {
+        let request = || Request::post("/key").body(Body::empty()).unwrap();
+        assert!(quotas(false).authenticate(&mut request()).is_err());
+
+        let mut request = request();
+        assert!(quotas(true).authenticate(&mut request).is_ok());
+        assert_eq!(request.uri().path(), "/");
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use solana_lite_rpc_core::encoding::BinaryEncoding;
+use solana_lite_rpc_services::transaction_replayer::ReplayStrategy;
+use solana_rpc_client_api::config::RpcSendTransactionConfig;
 use solana_sdk::commitment_config::CommitmentLevel;
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -15,6 +17,15 @@ pub struct SendTransactionConfig {
     //    pub min_context_slot: Option<Slot>,
 }
 
+/// solana send transaction config extended with lite-rpc specific options
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiteSendTransactionConfig {
+    #[serde(flatten)]
+    pub rpc_config: RpcSendTransactionConfig,
+    pub replay_strategy: Option<ReplayStrategy>,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IsBlockHashValidConfig {
@@ -0,0 +1,67 @@
+// Rotation of the identity of the staked quic connections, triggered with the reloadIdentity admin method
+
+use anyhow::bail;
+use log::{info, warn};
+use solana_lite_rpc_core::{
+    keypair_loader::load_keypair_file, structures::identity_stakes::IdentityStakes,
+};
+use solana_lite_rpc_services::tpu_utils::tpu_service::TpuService;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Reloads the identity keypair file, falling back on the next keypair files when it is not staked
+#[derive(Clone)]
+pub struct IdentityRotation {
+    tpu_service: TpuService,
+    identity_stakes: IdentityStakes,
+    // the configured identity keypair file first, then the fallbacks
+    keypair_files: Vec<String>,
+}
+
+impl IdentityRotation {
+    pub fn new(
+        tpu_service: TpuService,
+        identity_stakes: IdentityStakes,
+        identity_keypair: Option<String>,
+        fallback_identity_keypairs: Vec<String>,
+    ) -> Self {
+        Self {
+            tpu_service,
+            identity_stakes,
+            keypair_files: identity_keypair
+                .into_iter()
+                .chain(fallback_identity_keypairs)
+                .collect(),
+        }
+    }
+
+    /// switches to the first keypair file which loads and is staked, or to the first one which loads
+    pub async fn rotate(&self) -> anyhow::Result<Pubkey> {
+        let mut first_loaded = None;
+        for path in &self.keypair_files {
+            let identity = match load_keypair_file(path).await {
+                Ok(identity) => identity,
+                Err(e) => {
+                    warn!("Cannot load identity keypair {path}: {e:?}");
+                    continue;
+                }
+            };
+            if self.identity_stakes.stakes_of(&identity.pubkey()).await > 0 {
+                return self.switch_to(identity).await;
+            }
+            info!("Identity {} of {path} is not staked", identity.pubkey());
+            first_loaded.get_or_insert(identity);
+        }
+        match first_loaded {
+            Some(identity) => self.switch_to(identity).await,
+            None => bail!("no identity keypair file could be loaded"),
+        }
+    }
+
+    async fn switch_to(&self, identity: Keypair) -> anyhow::Result<Pubkey> {
+        self.tpu_service.update_identity(&identity).await?;
+        Ok(identity.pubkey())
+    }
+}
@@ -2,13 +2,17 @@ use const_env::from_env;
 use solana_transaction_status::TransactionConfirmationStatus;
 
 pub mod bridge;
+pub mod bridge_admin;
 pub mod bridge_pubsub;
 pub mod cli;
+pub mod client_quotas;
 pub mod configs;
 pub mod errors;
+pub mod identity_rotation;
 pub mod jsonrpsee_subscrption_handler_sink;
 pub mod postgres_logger;
 pub mod rpc;
+pub mod rpc_admin;
 pub mod rpc_errors;
 pub mod rpc_pubsub;
 pub mod service_spawner;
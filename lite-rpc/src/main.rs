@@ -1,17 +1,19 @@
 pub mod rpc_tester;
 
 use crate::rpc_tester::RpcTester;
-use anyhow::bail;
+use anyhow::{bail, Context};
 use dashmap::DashMap;
 use itertools::Itertools;
 use lite_rpc::bridge::LiteBridge;
+use lite_rpc::bridge_admin::LiteAdminBridge;
 use lite_rpc::bridge_pubsub::LitePubSubBridge;
-use lite_rpc::cli::Config;
+use lite_rpc::cli::{Config, GrpcSourceDisplay, QuicProxyConfig};
+use lite_rpc::identity_rotation::IdentityRotation;
 use lite_rpc::postgres_logger::PostgresLogger;
 use lite_rpc::service_spawner::ServiceSpawner;
 use lite_rpc::start_server::start_servers;
 use lite_rpc::DEFAULT_MAX_NUMBER_OF_TXS_IN_QUEUE;
-use log::info;
+use log::{error, info};
 use solana_lite_rpc_accounts::account_service::AccountService;
 use solana_lite_rpc_accounts::account_store_interface::AccountStorageInterface;
 use solana_lite_rpc_accounts::inmemory_account_store::InmemoryAccountStore;
@@ -19,20 +21,27 @@ use solana_lite_rpc_accounts_on_demand::accounts_on_demand::AccountsOnDemand;
 use solana_lite_rpc_address_lookup_tables::address_lookup_table_store::AddressLookupTableStore;
 use solana_lite_rpc_blockstore::history::History;
 use solana_lite_rpc_cluster_endpoints::endpoint_stremers::EndpointStreaming;
+use solana_lite_rpc_cluster_endpoints::fork_tracker::create_fork_tracker_task;
 
-use solana_lite_rpc_cluster_endpoints::geyser_grpc_connector::{
-    GrpcConnectionTimeouts, GrpcSourceConfig,
-};
+use solana_lite_rpc_cluster_endpoints::geyser_grpc_connector::GrpcConnectionTimeouts;
+use solana_lite_rpc_cluster_endpoints::gossip_cluster_info::start_gossip_cluster_info_task;
 use solana_lite_rpc_cluster_endpoints::grpc_inspect::{
     debugtask_blockstream_confirmation_sequence, debugtask_blockstream_slot_progression,
 };
+use solana_lite_rpc_cluster_endpoints::grpc_source_health::GrpcSourcesHealth;
+use solana_lite_rpc_cluster_endpoints::grpc_sources::GrpcSources;
 use solana_lite_rpc_cluster_endpoints::grpc_subscription::create_grpc_subscription;
 use solana_lite_rpc_cluster_endpoints::json_rpc_leaders_getter::JsonRpcLeaderGetter;
 use solana_lite_rpc_cluster_endpoints::json_rpc_subscription::create_json_rpc_polling_subscription;
 use solana_lite_rpc_cluster_endpoints::rpc_polling::poll_blocks::NUM_PARALLEL_TASKS_DEFAULT;
+use solana_lite_rpc_cluster_endpoints::stream_recording::{
+    create_replay_subscription, record_endpoint_streams,
+};
+use solana_lite_rpc_cluster_endpoints::websocket_subscription::create_websocket_subscription;
 use solana_lite_rpc_core::keypair_loader::load_identity_keypair;
 use solana_lite_rpc_core::stores::{
     block_information_store::{BlockInformation, BlockInformationStore},
+    bundle_store::BundleStore,
     cluster_info_store::ClusterInfo,
     data_cache::{DataCache, SlotCache},
     subscription_store::SubscriptionStore,
@@ -44,24 +53,34 @@ use solana_lite_rpc_core::structures::{
     epoch::EpochCache, identity_stakes::IdentityStakes, notifications::NotificationSender,
 };
 use solana_lite_rpc_core::traits::address_lookup_table_interface::AddressLookupTableInterface;
+use solana_lite_rpc_core::traits::leaders_fetcher_interface::LeaderFetcherInterface;
 use solana_lite_rpc_core::types::BlockStream;
 use solana_lite_rpc_core::utils::wait_till_block_of_commitment_is_recieved;
 use solana_lite_rpc_core::AnyhowJoinHandle;
 use solana_lite_rpc_prioritization_fees::account_prio_service::AccountPrioService;
 use solana_lite_rpc_services::data_caching_service::DataCachingService;
-use solana_lite_rpc_services::tpu_utils::tpu_connection_path::TpuConnectionPath;
+use solana_lite_rpc_services::tpu_utils::tpu_connection_path::{
+    ForwardProxy, ProxyBalancing, TpuConnectionPath,
+};
 use solana_lite_rpc_services::tpu_utils::tpu_service::{TpuService, TpuServiceConfig};
 use solana_lite_rpc_services::transaction_replayer::TransactionReplayer;
+use solana_lite_rpc_services::transaction_wal::TransactionWal;
 use solana_lite_rpc_services::tx_sender::TxSender;
+use solana_lite_rpc_stakevote::local_schedule::{
+    start_local_leader_schedule_task, start_rpc_leader_schedule_task,
+};
 
 use lite_rpc::postgres_logger;
 use solana_lite_rpc_prioritization_fees::start_block_priofees_task;
 use solana_lite_rpc_util::obfuscate_rpcurl;
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Keypair;
 use solana_sdk::signer::Signer;
 use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::AsyncReadExt;
@@ -98,36 +117,64 @@ pub async fn start_postgres(
 
 pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow::Result<()> {
     let grpc_sources = args.get_grpc_sources();
-    log::info!("grpc_sources:{grpc_sources:?}");
+    log::info!(
+        "grpc_sources:{:?}",
+        grpc_sources.iter().map(GrpcSourceDisplay).collect_vec()
+    );
     let Config {
+        ws_addr,
         lite_rpc_ws_addr,
         lite_rpc_http_addr,
+        lite_rpc_admin_addr,
         fanout_size,
         postgres,
         prometheus_addr,
         identity_keypair,
+        fallback_identity_keypairs,
         maximum_retries_per_tx,
         transaction_retry_after_secs,
         quic_proxy_addr,
+        quic_proxy_identity,
+        quic_proxy_delivery_reports,
+        quic_proxies,
+        quic_proxy_balancing,
+        quic_proxy_fallback_to_direct,
+        transaction_wal_path,
         use_grpc,
+        use_websocket,
         enable_grpc_stream_inspection,
+        record_endpoint_streams: record_streams_path,
+        replay_endpoint_streams: replay_streams_path,
+        replay_speed,
+        gossip_entrypoint,
+        gossip_shred_version,
+        local_leader_schedule,
         enable_address_lookup_tables,
         address_lookup_tables_binary,
         account_filters,
         enable_accounts_on_demand_accounts_service,
         quic_connection_parameters,
+        quic_bind_config,
+        client_quotas,
         ..
     } = args;
 
     let validator_identity = Arc::new(
-        load_identity_keypair(identity_keypair)
+        load_identity_keypair(identity_keypair.clone())
             .await?
             .unwrap_or_else(Keypair::new),
     );
 
     let retry_after = Duration::from_secs(transaction_retry_after_secs);
 
-    let tpu_connection_path = configure_tpu_connection_path(quic_proxy_addr);
+    let tpu_connection_path = configure_tpu_connection_path(
+        quic_proxy_addr,
+        quic_proxy_identity,
+        quic_proxies,
+        quic_proxy_balancing,
+        quic_proxy_fallback_to_direct,
+        quic_proxy_delivery_reports,
+    )?;
 
     let account_filters = if let Some(account_filters) = account_filters {
         serde_json::from_str::<AccountFilters>(account_filters.as_str())
@@ -151,27 +198,62 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
         receive_timeout: Duration::from_secs(15),
     };
 
-    let gprc_sources = grpc_sources
-        .iter()
-        .map(|s| GrpcSourceConfig::new(s.addr.clone(), s.x_token.clone(), None, timeouts.clone()))
-        .collect_vec();
-
-    let (subscriptions, cluster_endpoint_tasks) = if use_grpc {
+    let grpc_sources = GrpcSources::new(grpc_sources, timeouts);
+
+    // source rankings and source changes for the admin rpc, only with grpc
+    let mut grpc_sources_health = None;
+    let mut dynamic_grpc_sources = None;
+    // epoch schedule and leaders of the recording when it is replayed
+    let mut replayed_cluster = None;
+    let (subscriptions, mut cluster_endpoint_tasks) = if let Some(replay_path) = replay_streams_path
+    {
+        info!("Replaying recorded endpoint streams...");
+        let (subscriptions, tasks, recorded_cluster) =
+            create_replay_subscription(PathBuf::from(replay_path), replay_speed)?;
+        replayed_cluster = Some(recorded_cluster);
+        (subscriptions, tasks)
+    } else if use_grpc {
         info!("Creating geyser subscription...");
+        let sources_health = GrpcSourcesHealth::new(&[]);
+        grpc_sources_health = Some(sources_health.clone());
+        dynamic_grpc_sources = Some(grpc_sources.clone());
         create_grpc_subscription(
             rpc_client.clone(),
-            grpc_sources
-                .iter()
-                .map(|s| {
-                    GrpcSourceConfig::new(s.addr.clone(), s.x_token.clone(), None, timeouts.clone())
-                })
-                .collect(),
+            grpc_sources.clone(),
             account_filters.clone(),
+            sources_health,
+        )?
+    } else if use_websocket {
+        info!("Creating websocket subscription...");
+        create_websocket_subscription(
+            rpc_client.clone(),
+            ws_addr,
+            account_filters.clone(),
+            NUM_PARALLEL_TASKS_DEFAULT,
         )?
     } else {
         info!("Creating RPC poll subscription...");
         create_json_rpc_polling_subscription(rpc_client.clone(), NUM_PARALLEL_TASKS_DEFAULT)?
     };
+    let (epoch_data, leader_schedule): (EpochCache, Arc<dyn LeaderFetcherInterface>) =
+        match replayed_cluster {
+            Some(replayed_cluster) => (
+                replayed_cluster.epoch_cache,
+                Arc::new(replayed_cluster.leader_schedule),
+            ),
+            None => (
+                EpochCache::bootstrap_epoch(&rpc_client).await?.0,
+                Arc::new(JsonRpcLeaderGetter::new(rpc_client.clone(), 1024, 128)),
+            ),
+        };
+    if let Some(record_path) = record_streams_path {
+        cluster_endpoint_tasks.push(record_endpoint_streams(
+            &subscriptions,
+            epoch_data.clone(),
+            leader_schedule.clone(),
+            PathBuf::from(record_path),
+        )?);
+    }
     let EndpointStreaming {
         // note: blocks_notifier will be dropped at some point
         blocks_notifier,
@@ -182,6 +264,10 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
         processed_account_stream,
     } = subscriptions;
 
+    let (fork_tracker_task, orphaned_block_notifier) =
+        create_fork_tracker_task(blocks_notifier.resubscribe());
+    cluster_endpoint_tasks.push(fork_tracker_task);
+
     if enable_grpc_stream_inspection {
         setup_grpc_stream_debugging(&blocks_notifier)
     } else {
@@ -200,7 +286,7 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
         let account_storage = if enable_accounts_on_demand_accounts_service {
             Arc::new(AccountsOnDemand::new(
                 rpc_client.clone(),
-                gprc_sources,
+                grpc_sources.subscribe_accounts(),
                 inmemory_account_storage,
                 account_notification_sender.clone(),
             ))
@@ -213,6 +299,7 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
         account_service.process_account_stream(
             account_stream.resubscribe(),
             blockinfo_notifier.resubscribe(),
+            orphaned_block_notifier.resubscribe(),
         );
 
         account_service
@@ -235,8 +322,6 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
     .await;
     info!("Got finalized block info: {:?}", finalized_block_info.slot);
 
-    let (epoch_data, _current_epoch_info) = EpochCache::bootstrap_epoch(&rpc_client).await?;
-
     let block_information_store =
         BlockInformationStore::new(BlockInformation::from_block_info(&finalized_block_info));
 
@@ -249,6 +334,7 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
         txs: TxStore {
             store: Arc::new(DashMap::new()),
         },
+        bundles: BundleStore::default(),
         epoch_data,
         leader_schedule: Arc::new(RwLock::new(CalculatedSchedule::default())),
     };
@@ -259,16 +345,43 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
     };
 
     // to avoid laggin we resubscribe to block notification
-    let data_caching_service = data_cache_service.listen(
+    let mut data_caching_service = data_cache_service.listen(
         blocks_notifier.resubscribe(),
         blockinfo_notifier.resubscribe(),
         slot_notifier.resubscribe(),
         cluster_info_notifier,
-        vote_account_notifier,
+        vote_account_notifier.resubscribe(),
+        orphaned_block_notifier.resubscribe(),
     );
+    if let Some(gossip_entrypoint) = gossip_entrypoint {
+        info!("Taking cluster info from gossip at {}", gossip_entrypoint);
+        data_caching_service.push(start_gossip_cluster_info_task(
+            gossip_entrypoint,
+            gossip_shred_version,
+            data_cache.cluster_info.clone(),
+        ));
+    }
+    if local_leader_schedule {
+        info!("Computing the next epoch leader schedule locally");
+        data_caching_service.push(start_local_leader_schedule_task(
+            data_cache.clone(),
+            rpc_client.clone(),
+            vote_account_notifier,
+            slot_notifier.resubscribe(),
+        ));
+    } else {
+        data_caching_service.push(start_rpc_leader_schedule_task(
+            data_cache.clone(),
+            rpc_client.clone(),
+            slot_notifier.resubscribe(),
+        ));
+    }
 
-    let (block_priofees_task, block_priofees_service) =
-        start_block_priofees_task(blocks_notifier.resubscribe(), 100);
+    let (block_priofees_task, block_priofees_service) = start_block_priofees_task(
+        blocks_notifier.resubscribe(),
+        orphaned_block_notifier.resubscribe(),
+        100,
+    );
 
     let address_lookup_tables: Option<Arc<dyn AddressLookupTableInterface>> =
         if enable_address_lookup_tables.unwrap_or_default() {
@@ -298,6 +411,7 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
     let (account_priofees_task, account_priofees_service) =
         AccountPrioService::start_account_priofees_task(
             blocks_notifier.resubscribe(),
+            orphaned_block_notifier.resubscribe(),
             100,
             address_lookup_tables,
         );
@@ -309,13 +423,12 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
         maximum_transaction_in_queue: 20000,
         quic_connection_params: quic_connection_parameters.unwrap_or_default(),
         tpu_connection_path,
+        quic_bind_config,
     };
 
     let spawner = ServiceSpawner {
         data_cache: data_cache.clone(),
     };
-    //init grpc leader schedule and vote account is configured.
-    let leader_schedule = Arc::new(JsonRpcLeaderGetter::new(rpc_client.clone(), 1024, 128));
     let tpu_service: TpuService = TpuService::new(
         tpu_config,
         validator_identity,
@@ -323,9 +436,24 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
         data_cache.clone(),
     )
     .await?;
+    let leader_scoring = tpu_service.start_leader_scoring(blocks_notifier.resubscribe());
+    let identity_rotation = IdentityRotation::new(
+        tpu_service.clone(),
+        data_cache.identity_stakes.clone(),
+        identity_keypair,
+        fallback_identity_keypairs,
+    );
+    let grpc_sources_reload = reload_grpc_sources_on_sighup(grpc_sources);
     let tx_sender = TxSender::new(data_cache.clone(), tpu_service.clone());
-    let tx_replayer =
+    let mut tx_replayer =
         TransactionReplayer::new(tpu_service.clone(), data_cache.clone(), retry_after);
+    let pending_transactions = if let Some(transaction_wal_path) = transaction_wal_path {
+        let (transaction_wal, pending_transactions) = TransactionWal::open(transaction_wal_path)?;
+        tx_replayer = tx_replayer.with_transaction_wal(transaction_wal);
+        pending_transactions
+    } else {
+        vec![]
+    };
     let (transaction_service, tx_service_jh) = spawner.spawn_tx_service(
         tx_sender,
         tx_replayer,
@@ -335,12 +463,23 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
         maximum_retries_per_tx,
         slot_notifier.resubscribe(),
     );
+    transaction_service
+        .restore_pending_transactions(pending_transactions)
+        .await?;
 
     let support_service =
         tokio::spawn(async move { spawner.spawn_support_services(prometheus_addr).await });
 
     let history = History::new();
 
+    let admin_service = LiteAdminBridge::new(
+        transaction_service.clone(),
+        grpc_sources_health,
+        dynamic_grpc_sources,
+        identity_rotation,
+        data_cache.cluster_info.clone(),
+    );
+
     let rpc_service = LiteBridge::new(
         rpc_client.clone(),
         data_cache.clone(),
@@ -357,15 +496,19 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
         account_priofees_service,
         blocks_notifier,
         blockinfo_notifier,
+        orphaned_block_notifier,
         accounts_service.clone(),
     );
 
     let bridge_service = tokio::spawn(start_servers(
         rpc_service,
         pubsub_service,
+        admin_service,
         lite_rpc_ws_addr,
         lite_rpc_http_addr,
+        lite_rpc_admin_addr,
         None,
+        client_quotas,
     ));
     drop(slot_notifier);
 
@@ -398,9 +541,39 @@ pub async fn start_lite_rpc(args: Config, rpc_client: Arc<RpcClient>) -> anyhow:
         res = account_priofees_task => {
             anyhow::bail!("account prioritization fees task failed {res:?}")
         }
+        res = leader_scoring => {
+            anyhow::bail!("leader scoring task failed {res:?}")
+        }
+        res = grpc_sources_reload => {
+            anyhow::bail!("grpc sources reload task failed {res:?}")
+        }
     }
 }
 
+// reload the grpc sources from the config on SIGHUP, the multiplexers follow the changes
+fn reload_grpc_sources_on_sighup(grpc_sources: GrpcSources) -> AnyhowJoinHandle {
+    tokio::spawn(async move {
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .context("install SIGHUP handler")?;
+        while hangup.recv().await.is_some() {
+            let config = match Config::load().await {
+                Ok(config) => config,
+                Err(e) => {
+                    error!("Failed to reload config for the grpc sources: {e:?}");
+                    continue;
+                }
+            };
+            let sources = config.get_grpc_sources();
+            info!(
+                "Received SIGHUP, reloading grpc sources {:?}",
+                sources.iter().map(GrpcSourceDisplay).collect_vec()
+            );
+            grpc_sources.replace(sources);
+        }
+        anyhow::bail!("SIGHUP signal stream closed")
+    })
+}
+
 fn setup_grpc_stream_debugging(blocks_notifier: &BlockStream) {
     info!("Setting up grpc stream inspection");
     // note: check failes for commitment_config processed because sources might disagree on the blocks
@@ -448,17 +621,48 @@ pub async fn main() -> anyhow::Result<()> {
     }
 }
 
-fn configure_tpu_connection_path(quic_proxy_addr: Option<String>) -> TpuConnectionPath {
-    match quic_proxy_addr {
-        None => TpuConnectionPath::QuicDirectPath,
-        Some(prox_address) => {
-            let proxy_socket_addr = parse_host_port(prox_address.as_str()).unwrap();
-            TpuConnectionPath::QuicForwardProxyPath {
-                // e.g. "127.0.0.1:11111" or "localhost:11111"
-                forward_proxy_address: proxy_socket_addr,
+fn configure_tpu_connection_path(
+    quic_proxy_addr: Option<String>,
+    quic_proxy_identity: Option<String>,
+    quic_proxies: Vec<QuicProxyConfig>,
+    balancing: ProxyBalancing,
+    fallback_to_direct: bool,
+    delivery_reports: bool,
+) -> anyhow::Result<TpuConnectionPath> {
+    let forward_proxies = quic_proxy_addr
+        .map(|address| QuicProxyConfig {
+            address,
+            identity: quic_proxy_identity,
+            weight: QuicProxyConfig::default_weight(),
+        })
+        .into_iter()
+        .chain(quic_proxies)
+        .map(|proxy| {
+            if proxy.weight == 0 {
+                bail!("weight of quic proxy {} must be at least 1", proxy.address);
             }
-        }
+            Ok(ForwardProxy {
+                // e.g. "127.0.0.1:11111" or "localhost:11111"
+                address: parse_host_port(&proxy.address).map_err(anyhow::Error::msg)?,
+                identity: proxy
+                    .identity
+                    .map(|identity| Pubkey::from_str(&identity))
+                    .transpose()
+                    .context("invalid quic proxy identity")?,
+                weight: proxy.weight,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if forward_proxies.is_empty() {
+        return Ok(TpuConnectionPath::QuicDirectPath);
     }
+    Ok(TpuConnectionPath::QuicForwardProxyPath {
+        forward_proxies,
+        balancing,
+        fallback_to_direct,
+        delivery_reports,
+    })
 }
 
 fn parse_host_port(host_port: &str) -> Result<SocketAddr, String> {
@@ -1,13 +1,15 @@
-use crate::configs::IsBlockHashValidConfig;
+use crate::configs::{IsBlockHashValidConfig, LiteSendTransactionConfig};
 use jsonrpsee::core::RpcResult;
 use jsonrpsee::proc_macros::rpc;
 use solana_account_decoder::UiAccount;
+use solana_lite_rpc_core::stores::bundle_store::BundleStatus;
 use solana_lite_rpc_prioritization_fees::prioritization_fee_calculation_method::PrioritizationFeeCalculationMethod;
 use solana_lite_rpc_prioritization_fees::rpc_data::{AccountPrioFeesStats, PrioFeesStats};
+use solana_lite_rpc_services::tpu_utils::slot_clock::SlotTiming;
 use solana_rpc_client_api::config::{
     RpcAccountInfoConfig, RpcBlocksConfigWrapper, RpcContextConfig, RpcGetVoteAccountsConfig,
-    RpcLeaderScheduleConfig, RpcProgramAccountsConfig, RpcRequestAirdropConfig,
-    RpcSendTransactionConfig, RpcSignatureStatusConfig, RpcSignaturesForAddressConfig,
+    RpcLeaderScheduleConfig, RpcLeaderScheduleConfigWrapper, RpcProgramAccountsConfig,
+    RpcRequestAirdropConfig, RpcSignatureStatusConfig, RpcSignaturesForAddressConfig,
 };
 use solana_rpc_client_api::response::{
     OptionalContext, Response as RpcResponse, RpcBlockhash,
@@ -133,9 +135,22 @@ pub trait LiteRpc {
     async fn send_transaction(
         &self,
         tx: String,
-        send_transaction_config: Option<RpcSendTransactionConfig>,
+        send_transaction_config: Option<LiteSendTransactionConfig>,
     ) -> RpcResult<String>;
 
+    #[method(name = "sendBundle")]
+    async fn send_bundle(
+        &self,
+        txs: Vec<String>,
+        send_transaction_config: Option<LiteSendTransactionConfig>,
+    ) -> RpcResult<String>;
+
+    #[method(name = "getBundleStatuses")]
+    async fn get_bundle_statuses(
+        &self,
+        bundle_ids: Vec<String>,
+    ) -> RpcResult<RpcResponse<Vec<Option<BundleStatus>>>>;
+
     // ***********************
     // Deprecated
     // ***********************
@@ -159,7 +174,7 @@ pub trait LiteRpc {
     #[method(name = "getLeaderSchedule")]
     async fn get_leader_schedule(
         &self,
-        slot: Option<u64>,
+        options: Option<RpcLeaderScheduleConfigWrapper>,
         config: Option<RpcLeaderScheduleConfig>,
     ) -> RpcResult<Option<HashMap<String, Vec<usize>>>>;
 
@@ -172,6 +187,15 @@ pub trait LiteRpc {
         config: Option<RpcGetVoteAccountsConfig>,
     ) -> RpcResult<RpcVoteAccountStatus>;
 
+    // ***********************
+    // slot clock used for the tpu fanout
+    // (this is special method not available in solana rpc)
+    // ***********************
+
+    // estimated current slot with its bounds and the time until the next `limit` leaders
+    #[method(name = "getSlotTiming")]
+    async fn get_slot_timing(&self, limit: Option<usize>) -> RpcResult<SlotTiming>;
+
     // ***********************
     // expose prio fees distribution per block
     // (this is special method not available in solana rpc)
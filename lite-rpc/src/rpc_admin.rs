@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+use jsonrpsee::core::RpcResult;
+use jsonrpsee::proc_macros::rpc;
+use solana_lite_rpc_cluster_endpoints::grpc_source_health::GrpcSourceStatus;
+use solana_lite_rpc_cluster_endpoints::grpc_sources::GrpcSourceSpec;
+use solana_lite_rpc_core::stores::cluster_info_store::ContactInfoSeen;
+use solana_lite_rpc_services::tpu_utils::leader_scores::LeaderScore;
+
+// served on the admin address only, which should not be reachable by clients
+#[rpc(server)]
+pub trait LiteRpcAdmin {
+    // inclusion rate and skip rate of the leaders we sent transactions to
+    #[method(name = "getLeaderScores")]
+    async fn get_leader_scores(&self) -> RpcResult<Vec<LeaderScore>>;
+
+    // grpc sources ranked by their health, empty without grpc
+    #[method(name = "getGrpcSourceRankings")]
+    async fn get_grpc_source_rankings(&self) -> RpcResult<Vec<GrpcSourceStatus>>;
+
+    // configured grpc sources with their tokens redacted, empty without grpc
+    #[method(name = "getGrpcSources")]
+    async fn get_grpc_sources(&self) -> RpcResult<Vec<GrpcSourceSpec>>;
+
+    // add a grpc source at runtime, returns the sources
+    #[method(name = "addGrpcSource")]
+    async fn add_grpc_source(&self, source: GrpcSourceSpec) -> RpcResult<Vec<GrpcSourceSpec>>;
+
+    // remove the grpc source with this address at runtime, returns the sources
+    #[method(name = "removeGrpcSource")]
+    async fn remove_grpc_source(&self, addr: String) -> RpcResult<Vec<GrpcSourceSpec>>;
+
+    // reload the identity keypair file, or a staked fallback, returns the new identity
+    #[method(name = "reloadIdentity")]
+    async fn reload_identity(&self) -> RpcResult<String>;
+
+    // where and when the contact info of each cluster node was last seen, keyed by identity
+    #[method(name = "getClusterNodesLastSeen")]
+    async fn get_cluster_nodes_last_seen(&self) -> RpcResult<HashMap<String, ContactInfoSeen>>;
+}
@@ -56,6 +56,10 @@ pub trait LiteRpcPubSub {
     #[subscription(name = "accountPrioritizationFeesSubscribe" => "accountPrioritizationFeesNotification", unsubscribe="accountPrioritizationFeesUnsubscribe", item=String)]
     async fn latest_account_priofees_subscribe(&self, account: String) -> SubscriptionResult;
 
+    /// processed blocks which got abandoned for another fork
+    #[subscription(name = "orphanedBlockSubscribe" => "orphanedBlockNotification", unsubscribe="orphanedBlockUnsubscribe", item=String)]
+    async fn orphaned_block_subscribe(&self) -> SubscriptionResult;
+
     #[subscription(name = "accountSubscribe" => "accountNotification", unsubscribe="accountUnsubscribe", item=String)]
     async fn account_subscribe(
         &self,
@@ -2,7 +2,7 @@ use solana_lite_rpc_core::types::BlockInfoStream;
 use solana_lite_rpc_core::{
     stores::data_cache::DataCache,
     structures::notifications::NotificationSender,
-    types::{BlockStream, ClusterInfoStream, SlotStream, VoteAccountStream},
+    types::{BlockStream, ClusterInfoStream, OrphanedBlockStream, SlotStream, VoteAccountStream},
     AnyhowJoinHandle,
 };
 use solana_lite_rpc_services::{
@@ -47,6 +47,7 @@ impl ServiceSpawner {
         slot_notification: SlotStream,
         cluster_info_notification: ClusterInfoStream,
         va_notification: VoteAccountStream,
+        orphaned_block_notification: OrphanedBlockStream,
     ) -> Vec<AnyhowJoinHandle> {
         let data_service = DataCachingService {
             data_cache: self.data_cache.clone(),
@@ -59,6 +60,7 @@ impl ServiceSpawner {
             slot_notification,
             cluster_info_notification,
             va_notification,
+            orphaned_block_notification,
         )
     }
 
@@ -1,12 +1,17 @@
 use crate::{
-    bridge::LiteBridge, bridge_pubsub::LitePubSubBridge, rpc::LiteRpcServer,
+    bridge::LiteBridge,
+    bridge_admin::LiteAdminBridge,
+    bridge_pubsub::LitePubSubBridge,
+    client_quotas::{ClientQuotaLayer, ClientQuotas, ClientQuotasConfig},
+    rpc::LiteRpcServer,
+    rpc_admin::LiteRpcAdminServer,
     rpc_pubsub::LiteRpcPubSubServer,
 };
 
 use hyper::Method;
 use jsonrpsee::server::ServerBuilder;
 use solana_lite_rpc_core::AnyhowJoinHandle;
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use tower_http::cors::{Any, CorsLayer};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -34,15 +39,26 @@ impl Default for ServerConfiguration {
 pub async fn start_servers(
     rpc: LiteBridge,
     pubsub: LitePubSubBridge,
+    admin: LiteAdminBridge,
     ws_addr: String,
     http_addr: String,
+    admin_addr: String,
     server_configuration: Option<ServerConfiguration>,
+    client_quotas: Option<ClientQuotasConfig>,
 ) -> anyhow::Result<()> {
     let rpc = rpc.into_rpc();
     let pubsub = pubsub.into_rpc();
+    let admin = admin.into_rpc();
     let server_configuration = server_configuration.unwrap_or_default();
+    let quota_layer = client_quotas.map(|config| {
+        ClientQuotaLayer::new(Arc::new(ClientQuotas::new(
+            config,
+            server_configuration.max_request_body_size,
+        )))
+    });
 
     let ws_server_handle = ServerBuilder::default()
+        .set_middleware(tower::ServiceBuilder::new().option_layer(quota_layer.clone()))
         .ws_only()
         .max_connections(server_configuration.max_connection)
         .max_subscriptions_per_connection(server_configuration.max_subscriptions_per_connection)
@@ -58,18 +74,27 @@ pub async fn start_servers(
         .allow_origin(Any)
         .allow_headers(Any);
 
-    let middleware = tower::ServiceBuilder::new().layer(cors);
+    let middleware = tower::ServiceBuilder::new()
+        .layer(cors)
+        .option_layer(quota_layer);
 
     let http_server_handle = ServerBuilder::default()
         .set_middleware(middleware)
         .max_connections(server_configuration.max_connection)
-        .max_request_body_size(server_configuration.max_response_body_size)
+        .max_request_body_size(server_configuration.max_request_body_size)
         .max_response_body_size(server_configuration.max_response_body_size)
         .http_only()
         .build(http_addr.clone())
         .await?
         .start(rpc);
 
+    // no quotas, the admin address is not meant to be reachable by clients
+    let admin_server_handle = ServerBuilder::default()
+        .http_only()
+        .build(admin_addr.clone())
+        .await?
+        .start(admin);
+
     let ws_server: AnyhowJoinHandle = tokio::spawn(async move {
         log::info!("Websocket Server started at {ws_addr:?}");
         ws_server_handle.stopped().await;
@@ -82,6 +107,12 @@ pub async fn start_servers(
         anyhow::bail!("HTTP server stopped");
     });
 
+    let admin_server: AnyhowJoinHandle = tokio::spawn(async move {
+        log::info!("Admin Server started at {admin_addr:?}");
+        admin_server_handle.stopped().await;
+        anyhow::bail!("Admin server stopped");
+    });
+
     tokio::select! {
         res = ws_server => {
             anyhow::bail!("WebSocket server {res:?}");
@@ -89,5 +120,8 @@ pub async fn start_servers(
         res = http_server => {
             anyhow::bail!("HTTP server {res:?}");
         },
+        res = admin_server => {
+            anyhow::bail!("Admin server {res:?}");
+        },
     }
 }
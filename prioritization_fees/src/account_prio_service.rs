@@ -1,7 +1,8 @@
 use std::sync::Arc;
 
 use solana_lite_rpc_core::{
-    traits::address_lookup_table_interface::AddressLookupTableInterface, types::BlockStream,
+    traits::address_lookup_table_interface::AddressLookupTableInterface,
+    types::{BlockStream, OrphanedBlockStream},
 };
 use solana_sdk::{pubkey::Pubkey, slot_history::Slot};
 use tokio::{sync::broadcast::Sender, task::JoinHandle};
@@ -21,6 +22,7 @@ pub struct AccountPrioService {
 impl AccountPrioService {
     pub fn start_account_priofees_task(
         mut block_stream: BlockStream,
+        mut orphaned_block_stream: OrphanedBlockStream,
         slots_to_retain: usize,
         address_lookup_tables_impl: Option<Arc<dyn AddressLookupTableInterface>>,
     ) -> (JoinHandle<()>, AccountPrioService) {
@@ -33,7 +35,26 @@ impl AccountPrioService {
             let priofees_update_sender = priofees_update_sender.clone();
             tokio::spawn(async move {
                 'recv_loop: loop {
-                    let block = block_stream.recv().await;
+                    let block = tokio::select! {
+                        block = block_stream.recv() => block,
+                        orphaned_block = orphaned_block_stream.recv() => {
+                            match orphaned_block {
+                                Ok(orphaned_block) => {
+                                    account_store.rollback_slot(orphaned_block.slot);
+                                }
+                                Err(Lagged(_lagged)) => {
+                                    log::warn!(
+                                        "channel lagged receiving orphaned block for account priofees calculation - continue"
+                                    );
+                                }
+                                Err(Closed) => {
+                                    log::error!("failed to receive orphaned block, sender closed - aborting");
+                                    break 'recv_loop;
+                                }
+                            }
+                            continue 'recv_loop;
+                        }
+                    };
                     match block {
                         Ok(block) => {
                             if !block.commitment_config.is_processed() {
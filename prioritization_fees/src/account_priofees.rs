@@ -247,6 +247,16 @@ impl AccountPrioStore {
         }
     }
 
+    /// drop the data of a slot whose processed block got orphaned
+    pub fn rollback_slot(&self, slot: Slot) {
+        let remove_slot = |_: &Pubkey, account_prio: &mut AccountPrio| {
+            account_prio.stats_by_slot.remove(&slot);
+            !account_prio.stats_by_slot.is_empty()
+        };
+        self.account_by_prio_fees_all.retain(remove_slot);
+        self.account_by_prio_fees_writeonly.retain(remove_slot);
+    }
+
     pub fn get_latest_stats(&self, account: &Pubkey) -> (Slot, AccountPrioFeesStats) {
         let all = self
             .account_by_prio_fees_all
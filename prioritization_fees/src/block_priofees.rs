@@ -1,7 +1,7 @@
 use crate::prioritization_fee_data::{BlockPrioData, PrioFeesData};
 use crate::rpc_data::{PrioFeesStats, PrioFeesUpdateMessage};
 use log::{error, info, trace, warn};
-use solana_lite_rpc_core::types::BlockStream;
+use solana_lite_rpc_core::types::{BlockStream, OrphanedBlockStream};
 use solana_sdk::clock::Slot;
 use std::collections::BTreeMap;
 use std::sync::Arc;
@@ -53,6 +53,7 @@ impl PrioFeesService {
 
 pub fn start_block_priofees_task(
     mut block_stream: BlockStream,
+    mut orphaned_block_stream: OrphanedBlockStream,
     slots_to_retain: u64,
 ) -> (JoinHandle<()>, PrioFeesService) {
     let recent_data = Arc::new(RwLock::new(BTreeMap::new()));
@@ -65,7 +66,25 @@ pub fn start_block_priofees_task(
     let jh_priofees_task = tokio::spawn(async move {
         let sender = priofees_update_sender.clone();
         'recv_loop: loop {
-            let block = block_stream.recv().await;
+            let block = tokio::select! {
+                block = block_stream.recv() => block,
+                orphaned_block = orphaned_block_stream.recv() => {
+                    match orphaned_block {
+                        Ok(orphaned_block) => {
+                            // stats of the slot came from the orphaned block
+                            recent_data.write().await.remove(&orphaned_block.slot);
+                        }
+                        Err(Lagged(_lagged)) => {
+                            warn!("channel lagged receiving orphaned block for block priofees calculation - continue");
+                        }
+                        Err(Closed) => {
+                            error!("failed to receive orphaned block, sender closed - aborting");
+                            break 'recv_loop;
+                        }
+                    }
+                    continue 'recv_loop;
+                }
+            };
             match block {
                 Ok(block) => {
                     if !block.commitment_config.is_processed() {
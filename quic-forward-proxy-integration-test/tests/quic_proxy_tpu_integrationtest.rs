@@ -6,6 +6,7 @@ use log::{debug, error, info, trace, warn};
 use solana_lite_rpc_core::solana_utils::SerializableTransaction;
 use solana_lite_rpc_core::stores::data_cache::DataCache;
 use solana_lite_rpc_core::structures::identity_stakes::IdentityStakesData;
+use solana_lite_rpc_core::structures::transaction_priority::TransactionPriority;
 use solana_lite_rpc_core::structures::transaction_sent_info::SentTransactionInfo;
 use solana_lite_rpc_services::tpu_utils::tpu_connection_manager::TpuConnectionManager;
 use solana_sdk::hash::Hash;
@@ -38,8 +39,9 @@ use tracing_subscriber::EnvFilter;
 use solana_lite_rpc_quic_forward_proxy::proxy::QuicForwardProxy;
 use solana_lite_rpc_quic_forward_proxy::tls_self_signed_pair_generator::SelfSignedTlsConfigProvider;
 use solana_lite_rpc_quic_forward_proxy::validator_identity::ValidatorIdentity;
-use solana_lite_rpc_services::quic_connection_utils::QuicConnectionParameters;
+use solana_lite_rpc_services::quic_connection_utils::{QuicBindConfig, QuicConnectionParameters};
 use solana_lite_rpc_services::tpu_utils::quic_proxy_connection_manager::QuicProxyConnectionManager;
+use solana_lite_rpc_services::tpu_utils::tpu_connection_path::{ForwardProxy, ProxyBalancing};
 use tracing_subscriber::fmt::format::FmtSpan;
 
 #[derive(Copy, Clone, Debug)]
@@ -62,6 +64,8 @@ const QUIC_CONNECTION_PARAMS: QuicConnectionParameters = QuicConnectionParameter
     number_of_transactions_per_unistream: 10,
     unistreams_to_create_new_connection_in_percentage: 10,
     prioritization_heap_size: None,
+    prioritization_heap_share_per_sender_in_percentage: 100,
+    prewarm_slots: 0,
 };
 
 #[test]
@@ -473,8 +477,13 @@ async fn start_literpc_client_direct_mode(
     )
     .expect("Failed to initialize QUIC connection certificates");
 
-    let tpu_connection_manager =
-        TpuConnectionManager::new(certificate, key, fanout_slots as usize).await;
+    let tpu_connection_manager = TpuConnectionManager::new(
+        certificate,
+        key,
+        fanout_slots as usize,
+        QuicBindConfig::default(),
+    )
+    .await;
 
     // this effectively controls how many connections we will have
     let mut connections_to_keep: HashMap<Pubkey, SocketAddr> = HashMap::new();
@@ -536,7 +545,7 @@ async fn start_literpc_client_direct_mode(
             format!("hi {}", i)
         );
 
-        broadcast_sender.send(raw_sample_tx)?;
+        broadcast_sender.send(raw_sample_tx.into())?;
     }
 
     while !broadcast_sender.is_empty() {
@@ -574,8 +583,20 @@ async fn start_literpc_client_proxy_mode(
     )
     .expect("Failed to initialize QUIC connection certificates");
 
-    let quic_proxy_connection_manager =
-        QuicProxyConnectionManager::new(certificate, key, forward_proxy_address).await;
+    let quic_proxy_connection_manager = QuicProxyConnectionManager::new(
+        certificate,
+        key,
+        &[ForwardProxy {
+            address: forward_proxy_address,
+            identity: None,
+            weight: 1,
+        }],
+        ProxyBalancing::default(),
+        None,
+        false,
+        None,
+    )
+    .await;
 
     // this effectively controls how many connections we will have
     let mut connections_to_keep: HashMap<Pubkey, SocketAddr> = HashMap::new();
@@ -646,7 +667,7 @@ async fn start_literpc_client_proxy_mode(
             format!("hi {}", i)
         );
 
-        broadcast_sender.send(raw_sample_tx)?;
+        broadcast_sender.send(raw_sample_tx.into())?;
         if (i + 1) % 1000 == 0 {
             yield_now().await;
         }
@@ -758,6 +779,8 @@ pub fn build_raw_sample_tx(i: u32) -> SentTransactionInfo {
         transaction,
         last_valid_block_height: 300,
         prioritization_fee: 0,
+        priority_score: TransactionPriority::from_transaction(&tx).score(),
+        fee_payer: payer_keypair.pubkey(),
     }
 }
 
@@ -0,0 +1,83 @@
+// Minimal http endpoint of the proxy
+// GET /metrics: prometheus metrics
+// GET /agents: forwarder agents with their last use
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use log::{error, info};
+use prometheus::{Encoder, TextEncoder};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::outbound::tx_forward::ActiveAgents;
+use crate::util::AnyhowJoinHandle;
+
+pub struct AdminServer;
+
+impl AdminServer {
+    fn create_response(status: &str, content_type: &str, payload: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            content_type,
+            payload.len(),
+            payload
+        )
+    }
+
+    async fn handle_stream(
+        stream: &mut TcpStream,
+        active_agents: &ActiveAgents,
+    ) -> anyhow::Result<()> {
+        let mut request_line = String::new();
+        BufReader::new(&mut *stream)
+            .read_line(&mut request_line)
+            .await?;
+        // e.g. "GET /metrics HTTP/1.1"
+        let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+
+        let response = match path {
+            "/metrics" => {
+                let mut metrics_buffer = Vec::new();
+                TextEncoder::new().encode(&prometheus::gather(), &mut metrics_buffer)?;
+                Self::create_response(
+                    "200 OK",
+                    "text/plain; version=0.0.4",
+                    &String::from_utf8(metrics_buffer)?,
+                )
+            }
+            "/agents" => Self::create_response(
+                "200 OK",
+                "application/json",
+                &serde_json::to_string(&active_agents.list().await)?,
+            ),
+            _ => Self::create_response("404 Not Found", "text/plain", "not found"),
+        };
+
+        stream.write_all(response.as_bytes()).await?;
+        stream.flush().await?;
+
+        Ok(())
+    }
+
+    pub fn serve(admin_addr: SocketAddr, active_agents: ActiveAgents) -> AnyhowJoinHandle {
+        tokio::spawn(async move {
+            let listener = TcpListener::bind(admin_addr).await?;
+            info!("Proxy admin endpoint listening on {}", admin_addr);
+
+            loop {
+                let Ok((mut stream, _addr)) = listener.accept().await else {
+                    error!("Error accepting admin stream");
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                    continue;
+                };
+
+                let active_agents = active_agents.clone();
+                tokio::spawn(async move {
+                    let _ = Self::handle_stream(&mut stream, &active_agents).await;
+                });
+            }
+        })
+    }
+}
@@ -8,4 +8,22 @@ pub struct Args {
     // e.g. 0.0.0.0:11111 or "localhost:11111"
     #[arg(short = 'l', long, env)]
     pub proxy_listen_addr: String,
+    // identities of the clients allowed to use the proxy, any authenticated client if empty
+    #[arg(long, env, value_delimiter = ',')]
+    pub client_allowlist: Vec<String>,
+    // transactions per second forwarded for each client identity, unlimited if not set
+    #[arg(long, env)]
+    pub max_transactions_per_second_per_client: Option<u32>,
+    // transactions per second forwarded for all clients together, unlimited if not set
+    #[arg(long, env)]
+    pub max_transactions_per_second: Option<u32>,
+    // rpc to follow the leader schedule, requests without tpu nodes go to the upcoming leaders
+    #[arg(long, env)]
+    pub rpc_url: Option<String>,
+    // slots of upcoming leaders to forward bare transactions to
+    #[arg(long, env, default_value_t = 12)]
+    pub fanout_slots: u64,
+    // http endpoint serving /metrics and /agents, e.g. 127.0.0.1:9092
+    #[arg(long, env)]
+    pub admin_addr: Option<String>,
 }
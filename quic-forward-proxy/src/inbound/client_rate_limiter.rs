@@ -0,0 +1,128 @@
+use dashmap::DashMap;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// buckets refill within a second, idle ones are full and can be dropped
+const IDLE_BUCKET_EVICTION: Duration = Duration::from_secs(10);
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: f64, now: Instant) -> Self {
+        Self {
+            tokens: capacity,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, capacity: f64, now: Instant) -> usize {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * capacity).min(capacity);
+        self.last_refill = now;
+        self.tokens as usize
+    }
+}
+
+/// Transactions per second forwarded through the proxy, for each client identity and in total
+pub struct ClientRateLimiter {
+    max_transactions_per_second_per_client: Option<u32>,
+    // clients without identity share one bucket
+    buckets: DashMap<Option<Pubkey>, TokenBucket>,
+    max_transactions_per_second: Option<u32>,
+    // shared by all clients, new identities do not get around it
+    global_bucket: Mutex<TokenBucket>,
+    last_eviction: Mutex<Instant>,
+}
+
+impl ClientRateLimiter {
+    pub fn new(
+        max_transactions_per_second_per_client: Option<u32>,
+        max_transactions_per_second: Option<u32>,
+    ) -> Self {
+        let now = Instant::now();
+        Self {
+            max_transactions_per_second_per_client,
+            buckets: DashMap::new(),
+            max_transactions_per_second,
+            global_bucket: Mutex::new(TokenBucket::new(
+                max_transactions_per_second.unwrap_or_default() as f64,
+                now,
+            )),
+            last_eviction: Mutex::new(now),
+        }
+    }
+
+    /// takes up to `nb_transactions` tokens of the client, returns how many transactions may be forwarded
+    pub fn try_acquire(&self, client_identity: Option<Pubkey>, nb_transactions: usize) -> usize {
+        let now = Instant::now();
+        self.evict_idle_buckets(now);
+
+        let mut global_bucket = self.global_bucket.lock().unwrap();
+        let mut acquired = match self.max_transactions_per_second {
+            Some(capacity) => nb_transactions.min(global_bucket.refill(capacity as f64, now)),
+            None => nb_transactions,
+        };
+        if let Some(capacity) = self.max_transactions_per_second_per_client {
+            let capacity = capacity as f64;
+            let mut bucket = self
+                .buckets
+                .entry(client_identity)
+                .or_insert_with(|| TokenBucket::new(capacity, now));
+            acquired = acquired.min(bucket.refill(capacity, now));
+            bucket.tokens -= acquired as f64;
+        }
+        if self.max_transactions_per_second.is_some() {
+            global_bucket.tokens -= acquired as f64;
+        }
+        acquired
+    }
+
+    fn evict_idle_buckets(&self, now: Instant) {
+        let mut last_eviction = self.last_eviction.lock().unwrap();
+        if now.duration_since(*last_eviction) < IDLE_BUCKET_EVICTION {
+            return;
+        }
+        *last_eviction = now;
+        self.buckets
+            .retain(|_, bucket| now.duration_since(bucket.last_refill) < IDLE_BUCKET_EVICTION);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_over_the_limit_are_split() {
+        let rate_limiter = ClientRateLimiter::new(Some(10), None);
+        let client = Some(Pubkey::new_unique());
+        assert_eq!(rate_limiter.try_acquire(client, 4), 4);
+        assert_eq!(rate_limiter.try_acquire(client, 25), 6);
+        assert_eq!(rate_limiter.try_acquire(client, 1), 0);
+        // other clients have their own bucket
+        assert_eq!(rate_limiter.try_acquire(Some(Pubkey::new_unique()), 25), 10);
+    }
+
+    #[test]
+    fn global_limit_applies_to_new_identities() {
+        let rate_limiter = ClientRateLimiter::new(Some(10), Some(15));
+        assert_eq!(rate_limiter.try_acquire(Some(Pubkey::new_unique()), 10), 10);
+        assert_eq!(rate_limiter.try_acquire(Some(Pubkey::new_unique()), 10), 5);
+        assert_eq!(rate_limiter.try_acquire(Some(Pubkey::new_unique()), 10), 0);
+    }
+
+    #[test]
+    fn idle_buckets_are_evicted() {
+        let rate_limiter = ClientRateLimiter::new(Some(10), None);
+        rate_limiter.try_acquire(Some(Pubkey::new_unique()), 1);
+        rate_limiter.try_acquire(Some(Pubkey::new_unique()), 1);
+        assert_eq!(rate_limiter.buckets.len(), 2);
+
+        rate_limiter.evict_idle_buckets(Instant::now() + IDLE_BUCKET_EVICTION);
+        assert_eq!(rate_limiter.buckets.len(), 0);
+    }
+}
@@ -1 +1,2 @@
+pub(crate) mod client_rate_limiter;
 pub(crate) mod proxy_listener;
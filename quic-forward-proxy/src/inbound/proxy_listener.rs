@@ -1,36 +1,65 @@
-use crate::proxy_request_format::TpuForwardingRequest;
-use crate::quic_util::connection_stats;
+use crate::inbound::client_rate_limiter::ClientRateLimiter;
+use crate::leader_tracker::LeaderTracker;
+use crate::proxy_request_format::{
+    DeliveryReport, DeliveryStatus, TpuForwardingRequest, TxDeliveryResult,
+};
+use crate::quic_util::{connection_stats, peer_identity};
 use crate::shared::ForwardPacket;
 use crate::tls_config_provider_server::ProxyTlsConfigProvider;
 use crate::tls_self_signed_pair_generator::SelfSignedTlsConfigProvider;
 use crate::util::FALLBACK_TIMEOUT;
 use anyhow::{anyhow, bail, Context};
+use itertools::Itertools;
 use log::{debug, error, info, trace, warn};
-use quinn::{Connecting, Endpoint, ServerConfig, VarInt};
+use prometheus::{opts, register_int_counter, register_int_gauge, IntCounter, IntGauge};
+use quinn::{Connecting, Connection, Endpoint, ServerConfig, VarInt};
 use solana_lite_rpc_core::network_utils::apply_gso_workaround;
 use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::{self, Sender};
+use tokio::time::Instant;
+
+lazy_static::lazy_static! {
+    static ref INBOUND_CONNECTIONS: IntGauge =
+        register_int_gauge!(opts!("quic_proxy_inbound_connections", "Number of open client connections")).unwrap();
+    static ref REQUESTS_RECEIVED: IntCounter =
+        register_int_counter!(opts!("quic_proxy_requests_received", "Number of forwarding requests received from clients")).unwrap();
+    static ref TXS_RECEIVED: IntCounter =
+        register_int_counter!(opts!("quic_proxy_txs_received", "Number of transactions received from clients")).unwrap();
+}
 
 // note: setting this to "1" did not make a difference!
 // solana server sets this to 256
 const MAX_CONCURRENT_UNI_STREAMS: u32 = 24;
+// a bidi stream is held until the delivery report is sent, up to DELIVERY_REPORT_TIMEOUT
+const MAX_CONCURRENT_BIDI_STREAMS: u32 = 512;
+// transactions not forwarded by then are reported as timed out
+const DELIVERY_REPORT_TIMEOUT: Duration = Duration::from_secs(8);
 
 pub struct ProxyListener {
     tls_config: Arc<SelfSignedTlsConfigProvider>,
     proxy_listener_addr: SocketAddr,
+    rate_limiter: Option<Arc<ClientRateLimiter>>,
+    leader_tracker: Option<Arc<LeaderTracker>>,
 }
 
 impl ProxyListener {
     pub fn new(
         proxy_listener_addr: SocketAddr,
         tls_config: Arc<SelfSignedTlsConfigProvider>,
+        rate_limiter: Option<Arc<ClientRateLimiter>>,
+        leader_tracker: Option<Arc<LeaderTracker>>,
     ) -> Self {
         Self {
             proxy_listener_addr,
             tls_config,
+            rate_limiter,
+            leader_tracker,
         }
     }
 
@@ -46,8 +75,17 @@ impl ProxyListener {
 
         while let Some(connecting) = endpoint.accept().await {
             let forwarder_channel_copy = forwarder_channel.clone();
+            let rate_limiter = self.rate_limiter.clone();
+            let leader_tracker = self.leader_tracker.clone();
             tokio::spawn(async move {
-                match Self::handle_client_connection(connecting, forwarder_channel_copy).await {
+                match Self::handle_client_connection(
+                    connecting,
+                    forwarder_channel_copy,
+                    rate_limiter,
+                    leader_tracker,
+                )
+                .await
+                {
                     Ok(()) => {
                         debug!("connection handled correctly");
                     }
@@ -74,8 +112,8 @@ impl ProxyListener {
         // note: this config must be aligned with lite-rpc's client config
         let transport_config = Arc::get_mut(&mut quinn_server_config.transport).unwrap();
         transport_config.max_concurrent_uni_streams(VarInt::from_u32(MAX_CONCURRENT_UNI_STREAMS));
-        // no bidi streams used
-        transport_config.max_concurrent_bidi_streams(VarInt::from_u32(0));
+        // bidi streams carry the requests expecting a delivery report
+        transport_config.max_concurrent_bidi_streams(VarInt::from_u32(MAX_CONCURRENT_BIDI_STREAMS));
         let timeout = Duration::from_secs(10).try_into().unwrap();
         transport_config.max_idle_timeout(Some(timeout));
         transport_config.keep_alive_interval(Some(Duration::from_millis(500)));
@@ -91,86 +129,297 @@ impl ProxyListener {
     async fn handle_client_connection(
         client_conn_handshake: Connecting,
         forwarder_channel: Sender<ForwardPacket>,
+        rate_limiter: Option<Arc<ClientRateLimiter>>,
+        leader_tracker: Option<Arc<LeaderTracker>>,
     ) -> anyhow::Result<()> {
         let client_connection = client_conn_handshake.await.context("handshake")?;
+        let client_identity = peer_identity(&client_connection);
 
         debug!(
-            "inbound connection established, client {}",
-            client_connection.remote_address()
+            "inbound connection established, client {} with identity {:?}",
+            client_connection.remote_address(),
+            client_identity
         );
 
+        INBOUND_CONNECTIONS.inc();
+        let result = Self::accept_client_streams(
+            &client_connection,
+            client_identity,
+            forwarder_channel,
+            rate_limiter,
+            leader_tracker,
+        )
+        .await;
+        INBOUND_CONNECTIONS.dec();
+        result
+    }
+
+    async fn accept_client_streams(
+        client_connection: &Connection,
+        client_identity: Option<Pubkey>,
+        forwarder_channel: Sender<ForwardPacket>,
+        rate_limiter: Option<Arc<ClientRateLimiter>>,
+        leader_tracker: Option<Arc<LeaderTracker>>,
+    ) -> anyhow::Result<()> {
         loop {
-            let maybe_stream = client_connection.accept_uni().await;
-            match maybe_stream {
-                Ok(mut recv_stream) => {
+            tokio::select! {
+                maybe_stream = client_connection.accept_uni() => {
+                    let mut recv_stream = match maybe_stream {
+                        Ok(recv_stream) => recv_stream,
+                        Err(err) => return Self::connection_error(err),
+                    };
                     let forwarder_channel_copy = forwarder_channel.clone();
+                    let rate_limiter = rate_limiter.clone();
+                    let leader_tracker = leader_tracker.clone();
                     tokio::spawn(async move {
                         let raw_request = recv_stream.read_to_end(10_000_000).await.unwrap();
-
-                        let proxy_request =
-                            TpuForwardingRequest::try_deserialize_from_wire_format(&raw_request)
-                                .unwrap();
-
-                        if proxy_request.get_tpu_nodes().is_empty() {
-                            warn!("no tpu nodes in request - skip");
+                        Self::forward_request(
+                            &raw_request,
+                            client_identity,
+                            forwarder_channel_copy,
+                            rate_limiter,
+                            leader_tracker,
+                        )
+                        .await;
+                    });
+                }
+                // requests expecting a delivery report
+                maybe_stream = client_connection.accept_bi() => {
+                    let (mut send_stream, mut recv_stream) = match maybe_stream {
+                        Ok(streams) => streams,
+                        Err(err) => return Self::connection_error(err),
+                    };
+                    let forwarder_channel_copy = forwarder_channel.clone();
+                    let rate_limiter = rate_limiter.clone();
+                    let leader_tracker = leader_tracker.clone();
+                    tokio::spawn(async move {
+                        let raw_request = recv_stream.read_to_end(10_000_000).await.unwrap();
+                        let Some(report) = Self::forward_request(
+                            &raw_request,
+                            client_identity,
+                            forwarder_channel_copy,
+                            rate_limiter,
+                            leader_tracker,
+                        )
+                        .await
+                        else {
+                            let _ = send_stream.finish().await;
+                            return;
+                        };
+                        let raw_report = report
+                            .try_serialize_wire_format()
+                            .expect("Expect to serialize delivery report");
+                        if let Err(err) = send_stream.write_all(&raw_report).await {
+                            debug!("failed to send delivery report to client: {}", err);
                             return;
                         }
+                        let _ = send_stream.finish().await;
+                    });
+                }
+            }
 
-                        trace!("proxy request details: {}", proxy_request);
-                        let txs = proxy_request.get_transaction_bytes();
+            debug!(
+                "Inbound connection stats: {}",
+                connection_stats(client_connection)
+            );
+        } // -- loop
+    }
 
-                        debug!(
-                            "enqueue transaction batch of size {} to {} tpu nodes",
-                            txs.len(),
-                            proxy_request.get_tpu_nodes().len(),
-                        );
-                        if forwarder_channel_copy.capacity() < forwarder_channel_copy.max_capacity()
-                        {
-                            debug!(
-                                "forward channel buffered: capacity {} of {}",
-                                forwarder_channel_copy.capacity(),
-                                forwarder_channel_copy.max_capacity()
-                            );
-                        }
+    fn connection_error(err: quinn::ConnectionError) -> anyhow::Result<()> {
+        match err {
+            quinn::ConnectionError::ApplicationClosed(reason) => {
+                debug!("connection closed by client - reason: {:?}", reason);
+                if reason.error_code != VarInt::from_u32(0) {
+                    return Err(anyhow!(
+                        "connection closed by client with unexpected reason: {:?}",
+                        reason
+                    ));
+                }
+                debug!("connection gracefully closed by client");
+                Ok(())
+            }
+            err => {
+                error!("failed to accept stream: {}", err);
+                bail!("error accepting stream");
+            }
+        }
+    }
 
-                        for tpu_node in proxy_request.get_tpu_nodes() {
-                            let tpu_address = tpu_node.tpu_socket_addr;
-                            forwarder_channel_copy
-                                .send_timeout(
-                                    ForwardPacket::new(
-                                        txs.clone(),
-                                        tpu_address,
-                                        proxy_request.get_hash(),
-                                    ),
-                                    FALLBACK_TIMEOUT,
-                                )
-                                .await
-                                .context("sending internal packet from proxy to forwarder")
-                                .unwrap();
-                        }
-                    });
+    // returns the delivery report if the client asked for one
+    async fn forward_request(
+        raw_request: &[u8],
+        client_identity: Option<Pubkey>,
+        forwarder_channel: Sender<ForwardPacket>,
+        rate_limiter: Option<Arc<ClientRateLimiter>>,
+        leader_tracker: Option<Arc<LeaderTracker>>,
+    ) -> Option<DeliveryReport> {
+        let proxy_request =
+            TpuForwardingRequest::try_deserialize_from_wire_format(raw_request).unwrap();
 
-                    debug!(
-                        "Inbound connection stats: {}",
-                        connection_stats(&client_connection)
-                    );
+        trace!("proxy request details: {}", proxy_request);
+        REQUESTS_RECEIVED.inc();
+        let mut txs = proxy_request.get_transaction_bytes();
+        let mut tx_signatures = proxy_request.get_transaction_signatures();
+        let mut bundles = proxy_request.get_bundles();
+        let mut bundle_signatures = proxy_request.get_bundle_signatures();
+        let tpu_nodes = match &leader_tracker {
+            // bare transactions, the proxy picks the upcoming leaders
+            Some(leader_tracker) if proxy_request.get_tpu_nodes().is_empty() => {
+                match leader_tracker.upcoming_tpu_nodes().await {
+                    Ok(tpu_nodes) => tpu_nodes,
+                    Err(err) => {
+                        warn!("failed to resolve the upcoming leaders: {:#}", err);
+                        vec![]
+                    }
                 }
-                Err(quinn::ConnectionError::ApplicationClosed(reason)) => {
-                    debug!("connection closed by client - reason: {:?}", reason);
-                    if reason.error_code != VarInt::from_u32(0) {
-                        return Err(anyhow!(
-                            "connection closed by client with unexpected reason: {:?}",
-                            reason
-                        ));
+            }
+            _ => proxy_request.get_tpu_nodes().clone(),
+        };
+        let all_signatures = tx_signatures
+            .iter()
+            .chain(bundle_signatures.iter().flatten())
+            .copied()
+            .collect_vec();
+        TXS_RECEIVED.inc_by(all_signatures.len() as u64);
+
+        let report = |statuses: &HashMap<(Signature, SocketAddr), DeliveryStatus>| {
+            proxy_request.wants_delivery_report().then(|| {
+                DeliveryReport::new(
+                    tpu_nodes
+                        .iter()
+                        .flat_map(|tpu_node| {
+                            all_signatures.iter().map(|signature| TxDeliveryResult {
+                                signature: *signature,
+                                tpu_node: tpu_node.clone(),
+                                status: statuses
+                                    .get(&(*signature, tpu_node.tpu_socket_addr))
+                                    .copied()
+                                    .unwrap_or(DeliveryStatus::TimedOut),
+                            })
+                        })
+                        .collect_vec(),
+                )
+            })
+        };
+
+        if tpu_nodes.is_empty() {
+            warn!("no tpu nodes in request - skip");
+            return report(&HashMap::new());
+        }
+
+        let mut statuses = HashMap::new();
+        if let Some(rate_limiter) = &rate_limiter {
+            let acquired = rate_limiter.try_acquire(client_identity, all_signatures.len());
+            if acquired < all_signatures.len() {
+                warn!(
+                    "client {:?} exceeded its rate limit - dropping {} of {} transactions",
+                    client_identity,
+                    all_signatures.len() - acquired,
+                    all_signatures.len()
+                );
+                // the transactions go first, then the bundles fitting as a whole
+                let mut remaining = acquired;
+                let nb_txs = txs.len().min(remaining);
+                remaining -= nb_txs;
+                txs.truncate(nb_txs);
+                let mut dropped_signatures = tx_signatures.split_off(nb_txs);
+                let (kept_bundles, dropped_bundles): (Vec<_>, Vec<_>) = bundles
+                    .into_iter()
+                    .zip(bundle_signatures)
+                    .partition(|(_, signatures)| {
+                        let fits = signatures.len() <= remaining;
+                        if fits {
+                            remaining -= signatures.len();
+                        }
+                        fits
+                    });
+                (bundles, bundle_signatures) = kept_bundles.into_iter().unzip();
+                dropped_signatures.extend(
+                    dropped_bundles
+                        .into_iter()
+                        .flat_map(|(_, signatures)| signatures),
+                );
+                for tpu_node in &tpu_nodes {
+                    for signature in &dropped_signatures {
+                        statuses.insert(
+                            (*signature, tpu_node.tpu_socket_addr),
+                            DeliveryStatus::Dropped,
+                        );
                     }
-                    debug!("connection gracefully closed by client");
-                    return Ok(());
                 }
-                Err(e) => {
-                    error!("failed to accept stream: {}", e);
-                    bail!("error accepting stream");
+            }
+        }
+
+        debug!(
+            "enqueue transaction batch of size {} and {} bundles to {} tpu nodes",
+            txs.len(),
+            bundles.len(),
+            tpu_nodes.len(),
+        );
+        if forwarder_channel.capacity() < forwarder_channel.max_capacity() {
+            debug!(
+                "forward channel buffered: capacity {} of {}",
+                forwarder_channel.capacity(),
+                forwarder_channel.max_capacity()
+            );
+        }
+
+        let (delivery_sender, mut delivery_receiver) = mpsc::unbounded_channel();
+        for tpu_node in &tpu_nodes {
+            let tpu_address = tpu_node.tpu_socket_addr;
+            let mut packets = Vec::with_capacity(bundles.len() + 1);
+            if !txs.is_empty() {
+                packets.push(ForwardPacket::new(
+                    txs.clone(),
+                    tx_signatures.clone(),
+                    tpu_address,
+                    proxy_request.get_hash(),
+                ));
+            }
+            for (bundle, signatures) in bundles
+                .iter()
+                .zip(&bundle_signatures)
+                .filter(|(bundle, _)| !bundle.is_empty())
+            {
+                packets.push(ForwardPacket::new_bundle(
+                    bundle.clone(),
+                    signatures.clone(),
+                    tpu_address,
+                    TpuForwardingRequest::get_bundle_hash(bundle),
+                ));
+            }
+            for packet in packets {
+                let packet = if proxy_request.wants_delivery_report() {
+                    packet.with_delivery_reporter(delivery_sender.clone())
+                } else {
+                    packet
+                };
+                forwarder_channel
+                    .send_timeout(packet, FALLBACK_TIMEOUT)
+                    .await
+                    .context("sending internal packet from proxy to forwarder")
+                    .unwrap();
+            }
+        }
+
+        if !proxy_request.wants_delivery_report() {
+            return None;
+        }
+        drop(delivery_sender);
+
+        // packets stay referenced in the forwarder queue, wait for the expected number of updates
+        let nb_forwarded =
+            tx_signatures.len() + bundle_signatures.iter().map(Vec::len).sum::<usize>();
+        let nb_expected = nb_forwarded * tpu_nodes.len();
+        let deadline = Instant::now() + DELIVERY_REPORT_TIMEOUT;
+        for _ in 0..nb_expected {
+            match tokio::time::timeout_at(deadline, delivery_receiver.recv()).await {
+                Ok(Some(update)) => {
+                    statuses.insert((update.signature, update.tpu_address), update.status);
                 }
-            }; // -- result
-        } // -- loop
+                Ok(None) | Err(_) => break,
+            }
+        }
+        report(&statuses)
     }
 }
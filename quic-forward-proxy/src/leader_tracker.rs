@@ -0,0 +1,228 @@
+// Leader schedule and cluster nodes as seen by the proxy
+// Used to pick the upcoming leaders for requests which come without tpu nodes
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use itertools::Itertools;
+use log::{debug, warn};
+use solana_lite_rpc_core::network_utils::tpu_quic_addresses;
+use solana_lite_rpc_core::stores::cluster_info_store::ClusterInfo;
+use solana_lite_rpc_core::traits::leaders_fetcher_interface::LeaderFetcherInterface;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::response::RpcContactInfo;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::proxy_request_format::TpuNode;
+use crate::util::AnyhowJoinHandle;
+
+const SLOT_POLL_INTERVAL: Duration = Duration::from_millis(400);
+const CLUSTER_NODES_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct LeaderTracker {
+    rpc_client: Arc<RpcClient>,
+    leader_schedule: Arc<dyn LeaderFetcherInterface>,
+    cluster_info: ClusterInfo,
+    current_slot: AtomicU64,
+    fanout_slots: u64,
+}
+
+impl LeaderTracker {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        leader_schedule: Arc<dyn LeaderFetcherInterface>,
+        fanout_slots: u64,
+    ) -> Self {
+        Self {
+            rpc_client,
+            leader_schedule,
+            cluster_info: ClusterInfo::default(),
+            current_slot: AtomicU64::new(0),
+            fanout_slots,
+        }
+    }
+
+    /// polls the processed slot and the cluster nodes from the rpc
+    pub fn start(self: &Arc<Self>) -> AnyhowJoinHandle {
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut slot_interval = tokio::time::interval(SLOT_POLL_INTERVAL);
+            let mut cluster_nodes_interval = tokio::time::interval(CLUSTER_NODES_POLL_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = slot_interval.tick() => {
+                        match this.rpc_client.get_slot_with_commitment(CommitmentConfig::processed()).await {
+                            Ok(slot) => {
+                                this.current_slot.fetch_max(slot, Ordering::Relaxed);
+                            }
+                            Err(err) => warn!("failed to get the current slot: {}", err),
+                        }
+                    }
+                    _ = cluster_nodes_interval.tick() => {
+                        if let Err(err) = this.update_cluster_nodes().await {
+                            warn!("failed to update the cluster nodes: {:#}", err);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    async fn update_cluster_nodes(&self) -> anyhow::Result<()> {
+        let cluster_nodes = self
+            .rpc_client
+            .get_cluster_nodes()
+            .await
+            .context("get cluster nodes")?;
+        self.set_cluster_nodes(cluster_nodes);
+        debug!(
+            "proxy knows {} cluster nodes",
+            self.cluster_info.cluster_nodes.len()
+        );
+        Ok(())
+    }
+
+    // nodes which left the cluster are dropped
+    fn set_cluster_nodes(&self, cluster_nodes: Vec<RpcContactInfo>) {
+        let mut current = HashSet::with_capacity(cluster_nodes.len());
+        for contact_info in cluster_nodes {
+            if let Ok(pubkey) = Pubkey::from_str(&contact_info.pubkey) {
+                current.insert(pubkey);
+                self.cluster_info
+                    .cluster_nodes
+                    .insert(pubkey, Arc::new(contact_info));
+            }
+        }
+        self.cluster_info
+            .cluster_nodes
+            .retain(|pubkey, _| current.contains(pubkey));
+    }
+
+    /// tpu nodes of the leaders of the next fanout slots
+    pub async fn upcoming_tpu_nodes(&self) -> anyhow::Result<Vec<TpuNode>> {
+        let current_slot = self.current_slot.load(Ordering::Relaxed);
+        if current_slot == 0 {
+            bail!("current slot not known yet");
+        }
+        let leaders = self
+            .leader_schedule
+            .get_slot_leaders(current_slot, current_slot + self.fanout_slots)
+            .await?;
+        let tpu_nodes = leaders
+            .iter()
+            .map(|leader| leader.pubkey)
+            .unique()
+            .filter_map(|identity| {
+                let contact_info = self.cluster_info.cluster_nodes.get(&identity)?;
+                // the tpu forwards address is only known from gossip
+                let tpu_socket_addr = *tpu_quic_addresses(&contact_info, None).first()?;
+                Some(TpuNode {
+                    tpu_socket_addr,
+                    identity_tpunode: identity,
+                })
+            })
+            .collect_vec();
+        if tpu_nodes.is_empty() {
+            bail!("no tpu address known for the leaders after slot {current_slot}");
+        }
+        Ok(tpu_nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use async_trait::async_trait;
+    use solana_lite_rpc_core::structures::leader_data::LeaderData;
+    use solana_sdk::slot_history::Slot;
+
+    use super::*;
+
+    struct FixedLeaders(Vec<Pubkey>);
+
+    #[async_trait]
+    impl LeaderFetcherInterface for FixedLeaders {
+        async fn get_slot_leaders(&self, from: Slot, to: Slot) -> anyhow::Result<Vec<LeaderData>> {
+            Ok((from..=to)
+                .map(|slot| LeaderData {
+                    leader_slot: slot,
+                    pubkey: self.0[slot as usize % self.0.len()],
+                })
+                .collect())
+        }
+    }
+
+    fn contact_info(pubkey: Pubkey, tpu_quic: Option<&str>, tpu: Option<&str>) -> RpcContactInfo {
+        RpcContactInfo {
+            pubkey: pubkey.to_string(),
+            gossip: None,
+            tpu: tpu.map(|address| address.parse().unwrap()),
+            tpu_quic: tpu_quic.map(|address| address.parse().unwrap()),
+            rpc: None,
+            pubsub: None,
+            version: None,
+            feature_set: None,
+            shred_version: None,
+        }
+    }
+
+    fn tracker(leaders: Vec<Pubkey>) -> LeaderTracker {
+        LeaderTracker::new(
+            Arc::new(RpcClient::new("http://127.0.0.1:8899".to_string())),
+            Arc::new(FixedLeaders(leaders)),
+            3,
+        )
+    }
+
+    #[tokio::test]
+    async fn upcoming_tpu_nodes_use_the_quic_addresses() {
+        let (advertised, legacy, unknown) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let tracker = tracker(vec![advertised, legacy, unknown]);
+        assert!(tracker.upcoming_tpu_nodes().await.is_err());
+
+        tracker.set_cluster_nodes(vec![
+            contact_info(advertised, Some("10.0.0.1:8009"), Some("10.0.0.1:8003")),
+            contact_info(legacy, None, Some("10.0.0.2:8003")),
+        ]);
+        tracker.current_slot.store(3, Ordering::Relaxed);
+        let tpu_nodes = tracker.upcoming_tpu_nodes().await.unwrap();
+        assert_eq!(
+            tpu_nodes
+                .iter()
+                .map(|tpu_node| (tpu_node.identity_tpunode, tpu_node.tpu_socket_addr))
+                .collect_vec(),
+            vec![
+                (advertised, "10.0.0.1:8009".parse::<SocketAddr>().unwrap()),
+                (legacy, "10.0.0.2:8009".parse::<SocketAddr>().unwrap()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn nodes_which_left_the_cluster_are_pruned() {
+        let (staying, leaving) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let tracker = tracker(vec![staying]);
+        tracker.set_cluster_nodes(vec![
+            contact_info(staying, Some("10.0.0.1:8009"), None),
+            contact_info(leaving, Some("10.0.0.2:8009"), None),
+        ]);
+        tracker.set_cluster_nodes(vec![contact_info(staying, Some("10.0.0.3:8009"), None)]);
+
+        let cluster_nodes = &tracker.cluster_info.cluster_nodes;
+        assert_eq!(cluster_nodes.len(), 1);
+        assert_eq!(
+            cluster_nodes.get(&staying).unwrap().tpu_quic,
+            Some("10.0.0.3:8009".parse().unwrap())
+        );
+    }
+}
@@ -1,7 +1,9 @@
 // lib definition is only required for 'quic-forward-proxy-integration-test' to work
 
+mod admin_server;
 mod cli;
 mod inbound;
+pub mod leader_tracker;
 mod outbound;
 pub mod proxy;
 pub mod proxy_request_format;
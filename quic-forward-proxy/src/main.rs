@@ -1,17 +1,25 @@
 use crate::cli::Args;
+use crate::leader_tracker::LeaderTracker;
 use crate::proxy::QuicForwardProxy;
 use crate::tls_self_signed_pair_generator::SelfSignedTlsConfigProvider;
-use anyhow::bail;
+use anyhow::{bail, Context};
 use clap::Parser;
 use dotenv::dotenv;
-use log::info;
+use log::{info, warn};
+use solana_lite_rpc_cluster_endpoints::json_rpc_leaders_getter::JsonRpcLeaderGetter;
 use solana_lite_rpc_core::keypair_loader::load_identity_keypair;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashSet;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::validator_identity::ValidatorIdentity;
 
+mod admin_server;
 pub mod cli;
 mod inbound;
+pub mod leader_tracker;
 mod outbound;
 pub mod proxy;
 pub mod proxy_request_format;
@@ -24,6 +32,11 @@ pub mod tls_self_signed_pair_generator;
 mod util;
 mod validator_identity;
 
+// upcoming slot leaders fetched at once by the leader schedule cache
+const LEADERS_TO_CACHE: u64 = 1024;
+// leaders of already passed slots kept in the cache
+const OLD_SLOT_LEADERS_TO_CACHE: u64 = 128;
+
 #[tokio::main(flavor = "multi_thread", worker_threads = 16)]
 pub async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
@@ -31,6 +44,12 @@ pub async fn main() -> anyhow::Result<()> {
     let Args {
         identity_keypair,
         proxy_listen_addr,
+        client_allowlist,
+        max_transactions_per_second_per_client,
+        max_transactions_per_second,
+        rpc_url,
+        fanout_slots,
+        admin_addr,
     } = Args::parse();
     dotenv().ok();
 
@@ -40,10 +59,55 @@ pub async fn main() -> anyhow::Result<()> {
         load_identity_keypair(Some(identity_keypair).filter(|s| !s.is_empty())).await?,
     );
 
-    let tls_config = Arc::new(SelfSignedTlsConfigProvider::new_singleton_self_signed_localhost());
-    let main_services = QuicForwardProxy::new(proxy_listener_addr, tls_config, validator_identity)
-        .await?
-        .start_services();
+    let client_allowlist = client_allowlist
+        .iter()
+        .map(|pubkey| Pubkey::from_str(pubkey.trim()))
+        .collect::<Result<HashSet<_>, _>>()
+        .context("invalid client allowlist")?;
+    let client_allowlist = if client_allowlist.is_empty() {
+        warn!("No client allowlist configured, any client can forward transactions");
+        if max_transactions_per_second.is_none() {
+            warn!("Any client can get around the limit per client with new identities, consider setting a total limit");
+        }
+        None
+    } else {
+        info!("Clients allowed to use the proxy: {:?}", client_allowlist);
+        Some(client_allowlist)
+    };
+
+    let tls_config = Arc::new(SelfSignedTlsConfigProvider::new_with_identity(
+        &validator_identity.get_keypair_for_tls(),
+        client_allowlist,
+    ));
+    let mut proxy =
+        QuicForwardProxy::new(proxy_listener_addr, tls_config, validator_identity).await?;
+    if let Some(max_transactions_per_second) = max_transactions_per_second_per_client {
+        proxy = proxy.with_max_transactions_per_second_per_client(max_transactions_per_second);
+    }
+    if let Some(max_transactions_per_second) = max_transactions_per_second {
+        proxy = proxy.with_max_transactions_per_second(max_transactions_per_second);
+    }
+    if let Some(admin_addr) = admin_addr {
+        proxy = proxy.with_admin_addr(admin_addr.parse().context("invalid admin address")?);
+    }
+    if let Some(rpc_url) = rpc_url {
+        info!(
+            "Proxy forwards requests without tpu nodes to the leaders of the next {} slots",
+            fanout_slots
+        );
+        let rpc_client = Arc::new(RpcClient::new(rpc_url));
+        let leader_schedule = Arc::new(JsonRpcLeaderGetter::new(
+            rpc_client.clone(),
+            LEADERS_TO_CACHE,
+            OLD_SLOT_LEADERS_TO_CACHE,
+        ));
+        proxy = proxy.with_leader_tracker(Arc::new(LeaderTracker::new(
+            rpc_client,
+            leader_schedule,
+            fanout_slots,
+        )));
+    }
+    let main_services = proxy.start_services();
 
     let ctrl_c_signal = tokio::signal::ctrl_c();
 
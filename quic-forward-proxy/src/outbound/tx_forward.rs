@@ -1,20 +1,31 @@
 use crate::outbound::debouncer::Debouncer;
 use crate::outbound::sharder::Sharder;
+use crate::proxy_request_format::DeliveryStatus;
 use crate::quic_util::SkipServerVerification;
 use crate::quinn_auto_reconnect::AutoReconnect;
 use crate::shared::ForwardPacket;
 use crate::util::timeout_fallback;
 use crate::validator_identity::ValidatorIdentity;
 use anyhow::{bail, Context};
+use dashmap::DashMap;
 use futures::future::join_all;
+use itertools::Itertools;
 use log::{debug, info, trace, warn};
+use prometheus::{
+    histogram_opts, opts, register_histogram, register_int_counter, register_int_gauge, Histogram,
+    IntCounter, IntGauge,
+};
 use quinn::{
     ClientConfig, Endpoint, EndpointConfig, IdleTimeout, TokioRuntime, TransportConfig, VarInt,
 };
+use serde::Serialize;
 use solana_lite_rpc_core::network_utils::apply_gso_workaround;
+use solana_lite_rpc_core::structures::transaction_priority::TransactionPriority;
 use solana_sdk::quic::QUIC_MAX_TIMEOUT;
+use solana_sdk::transaction::VersionedTransaction;
 use solana_streamer::nonblocking::quic::ALPN_TPU_PROTOCOL_ID;
 use solana_streamer::tls_certificates::new_self_signed_tls_certificate;
+use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -23,6 +34,25 @@ use std::time::{Duration, Instant};
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::RwLock;
 
+lazy_static::lazy_static! {
+    static ref TPU_AGENTS: IntGauge =
+        register_int_gauge!(opts!("quic_proxy_tpu_agents", "Number of active forwarder agents, one per tpu node")).unwrap();
+    static ref TPU_AGENTS_STARTED: IntCounter =
+        register_int_counter!(opts!("quic_proxy_tpu_agents_started", "Number of forwarder agents started")).unwrap();
+    static ref TPU_AGENTS_SHUTDOWN: IntCounter =
+        register_int_counter!(opts!("quic_proxy_tpu_agents_shutdown", "Number of idle forwarder agents shut down")).unwrap();
+    static ref TXS_SENT: IntCounter =
+        register_int_counter!(opts!("quic_proxy_txs_sent", "Number of transactions sent to tpu nodes")).unwrap();
+    static ref TXS_SEND_FAILED: IntCounter =
+        register_int_counter!(opts!("quic_proxy_txs_send_failed", "Number of transactions which could not be sent to tpu nodes")).unwrap();
+    static ref TX_SEND_TIMEOUTS: IntCounter =
+        register_int_counter!(opts!("quic_proxy_tx_send_timeouts", "Number of batches or bundles timing out while sent to tpu nodes")).unwrap();
+    static ref TX_BATCH_SEND_TIME: Histogram =
+        register_histogram!(histogram_opts!("quic_proxy_tx_batch_send_time_sec", "Time to send a batch of transactions to a tpu node")).unwrap();
+    static ref TX_BUNDLE_SEND_TIME: Histogram =
+        register_histogram!(histogram_opts!("quic_proxy_tx_bundle_send_time_sec", "Time to send a bundle to a tpu node")).unwrap();
+}
+
 const MAX_PARALLEL_STREAMS: usize = 6;
 pub const PARALLEL_TPU_CONNECTION_COUNT: usize = 4;
 const AGENT_SHUTDOWN_IDLE: Duration = Duration::from_millis(2500); // ms; should be 4x400ms+buffer
@@ -40,10 +70,60 @@ impl AgentHandle {
     }
 }
 
+/// agents currently forwarding to tpu nodes, shared with the admin endpoint
+#[derive(Clone, Default)]
+pub struct ActiveAgents {
+    agents: Arc<DashMap<SocketAddr, AgentState>>,
+}
+
+#[derive(Clone)]
+struct AgentState {
+    started_at: Instant,
+    last_used_at: Arc<RwLock<Instant>>,
+    connections: Vec<Arc<AutoReconnect>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgentInfo {
+    pub tpu_address: SocketAddr,
+    // established connections out of the parallel connections of the agent
+    pub connections: usize,
+    pub running_secs: u64,
+    pub idle_ms: u128,
+}
+
+impl ActiveAgents {
+    pub async fn list(&self) -> Vec<AgentInfo> {
+        let agents = self
+            .agents
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect_vec();
+        let mut infos = Vec::with_capacity(agents.len());
+        for (tpu_address, agent) in agents {
+            let mut connections = 0;
+            for connection in &agent.connections {
+                if connection.is_connected().await {
+                    connections += 1;
+                }
+            }
+            infos.push(AgentInfo {
+                tpu_address,
+                connections,
+                running_secs: agent.started_at.elapsed().as_secs(),
+                idle_ms: agent.last_used_at.read().await.elapsed().as_millis(),
+            });
+        }
+        infos.sort_by_key(|info| info.idle_ms);
+        infos
+    }
+}
+
 // takes transactions from upstream clients and forwards them to the TPU
 pub async fn tx_forwarder(
     validator_identity: ValidatorIdentity,
     mut transaction_channel: Receiver<ForwardPacket>,
+    active_agents: ActiveAgents,
     exit_signal: Arc<AtomicBool>,
 ) -> anyhow::Result<()> {
     info!("TPU Quic forwarder started");
@@ -70,12 +150,14 @@ pub async fn tx_forwarder(
 
         agents.entry(tpu_address).or_insert_with(|| {
             let agent_exit_signal = Arc::new(AtomicBool::new(false));
+            let mut connections = Vec::with_capacity(PARALLEL_TPU_CONNECTION_COUNT);
 
             for connection_idx in 0..PARALLEL_TPU_CONNECTION_COUNT {
                 let sharder =
                     Sharder::new(connection_idx as u32, PARALLEL_TPU_CONNECTION_COUNT as u32);
                 let global_exit_signal = exit_signal.clone();
-                let endpoint_copy = endpoint.clone();
+                let auto_connection = Arc::new(AutoReconnect::new(endpoint.clone(), tpu_address));
+                connections.push(auto_connection.clone());
                 let agent_exit_signal_copy = agent_exit_signal.clone();
                 let mut per_connection_receiver = broadcast_in.subscribe();
                 tokio::spawn(async move {
@@ -83,9 +165,6 @@ pub async fn tx_forwarder(
                         "Start Quic forwarder agent #{} for TPU {}",
                         connection_idx, tpu_address
                     );
-                    // get a copy of the packet from broadcast channel
-                    let auto_connection = AutoReconnect::new(endpoint_copy, tpu_address);
-
                     // TODO check exit signal (using select! or maybe replace with oneshot)
                     let _exit_signal_copy = global_exit_signal.clone();
                     'tx_channel_loop: loop {
@@ -136,11 +215,12 @@ pub async fn tx_forwarder(
                             warn!("Agent ({} #{}) connection permanently dead, {} remaining - stopping",
                                 tpu_address, connection_idx,
                                 per_connection_receiver.len());
+                            packet.report_delivery_of_all(DeliveryStatus::Skipped);
                             break 'tx_channel_loop;
                         }
 
-                        let mut transactions_batch: Vec<Vec<u8>> = packet.transactions.clone();
-
+                        let mut packets = vec![packet];
+                        // other packets are forwarded by the agent of their tpu node or the connection of their shard
                         'more: while let Ok(more) = per_connection_receiver.try_recv() {
                             if more.tpu_address != tpu_address {
                                 continue 'more;
@@ -148,27 +228,89 @@ pub async fn tx_forwarder(
                             if !sharder.matching(more.shard_hash) {
                                 continue 'more;
                             }
-                            transactions_batch.extend(more.transactions.clone());
+                            packets.push(more);
                         }
 
+                        for bundle in packets.iter().filter(|packet| packet.is_bundle) {
+                            let timer = TX_BUNDLE_SEND_TIME.start_timer();
+                            let result = timeout_fallback(send_tx_bundle_to_tpu(
+                                &auto_connection,
+                                &bundle.transactions,
+                            ))
+                            .await;
+                            timer.observe_duration();
+                            match result {
+                                Ok(nb_sent) => {
+                                    TXS_SENT.inc_by(nb_sent as u64);
+                                    if nb_sent < bundle.transactions.len() {
+                                        TXS_SEND_FAILED.inc();
+                                    }
+                                    for tx_index in 0..bundle.transactions.len() {
+                                        bundle.report_delivery(tx_index, match tx_index.cmp(&nb_sent) {
+                                            std::cmp::Ordering::Less => DeliveryStatus::Sent,
+                                            std::cmp::Ordering::Equal => DeliveryStatus::SendFailed,
+                                            // rest of the bundle is skipped
+                                            std::cmp::Ordering::Greater => DeliveryStatus::Dropped,
+                                        });
+                                    }
+                                }
+                                Err(_elapsed) => {
+                                    TX_SEND_TIMEOUTS.inc();
+                                    warn!("timeout sending bundle of {} txs to tpu node {}",
+                                        bundle.transactions.len(), auto_connection.target_address);
+                                }
+                            }
+                        }
+
+                        // (packet, index of the transaction in the packet)
+                        let mut transactions_batch = packets
+                            .iter()
+                            .filter(|packet| !packet.is_bundle)
+                            .flat_map(|packet| (0..packet.transactions.len()).map(move |tx_index| (packet, tx_index)))
+                            .collect_vec();
+                        if transactions_batch.is_empty() {
+                            continue 'tx_channel_loop;
+                        }
+                        // forward the highest fee per compute unit first
+                        transactions_batch.sort_by_cached_key(|(packet, tx_index)| Reverse(priority_score(&packet.transactions[*tx_index])));
+
                         debug!(
                             "forwarding transaction batch of size {} to address {}",
                             transactions_batch.len(),
-                            packet.tpu_address
+                            tpu_address
                         );
 
+                        let raw_txs = transactions_batch
+                            .iter()
+                            .map(|(packet, tx_index)| &packet.transactions[*tx_index])
+                            .collect_vec();
+                        let timer = TX_BATCH_SEND_TIME.start_timer();
                         let result = timeout_fallback(send_tx_batch_to_tpu(
                             &auto_connection,
-                            &transactions_batch,
+                            &raw_txs,
                         ))
-                        .await
-                        .context(format!(
+                        .await;
+                        timer.observe_duration();
+                        if result.is_err() {
+                            TX_SEND_TIMEOUTS.inc();
+                        }
+                        let result = result.context(format!(
                             "send txs to tpu node {}",
                             auto_connection.target_address
                         ));
 
                         match result {
-                            Ok(()) => {
+                            Ok(sent) => {
+                                let nb_sent = sent.iter().filter(|sent| **sent).count();
+                                TXS_SENT.inc_by(nb_sent as u64);
+                                TXS_SEND_FAILED.inc_by((sent.len() - nb_sent) as u64);
+                                for ((packet, tx_index), sent) in transactions_batch.iter().zip(sent) {
+                                    packet.report_delivery(*tx_index, if sent {
+                                        DeliveryStatus::Sent
+                                    } else {
+                                        DeliveryStatus::SendFailed
+                                    });
+                                }
                                 debug!("send_txs_to_tpu_static sent {}", transactions_batch.len());
                                 debug!(
                                     "Outbound connection stats: {}",
@@ -181,6 +323,13 @@ pub async fn tx_forwarder(
                         }
                     } // -- while all packtes from channel
 
+                    // the packets of this connection left in the channel are not forwarded anymore
+                    while let Ok(packet) = per_connection_receiver.try_recv() {
+                        if packet.tpu_address == tpu_address && sharder.matching(packet.shard_hash) {
+                            packet.report_delivery_of_all(DeliveryStatus::Skipped);
+                        }
+                    }
+
 
                     auto_connection.force_shutdown().await;
                     warn!(
@@ -190,11 +339,22 @@ pub async fn tx_forwarder(
                 }); // -- spawned thread for one connection to one TPU
             } // -- for parallel connections to one TPU
 
+            TPU_AGENTS_STARTED.inc();
+            TPU_AGENTS.inc();
             let now = Instant::now();
+            let last_used_at = Arc::new(RwLock::new(now));
+            active_agents.agents.insert(
+                tpu_address,
+                AgentState {
+                    started_at: now,
+                    last_used_at: last_used_at.clone(),
+                    connections,
+                },
+            );
             AgentHandle {
                 tpu_address,
                 agent_exit_signal,
-                last_used_at: Arc::new(RwLock::new(now))
+                last_used_at,
             }
         }); // -- new agent
 
@@ -202,7 +362,7 @@ pub async fn tx_forwarder(
         agent.touch().await;
 
         if agent_shutdown_debouncer.can_fire() {
-            cleanup_agents(&mut agents, &tpu_address).await;
+            cleanup_agents(&mut agents, &active_agents, &tpu_address).await;
         }
 
         if broadcast_in.len() > 5 {
@@ -219,6 +379,7 @@ pub async fn tx_forwarder(
 
 async fn cleanup_agents(
     agents: &mut HashMap<SocketAddr, AgentHandle>,
+    active_agents: &ActiveAgents,
     current_tpu_address: &SocketAddr,
 ) {
     let now = Instant::now();
@@ -240,11 +401,14 @@ async fn cleanup_agents(
 
     for tpu_address in to_shutdown.iter() {
         if let Some(removed_agent) = agents.remove(tpu_address) {
+            active_agents.agents.remove(tpu_address);
+            TPU_AGENTS.dec();
             let was_signaled = removed_agent
                 .agent_exit_signal
                 .compare_exchange(false, true, Ordering::SeqCst, Ordering::Relaxed)
                 .is_ok();
             if was_signaled {
+                TPU_AGENTS_SHUTDOWN.inc();
                 let unused_period = {
                     let last_used_ts = removed_agent.last_used_at.read().await;
                     Instant::now() - *last_used_ts
@@ -319,14 +483,44 @@ fn create_tpu_client_endpoint(
     endpoint
 }
 
+// transactions which cannot be decoded go last
+fn priority_score(raw_tx: &[u8]) -> u64 {
+    bincode::deserialize::<VersionedTransaction>(raw_tx)
+        .map(|tx| TransactionPriority::from_transaction(&tx).score())
+        .unwrap_or_default()
+}
+
 // send potentially large amount of transactions to a single TPU
+// returns whether each transaction was sent
 #[tracing::instrument(skip_all, level = "debug")]
-async fn send_tx_batch_to_tpu(auto_connection: &AutoReconnect, txs: &[Vec<u8>]) {
+async fn send_tx_batch_to_tpu(auto_connection: &AutoReconnect, txs: &[&Vec<u8>]) -> Vec<bool> {
+    let mut sent = Vec::with_capacity(txs.len());
     for chunk in txs.chunks(MAX_PARALLEL_STREAMS) {
-        let all_send_fns = chunk.iter().map(|tx_raw| {
-            auto_connection.send_uni(tx_raw) // ignores error
-        });
+        let all_send_fns = chunk.iter().map(|tx_raw| auto_connection.send_uni(tx_raw));
 
-        join_all(all_send_fns).await;
+        sent.extend(
+            join_all(all_send_fns)
+                .await
+                .iter()
+                .map(|result| result.is_ok()),
+        );
+    }
+    sent
+}
+
+// send the transactions of a bundle one after another over the same connection
+// returns the number of transactions sent
+#[tracing::instrument(skip_all, level = "debug")]
+async fn send_tx_bundle_to_tpu(auto_connection: &AutoReconnect, txs: &[Vec<u8>]) -> usize {
+    for (nb_sent, tx_raw) in txs.iter().enumerate() {
+        if let Err(err) = auto_connection.send_uni(tx_raw).await {
+            // the remaining transactions of the bundle are worthless without this one
+            debug!(
+                "failed to send bundle transaction - skip rest of bundle: {}",
+                err
+            );
+            return nb_sent;
+        }
     }
+    txs.len()
 }
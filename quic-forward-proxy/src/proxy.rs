@@ -4,8 +4,11 @@ use anyhow::bail;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
+use crate::admin_server::AdminServer;
+use crate::inbound::client_rate_limiter::ClientRateLimiter;
 use crate::inbound::proxy_listener;
-use crate::outbound::tx_forward::tx_forwarder;
+use crate::leader_tracker::LeaderTracker;
+use crate::outbound::tx_forward::{tx_forwarder, ActiveAgents};
 use crate::tls_self_signed_pair_generator::SelfSignedTlsConfigProvider;
 use crate::util::AnyhowJoinHandle;
 use crate::validator_identity::ValidatorIdentity;
@@ -16,6 +19,14 @@ pub struct QuicForwardProxy {
     validator_identity: ValidatorIdentity,
     tls_config: Arc<SelfSignedTlsConfigProvider>,
     pub proxy_listener_addr: SocketAddr,
+    // per client identity, unlimited if not set
+    max_transactions_per_second_per_client: Option<u32>,
+    // over all clients, unlimited if not set
+    max_transactions_per_second: Option<u32>,
+    // picks the leaders for requests without tpu nodes, such requests are skipped if not set
+    leader_tracker: Option<Arc<LeaderTracker>>,
+    // prometheus metrics and agents, disabled if not set
+    admin_addr: Option<SocketAddr>,
 }
 
 impl QuicForwardProxy {
@@ -30,16 +41,66 @@ impl QuicForwardProxy {
             proxy_listener_addr,
             validator_identity,
             tls_config,
+            max_transactions_per_second_per_client: None,
+            max_transactions_per_second: None,
+            leader_tracker: None,
+            admin_addr: None,
         })
     }
 
+    pub fn with_max_transactions_per_second_per_client(
+        mut self,
+        max_transactions_per_second: u32,
+    ) -> Self {
+        self.max_transactions_per_second_per_client = Some(max_transactions_per_second);
+        self
+    }
+
+    pub fn with_max_transactions_per_second(mut self, max_transactions_per_second: u32) -> Self {
+        self.max_transactions_per_second = Some(max_transactions_per_second);
+        self
+    }
+
+    pub fn with_leader_tracker(mut self, leader_tracker: Arc<LeaderTracker>) -> Self {
+        self.leader_tracker = Some(leader_tracker);
+        self
+    }
+
+    pub fn with_admin_addr(mut self, admin_addr: SocketAddr) -> Self {
+        self.admin_addr = Some(admin_addr);
+        self
+    }
+
     pub async fn start_services(self) -> anyhow::Result<()> {
         let exit_signal = Arc::new(AtomicBool::new(false));
 
         let (forwarder_channel, forward_receiver) = tokio::sync::mpsc::channel(1000);
 
-        let proxy_listener =
-            proxy_listener::ProxyListener::new(self.proxy_listener_addr, self.tls_config);
+        let rate_limiter = (self.max_transactions_per_second_per_client.is_some()
+            || self.max_transactions_per_second.is_some())
+        .then(|| {
+            Arc::new(ClientRateLimiter::new(
+                self.max_transactions_per_second_per_client,
+                self.max_transactions_per_second,
+            ))
+        });
+        let proxy_listener = proxy_listener::ProxyListener::new(
+            self.proxy_listener_addr,
+            self.tls_config,
+            rate_limiter,
+            self.leader_tracker.clone(),
+        );
+
+        let leader_tracker = self
+            .leader_tracker
+            .map(|leader_tracker| leader_tracker.start());
+        // never completes if no leader tracker is configured
+        let leader_tracker = async move {
+            match leader_tracker {
+                Some(leader_tracker) => leader_tracker.await,
+                None => std::future::pending().await,
+            }
+        };
 
         let quic_proxy = tokio::spawn(async move {
             proxy_listener
@@ -48,11 +109,23 @@ impl QuicForwardProxy {
                 .expect("proxy listen service");
         });
 
+        let active_agents = ActiveAgents::default();
+        let admin_server = self
+            .admin_addr
+            .map(|admin_addr| AdminServer::serve(admin_addr, active_agents.clone()));
+        let admin_server = async move {
+            match admin_server {
+                Some(admin_server) => admin_server.await,
+                None => std::future::pending().await,
+            }
+        };
+
         let validator_identity = self.validator_identity.clone();
         let exit_signal_clone = exit_signal.clone();
         let forwarder: AnyhowJoinHandle = tokio::spawn(tx_forwarder(
             validator_identity,
             forward_receiver,
+            active_agents,
             exit_signal_clone,
         ));
 
@@ -63,6 +136,12 @@ impl QuicForwardProxy {
             res = forwarder => {
                 bail!("TPU Quic Tx forwarder exited unexpectedly {res:?}");
             },
+            res = leader_tracker => {
+                bail!("Leader tracker exited unexpectedly {res:?}");
+            },
+            res = admin_server => {
+                bail!("Admin endpoint exited unexpectedly {res:?}");
+            },
         }
     }
 }
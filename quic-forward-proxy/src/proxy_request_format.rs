@@ -15,10 +15,17 @@ use std::net::SocketAddr;
 /// compat info: non-public format ATM
 /// initial version
 pub const FORMAT_VERSION1: u16 = 2500;
+/// adds bundles of transactions which must be forwarded in order
+pub const FORMAT_VERSION2: u16 = 2501;
+/// same layout as version 2; the client expects a delivery report on the same bidirectional stream
+pub const FORMAT_VERSION3: u16 = 2502;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TxData(Signature, Vec<u8>);
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BundleData(Vec<TxData>);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TpuNode {
     pub tpu_socket_addr: SocketAddr,
@@ -31,14 +38,26 @@ pub struct TpuForwardingRequest {
     // note: this data gets stale
     tpu_nodes: Vec<TpuNode>,
     transactions: Vec<TxData>,
+    // note: not part of version 1 of the wire format
+    #[serde(skip)]
+    bundles: Vec<BundleData>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TpuForwardingRequestV2 {
+    format_version: u16,
+    tpu_nodes: Vec<TpuNode>,
+    transactions: Vec<TxData>,
+    bundles: Vec<BundleData>,
 }
 
 impl Display for TpuForwardingRequest {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "TpuForwardingRequest t9 {} tpu nodes",
+            "TpuForwardingRequest t9 {} tpu nodes, {} bundles",
             &self.tpu_nodes.len(),
+            &self.bundles.len(),
         )
     }
 }
@@ -57,27 +76,71 @@ impl TpuForwardingRequest {
                     identity_tpunode: *identity,
                 })
                 .collect_vec(),
-            transactions: transactions
-                .iter()
-                .map(|tx| TxData(tx.signatures[0], bincode::serialize(tx).unwrap()))
-                .collect_vec(),
+            transactions: transactions.iter().map(Self::tx_data).collect_vec(),
+            bundles: vec![],
         }
     }
 
+    // test only
+    pub fn new_with_bundles(
+        tpu_fanout_nodes: Vec<(SocketAddr, Pubkey)>,
+        transactions: Vec<VersionedTransaction>,
+        bundles: Vec<Vec<VersionedTransaction>>,
+    ) -> Self {
+        let mut request = Self::new(tpu_fanout_nodes, transactions);
+        request.bundles = bundles
+            .iter()
+            .map(|bundle| BundleData(bundle.iter().map(Self::tx_data).collect_vec()))
+            .collect_vec();
+        request
+    }
+
+    fn tx_data(tx: &VersionedTransaction) -> TxData {
+        TxData(tx.signatures[0], bincode::serialize(tx).unwrap())
+    }
+
     // test only
     pub fn try_serialize_wire_format(&self) -> anyhow::Result<Vec<u8>> {
-        bincode::serialize(&self)
-            .context("serialize proxy request")
-            .map_err(anyhow::Error::from)
+        if self.bundles.is_empty() {
+            bincode::serialize(&self)
+        } else {
+            bincode::serialize(&TpuForwardingRequestV2 {
+                format_version: FORMAT_VERSION2,
+                tpu_nodes: self.tpu_nodes.clone(),
+                transactions: self.transactions.clone(),
+                bundles: self.bundles.clone(),
+            })
+        }
+        .context("serialize proxy request")
+        .map_err(anyhow::Error::from)
     }
 
     pub fn try_deserialize_from_wire_format(
         raw_proxy_request: &[u8],
     ) -> anyhow::Result<TpuForwardingRequest> {
-        let request = bincode::deserialize::<TpuForwardingRequest>(raw_proxy_request);
+        let format_version =
+            bincode::deserialize::<u16>(raw_proxy_request).context("deserialize proxy request")?;
+
+        let request = match format_version {
+            FORMAT_VERSION2 | FORMAT_VERSION3 => {
+                bincode::deserialize::<TpuForwardingRequestV2>(raw_proxy_request).map(|request| {
+                    TpuForwardingRequest {
+                        format_version: request.format_version,
+                        tpu_nodes: request.tpu_nodes,
+                        transactions: request.transactions,
+                        bundles: request.bundles,
+                    }
+                })
+            }
+            _ => bincode::deserialize::<TpuForwardingRequest>(raw_proxy_request),
+        };
 
         if let Ok(ref req) = request {
-            assert_eq!(req.format_version, FORMAT_VERSION1);
+            assert!(
+                req.format_version == FORMAT_VERSION1
+                    || req.format_version == FORMAT_VERSION2
+                    || req.format_version == FORMAT_VERSION3
+            );
         }
 
         request
@@ -89,6 +152,21 @@ impl TpuForwardingRequest {
         &self.tpu_nodes
     }
 
+    pub fn wants_delivery_report(&self) -> bool {
+        self.format_version == FORMAT_VERSION3
+    }
+
+    pub fn get_transaction_signatures(&self) -> Vec<Signature> {
+        self.transactions.iter().map(|tx| tx.0).collect_vec()
+    }
+
+    pub fn get_bundle_signatures(&self) -> Vec<Vec<Signature>> {
+        self.bundles
+            .iter()
+            .map(|bundle| bundle.0.iter().map(|tx| tx.0).collect_vec())
+            .collect_vec()
+    }
+
     pub fn get_transaction_bytes(&self) -> Vec<Vec<u8>> {
         self.transactions
             .iter()
@@ -96,10 +174,70 @@ impl TpuForwardingRequest {
             .collect_vec()
     }
 
+    pub fn get_bundles(&self) -> Vec<Vec<Vec<u8>>> {
+        self.bundles
+            .iter()
+            .map(|bundle| bundle.0.iter().map(|tx| tx.1.clone()).collect_vec())
+            .collect_vec()
+    }
+
     pub fn get_hash(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
         // note: assumes that there are transactions with >=0 signatures
-        self.transactions[0].0.hash(&mut hasher);
+        match self.transactions.first() {
+            Some(tx) => tx.0.hash(&mut hasher),
+            None => self.bundles[0].0[0].0.hash(&mut hasher),
+        }
         hasher.finish()
     }
+
+    pub fn get_bundle_hash(bundle: &[Vec<u8>]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        bundle[0].hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// outcome of forwarding a transaction to a tpu node
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DeliveryStatus {
+    // written to a stream of the tpu node
+    Sent,
+    // the connection to the tpu node failed
+    SendFailed,
+    // not forwarded before the report was due
+    TimedOut,
+    // refused by the proxy, e.g. over the rate limit of the client
+    Dropped,
+    // given up by the forwarder, e.g. its connection to the tpu node is dead
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TxDeliveryResult {
+    pub signature: Signature,
+    pub tpu_node: TpuNode,
+    pub status: DeliveryStatus,
+}
+
+/// proxy to lite-rpc answer to a request of format version 3
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeliveryReport {
+    format_version: u16,
+    pub results: Vec<TxDeliveryResult>,
+}
+
+impl DeliveryReport {
+    pub fn new(results: Vec<TxDeliveryResult>) -> Self {
+        DeliveryReport {
+            format_version: FORMAT_VERSION3,
+            results,
+        }
+    }
+
+    pub fn try_serialize_wire_format(&self) -> anyhow::Result<Vec<u8>> {
+        bincode::serialize(&self)
+            .context("serialize delivery report")
+            .map_err(anyhow::Error::from)
+    }
 }
@@ -1,4 +1,9 @@
+use log::warn;
 use quinn::Connection;
+use rustls::{server::ClientCertVerified, DistinguishedName};
+use solana_sdk::pubkey::Pubkey;
+use solana_streamer::tls_certificates::get_pubkey_from_tls_certificate;
+use std::collections::HashSet;
 use std::sync::Arc;
 
 pub const ALPN_TPU_FORWARDPROXY_PROTOCOL_ID: &[u8] = b"solana-tpu-forward-proxy";
@@ -25,6 +30,56 @@ impl rustls::client::ServerCertVerifier for SkipServerVerification {
     }
 }
 
+/// Accepts the clients presenting the certificate of their keypair, restricted to the allowlist if set
+pub struct AllowlistClientVerification {
+    allowlist: Option<HashSet<Pubkey>>,
+}
+
+impl AllowlistClientVerification {
+    pub fn new(allowlist: Option<HashSet<Pubkey>>) -> Arc<Self> {
+        Arc::new(Self { allowlist })
+    }
+}
+
+impl rustls::server::ClientCertVerifier for AllowlistClientVerification {
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    // the handshake signature proves the client owns the keypair of the certificate
+    fn verify_client_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _now: std::time::SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        let Some(client_identity) = get_pubkey_from_tls_certificate(end_entity) else {
+            return Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::BadEncoding,
+            ));
+        };
+        if let Some(allowlist) = &self.allowlist {
+            if !allowlist.contains(&client_identity) {
+                warn!("rejecting client {} not in the allowlist", client_identity);
+                return Err(rustls::Error::InvalidCertificate(
+                    rustls::CertificateError::ApplicationVerificationFailure,
+                ));
+            }
+        }
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+/// identity of the keypair the peer authenticated with
+pub fn peer_identity(connection: &Connection) -> Option<Pubkey> {
+    connection
+        .peer_identity()?
+        .downcast::<Vec<rustls::Certificate>>()
+        .ok()?
+        .first()
+        .and_then(get_pubkey_from_tls_certificate)
+}
+
 //  stable_id 140266619216912, rtt=2.156683ms,
 // stats FrameStats { ACK: 3, CONNECTION_CLOSE: 0, CRYPTO: 3,
 // DATA_BLOCKED: 0, DATAGRAM: 0, HANDSHAKE_DONE: 1, MAX_DATA: 0,
@@ -40,3 +95,43 @@ pub fn connection_stats(connection: &Connection) -> String {
         connection.stats().path.rtt
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::server::ClientCertVerifier;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_streamer::tls_certificates::new_self_signed_tls_certificate;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::SystemTime;
+
+    fn certificate(keypair: &Keypair) -> rustls::Certificate {
+        new_self_signed_tls_certificate(keypair, IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn allowlist_client_verification() {
+        let allowed = Keypair::new();
+        let other = Keypair::new();
+
+        let verification =
+            AllowlistClientVerification::new(Some(HashSet::from([allowed.pubkey()])));
+        assert!(verification
+            .verify_client_cert(&certificate(&allowed), &[], SystemTime::now())
+            .is_ok());
+        assert!(verification
+            .verify_client_cert(&certificate(&other), &[], SystemTime::now())
+            .is_err());
+        assert!(verification
+            .verify_client_cert(&rustls::Certificate(vec![1, 2, 3]), &[], SystemTime::now())
+            .is_err());
+
+        // any authenticated client without allowlist
+        let verification = AllowlistClientVerification::new(None);
+        assert!(verification
+            .verify_client_cert(&certificate(&other), &[], SystemTime::now())
+            .is_ok());
+    }
+}
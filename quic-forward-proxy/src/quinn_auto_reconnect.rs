@@ -49,6 +49,11 @@ impl AutoReconnect {
         matches!(&*lock, ConnectionState::PermanentError)
     }
 
+    pub async fn is_connected(&self) -> bool {
+        let lock = self.current.read().await;
+        matches!(&*lock, ConnectionState::Connection(conn) if conn.close_reason().is_none())
+    }
+
     pub async fn send_uni(&self, payload: &Vec<u8>) -> anyhow::Result<()> {
         let mut send_stream = timeout(SEND_TIMEOUT, self.refresh_and_get().await?.open_uni())
             .await
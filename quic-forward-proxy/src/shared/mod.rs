@@ -1,20 +1,79 @@
+use crate::proxy_request_format::DeliveryStatus;
+use solana_sdk::signature::Signature;
 use std::net::SocketAddr;
+use tokio::sync::mpsc::UnboundedSender;
 
 /// internal structure with transactions and target TPU
 #[derive(Debug)]
 pub struct ForwardPacket {
     pub transactions: Vec<Vec<u8>>,
+    pub signatures: Vec<Signature>,
     pub tpu_address: SocketAddr,
     pub shard_hash: u64,
+    // transactions of a bundle must be sent in order and never be mixed with others
+    pub is_bundle: bool,
+    // set if the client asked for a delivery report
+    delivery_reporter: Option<UnboundedSender<DeliveryUpdate>>,
+}
+
+/// outcome of forwarding one transaction of a packet
+#[derive(Debug)]
+pub struct DeliveryUpdate {
+    pub signature: Signature,
+    pub tpu_address: SocketAddr,
+    pub status: DeliveryStatus,
 }
 
 impl ForwardPacket {
-    pub fn new(transactions: Vec<Vec<u8>>, tpu_address: SocketAddr, hash: u64) -> Self {
+    pub fn new(
+        transactions: Vec<Vec<u8>>,
+        signatures: Vec<Signature>,
+        tpu_address: SocketAddr,
+        hash: u64,
+    ) -> Self {
         assert!(!transactions.is_empty(), "no transactions");
+        assert_eq!(transactions.len(), signatures.len(), "one signature per tx");
         Self {
             transactions,
+            signatures,
             tpu_address,
             shard_hash: hash,
+            is_bundle: false,
+            delivery_reporter: None,
+        }
+    }
+
+    pub fn new_bundle(
+        transactions: Vec<Vec<u8>>,
+        signatures: Vec<Signature>,
+        tpu_address: SocketAddr,
+        hash: u64,
+    ) -> Self {
+        Self {
+            is_bundle: true,
+            ..Self::new(transactions, signatures, tpu_address, hash)
+        }
+    }
+
+    pub fn with_delivery_reporter(mut self, reporter: UnboundedSender<DeliveryUpdate>) -> Self {
+        self.delivery_reporter = Some(reporter);
+        self
+    }
+
+    pub fn report_delivery_of_all(&self, status: DeliveryStatus) {
+        for tx_index in 0..self.signatures.len() {
+            self.report_delivery(tx_index, status);
+        }
+    }
+
+    pub fn report_delivery(&self, tx_index: usize, status: DeliveryStatus) {
+        if let Some(reporter) = &self.delivery_reporter {
+            // the client is gone if the report is not awaited anymore
+            let _ = reporter.send(DeliveryUpdate {
+                signature: self.signatures[tx_index],
+                tpu_address: self.tpu_address,
+                status,
+            });
         }
     }
 }
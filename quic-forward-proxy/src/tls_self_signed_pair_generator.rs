@@ -1,8 +1,15 @@
-use crate::quic_util::{SkipServerVerification, ALPN_TPU_FORWARDPROXY_PROTOCOL_ID};
+use crate::quic_util::{
+    AllowlistClientVerification, SkipServerVerification, ALPN_TPU_FORWARDPROXY_PROTOCOL_ID,
+};
 use crate::tls_config_provider_client::TpuClientTlsConfigProvider;
 use crate::tls_config_provider_server::ProxyTlsConfigProvider;
 use rcgen::generate_simple_self_signed;
 use rustls::{Certificate, ClientConfig, PrivateKey, ServerConfig};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_streamer::tls_certificates::new_self_signed_tls_certificate;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr};
 
 impl ProxyTlsConfigProvider for SelfSignedTlsConfigProvider {
     fn get_server_tls_crypto_config(&self) -> ServerConfig {
@@ -33,6 +40,23 @@ impl SelfSignedTlsConfigProvider {
         }
     }
 
+    /// certificate of the proxy identity so that clients can pin it,
+    /// clients must authenticate with the certificate of their keypair
+    pub fn new_with_identity(
+        identity: &Keypair,
+        client_allowlist: Option<HashSet<Pubkey>>,
+    ) -> Self {
+        let (certificate, private_key) =
+            new_self_signed_tls_certificate(identity, IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+                .expect("Failed to initialize the proxy certificate");
+        let server_crypto =
+            Self::build_server_crypto_with_client_auth(certificate, private_key, client_allowlist);
+        Self {
+            client_crypto: Self::build_client_crypto_insecure(),
+            server_crypto,
+        }
+    }
+
     fn gen_tls_certificate_and_key(hostnames: Vec<String>) -> (Certificate, PrivateKey) {
         let cert = generate_simple_self_signed(hostnames).unwrap();
         let key = cert.serialize_private_key_der();
@@ -54,7 +78,6 @@ impl SelfSignedTlsConfigProvider {
         // let (server_cert, server_key) = gen_tls_certificate_and_key();
 
         let mut server_crypto = rustls::ServerConfig::builder()
-            // FIXME we want client auth
             .with_safe_defaults()
             .with_no_client_auth()
             .with_single_cert(vec![server_cert], server_key)
@@ -63,6 +86,20 @@ impl SelfSignedTlsConfigProvider {
         server_crypto
     }
 
+    fn build_server_crypto_with_client_auth(
+        server_cert: Certificate,
+        server_key: PrivateKey,
+        client_allowlist: Option<HashSet<Pubkey>>,
+    ) -> ServerConfig {
+        let mut server_crypto = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(AllowlistClientVerification::new(client_allowlist))
+            .with_single_cert(vec![server_cert], server_key)
+            .unwrap();
+        server_crypto.alpn_protocols = vec![ALPN_TPU_FORWARDPROXY_PROTOCOL_ID.to_vec()];
+        server_crypto
+    }
+
     pub fn get_client_tls_crypto_config(&self) -> &ClientConfig {
         &self.client_crypto
     }
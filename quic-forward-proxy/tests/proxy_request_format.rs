@@ -1,7 +1,10 @@
-use solana_lite_rpc_quic_forward_proxy::proxy_request_format::TpuForwardingRequest;
+use solana_lite_rpc_core::structures::proxy_request_format as lite_rpc_format;
+use solana_lite_rpc_quic_forward_proxy::proxy_request_format::{
+    DeliveryReport, DeliveryStatus, TpuForwardingRequest, TxDeliveryResult,
+};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, Signer};
-use solana_sdk::transaction::Transaction;
+use solana_sdk::transaction::{Transaction, VersionedTransaction};
 use std::str::FromStr;
 
 #[test]
@@ -40,3 +43,83 @@ fn deserialize_error() {
     let result = TpuForwardingRequest::try_deserialize_from_wire_format(value);
     assert_eq!(result.unwrap_err().to_string(), "deserialize proxy request");
 }
+
+#[test]
+fn roundtrip_with_bundles() {
+    let payer = Keypair::new();
+    let payer_pubkey = payer.pubkey();
+
+    let memo_tx = |msg: &str| -> VersionedTransaction {
+        let memo_ix = spl_memo::build_memo(msg.as_bytes(), &[&payer_pubkey]);
+        Transaction::new_with_payer(&[memo_ix], Some(&payer_pubkey)).into()
+    };
+
+    let wire_data = TpuForwardingRequest::new_with_bundles(
+        vec![(
+            "127.0.0.1:5454".parse().unwrap(),
+            Pubkey::from_str("Bm8rtweCQ19ksNebrLY92H7x4bCaeDJSSmEeWqkdCeop").unwrap(),
+        )],
+        vec![],
+        vec![vec![memo_tx("first"), memo_tx("second")]],
+    )
+    .try_serialize_wire_format()
+    .unwrap();
+
+    let request = TpuForwardingRequest::try_deserialize_from_wire_format(&wire_data).unwrap();
+
+    assert_eq!(request.get_tpu_nodes().len(), 1);
+    assert!(request.get_transaction_bytes().is_empty());
+    assert_eq!(request.get_bundles().len(), 1);
+    assert_eq!(request.get_bundles()[0].len(), 2);
+}
+
+#[test]
+fn roundtrip_with_delivery_report() {
+    let payer = Keypair::new();
+    let payer_pubkey = payer.pubkey();
+    let memo_ix = spl_memo::build_memo("Hello world".as_bytes(), &[&payer_pubkey]);
+    let tx: VersionedTransaction =
+        Transaction::new_with_payer(&[memo_ix], Some(&payer_pubkey)).into();
+    let signature = tx.signatures[0];
+
+    // request as sent by lite-rpc
+    let wire_data = lite_rpc_format::TpuForwardingRequest::new(
+        &[(
+            "127.0.0.1:5454".parse().unwrap(),
+            Pubkey::from_str("Bm8rtweCQ19ksNebrLY92H7x4bCaeDJSSmEeWqkdCeop").unwrap(),
+        )],
+        &[lite_rpc_format::TxData::new(
+            signature,
+            bincode::serialize(&tx).unwrap(),
+        )],
+    )
+    .with_delivery_report()
+    .try_serialize_wire_format()
+    .unwrap();
+
+    let request = TpuForwardingRequest::try_deserialize_from_wire_format(&wire_data).unwrap();
+    assert!(request.wants_delivery_report());
+    assert_eq!(request.get_transaction_signatures(), vec![signature]);
+
+    // report as answered by the proxy
+    let raw_report = DeliveryReport::new(vec![TxDeliveryResult {
+        signature,
+        tpu_node: request.get_tpu_nodes()[0].clone(),
+        status: DeliveryStatus::Skipped,
+    }])
+    .try_serialize_wire_format()
+    .unwrap();
+
+    let report =
+        lite_rpc_format::DeliveryReport::try_deserialize_from_wire_format(&raw_report).unwrap();
+    assert_eq!(report.results.len(), 1);
+    assert_eq!(report.results[0].signature, signature);
+    assert_eq!(
+        report.results[0].tpu_node.tpu_socket_addr,
+        "127.0.0.1:5454".parse().unwrap()
+    );
+    assert_eq!(
+        report.results[0].status,
+        lite_rpc_format::DeliveryStatus::Skipped
+    );
+}
@@ -8,12 +8,16 @@ use solana_lite_rpc_core::stores::{
     block_information_store::BlockInformation, data_cache::DataCache,
 };
 use solana_lite_rpc_core::structures::block_info::BlockInfo;
-use solana_lite_rpc_core::types::{BlockStream, ClusterInfoStream, SlotStream, VoteAccountStream};
+use solana_lite_rpc_core::structures::orphaned_block::OrphanedBlock;
+use solana_lite_rpc_core::structures::produced_block::ProducedBlock;
+use solana_lite_rpc_core::types::{
+    BlockStream, ClusterInfoStream, OrphanedBlockStream, SlotStream, VoteAccountStream,
+};
 use solana_lite_rpc_core::AnyhowJoinHandle;
 use solana_sdk::clock::MAX_RECENT_BLOCKHASHES;
 use solana_sdk::commitment_config::CommitmentLevel;
 use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
-use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
 use tokio::sync::broadcast::Receiver;
 
 lazy_static::lazy_static! {
@@ -34,6 +38,9 @@ lazy_static::lazy_static! {
 
     static ref TXS_PROCESSED: IntCounter =
     register_int_counter!(opts!("literpc_txs_processed", "Number of Transactions Processed")).unwrap();
+
+    static ref TXS_ROLLED_BACK: IntCounter =
+    register_int_counter!(opts!("literpc_txs_rolled_back", "Number of Transactions whose processed block got orphaned")).unwrap();
 }
 
 pub struct DataCachingService {
@@ -49,74 +56,56 @@ impl DataCachingService {
         slot_notification: SlotStream,
         cluster_info_notification: ClusterInfoStream,
         va_notification: VoteAccountStream,
+        orphaned_block_notification: OrphanedBlockStream,
     ) -> Vec<AnyhowJoinHandle> {
-        let data_cache = self.data_cache.clone();
-        let block_information_store_block = data_cache.block_information_store.clone();
-        let block_information_store_block_info = data_cache.block_information_store.clone();
+        let block_information_store_block_info = self.data_cache.block_information_store.clone();
 
+        // orphaned blocks are rolled back in the same task which ingests the blocks
+        let data_cache = self.data_cache.clone();
         let block_cache_jh = tokio::spawn(async move {
             let mut block_notifier = block_notifier;
+            let mut orphaned_block_notification = orphaned_block_notification;
             loop {
-                let block = match block_notifier.recv().await {
-                    Ok(block) => block,
-                    Err(RecvError::Lagged(blocks_lagged)) => {
-                        warn!("Lagged {} blocks - continue", blocks_lagged);
-                        continue;
-                    }
-                    Err(RecvError::Closed) => {
-                        bail!("Block stream has been closed - abort");
+                tokio::select! {
+                    biased;
+                    block = block_notifier.recv() => {
+                        match block {
+                            Ok(block) => ingest_block(&data_cache, &block).await,
+                            Err(RecvError::Lagged(blocks_lagged)) => {
+                                warn!("Lagged {} blocks - continue", blocks_lagged);
+                            }
+                            Err(RecvError::Closed) => {
+                                bail!("Block stream has been closed - abort");
+                            }
+                        }
                     }
-                };
-
-                // note: most likely the block has been added from blockinfo_notifier stream already
-                block_information_store_block
-                    .add_block(BlockInformation::from_block(&block))
-                    .await;
-
-                let confirmation_status = match block.commitment_config.commitment {
-                    CommitmentLevel::Finalized => TransactionConfirmationStatus::Finalized,
-                    CommitmentLevel::Confirmed => TransactionConfirmationStatus::Confirmed,
-                    _ => TransactionConfirmationStatus::Processed,
-                };
-
-                for tx in &block.transactions {
-                    let block_info =
-                        block_information_store_block.get_block_info(&tx.recent_blockhash);
-                    let last_valid_blockheight = if let Some(block_info) = block_info {
-                        block_info.last_valid_blockheight
-                    } else {
-                        block.block_height + MAX_RECENT_BLOCKHASHES as u64
-                    };
-
-                    if data_cache.txs.update_status(
-                        tx.signature,
-                        TransactionStatus {
-                            slot: block.slot,
-                            confirmations: None,
-                            status: tx.err.clone().map_or(Ok(()), Err),
-                            err: tx.err.clone(),
-                            confirmation_status: Some(confirmation_status.clone()),
-                        },
-                        last_valid_blockheight,
-                    ) {
-                        // transaction updated
-                        match confirmation_status {
-                            TransactionConfirmationStatus::Finalized => {
-                                TXS_FINALIZED.inc();
+                    orphaned_block = orphaned_block_notification.recv() => {
+                        let orphaned_block = match orphaned_block {
+                            Ok(orphaned_block) => orphaned_block,
+                            Err(RecvError::Lagged(orphaned_lagged)) => {
+                                warn!("Lagged {} orphaned blocks - continue", orphaned_lagged);
+                                continue;
                             }
-                            TransactionConfirmationStatus::Confirmed => {
-                                TXS_CONFIRMED.inc();
+                            Err(RecvError::Closed) => {
+                                bail!("Orphaned block stream has been closed - abort");
                             }
-                            TransactionConfirmationStatus::Processed => {
-                                TXS_PROCESSED.inc();
+                        };
+                        // the fork tracker got the orphaned block from the same block stream,
+                        // it is queued here already and must not be ingested after the rollback
+                        loop {
+                            match block_notifier.try_recv() {
+                                Ok(block) => ingest_block(&data_cache, &block).await,
+                                Err(TryRecvError::Lagged(blocks_lagged)) => {
+                                    warn!("Lagged {} blocks - continue", blocks_lagged);
+                                }
+                                Err(TryRecvError::Empty) => break,
+                                Err(TryRecvError::Closed) => {
+                                    bail!("Block stream has been closed - abort");
+                                }
                             }
                         }
+                        rollback_orphaned_block(&data_cache, &orphaned_block);
                     }
-                    // notify
-                    data_cache
-                        .tx_subs
-                        .notify(block.slot, tx, block.commitment_config)
-                        .await;
                 }
             }
         });
@@ -204,3 +193,67 @@ impl DataCachingService {
         ]
     }
 }
+
+async fn ingest_block(data_cache: &DataCache, block: &ProducedBlock) {
+    // note: most likely the block has been added from blockinfo_notifier stream already
+    data_cache
+        .block_information_store
+        .add_block(BlockInformation::from_block(block))
+        .await;
+
+    let confirmation_status = match block.commitment_config.commitment {
+        CommitmentLevel::Finalized => TransactionConfirmationStatus::Finalized,
+        CommitmentLevel::Confirmed => TransactionConfirmationStatus::Confirmed,
+        _ => TransactionConfirmationStatus::Processed,
+    };
+
+    for tx in &block.transactions {
+        let block_info = data_cache
+            .block_information_store
+            .get_block_info(&tx.recent_blockhash);
+        let last_valid_blockheight = if let Some(block_info) = block_info {
+            block_info.last_valid_blockheight
+        } else {
+            block.block_height + MAX_RECENT_BLOCKHASHES as u64
+        };
+
+        if data_cache.txs.update_status(
+            tx.signature,
+            TransactionStatus {
+                slot: block.slot,
+                confirmations: None,
+                status: tx.err.clone().map_or(Ok(()), Err),
+                err: tx.err.clone(),
+                confirmation_status: Some(confirmation_status.clone()),
+            },
+            last_valid_blockheight,
+        ) {
+            // transaction updated
+            match confirmation_status {
+                TransactionConfirmationStatus::Finalized => {
+                    TXS_FINALIZED.inc();
+                }
+                TransactionConfirmationStatus::Confirmed => {
+                    TXS_CONFIRMED.inc();
+                }
+                TransactionConfirmationStatus::Processed => {
+                    TXS_PROCESSED.inc();
+                }
+            }
+        }
+        // notify
+        data_cache
+            .tx_subs
+            .notify(block.slot, tx, block.commitment_config)
+            .await;
+    }
+}
+
+fn rollback_orphaned_block(data_cache: &DataCache, orphaned_block: &OrphanedBlock) {
+    // transactions of the orphaned block are pending again
+    let rolled_back = data_cache.txs.rollback_processed(orphaned_block);
+    TXS_ROLLED_BACK.inc_by(rolled_back as u64);
+    data_cache
+        .block_information_store
+        .remove_processed_block(&orphaned_block.blockhash);
+}
@@ -6,4 +6,5 @@ pub mod quic_connection_utils;
 pub mod tpu_utils;
 pub mod transaction_replayer;
 pub mod transaction_service;
+pub mod transaction_wal;
 pub mod tx_sender;
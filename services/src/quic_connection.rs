@@ -1,5 +1,5 @@
 use crate::quic_connection_utils::{
-    QuicConnectionError, QuicConnectionParameters, QuicConnectionUtils,
+    QuicBindConfig, QuicConnectionError, QuicConnectionParameters, QuicConnectionUtils,
 };
 use futures::FutureExt;
 use log::warn;
@@ -8,7 +8,7 @@ use quinn::{Connection, Endpoint, VarInt};
 use solana_lite_rpc_core::structures::rotating_queue::RotatingQueue;
 use solana_sdk::pubkey::Pubkey;
 use std::{
-    net::SocketAddr,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
     sync::{
         atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
@@ -16,7 +16,72 @@ use std::{
 };
 use tokio::sync::{broadcast, OwnedSemaphorePermit, RwLock, Semaphore};
 
-pub type EndpointPool = RotatingQueue<Endpoint>;
+/// Endpoints bound to the configured local addresses, picked by the address family of the peer
+#[derive(Clone)]
+pub struct EndpointPool {
+    ipv4: RotatingQueue<Endpoint>,
+    ipv6: RotatingQueue<Endpoint>,
+}
+
+impl EndpointPool {
+    pub fn new(
+        certificate: rustls::Certificate,
+        key: rustls::PrivateKey,
+        bind_config: &QuicBindConfig,
+        number_of_endpoints: usize,
+    ) -> Self {
+        // at least one endpoint per address
+        let number_of_endpoints = number_of_endpoints.max(bind_config.addresses.len());
+        let endpoints = bind_config
+            .addresses
+            .iter()
+            .cycle()
+            .take(number_of_endpoints)
+            .map(|address| {
+                QuicConnectionUtils::create_endpoint(
+                    certificate.clone(),
+                    key.clone(),
+                    *address,
+                    bind_config.port_range,
+                )
+            })
+            .collect::<Vec<_>>();
+        Self::from_endpoints(endpoints)
+    }
+
+    pub fn from_endpoints(endpoints: Vec<Endpoint>) -> Self {
+        let reaching = |remote: SocketAddr| {
+            endpoints
+                .iter()
+                .filter(|endpoint| {
+                    endpoint
+                        .local_addr()
+                        .is_ok_and(|local| QuicConnectionUtils::can_reach(local.ip(), &remote))
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        Self {
+            ipv4: RotatingQueue::from_vec(reaching(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))),
+            ipv6: RotatingQueue::from_vec(reaching(SocketAddr::from((Ipv6Addr::LOCALHOST, 0)))),
+        }
+    }
+
+    /// next endpoint able to reach `remote`
+    pub fn get(&self, remote: &SocketAddr) -> Option<Endpoint> {
+        match remote {
+            SocketAddr::V4(_) => self.ipv4.get(),
+            SocketAddr::V6(_) => self.ipv6.get(),
+        }
+    }
+
+    pub fn can_reach(&self, remote: &SocketAddr) -> bool {
+        match remote {
+            SocketAddr::V4(_) => !self.ipv4.is_empty(),
+            SocketAddr::V6(_) => !self.ipv6.is_empty(),
+        }
+    }
+}
 
 lazy_static::lazy_static! {
     static ref NB_QUIC_CONNECTION_RESET: GenericGauge<prometheus::core::AtomicI64> =
@@ -125,7 +190,32 @@ impl QuicConnection {
         }
     }
 
-    pub async fn send_transaction(&self, tx: &Vec<u8>, mut exit_notify: broadcast::Receiver<()>) {
+    // open the connection before it is needed, resuming with 0-RTT if a session ticket is cached
+    pub async fn prewarm(&self, exit_notify: broadcast::Receiver<()>) -> bool {
+        let mut lk = self.connection.write().await;
+        if lk
+            .as_ref()
+            .is_some_and(|conn| conn.close_reason().is_none())
+        {
+            return true;
+        }
+        let connection = self.connect(true, exit_notify).await;
+        let connected = connection.is_some();
+        *lk = connection;
+        self.has_connected_once.store(connected, Ordering::Relaxed);
+        connected
+    }
+
+    pub fn socket_address(&self) -> SocketAddr {
+        self.socket_address
+    }
+
+    /// returns if the transaction was written to a stream, None when interrupted by the exit notification
+    pub async fn send_transaction(
+        &self,
+        tx: &Vec<u8>,
+        mut exit_notify: broadcast::Receiver<()>,
+    ) -> Option<bool> {
         let connection_retry_count = self.connection_params.connection_retry_count;
         for _ in 0..connection_retry_count {
             let mut do_retry = false;
@@ -135,7 +225,7 @@ impl QuicConnection {
                     conn
                 },
                 _ = exit_notify.recv() => {
-                    break;
+                    return None;
                 }
             };
 
@@ -150,7 +240,7 @@ impl QuicConnection {
                         res
                     },
                     _ = exit_notify.recv() => {
-                        break;
+                        return None;
                     }
                 };
                 match open_uni_result {
@@ -165,12 +255,13 @@ impl QuicConnection {
                                 res
                             },
                             _ = exit_notify.recv() => {
-                                break;
+                                return None;
                             }
                         };
                         match write_add_result {
                             Ok(()) => {
                                 SEND_TRANSCTION_SUCESSFUL.inc();
+                                return Some(true);
                             }
                             Err(QuicConnectionError::ConnectionError { retry }) => {
                                 do_retry = retry;
@@ -204,6 +295,7 @@ impl QuicConnection {
                 break;
             }
         }
+        Some(false)
     }
 
     pub fn get_timeout_count(&self) -> u64 {
@@ -256,13 +348,17 @@ impl QuicConnectionPool {
         connection_parameters: QuicConnectionParameters,
         nb_connection: usize,
         max_number_of_unistream_connection: usize,
+        prewarmed_connection: Option<QuicConnection>,
     ) -> Self {
         let mut connections = vec![];
+        connections.extend(prewarmed_connection.into_iter().take(nb_connection));
         // should not clone connection each time but create a new one
-        for _ in 0..nb_connection {
+        for _ in connections.len()..nb_connection {
             connections.push(QuicConnection::new(
                 identity,
-                endpoints.get().expect("Should get and endpoint"),
+                endpoints
+                    .get(&socket_address)
+                    .expect("Should get and endpoint"),
                 socket_address,
                 connection_parameters,
             ));
@@ -1,4 +1,4 @@
-use log::trace;
+use log::{trace, warn};
 use prometheus::{
     core::GenericGauge, histogram_opts, opts, register_histogram, register_int_gauge, Histogram,
 };
@@ -9,8 +9,9 @@ use quinn::{
 use serde::{Deserialize, Serialize};
 use solana_lite_rpc_core::network_utils::apply_gso_workaround;
 use solana_sdk::pubkey::Pubkey;
+use solana_streamer::tls_certificates::get_pubkey_from_tls_certificate;
 use std::{
-    net::{IpAddr, Ipv4Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
     sync::Arc,
     time::Duration,
 };
@@ -109,6 +110,22 @@ pub struct QuicConnectionParameters {
     pub number_of_transactions_per_unistream: usize,
     pub unistreams_to_create_new_connection_in_percentage: u8,
     pub prioritization_heap_size: Option<usize>,
+    // share of a full prioritization heap a single fee payer can occupy, fee payers can be rotated so it is not a sybil defense
+    #[serde(default = "QuicConnectionParameters::default_prioritization_heap_share_per_sender")]
+    pub prioritization_heap_share_per_sender_in_percentage: u8,
+    // connections are opened that many slots before the leader enters the fanout, 0 to disable
+    #[serde(default = "QuicConnectionParameters::default_prewarm_slots")]
+    pub prewarm_slots: u64,
+}
+
+impl QuicConnectionParameters {
+    fn default_prioritization_heap_share_per_sender() -> u8 {
+        25
+    }
+
+    fn default_prewarm_slots() -> u64 {
+        8
+    }
 }
 
 impl Default for QuicConnectionParameters {
@@ -123,24 +140,96 @@ impl Default for QuicConnectionParameters {
             number_of_transactions_per_unistream: 1,
             unistreams_to_create_new_connection_in_percentage: 10,
             prioritization_heap_size: None,
+            prioritization_heap_share_per_sender_in_percentage:
+                Self::default_prioritization_heap_share_per_sender(),
+            prewarm_slots: Self::default_prewarm_slots(),
+        }
+    }
+}
+
+/// Local addresses and ports the outgoing quic endpoints bind to
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct QuicBindConfig {
+    // endpoints are spread over the addresses
+    pub addresses: Vec<IpAddr>,
+    // start inclusive, end exclusive
+    pub port_range: (u16, u16),
+}
+
+impl Default for QuicBindConfig {
+    fn default() -> Self {
+        Self {
+            addresses: vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)],
+            port_range: (8000, 10000),
         }
     }
 }
 
+impl QuicBindConfig {
+    /// address advertised in the client certificates
+    pub fn certificate_address(&self) -> IpAddr {
+        self.addresses
+            .first()
+            .copied()
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    }
+
+    /// first configured address able to reach `remote`, the unspecified address of its family otherwise
+    pub fn address_for(&self, remote: &SocketAddr) -> IpAddr {
+        self.addresses
+            .iter()
+            .copied()
+            .find(|address| QuicConnectionUtils::can_reach(*address, remote))
+            .unwrap_or(match remote {
+                SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            })
+    }
+}
+
 pub struct QuicConnectionUtils {}
 
 impl QuicConnectionUtils {
-    pub fn create_endpoint(certificate: rustls::Certificate, key: rustls::PrivateKey) -> Endpoint {
+    /// an ipv6 socket bound to the unspecified address is dual stack and also reaches ipv4 peers
+    pub fn can_reach(local: IpAddr, remote: &SocketAddr) -> bool {
+        match local {
+            IpAddr::V4(_) => remote.is_ipv4(),
+            IpAddr::V6(local) => remote.is_ipv6() || local.is_unspecified(),
+        }
+    }
+
+    // solana_net_utils::bind_in_range only creates ipv4 sockets
+    pub fn bind_in_range(address: IpAddr, port_range: (u16, u16)) -> std::io::Result<UdpSocket> {
+        let mut last_error = None;
+        for port in port_range.0..port_range.1 {
+            match UdpSocket::bind(SocketAddr::new(address, port)) {
+                Ok(socket) => return Ok(socket),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("empty port range {port_range:?}"),
+            )
+        }))
+    }
+
+    pub fn create_endpoint(
+        certificate: rustls::Certificate,
+        key: rustls::PrivateKey,
+        bind_address: IpAddr,
+        port_range: (u16, u16),
+    ) -> Endpoint {
         const DATAGRAM_RECEIVE_BUFFER_SIZE: usize = 64 * 1024 * 1024;
         const DATAGRAM_SEND_BUFFER_SIZE: usize = 64 * 1024 * 1024;
         const INITIAL_MAXIMUM_TRANSMISSION_UNIT: u16 = MINIMUM_MAXIMUM_TRANSMISSION_UNIT;
         const MINIMUM_MAXIMUM_TRANSMISSION_UNIT: u16 = 1280;
 
         let mut endpoint = {
-            let client_socket =
-                solana_net_utils::bind_in_range(IpAddr::V4(Ipv4Addr::UNSPECIFIED), (8000, 10000))
-                    .expect("create_endpoint bind_in_range")
-                    .1;
+            let client_socket = Self::bind_in_range(bind_address, port_range)
+                .expect("create_endpoint bind_in_range");
             let mut config = EndpointConfig::default();
             config
                 .max_udp_payload_size(MINIMUM_MAXIMUM_TRANSMISSION_UNIT)
@@ -416,3 +505,74 @@ impl rustls::client::ServerCertVerifier for SkipServerVerification {
         Ok(rustls::client::ServerCertVerified::assertion())
     }
 }
+
+/// Accepts only the server authenticating with the certificate of the expected identity
+pub struct PinnedServerVerification {
+    identity: Pubkey,
+}
+
+impl PinnedServerVerification {
+    pub fn new(identity: Pubkey) -> Arc<Self> {
+        Arc::new(Self { identity })
+    }
+}
+
+impl rustls::client::ServerCertVerifier for PinnedServerVerification {
+    // the handshake signature proves the server owns the keypair of the certificate
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: std::time::SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        match get_pubkey_from_tls_certificate(end_entity) {
+            Some(identity) if identity == self.identity => {
+                Ok(rustls::client::ServerCertVerified::assertion())
+            }
+            Some(identity) => {
+                warn!(
+                    "server identity {} does not match the expected identity {}",
+                    identity, self.identity
+                );
+                Err(rustls::Error::InvalidCertificate(
+                    rustls::CertificateError::ApplicationVerificationFailure,
+                ))
+            }
+            None => Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::BadEncoding,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::client::ServerCertVerifier;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_streamer::tls_certificates::new_self_signed_tls_certificate;
+
+    #[test]
+    fn pinned_server_verification() {
+        let proxy = Keypair::new();
+        let verification = PinnedServerVerification::new(proxy.pubkey());
+        let verify = |keypair: &Keypair| {
+            let (certificate, _) =
+                new_self_signed_tls_certificate(keypair, IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+                    .unwrap();
+            verification.verify_server_cert(
+                &certificate,
+                &[],
+                &rustls::ServerName::try_from("localhost").unwrap(),
+                &mut std::iter::empty(),
+                &[],
+                std::time::SystemTime::now(),
+            )
+        };
+        assert!(verify(&proxy).is_ok());
+        assert!(verify(&Keypair::new()).is_err());
+    }
+}
@@ -0,0 +1,514 @@
+// Tracks which leaders include the transactions sent by lite-rpc
+// Transactions seen in confirmed blocks are matched back to the leader which produced the block
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use dashmap::DashMap;
+use log::{debug, warn};
+use prometheus::{opts, register_gauge_vec, GaugeVec};
+use serde::{Deserialize, Serialize};
+use solana_lite_rpc_core::{
+    structures::{
+        leader_data::LeaderData, produced_block::ProducedBlock,
+        transaction_sent_info::TransactionToSend,
+    },
+    types::BlockStream,
+    AnyhowJoinHandle,
+};
+use solana_sdk::{pubkey::Pubkey, signature::Signature, slot_history::Slot};
+use tokio::sync::broadcast::error::RecvError;
+
+lazy_static::lazy_static! {
+    static ref LEADER_INCLUSION_RATE: GaugeVec =
+        register_gauge_vec!(opts!("literpc_leader_inclusion_rate", "Share of the pending transactions included by a leader in its blocks"), &["identity"]).unwrap();
+    static ref LEADER_SKIP_RATE: GaugeVec =
+        register_gauge_vec!(opts!("literpc_leader_skip_rate", "Share of the slots skipped by a leader"), &["identity"]).unwrap();
+}
+
+// transactions not seen after that many slots have expired
+const MAX_PENDING_SLOTS: u64 = 300;
+// leader schedule kept to attribute skipped slots
+const SCHEDULE_HISTORY_SLOTS: u64 = 1024;
+// minimum number of observations before a leader can be considered unreliable
+const MIN_SLOTS_SCORED: u64 = 8;
+const MIN_TRANSACTIONS_SCORED: u64 = 32;
+const MAX_SKIP_RATE: f64 = 0.25;
+// leaders including less than this fraction of the average inclusion rate are unreliable
+const MIN_RELATIVE_INCLUSION_RATE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct LeaderStats {
+    produced_slots: u64,
+    skipped_slots: u64,
+    // transactions included in blocks of the leader
+    landed_transactions: u64,
+    // transactions sent to the leader but not included in its window
+    missed_transactions: u64,
+    landing_latency_slots: u64,
+}
+
+impl LeaderStats {
+    fn skip_rate(&self) -> f64 {
+        let slots = self.produced_slots + self.skipped_slots;
+        if slots == 0 {
+            0.0
+        } else {
+            self.skipped_slots as f64 / slots as f64
+        }
+    }
+
+    fn inclusion_rate(&self) -> Option<f64> {
+        let transactions = self.landed_transactions + self.missed_transactions;
+        (transactions > 0).then(|| self.landed_transactions as f64 / transactions as f64)
+    }
+}
+
+/// Delivery score of a leader
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderScore {
+    pub identity: String,
+    pub produced_slots: u64,
+    pub skipped_slots: u64,
+    pub landed_transactions: u64,
+    pub missed_transactions: u64,
+    pub skip_rate: f64,
+    pub inclusion_rate: Option<f64>,
+    // slots between the first send and the block including the transaction
+    pub average_landing_latency_slots: Option<f64>,
+    pub unreliable: bool,
+}
+
+struct PendingTransaction {
+    // slots of the leaders the transaction was sent to, starting at the slot it was sent first
+    targeted_slots: RangeInclusive<Slot>,
+    // leaders which produced a targeted block without the transaction, in their current window
+    passed_over_by: Vec<Pubkey>,
+}
+
+// the indexes may still list transactions which landed, they are skipped when looked up
+#[derive(Default)]
+struct PendingTransactions {
+    transactions: HashMap<Signature, PendingTransaction>,
+    by_targeted_slot: BTreeMap<Slot, Vec<Signature>>,
+    by_first_slot: BTreeMap<Slot, Vec<Signature>>,
+    // transactions passed over by a leader whose window is not over yet
+    by_passing_leader: HashMap<Pubkey, Vec<Signature>>,
+}
+
+impl PendingTransactions {
+    fn insert(&mut self, signature: Signature, targeted_slots: RangeInclusive<Slot>) {
+        if self.transactions.contains_key(&signature) {
+            return;
+        }
+        self.by_first_slot
+            .entry(*targeted_slots.start())
+            .or_default()
+            .push(signature);
+        for slot in targeted_slots.clone() {
+            self.by_targeted_slot
+                .entry(slot)
+                .or_default()
+                .push(signature);
+        }
+        self.transactions.insert(
+            signature,
+            PendingTransaction {
+                targeted_slots,
+                passed_over_by: vec![],
+            },
+        );
+    }
+
+    // returns the leaders which missed a transaction
+    fn on_block(&mut self, slot: Slot, leader: Pubkey) -> Vec<Pubkey> {
+        let mut missed_by = vec![];
+        // a block of another leader ends the window of the leaders which passed over a transaction
+        let passing_leaders = self
+            .by_passing_leader
+            .keys()
+            .filter(|x| **x != leader)
+            .copied()
+            .collect::<Vec<_>>();
+        for passing_leader in passing_leaders {
+            for signature in self.by_passing_leader.remove(&passing_leader).unwrap() {
+                let Some(pending) = self.transactions.get_mut(&signature) else {
+                    continue;
+                };
+                let nb_passed_over = pending.passed_over_by.len();
+                pending.passed_over_by.retain(|x| *x != passing_leader);
+                if pending.passed_over_by.len() < nb_passed_over {
+                    missed_by.push(passing_leader);
+                }
+            }
+        }
+
+        let expired_slots = self
+            .by_first_slot
+            .range(..slot.saturating_sub(MAX_PENDING_SLOTS))
+            .map(|(slot, _)| *slot)
+            .collect::<Vec<_>>();
+        for expired_slot in expired_slots {
+            for signature in self.by_first_slot.remove(&expired_slot).unwrap() {
+                if let Some(mut pending) = self.transactions.remove(&signature) {
+                    missed_by.append(&mut pending.passed_over_by);
+                }
+            }
+        }
+        self.by_targeted_slot = self
+            .by_targeted_slot
+            .split_off(&slot.saturating_sub(MAX_PENDING_SLOTS));
+
+        for signature in self.by_targeted_slot.remove(&slot).unwrap_or_default() {
+            let Some(pending) = self.transactions.get_mut(&signature) else {
+                continue;
+            };
+            if !pending.passed_over_by.contains(&leader) {
+                pending.passed_over_by.push(leader);
+                self.by_passing_leader
+                    .entry(leader)
+                    .or_default()
+                    .push(signature);
+            }
+        }
+        missed_by
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct LeaderScoreTracker {
+    sent_transactions: Arc<Mutex<PendingTransactions>>,
+    schedule: Arc<DashMap<Slot, Pubkey>>,
+    stats: Arc<DashMap<Pubkey, LeaderStats>>,
+}
+
+impl LeaderScoreTracker {
+    pub fn record_sent(
+        &self,
+        transaction: &TransactionToSend,
+        targeted_slots: RangeInclusive<Slot>,
+    ) {
+        let mut sent_transactions = self.sent_transactions.lock().unwrap();
+        for tx in transaction.transactions() {
+            sent_transactions.insert(tx.signature, targeted_slots.clone());
+        }
+    }
+
+    fn record_missed(&self, leaders: impl IntoIterator<Item = Pubkey>) {
+        for leader in leaders {
+            self.stats.entry(leader).or_default().missed_transactions += 1;
+        }
+    }
+
+    pub fn record_schedule(&self, leaders: &[LeaderData]) {
+        for leader in leaders {
+            self.schedule.insert(leader.leader_slot, leader.pubkey);
+        }
+    }
+
+    fn leader_of(&self, block: &ProducedBlock) -> Option<Pubkey> {
+        self.schedule
+            .get(&block.slot)
+            .map(|leader| *leader)
+            .or_else(|| block.leader_id.as_ref()?.parse().ok())
+    }
+
+    pub fn on_block(&self, block: &ProducedBlock) {
+        let Some(leader) = self.leader_of(block) else {
+            debug!("no leader known for slot {}", block.slot);
+            return;
+        };
+
+        for skipped_slot in block.parent_slot + 1..block.slot {
+            if let Some(skipped_leader) = self.schedule.get(&skipped_slot) {
+                self.stats.entry(*skipped_leader).or_default().skipped_slots += 1;
+            }
+        }
+
+        let mut stats = LeaderStats {
+            produced_slots: 1,
+            ..Default::default()
+        };
+        let mut missed_by = vec![];
+        {
+            let mut sent_transactions = self.sent_transactions.lock().unwrap();
+            for tx in &block.transactions {
+                if let Some(pending) = sent_transactions.transactions.remove(&tx.signature) {
+                    stats.landed_transactions += 1;
+                    stats.landing_latency_slots +=
+                        block.slot.saturating_sub(*pending.targeted_slots.start());
+                    missed_by.extend(pending.passed_over_by.into_iter().filter(|x| *x != leader));
+                }
+            }
+            missed_by.append(&mut sent_transactions.on_block(block.slot, leader));
+        }
+        self.record_missed(missed_by);
+        self.schedule
+            .retain(|slot, _| *slot + SCHEDULE_HISTORY_SLOTS >= block.slot);
+
+        let mut leader_stats = self.stats.entry(leader).or_default();
+        leader_stats.produced_slots += stats.produced_slots;
+        leader_stats.landed_transactions += stats.landed_transactions;
+        leader_stats.missed_transactions += stats.missed_transactions;
+        leader_stats.landing_latency_slots += stats.landing_latency_slots;
+        let leader_stats = *leader_stats;
+
+        let identity = leader.to_string();
+        LEADER_SKIP_RATE
+            .with_label_values(&[&identity])
+            .set(leader_stats.skip_rate());
+        if let Some(inclusion_rate) = leader_stats.inclusion_rate() {
+            LEADER_INCLUSION_RATE
+                .with_label_values(&[&identity])
+                .set(inclusion_rate);
+        }
+    }
+
+    /// drops the scores of the leaders which are not in the `scheduled` leaders anymore
+    pub fn retain_scheduled(&self, scheduled: &HashSet<Pubkey>) {
+        self.stats.retain(|identity, _| {
+            let keep = scheduled.contains(identity);
+            if !keep {
+                let identity = identity.to_string();
+                let _ = LEADER_SKIP_RATE.remove_label_values(&[&identity]);
+                let _ = LEADER_INCLUSION_RATE.remove_label_values(&[&identity]);
+            }
+            keep
+        });
+    }
+
+    fn average_inclusion_rate(&self) -> Option<f64> {
+        let (landed, total) = self.stats.iter().fold((0, 0), |(landed, total), stats| {
+            (
+                landed + stats.landed_transactions,
+                total + stats.landed_transactions + stats.missed_transactions,
+            )
+        });
+        (total > 0).then(|| landed as f64 / total as f64)
+    }
+
+    fn is_unreliable_with(&self, stats: &LeaderStats, average_inclusion_rate: Option<f64>) -> bool {
+        let skips_slots = stats.produced_slots + stats.skipped_slots >= MIN_SLOTS_SCORED
+            && stats.skip_rate() > MAX_SKIP_RATE;
+        let drops_transactions = stats.landed_transactions + stats.missed_transactions
+            >= MIN_TRANSACTIONS_SCORED
+            && matches!(
+                (stats.inclusion_rate(), average_inclusion_rate),
+                (Some(rate), Some(average)) if rate < average * MIN_RELATIVE_INCLUSION_RATE
+            );
+        skips_slots || drops_transactions
+    }
+
+    /// leaders which skip their slots or do not include our transactions
+    pub fn unreliable_leaders(&self, leaders: &[Pubkey]) -> Vec<Pubkey> {
+        let average_inclusion_rate = self.average_inclusion_rate();
+        leaders
+            .iter()
+            .filter(|leader| {
+                self.stats.get(leader).is_some_and(|stats| {
+                    self.is_unreliable_with(stats.value(), average_inclusion_rate)
+                })
+            })
+            .copied()
+            .collect()
+    }
+
+    pub fn scores(&self) -> Vec<LeaderScore> {
+        let average_inclusion_rate = self.average_inclusion_rate();
+        let mut scores = self
+            .stats
+            .iter()
+            .map(|entry| {
+                let stats = entry.value();
+                LeaderScore {
+                    identity: entry.key().to_string(),
+                    produced_slots: stats.produced_slots,
+                    skipped_slots: stats.skipped_slots,
+                    landed_transactions: stats.landed_transactions,
+                    missed_transactions: stats.missed_transactions,
+                    skip_rate: stats.skip_rate(),
+                    inclusion_rate: stats.inclusion_rate(),
+                    average_landing_latency_slots: (stats.landed_transactions > 0).then(|| {
+                        stats.landing_latency_slots as f64 / stats.landed_transactions as f64
+                    }),
+                    unreliable: self.is_unreliable_with(stats, average_inclusion_rate),
+                }
+            })
+            .collect::<Vec<_>>();
+        scores.sort_by(|a, b| a.identity.cmp(&b.identity));
+        scores
+    }
+
+    pub fn start(&self, mut blocks: BlockStream) -> AnyhowJoinHandle {
+        let this = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let block = match blocks.recv().await {
+                    Ok(block) => block,
+                    Err(RecvError::Lagged(nb)) => {
+                        warn!("leader score tracker lagged {nb} blocks");
+                        continue;
+                    }
+                    Err(e) => return Err(e).context("leader score tracker block stream"),
+                };
+                // every block is seen once at confirmed commitment
+                if block.commitment_config.is_confirmed() {
+                    this.on_block(&block);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_lite_rpc_core::structures::{
+        produced_block::{ProducedBlockInner, TransactionInfo},
+        transaction_sent_info::SentTransactionInfo,
+    };
+    use solana_sdk::{commitment_config::CommitmentConfig, hash::Hash, message::VersionedMessage};
+
+    use super::*;
+
+    fn sent(signature: Signature) -> TransactionToSend {
+        SentTransactionInfo {
+            signature,
+            slot: 1,
+            transaction: Arc::new(vec![]),
+            last_valid_block_height: 300,
+            prioritization_fee: 0,
+            priority_score: 0,
+            fee_payer: Pubkey::new_unique(),
+        }
+        .into()
+    }
+
+    fn block(slot: Slot, parent_slot: Slot, signatures: &[Signature]) -> ProducedBlock {
+        let transactions = signatures
+            .iter()
+            .map(|signature| TransactionInfo {
+                signature: *signature,
+                is_vote: false,
+                err: None,
+                cu_requested: None,
+                prioritization_fees: None,
+                cu_consumed: None,
+                recent_blockhash: Hash::default(),
+                message: VersionedMessage::Legacy(Default::default()),
+                writable_accounts: vec![],
+                readable_accounts: vec![],
+                address_lookup_tables: vec![],
+            })
+            .collect();
+        ProducedBlock::new(
+            ProducedBlockInner {
+                transactions,
+                leader_id: None,
+                blockhash: Hash::default(),
+                block_height: slot,
+                slot,
+                parent_slot,
+                block_time: 0,
+                previous_blockhash: Hash::default(),
+                rewards: None,
+            },
+            CommitmentConfig::confirmed(),
+        )
+    }
+
+    #[test]
+    fn scores_skips_and_inclusions() {
+        let tracker = LeaderScoreTracker::default();
+        let good = Pubkey::new_unique();
+        let skipper = Pubkey::new_unique();
+        let next = Pubkey::new_unique();
+        tracker.record_schedule(
+            &(100..116)
+                .map(|leader_slot| LeaderData {
+                    leader_slot,
+                    pubkey: match leader_slot {
+                        ..=103 => good,
+                        104..=111 => skipper,
+                        _ => next,
+                    },
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let landed = Signature::new_unique();
+        let pending = Signature::new_unique();
+        tracker.record_sent(&sent(landed), 99..=111);
+        tracker.record_sent(&sent(pending), 99..=111);
+
+        tracker.on_block(&block(100, 99, &[landed]));
+        // slots 104 to 111 of the second leader are skipped
+        tracker.on_block(&block(112, 100, &[]));
+
+        let scores = tracker.scores();
+        let good_score = scores
+            .iter()
+            .find(|score| score.identity == good.to_string())
+            .unwrap();
+        assert_eq!(good_score.produced_slots, 1);
+        assert_eq!(good_score.skipped_slots, 3);
+        assert_eq!(good_score.landed_transactions, 1);
+        assert_eq!(good_score.missed_transactions, 1);
+        assert_eq!(good_score.average_landing_latency_slots, Some(1.0));
+
+        assert_eq!(tracker.unreliable_leaders(&[good, skipper]), vec![skipper]);
+    }
+
+    #[test]
+    fn misses_only_count_targeted_leaders_after_their_window() {
+        let tracker = LeaderScoreTracker::default();
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+        let untargeted = Pubkey::new_unique();
+        tracker.record_schedule(
+            &(100..112)
+                .map(|leader_slot| LeaderData {
+                    leader_slot,
+                    pubkey: match leader_slot {
+                        ..=103 => first,
+                        104..=107 => second,
+                        _ => untargeted,
+                    },
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let late = Signature::new_unique();
+        let dropped = Signature::new_unique();
+        tracker.record_sent(&sent(late), 100..=107);
+        tracker.record_sent(&sent(dropped), 100..=107);
+
+        // the first leader includes the transaction in a later block of its window
+        tracker.on_block(&block(100, 99, &[]));
+        tracker.on_block(&block(101, 100, &[late]));
+        for slot in 104..=109 {
+            tracker.on_block(&block(slot, slot - 1, &[]));
+        }
+
+        let scores = tracker.scores();
+        let score_of = |leader: Pubkey| {
+            scores
+                .iter()
+                .find(|score| score.identity == leader.to_string())
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(score_of(first).landed_transactions, 1);
+        assert_eq!(score_of(first).missed_transactions, 1);
+        assert_eq!(score_of(second).missed_transactions, 1);
+        assert_eq!(score_of(untargeted).missed_transactions, 0);
+
+        tracker.retain_scheduled(&HashSet::from([first]));
+        assert_eq!(tracker.scores().len(), 1);
+        assert_eq!(tracker.scores()[0].identity, first.to_string());
+    }
+}
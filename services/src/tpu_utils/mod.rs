@@ -1,6 +1,11 @@
 pub mod tpu_service;
 
+pub mod leader_scores;
+pub mod proxy_delivery_reports;
+pub mod proxy_group;
 pub mod quic_proxy_connection_manager;
 pub mod quinn_auto_reconnect;
+pub mod slot_clock;
+pub mod tpu_address_health;
 pub mod tpu_connection_manager;
 pub mod tpu_connection_path;
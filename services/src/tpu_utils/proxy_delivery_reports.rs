@@ -0,0 +1,149 @@
+// Delivery reports of the quic forward proxy
+// The proxy tells for each transaction whether it reached the tpu of each leader
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use solana_lite_rpc_core::structures::{
+    proxy_request_format::{DeliveryReport, DeliveryStatus},
+    transaction_sent_info::TransactionToSend,
+};
+use solana_sdk::signature::Signature;
+
+lazy_static::lazy_static! {
+    static ref PROXY_DELIVERY_RESULTS: IntCounterVec =
+        register_int_counter_vec!(opts!("literpc_proxy_delivery_results", "Delivery results reported by the quic proxy per transaction and tpu"), &["status"]).unwrap();
+}
+
+// reports of transactions not forwarded again are forgotten after this delay
+const REPORT_TTL: Duration = Duration::from_secs(120);
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct ProxyDeliveryReports {
+    // best status over the tpus of the last forwarding of the transaction
+    statuses: Arc<DashMap<Signature, (DeliveryStatus, Instant)>>,
+    last_cleanup: Arc<Mutex<Instant>>,
+}
+
+impl Default for ProxyDeliveryReports {
+    fn default() -> Self {
+        Self {
+            statuses: Arc::new(DashMap::new()),
+            last_cleanup: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+}
+
+impl ProxyDeliveryReports {
+    /// the transaction is sent again, a new report is pending
+    pub fn forwarded(&self, transaction: &TransactionToSend) {
+        for tx in transaction.transactions() {
+            self.statuses.remove(&tx.signature);
+        }
+    }
+
+    pub fn record(&self, report: &DeliveryReport) {
+        // a transaction is delivered if any of the tpus got it
+        let mut statuses: HashMap<Signature, DeliveryStatus> = HashMap::new();
+        for result in &report.results {
+            PROXY_DELIVERY_RESULTS
+                .with_label_values(&[&result.status.to_string()])
+                .inc();
+            statuses
+                .entry(result.signature)
+                .and_modify(|status| *status = (*status).min(result.status))
+                .or_insert(result.status);
+        }
+        let now = Instant::now();
+        for (signature, status) in statuses {
+            self.statuses.insert(signature, (status, now));
+        }
+
+        if let Ok(mut last_cleanup) = self.last_cleanup.try_lock() {
+            if last_cleanup.elapsed() > CLEANUP_INTERVAL {
+                self.statuses
+                    .retain(|_, (_, reported_at)| reported_at.elapsed() < REPORT_TTL);
+                *last_cleanup = now;
+            }
+        }
+    }
+
+    pub fn get(&self, signature: &Signature) -> Option<DeliveryStatus> {
+        self.statuses.get(signature).map(|entry| entry.0)
+    }
+
+    /// the proxy reported that some transaction reached none of the tpus
+    pub fn is_undelivered(&self, transaction: &TransactionToSend) -> bool {
+        transaction.transactions().iter().any(|tx| {
+            self.get(&tx.signature)
+                .is_some_and(|status| status != DeliveryStatus::Sent)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+
+    use solana_lite_rpc_core::structures::{
+        proxy_request_format::{TpuNode, TxDeliveryResult},
+        transaction_sent_info::SentTransactionInfo,
+    };
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    fn result(signature: Signature, tpu: &str, status: DeliveryStatus) -> TxDeliveryResult {
+        TxDeliveryResult {
+            signature,
+            tpu_node: TpuNode {
+                tpu_socket_addr: SocketAddr::from_str(tpu).unwrap(),
+                identity_tpunode: Pubkey::new_unique(),
+            },
+            status,
+        }
+    }
+
+    fn sent(signature: Signature) -> TransactionToSend {
+        SentTransactionInfo {
+            signature,
+            slot: 1,
+            transaction: Arc::new(vec![]),
+            last_valid_block_height: 300,
+            prioritization_fee: 0,
+            priority_score: 0,
+            fee_payer: Pubkey::new_unique(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn delivered_if_any_tpu_got_it() {
+        let reports = ProxyDeliveryReports::default();
+        let delivered = Signature::new_unique();
+        let undelivered = Signature::new_unique();
+        reports.record(&DeliveryReport::new(vec![
+            result(delivered, "10.0.0.1:8009", DeliveryStatus::SendFailed),
+            result(delivered, "10.0.0.2:8009", DeliveryStatus::Sent),
+            result(undelivered, "10.0.0.1:8009", DeliveryStatus::SendFailed),
+            result(undelivered, "10.0.0.2:8009", DeliveryStatus::TimedOut),
+        ]));
+
+        assert_eq!(reports.get(&delivered), Some(DeliveryStatus::Sent));
+        assert_eq!(reports.get(&undelivered), Some(DeliveryStatus::SendFailed));
+        assert!(!reports.is_undelivered(&sent(delivered)));
+        assert!(reports.is_undelivered(&sent(undelivered)));
+
+        // pending again until the next report
+        reports.forwarded(&sent(undelivered));
+        assert_eq!(reports.get(&undelivered), None);
+        assert!(!reports.is_undelivered(&sent(undelivered)));
+    }
+}
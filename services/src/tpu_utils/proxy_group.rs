@@ -0,0 +1,293 @@
+// Group of quic forward proxies
+// Spreads the transactions over the healthy proxies and fails over when one of them goes down
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+};
+
+use futures::future::join_all;
+use itertools::Itertools;
+use log::{info, warn};
+use prometheus::{core::GenericGauge, opts, register_int_counter, register_int_gauge, IntCounter};
+use solana_sdk::pubkey::Pubkey;
+
+use super::{
+    quic_proxy_connection_manager::TpuNode,
+    quinn_auto_reconnect::AutoReconnect,
+    tpu_connection_path::{ForwardProxy, ProxyBalancing},
+};
+
+lazy_static::lazy_static! {
+    static ref NB_HEALTHY_PROXIES: GenericGauge<prometheus::core::AtomicI64> =
+        register_int_gauge!(opts!("literpc_quic_proxies_healthy", "Number of healthy quic forward proxies")).unwrap();
+    static ref NB_PROXY_FAILOVERS: IntCounter =
+        register_int_counter!(opts!("literpc_quic_proxy_failovers", "Number of times a quic forward proxy was marked unhealthy")).unwrap();
+}
+
+// failed sends or health checks in a row before the proxy is skipped
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+pub struct ProxyGroupMember {
+    pub proxy: ForwardProxy,
+    pub connection: AutoReconnect,
+    consecutive_failures: AtomicU32,
+}
+
+impl ProxyGroupMember {
+    fn is_healthy(&self) -> bool {
+        self.consecutive_failures.load(Ordering::Relaxed) < MAX_CONSECUTIVE_FAILURES
+    }
+}
+
+pub struct ProxyGroup {
+    members: Vec<ProxyGroupMember>,
+    balancing: ProxyBalancing,
+    round_robin: AtomicU64,
+}
+
+impl ProxyGroup {
+    pub fn new(members: Vec<(ForwardProxy, AutoReconnect)>, balancing: ProxyBalancing) -> Self {
+        assert!(!members.is_empty(), "no quic proxy configured");
+        NB_HEALTHY_PROXIES.set(members.len() as i64);
+        Self {
+            members: members
+                .into_iter()
+                .map(|(proxy, connection)| ProxyGroupMember {
+                    proxy,
+                    connection,
+                    consecutive_failures: AtomicU32::new(0),
+                })
+                .collect(),
+            balancing,
+            round_robin: AtomicU64::new(0),
+        }
+    }
+
+    pub fn member(&self, index: usize) -> &ProxyGroupMember {
+        &self.members[index]
+    }
+
+    pub fn has_healthy_proxy(&self) -> bool {
+        self.members.iter().any(ProxyGroupMember::is_healthy)
+    }
+
+    fn update_healthy_gauge(&self) {
+        NB_HEALTHY_PROXIES.set(self.members.iter().filter(|m| m.is_healthy()).count() as i64);
+    }
+
+    pub fn record_success(&self, index: usize) {
+        let member = &self.members[index];
+        if member.consecutive_failures.swap(0, Ordering::Relaxed) >= MAX_CONSECUTIVE_FAILURES {
+            info!("Quic proxy {} is healthy again", member.proxy);
+            self.update_healthy_gauge();
+        }
+    }
+
+    pub fn record_failure(&self, index: usize) {
+        let member = &self.members[index];
+        if member.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1
+            == MAX_CONSECUTIVE_FAILURES
+        {
+            warn!("Quic proxy {} is unhealthy - failing over", member.proxy);
+            NB_PROXY_FAILOVERS.inc();
+            self.update_healthy_gauge();
+        }
+    }
+
+    // healthy proxies, all of them if none is healthy
+    fn candidates(&self) -> Vec<usize> {
+        let healthy = (0..self.members.len())
+            .filter(|index| self.members[*index].is_healthy())
+            .collect_vec();
+        if healthy.is_empty() {
+            (0..self.members.len()).collect_vec()
+        } else {
+            healthy
+        }
+    }
+
+    /// proxies a batch is sent through, with the tpu nodes each of them forwards to
+    pub fn assign(&self, tpu_nodes: &[TpuNode]) -> Vec<(usize, Vec<TpuNode>)> {
+        let candidates = self.candidates();
+        match self.balancing {
+            ProxyBalancing::WeightedRoundRobin => {
+                let total_weight: u64 = candidates
+                    .iter()
+                    .map(|index| self.members[*index].proxy.weight.max(1) as u64)
+                    .sum();
+                let mut slot = self.round_robin.fetch_add(1, Ordering::Relaxed) % total_weight;
+                let chosen = candidates
+                    .iter()
+                    .copied()
+                    .find(|index| {
+                        let weight = self.members[*index].proxy.weight.max(1) as u64;
+                        if slot < weight {
+                            return true;
+                        }
+                        slot -= weight;
+                        false
+                    })
+                    .expect("slot within the total weight");
+                vec![(chosen, tpu_nodes.to_vec())]
+            }
+            ProxyBalancing::ShardByLeader => tpu_nodes
+                .iter()
+                .into_group_map_by(|tpu_node| {
+                    // rendezvous hashing, only the leaders of a failed proxy move
+                    *candidates
+                        .iter()
+                        .max_by(|a, b| {
+                            let score_a =
+                                rendezvous_score(&tpu_node.tpu_identity, &self.members[**a].proxy);
+                            let score_b =
+                                rendezvous_score(&tpu_node.tpu_identity, &self.members[**b].proxy);
+                            score_a.total_cmp(&score_b)
+                        })
+                        .expect("at least one proxy")
+                })
+                .into_iter()
+                .map(|(index, tpu_nodes)| (index, tpu_nodes.into_iter().copied().collect_vec()))
+                .sorted_by_key(|(index, _)| *index)
+                .collect_vec(),
+        }
+    }
+
+    /// healthy proxy to resend a batch through which the proxies in `failed` did not take
+    pub fn retry_candidate(&self, failed: &[usize]) -> Option<usize> {
+        (0..self.members.len())
+            .filter(|index| !failed.contains(index) && self.members[*index].is_healthy())
+            .max_by_key(|index| self.members[*index].proxy.weight)
+    }
+
+    /// connect to every proxy, restores the unhealthy ones which are reachable again
+    pub async fn check_health(&self) {
+        let checks = self
+            .members
+            .iter()
+            .enumerate()
+            .map(|(index, member)| async move {
+                if !member.is_healthy() {
+                    member.connection.reset_permanent_error().await;
+                }
+                match member.connection.refresh_and_get().await {
+                    Ok(_) => self.record_success(index),
+                    Err(_) => self.record_failure(index),
+                }
+            });
+        join_all(checks).await;
+    }
+}
+
+// weighted rendezvous hashing score of a proxy for a leader
+fn rendezvous_score(leader: &Pubkey, proxy: &ForwardProxy) -> f64 {
+    let mut hasher = DefaultHasher::new();
+    leader.hash(&mut hasher);
+    proxy.address.hash(&mut hasher);
+    // uniform in (0, 1) from the 53 bits a f64 holds
+    let unit = ((hasher.finish() >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+    -(proxy.weight.max(1) as f64) / unit.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+
+    use quinn::Endpoint;
+
+    use super::*;
+
+    fn group(balancing: ProxyBalancing, weights: &[u32]) -> ProxyGroup {
+        let endpoint = Endpoint::client(SocketAddr::from_str("127.0.0.1:0").unwrap()).unwrap();
+        let members = weights
+            .iter()
+            .enumerate()
+            .map(|(index, weight)| {
+                let address =
+                    SocketAddr::from_str(&format!("127.0.0.1:{}", 20_000 + index)).unwrap();
+                (
+                    ForwardProxy {
+                        address,
+                        identity: None,
+                        weight: *weight,
+                    },
+                    AutoReconnect::new(endpoint.clone(), address),
+                )
+            })
+            .collect();
+        ProxyGroup::new(members, balancing)
+    }
+
+    fn tpu_nodes(count: usize) -> Vec<TpuNode> {
+        (0..count)
+            .map(|index| TpuNode {
+                tpu_identity: Pubkey::new_unique(),
+                tpu_address: SocketAddr::from_str(&format!("10.0.0.{}:8009", index + 1)).unwrap(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn weighted_round_robin_fails_over() {
+        let group = group(ProxyBalancing::WeightedRoundRobin, &[2, 1]);
+        let nodes = tpu_nodes(2);
+        let chosen = (0..6).map(|_| group.assign(&nodes)[0].0).collect_vec();
+        assert_eq!(chosen, vec![0, 0, 1, 0, 0, 1]);
+
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            group.record_failure(0);
+        }
+        assert!(group.has_healthy_proxy());
+        assert!((0..3).all(|_| group.assign(&nodes)[0].0 == 1));
+
+        group.record_success(0);
+        assert!((0..6).any(|_| group.assign(&nodes)[0].0 == 0));
+    }
+
+    #[tokio::test]
+    async fn failed_batch_is_retried_on_another_healthy_proxy() {
+        let group = group(ProxyBalancing::WeightedRoundRobin, &[1, 3, 2]);
+        assert_eq!(group.retry_candidate(&[0]), Some(1));
+        assert_eq!(group.retry_candidate(&[1]), Some(2));
+
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            group.record_failure(2);
+        }
+        assert_eq!(group.retry_candidate(&[1]), Some(0));
+        assert_eq!(group.retry_candidate(&[0, 1]), None);
+    }
+
+    #[tokio::test]
+    async fn shard_by_leader_only_moves_leaders_of_failed_proxy() {
+        let group = group(ProxyBalancing::ShardByLeader, &[1, 1, 1]);
+        let nodes = tpu_nodes(30);
+        let proxy_of = |assignment: Vec<(usize, Vec<TpuNode>)>| {
+            assignment
+                .into_iter()
+                .flat_map(|(index, nodes)| {
+                    nodes
+                        .into_iter()
+                        .map(move |node| (node.tpu_identity, index))
+                })
+                .collect::<std::collections::HashMap<_, _>>()
+        };
+        let before = proxy_of(group.assign(&nodes));
+        assert_eq!(before.len(), nodes.len());
+        // stable across batches
+        assert_eq!(before, proxy_of(group.assign(&nodes)));
+
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            group.record_failure(1);
+        }
+        let after = proxy_of(group.assign(&nodes));
+        for (leader, proxy) in before {
+            if proxy == 1 {
+                assert_ne!(after[&leader], 1);
+            } else {
+                assert_eq!(after[&leader], proxy);
+            }
+        }
+    }
+}
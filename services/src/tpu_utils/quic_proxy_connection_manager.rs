@@ -1,3 +1,4 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::net::{SocketAddr, UdpSocket};
 use std::sync::atomic::AtomicBool;
@@ -5,22 +6,34 @@ use std::sync::atomic::Ordering::Relaxed;
 use std::sync::Arc;
 
 use anyhow::bail;
-use solana_lite_rpc_core::structures::transaction_sent_info::SentTransactionInfo;
+use solana_lite_rpc_core::structures::transaction_sent_info::TransactionToSend;
 use std::time::Duration;
 
+use futures::future::join_all;
 use itertools::Itertools;
 use log::{debug, info, trace, warn};
-use quinn::{ClientConfig, Endpoint, EndpointConfig, TokioRuntime, TransportConfig, VarInt};
+use quinn::{
+    ClientConfig, Endpoint, EndpointConfig, RecvStream, TokioRuntime, TransportConfig, VarInt,
+};
 use solana_sdk::pubkey::Pubkey;
 
 use tokio::sync::broadcast::error::TryRecvError;
-use tokio::sync::{broadcast::Receiver, RwLock};
+use tokio::sync::{broadcast::Receiver, mpsc::UnboundedSender, RwLock};
+use tokio::time::timeout;
 
-use crate::quic_connection_utils::{QuicConnectionParameters, SkipServerVerification};
+use crate::quic_connection_utils::{
+    PinnedServerVerification, QuicBindConfig, QuicConnectionParameters, QuicConnectionUtils,
+    SkipServerVerification,
+};
 use solana_lite_rpc_core::network_utils::apply_gso_workaround;
-use solana_lite_rpc_core::structures::proxy_request_format::{TpuForwardingRequest, TxData};
+use solana_lite_rpc_core::structures::proxy_request_format::{
+    BundleData, DeliveryReport, TpuForwardingRequest, TxData,
+};
 
+use crate::tpu_utils::proxy_delivery_reports::ProxyDeliveryReports;
+use crate::tpu_utils::proxy_group::ProxyGroup;
 use crate::tpu_utils::quinn_auto_reconnect::AutoReconnect;
+use crate::tpu_utils::tpu_connection_path::{ForwardProxy, ProxyBalancing};
 
 #[derive(Clone, Copy, Debug)]
 pub struct TpuNode {
@@ -29,40 +42,96 @@ pub struct TpuNode {
 }
 
 pub struct QuicProxyConnectionManager {
-    endpoint: Endpoint,
+    proxy_group: Arc<ProxyGroup>,
+    // batches are not sent while no proxy is healthy, the tpus are reached directly then
+    // the batches the last proxy failed on are handed over to the direct connections
+    direct_fallback: Option<UnboundedSender<Vec<TransactionToSend>>>,
     simple_thread_started: AtomicBool,
-    proxy_addr: SocketAddr,
     current_tpu_nodes: Arc<RwLock<Vec<TpuNode>>>,
     exit_signal: Arc<AtomicBool>,
+    // set if the proxy is asked for delivery reports
+    delivery_reports: Option<ProxyDeliveryReports>,
 }
 
 const CHUNK_SIZE_PER_STREAM: usize = 20;
+// the proxy reports within its own timeout, this only guards against a stuck stream
+const DELIVERY_REPORT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_DELIVERY_REPORT_SIZE: usize = 10_000_000;
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
 
 impl QuicProxyConnectionManager {
     pub async fn new(
         certificate: rustls::Certificate,
         key: rustls::PrivateKey,
-        proxy_addr: SocketAddr,
+        forward_proxies: &[ForwardProxy],
+        balancing: ProxyBalancing,
+        direct_fallback: Option<UnboundedSender<Vec<TransactionToSend>>>,
+        delivery_reports: bool,
+        bind_config: Option<&QuicBindConfig>,
     ) -> Self {
-        info!("Configure Quic proxy connection manager to {}", proxy_addr);
-        let endpoint = Self::create_proxy_client_endpoint(certificate, key);
+        let members = forward_proxies
+            .iter()
+            .map(|proxy| {
+                info!("Configure Quic proxy connection manager to {}", proxy);
+                if proxy.identity.is_none() {
+                    warn!(
+                        "Quic proxy identity of {} not configured, the proxy certificate is not verified",
+                        proxy.address
+                    );
+                }
+                let client_socket = match bind_config {
+                    Some(bind_config) => QuicConnectionUtils::bind_in_range(
+                        bind_config.address_for(&proxy.address),
+                        bind_config.port_range,
+                    )
+                    .expect("create_proxy_client_endpoint bind_in_range"),
+                    None => UdpSocket::bind("[::]:0").unwrap(),
+                };
+                let endpoint = Self::create_proxy_client_endpoint(
+                    certificate.clone(),
+                    key.clone(),
+                    proxy.identity,
+                    client_socket,
+                );
+                (*proxy, AutoReconnect::new(endpoint, proxy.address))
+            })
+            .collect();
 
         Self {
-            endpoint,
+            proxy_group: Arc::new(ProxyGroup::new(members, balancing)),
+            direct_fallback,
             simple_thread_started: AtomicBool::from(false),
-            proxy_addr,
             current_tpu_nodes: Arc::new(RwLock::new(vec![])),
             exit_signal: Arc::new(AtomicBool::from(false)),
+            delivery_reports: delivery_reports.then(ProxyDeliveryReports::default),
         }
     }
 
+    pub fn delivery_reports(&self) -> Option<ProxyDeliveryReports> {
+        self.delivery_reports.clone()
+    }
+
+    pub fn has_healthy_proxy(&self) -> bool {
+        self.proxy_group.has_healthy_proxy()
+    }
+
+    // tpu nodes of the current fanout
+    pub async fn current_connections(&self) -> HashMap<Pubkey, SocketAddr> {
+        self.current_tpu_nodes
+            .read()
+            .await
+            .iter()
+            .map(|tpu_node| (tpu_node.tpu_identity, tpu_node.tpu_address))
+            .collect()
+    }
+
     pub fn signal_shutdown(&self) {
         self.exit_signal.store(true, Relaxed);
     }
 
     pub async fn update_connection(
         &self,
-        broadcast_receiver: Receiver<SentTransactionInfo>,
+        broadcast_receiver: Receiver<TransactionToSend>,
         // for duration of this slot these tpu nodes will receive the transactions
         connections_to_keep: HashMap<Pubkey, SocketAddr>,
         connection_parameters: QuicConnectionParameters,
@@ -101,25 +170,32 @@ impl QuicProxyConnectionManager {
 
         info!("Starting very simple proxy thread");
 
+        tokio::spawn(Self::check_proxy_health(
+            self.proxy_group.clone(),
+            self.exit_signal.clone(),
+        ));
+
         let exit_signal = self.exit_signal.clone();
         tokio::spawn(Self::read_transactions_and_broadcast(
             broadcast_receiver,
             self.current_tpu_nodes.clone(),
-            self.proxy_addr,
-            self.endpoint.clone(),
+            self.proxy_group.clone(),
+            self.direct_fallback.clone(),
             exit_signal,
             connection_parameters,
+            self.delivery_reports.clone(),
         ));
     }
 
     fn create_proxy_client_endpoint(
         certificate: rustls::Certificate,
         key: rustls::PrivateKey,
+        proxy_identity: Option<Pubkey>,
+        client_socket: UdpSocket,
     ) -> Endpoint {
         const ALPN_TPU_FORWARDPROXY_PROTOCOL_ID: &[u8] = b"solana-tpu-forward-proxy";
 
         let mut endpoint = {
-            let client_socket = UdpSocket::bind("[::]:0").unwrap();
             let config = EndpointConfig::default();
             Endpoint::new(config, None, client_socket, Arc::new(TokioRuntime))
                 .expect("create_endpoint quinn::Endpoint::new")
@@ -127,7 +203,10 @@ impl QuicProxyConnectionManager {
 
         let mut crypto = rustls::ClientConfig::builder()
             .with_safe_defaults()
-            .with_custom_certificate_verifier(SkipServerVerification::new())
+            .with_custom_certificate_verifier(match proxy_identity {
+                Some(proxy_identity) => PinnedServerVerification::new(proxy_identity),
+                None => SkipServerVerification::new(),
+            })
             .with_client_auth_cert(vec![certificate], key)
             .expect("Failed to set QUIC client certificates");
 
@@ -154,15 +233,14 @@ impl QuicProxyConnectionManager {
 
     // send transactions to quic proxy
     async fn read_transactions_and_broadcast(
-        mut transaction_receiver: Receiver<SentTransactionInfo>,
+        mut transaction_receiver: Receiver<TransactionToSend>,
         current_tpu_nodes: Arc<RwLock<Vec<TpuNode>>>,
-        proxy_addr: SocketAddr,
-        endpoint: Endpoint,
+        proxy_group: Arc<ProxyGroup>,
+        direct_fallback: Option<UnboundedSender<Vec<TransactionToSend>>>,
         exit_signal: Arc<AtomicBool>,
         connection_parameters: QuicConnectionParameters,
+        delivery_reports: Option<ProxyDeliveryReports>,
     ) {
-        let auto_connection = AutoReconnect::new(endpoint, proxy_addr);
-
         loop {
             // exit signal set
             if exit_signal.load(Relaxed) {
@@ -173,14 +251,10 @@ impl QuicProxyConnectionManager {
             tokio::select! {
                 tx = transaction_receiver.recv() => {
 
-                    let first_tx: TxData = match tx {
-                        Ok(SentTransactionInfo{
-                            signature,
-                            transaction,
-                            ..
-                        }) => {
-                            let transaction = transaction.as_ref().clone();
-                            TxData::new(signature, transaction)
+                    let mut batch: Vec<TransactionToSend> = vec![];
+                    match tx {
+                        Ok(transaction) => {
+                            batch.push(transaction);
                         },
                         Err(e) => {
                             warn!("Broadcast channel error (close) on recv: {} - aborting", e);
@@ -188,16 +262,10 @@ impl QuicProxyConnectionManager {
                         }
                     };
 
-                    let mut txs: Vec<TxData> = vec![first_tx];
                     for _ in 1..connection_parameters.number_of_transactions_per_unistream {
                         match transaction_receiver.try_recv() {
-                            Ok(SentTransactionInfo{
-                                signature,
-                                transaction,
-                                ..
-                            }) => {
-                                let transaction = transaction.as_ref().clone();
-                                txs.push(TxData::new(signature, transaction));
+                            Ok(transaction) => {
+                                batch.push(transaction);
                             },
                             Err(TryRecvError::Empty) => {
                                 break;
@@ -210,6 +278,15 @@ impl QuicProxyConnectionManager {
                         };
                     }
 
+                    // highest fee per compute unit first so they are not stuck behind cheaper ones
+                    batch.sort_by_key(|transaction| Reverse(transaction.priority_score()));
+                    let fallback_batch = direct_fallback.is_some().then(|| batch.clone());
+                    let mut txs: Vec<TxData> = vec![];
+                    let mut bundles: Vec<BundleData> = vec![];
+                    for transaction in batch {
+                        Self::add_to_batch(transaction, &mut txs, &mut bundles);
+                    }
+
                     let tpu_fanout_nodes = current_tpu_nodes.read().await.clone();
 
                     if tpu_fanout_nodes.is_empty() {
@@ -217,17 +294,78 @@ impl QuicProxyConnectionManager {
                         continue;
                     }
 
-                    trace!("Sending copy of transaction batch of {} txs to {} tpu nodes via quic proxy",
-                            txs.len(), tpu_fanout_nodes.len());
-
-                    let send_result =
-                        Self::send_copy_of_txs_to_quicproxy(
-                            &txs, &auto_connection,
-                            proxy_addr,
-                            tpu_fanout_nodes)
-                        .await;
-                    if let Err(e) = send_result {
-                        warn!("Failed to send copy of txs to quic proxy - skip (error {})", e);
+                    if direct_fallback.is_some() && !proxy_group.has_healthy_proxy() {
+                        trace!("No healthy quic proxy - {} txs and {} bundles are sent directly", txs.len(), bundles.len());
+                        continue;
+                    }
+
+                    trace!("Sending copy of transaction batch of {} txs and {} bundles to {} tpu nodes via quic proxy",
+                            txs.len(), bundles.len(), tpu_fanout_nodes.len());
+
+                    let sends = proxy_group.assign(&tpu_fanout_nodes).into_iter().map(|(index, tpu_nodes)| {
+                        let member = proxy_group.member(index);
+                        let txs = &txs;
+                        let bundles = &bundles;
+                        let delivery_reports = delivery_reports.as_ref();
+                        async move {
+                            let send_result = Self::send_copy_of_txs_to_quicproxy(
+                                txs, bundles, &member.connection,
+                                member.proxy.address,
+                                tpu_nodes.clone(),
+                                delivery_reports)
+                            .await;
+                            (index, tpu_nodes, send_result)
+                        }
+                    });
+                    let mut failed = vec![];
+                    for (index, tpu_nodes, send_result) in join_all(sends).await {
+                        match send_result {
+                            Ok(()) => proxy_group.record_success(index),
+                            Err(e) => {
+                                warn!("Failed to send copy of txs to quic proxy {} - retry (error {})",
+                                    proxy_group.member(index).proxy, e);
+                                proxy_group.record_failure(index);
+                                failed.push((index, tpu_nodes));
+                            }
+                        }
+                    }
+
+                    // re-queue the failed batches on the next healthy proxy
+                    let failed_proxies = failed.iter().map(|(index, _)| *index).collect_vec();
+                    let mut undelivered = false;
+                    for (_, tpu_nodes) in failed {
+                        let Some(index) = proxy_group.retry_candidate(&failed_proxies) else {
+                            undelivered = true;
+                            continue;
+                        };
+                        let member = proxy_group.member(index);
+                        match Self::send_copy_of_txs_to_quicproxy(
+                            &txs, &bundles, &member.connection,
+                            member.proxy.address,
+                            tpu_nodes,
+                            delivery_reports.as_ref())
+                        .await {
+                            Ok(()) => proxy_group.record_success(index),
+                            Err(e) => {
+                                warn!("Failed to resend copy of txs to quic proxy {} (error {})",
+                                    member.proxy, e);
+                                proxy_group.record_failure(index);
+                                undelivered = true;
+                            }
+                        }
+                    }
+
+                    if !undelivered {
+                        continue;
+                    }
+                    match (&direct_fallback, fallback_batch) {
+                        // the direct connections are opened right away instead of on the next slot
+                        (Some(direct_fallback), Some(fallback_batch)) if !proxy_group.has_healthy_proxy() => {
+                            if direct_fallback.send(fallback_batch).is_err() {
+                                warn!("Direct fallback stopped - {} txs and {} bundles are dropped", txs.len(), bundles.len());
+                            }
+                        }
+                        _ => warn!("No quic proxy took the batch - {} txs and {} bundles are dropped", txs.len(), bundles.len()),
                     }
 
                 },
@@ -235,25 +373,80 @@ impl QuicProxyConnectionManager {
         } // -- loop
     }
 
+    async fn check_proxy_health(proxy_group: Arc<ProxyGroup>, exit_signal: Arc<AtomicBool>) {
+        let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+        while !exit_signal.load(Relaxed) {
+            interval.tick().await;
+            proxy_group.check_health().await;
+        }
+    }
+
+    fn add_to_batch(
+        transaction: TransactionToSend,
+        txs: &mut Vec<TxData>,
+        bundles: &mut Vec<BundleData>,
+    ) {
+        match transaction {
+            TransactionToSend::Single(tx) => {
+                txs.push(TxData::new(tx.signature, tx.transaction.as_ref().clone()));
+            }
+            TransactionToSend::Bundle(bundle) => {
+                bundles.push(BundleData::new(
+                    bundle
+                        .transactions
+                        .iter()
+                        .map(|tx| TxData::new(tx.signature, tx.transaction.as_ref().clone()))
+                        .collect_vec(),
+                ));
+            }
+        }
+    }
+
     async fn send_copy_of_txs_to_quicproxy(
         txs: &[TxData],
+        bundles: &[BundleData],
         auto_connection: &AutoReconnect,
         _proxy_address: SocketAddr,
         tpu_fanout_nodes: Vec<TpuNode>,
+        delivery_reports: Option<&ProxyDeliveryReports>,
     ) -> anyhow::Result<()> {
         let tpu_data = tpu_fanout_nodes
             .iter()
             .map(|tpu| (tpu.tpu_address, tpu.tpu_identity))
             .collect_vec();
 
-        for chunk in txs.chunks(CHUNK_SIZE_PER_STREAM) {
-            let forwarding_request = TpuForwardingRequest::new(&tpu_data, chunk);
-            debug!("forwarding_request: {}", forwarding_request);
+        // bundles are never split across requests
+        let requests = txs
+            .chunks(CHUNK_SIZE_PER_STREAM)
+            .map(|chunk| TpuForwardingRequest::new(&tpu_data, chunk))
+            .chain(bundles.iter().map(|bundle| {
+                TpuForwardingRequest::new_with_bundles(&tpu_data, &[], std::slice::from_ref(bundle))
+            }));
 
-            let proxy_request_raw =
-                bincode::serialize(&forwarding_request).expect("Expect to serialize transactions");
+        for forwarding_request in requests {
+            debug!("forwarding_request: {}", forwarding_request);
 
-            let send_result = auto_connection.send_uni(&proxy_request_raw).await;
+            let forwarding_request = match delivery_reports {
+                Some(_) => forwarding_request.with_delivery_report(),
+                None => forwarding_request,
+            };
+            let proxy_request_raw = forwarding_request
+                .try_serialize_wire_format()
+                .expect("Expect to serialize transactions");
+
+            let send_result =
+                match delivery_reports {
+                    Some(delivery_reports) => auto_connection
+                        .send_bi(&proxy_request_raw)
+                        .await
+                        .map(|recv_stream| {
+                            tokio::spawn(Self::receive_delivery_report(
+                                recv_stream,
+                                delivery_reports.clone(),
+                            ));
+                        }),
+                    None => auto_connection.send_uni(&proxy_request_raw).await,
+                };
 
             match send_result {
                 Ok(()) => {
@@ -267,4 +460,38 @@ impl QuicProxyConnectionManager {
 
         Ok(())
     }
+
+    async fn receive_delivery_report(
+        mut recv_stream: RecvStream,
+        delivery_reports: ProxyDeliveryReports,
+    ) {
+        let raw_report = match timeout(
+            DELIVERY_REPORT_TIMEOUT,
+            recv_stream.read_to_end(MAX_DELIVERY_REPORT_SIZE),
+        )
+        .await
+        {
+            Ok(Ok(raw_report)) => raw_report,
+            Ok(Err(e)) => {
+                warn!("Failed to read delivery report from quic proxy: {}", e);
+                return;
+            }
+            Err(_elapsed) => {
+                warn!("Timeout waiting for delivery report from quic proxy");
+                return;
+            }
+        };
+        match DeliveryReport::try_deserialize_from_wire_format(&raw_report) {
+            Ok(report) => {
+                trace!(
+                    "Delivery report from quic proxy with {} results",
+                    report.results.len()
+                );
+                delivery_reports.record(&report);
+            }
+            Err(e) => {
+                warn!("Invalid delivery report from quic proxy: {}", e);
+            }
+        }
+    }
 }
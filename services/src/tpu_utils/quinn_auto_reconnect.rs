@@ -1,6 +1,6 @@
 use anyhow::{bail, Context};
 use log::{info, warn};
-use quinn::{Connection, ConnectionError, Endpoint};
+use quinn::{Connection, ConnectionError, Endpoint, RecvStream};
 use std::fmt;
 use std::net::SocketAddr;
 use std::time::Duration;
@@ -41,6 +41,14 @@ impl AutoReconnect {
         matches!(&*lock, ConnectionState::PermanentError)
     }
 
+    // a permanently failed target is tried again on the next send
+    pub async fn reset_permanent_error(&self) {
+        let mut lock = self.current.write().await;
+        if matches!(&*lock, ConnectionState::PermanentError) {
+            *lock = ConnectionState::NotConnected;
+        }
+    }
+
     pub async fn send_uni(&self, payload: &Vec<u8>) -> anyhow::Result<()> {
         let mut send_stream = timeout(SEND_TIMEOUT, self.refresh_and_get().await?.open_uni())
             .await
@@ -50,6 +58,17 @@ impl AutoReconnect {
         Ok(())
     }
 
+    // the answer of the peer is read from the returned stream
+    pub async fn send_bi(&self, payload: &Vec<u8>) -> anyhow::Result<RecvStream> {
+        let (mut send_stream, recv_stream) =
+            timeout(SEND_TIMEOUT, self.refresh_and_get().await?.open_bi())
+                .await
+                .context("open bi stream for sending")??;
+        send_stream.write_all(payload.as_slice()).await?;
+        send_stream.finish().await?;
+        Ok(recv_stream)
+    }
+
     pub async fn refresh_and_get(&self) -> anyhow::Result<Connection> {
         self.refresh().await;
 
@@ -173,12 +192,13 @@ impl AutoReconnect {
         match connection.await {
             Ok(conn) => Some(conn),
             Err(ConnectionError::TimedOut) => None,
-            // maybe we should also treat TransportError explicitly
+            // e.g. the peer failed authentication, other targets may still be used
             Err(unexpected_error) => {
-                panic!(
+                warn!(
                     "Connection to {} failed with unexpected error: {}",
                     self.target_address, unexpected_error
                 );
+                None
             }
         }
     }
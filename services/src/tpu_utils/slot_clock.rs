@@ -0,0 +1,326 @@
+// Estimates the current slot and the start of the upcoming leader slots
+// from the arrival times of the processed slots, skipped slots are part of the observed slot durations
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use prometheus::{opts, register_gauge, register_int_gauge, Gauge, IntGauge};
+use serde::{Deserialize, Serialize};
+use solana_lite_rpc_core::structures::leader_data::LeaderData;
+use solana_sdk::{clock::DEFAULT_MS_PER_SLOT, pubkey::Pubkey, slot_history::Slot};
+
+lazy_static::lazy_static! {
+    static ref SLOT_CLOCK_ESTIMATED_SLOT: IntGauge =
+        register_int_gauge!(opts!("literpc_slot_clock_estimated_slot", "Current slot estimated by the slot clock")).unwrap();
+    static ref SLOT_CLOCK_SLOT_DURATION_MS: Gauge =
+        register_gauge!(opts!("literpc_slot_clock_slot_duration_ms", "Average observed slot duration")).unwrap();
+    static ref SLOT_CLOCK_SKIP_RATE: Gauge =
+        register_gauge!(opts!("literpc_slot_clock_skip_rate", "Share of the recent slots not seen as processed")).unwrap();
+}
+
+// slot arrivals kept for the slot duration distribution
+const MAX_SAMPLES: usize = 256;
+const MIN_SAMPLES: usize = 8;
+// longer gaps are a stalled stream and not slot timing
+const MAX_SAMPLE_GAP_SLOTS: u64 = 32;
+// slots arriving in bursts are not counted as shorter than this
+const MIN_SLOT_DURATION_MS: f64 = 50.0;
+// the estimate does not run further ahead of the last processed slot
+const MAX_EXTRAPOLATED_SLOTS: u64 = 150;
+// bounds used until enough slots were observed
+const DEFAULT_FAST_SLOT_RATIO: f64 = 0.9;
+const DEFAULT_SLOW_SLOT_RATIO: f64 = 1.25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlotDurations {
+    pub mean_ms: f64,
+    // 10th and 90th percentile
+    pub fast_ms: f64,
+    pub slow_ms: f64,
+}
+
+impl Default for SlotDurations {
+    fn default() -> Self {
+        let slot_ms = DEFAULT_MS_PER_SLOT as f64;
+        Self {
+            mean_ms: slot_ms,
+            fast_ms: slot_ms * DEFAULT_FAST_SLOT_RATIO,
+            slow_ms: slot_ms * DEFAULT_SLOW_SLOT_RATIO,
+        }
+    }
+}
+
+/// Current slot with its bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SlotEstimate {
+    pub processed_slot: Slot,
+    pub slot: Slot,
+    pub earliest_slot: Slot,
+    pub latest_slot: Slot,
+}
+
+/// Time until the first slot of an upcoming leader
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderSlotTiming {
+    pub identity: String,
+    pub first_slot: Slot,
+    pub last_slot: Slot,
+    pub expected_ms: u64,
+    pub earliest_ms: u64,
+    pub latest_ms: u64,
+    // the leader skips its slots or does not include our transactions
+    pub unreliable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotTiming {
+    pub processed_slot: Slot,
+    pub ms_since_processed_slot: u64,
+    pub estimated_slot: Slot,
+    pub earliest_slot: Slot,
+    pub latest_slot: Slot,
+    pub slot_duration_ms: f64,
+    pub slot_duration_p10_ms: f64,
+    pub slot_duration_p90_ms: f64,
+    pub skip_rate: f64,
+    pub upcoming_leaders: Vec<LeaderSlotTiming>,
+}
+
+#[derive(Default)]
+struct SlotClockState {
+    last_slot: Slot,
+    last_arrival: Option<Instant>,
+    // slots advanced and time elapsed between consecutive processed slots
+    samples: VecDeque<(u64, Duration)>,
+    durations: SlotDurations,
+    skip_rate: f64,
+}
+
+impl SlotClockState {
+    fn update_distribution(&mut self) {
+        self.skip_rate = {
+            let slots: u64 = self.samples.iter().map(|(gap, _)| gap).sum();
+            let skipped: u64 = self.samples.iter().map(|(gap, _)| gap - 1).sum();
+            if slots == 0 {
+                0.0
+            } else {
+                skipped as f64 / slots as f64
+            }
+        };
+        if self.samples.len() < MIN_SAMPLES {
+            self.durations = SlotDurations::default();
+            return;
+        }
+
+        // duration per slot weighted by the slots of the sample
+        let mut per_slot = self
+            .samples
+            .iter()
+            .map(|(gap, elapsed)| {
+                let ms = elapsed.as_secs_f64() * 1000.0 / *gap as f64;
+                (ms.max(MIN_SLOT_DURATION_MS), *gap)
+            })
+            .collect::<Vec<_>>();
+        per_slot.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let slots: u64 = per_slot.iter().map(|(_, gap)| gap).sum();
+        let total_ms: f64 = per_slot.iter().map(|(ms, gap)| ms * *gap as f64).sum();
+        let percentile = |q: f64| {
+            let rank = (slots as f64 * q).ceil() as u64;
+            let mut seen = 0;
+            per_slot
+                .iter()
+                .find(|(_, gap)| {
+                    seen += gap;
+                    seen >= rank
+                })
+                .map(|(ms, _)| *ms)
+                .unwrap_or_default()
+        };
+        self.durations = SlotDurations {
+            mean_ms: total_ms / slots as f64,
+            fast_ms: percentile(0.1),
+            slow_ms: percentile(0.9),
+        };
+    }
+
+    fn elapsed_ms(&self, now: Instant) -> f64 {
+        self.last_arrival
+            .map(|last_arrival| now.saturating_duration_since(last_arrival).as_secs_f64() * 1000.0)
+            .unwrap_or_default()
+    }
+
+    fn extrapolate(&self, elapsed_ms: f64, slot_ms: f64) -> Slot {
+        let slots = (elapsed_ms / slot_ms) as u64;
+        self.last_slot + slots.min(MAX_EXTRAPOLATED_SLOTS)
+    }
+
+    // slot last_slot + 1 starts when last_slot is seen processed
+    fn ms_until(&self, slot: Slot, elapsed_ms: f64, slot_ms: f64) -> u64 {
+        let slots_ahead = slot.saturating_sub(self.last_slot + 1);
+        (slots_ahead as f64 * slot_ms - elapsed_ms).max(0.0) as u64
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct SlotClock {
+    state: Arc<Mutex<SlotClockState>>,
+}
+
+impl SlotClock {
+    pub fn record_processed_slot(&self, slot: Slot, at: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if slot <= state.last_slot {
+            return;
+        }
+        if let Some(last_arrival) = state.last_arrival {
+            let gap = slot - state.last_slot;
+            if gap <= MAX_SAMPLE_GAP_SLOTS {
+                let elapsed = at.saturating_duration_since(last_arrival);
+                state.samples.push_back((gap, elapsed));
+                if state.samples.len() > MAX_SAMPLES {
+                    state.samples.pop_front();
+                }
+                state.update_distribution();
+            }
+        }
+        state.last_slot = slot;
+        state.last_arrival = Some(at);
+
+        SLOT_CLOCK_SLOT_DURATION_MS.set(state.durations.mean_ms);
+        SLOT_CLOCK_SKIP_RATE.set(state.skip_rate);
+    }
+
+    pub fn slot_durations(&self) -> SlotDurations {
+        self.state.lock().unwrap().durations
+    }
+
+    pub fn estimate(&self, now: Instant) -> SlotEstimate {
+        let state = self.state.lock().unwrap();
+        let elapsed_ms = state.elapsed_ms(now);
+        let durations = state.durations;
+        let estimate = SlotEstimate {
+            processed_slot: state.last_slot,
+            slot: state.extrapolate(elapsed_ms, durations.mean_ms),
+            earliest_slot: state.extrapolate(elapsed_ms, durations.slow_ms),
+            latest_slot: state.extrapolate(elapsed_ms, durations.fast_ms),
+        };
+        SLOT_CLOCK_ESTIMATED_SLOT.set(estimate.slot as i64);
+        estimate
+    }
+
+    /// leaders are the slot leaders from the estimated slot on, grouped into their leader windows
+    pub fn timing(
+        &self,
+        now: Instant,
+        leaders: &[LeaderData],
+        unreliable_leaders: &[Pubkey],
+    ) -> SlotTiming {
+        let estimate = self.estimate(now);
+        let state = self.state.lock().unwrap();
+        let elapsed_ms = state.elapsed_ms(now);
+        let durations = state.durations;
+
+        let mut upcoming_leaders: Vec<LeaderSlotTiming> = vec![];
+        let identities = leaders.iter().map(|leader| leader.pubkey.to_string());
+        for (leader, identity) in leaders.iter().zip(identities) {
+            if let Some(window) = upcoming_leaders.last_mut() {
+                if window.identity == identity && window.last_slot + 1 == leader.leader_slot {
+                    window.last_slot = leader.leader_slot;
+                    continue;
+                }
+            }
+            upcoming_leaders.push(LeaderSlotTiming {
+                identity,
+                first_slot: leader.leader_slot,
+                last_slot: leader.leader_slot,
+                expected_ms: state.ms_until(leader.leader_slot, elapsed_ms, durations.mean_ms),
+                earliest_ms: state.ms_until(leader.leader_slot, elapsed_ms, durations.fast_ms),
+                latest_ms: state.ms_until(leader.leader_slot, elapsed_ms, durations.slow_ms),
+                unreliable: unreliable_leaders.contains(&leader.pubkey),
+            });
+        }
+
+        SlotTiming {
+            processed_slot: estimate.processed_slot,
+            ms_since_processed_slot: elapsed_ms as u64,
+            estimated_slot: estimate.slot,
+            earliest_slot: estimate.earliest_slot,
+            latest_slot: estimate.latest_slot,
+            slot_duration_ms: durations.mean_ms,
+            slot_duration_p10_ms: durations.fast_ms,
+            slot_duration_p90_ms: durations.slow_ms,
+            skip_rate: state.skip_rate,
+            upcoming_leaders,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_from_observed_slot_times() {
+        let clock = SlotClock::default();
+        let start = Instant::now();
+        let ms = |ms: u64| start + Duration::from_millis(ms);
+
+        // 400ms slots, 120 and 121 skipped by their leader
+        let mut at = 0;
+        for slot in (100..=130).filter(|slot| !(120..=121).contains(slot)) {
+            clock.record_processed_slot(slot, ms(at));
+            at += if slot == 119 { 1200 } else { 400 };
+        }
+        let durations = clock.slot_durations();
+        assert_eq!(durations.mean_ms, 400.0);
+        assert_eq!((durations.fast_ms, durations.slow_ms), (400.0, 400.0));
+
+        // last slot 130 seen at 12000ms
+        let estimate = clock.estimate(ms(12000 + 1000));
+        assert_eq!(estimate.processed_slot, 130);
+        assert_eq!(estimate.slot, 132);
+
+        let leader = Pubkey::new_unique();
+        let unreliable = Pubkey::new_unique();
+        let leaders = (132..140)
+            .map(|leader_slot| LeaderData {
+                leader_slot,
+                pubkey: if leader_slot < 136 {
+                    leader
+                } else {
+                    unreliable
+                },
+            })
+            .collect::<Vec<_>>();
+        let timing = clock.timing(ms(12000 + 1000), &leaders, &[unreliable]);
+        assert_eq!(timing.skip_rate, 2.0 / 30.0);
+        assert_eq!(timing.upcoming_leaders.len(), 2);
+        let next = &timing.upcoming_leaders[1];
+        assert_eq!((next.first_slot, next.last_slot), (136, 139));
+        // slot 131 started at 12000ms, 136 five slots later
+        assert_eq!(next.expected_ms, 1000);
+        assert!(next.unreliable);
+    }
+
+    #[test]
+    fn bounds_widen_with_jitter() {
+        let clock = SlotClock::default();
+        let start = Instant::now();
+        let mut at = 0;
+        for (i, slot) in (100..=140).enumerate() {
+            clock.record_processed_slot(slot, start + Duration::from_millis(at));
+            at += if i % 2 == 0 { 300 } else { 500 };
+        }
+        let durations = clock.slot_durations();
+        assert_eq!(durations.mean_ms, 400.0);
+        assert_eq!((durations.fast_ms, durations.slow_ms), (300.0, 500.0));
+
+        // 40 slots seen in 16s, 3s later
+        let estimate = clock.estimate(start + Duration::from_millis(16000 + 3000));
+        assert_eq!(estimate.slot, 147);
+        assert_eq!((estimate.earliest_slot, estimate.latest_slot), (146, 150));
+    }
+}
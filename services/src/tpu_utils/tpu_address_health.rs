@@ -0,0 +1,115 @@
+// Health of the quic tpu addresses of each leader
+// Connections move to the next address of a leader after repeated failures on the preferred one
+
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use prometheus::{opts, register_int_counter, IntCounter};
+use solana_sdk::pubkey::Pubkey;
+
+lazy_static::lazy_static! {
+    static ref NB_TPU_ADDRESS_FAILURES: IntCounter =
+        register_int_counter!(opts!("literpc_tpu_address_failures", "Number of transactions which could not be sent to a tpu address")).unwrap();
+    static ref NB_TPU_ADDRESS_FALLBACKS: IntCounter =
+        register_int_counter!(opts!("literpc_tpu_address_fallbacks", "Number of times a leader was reached on a fallback tpu address")).unwrap();
+}
+
+// failures in a row before the next address of the leader is used
+const MAX_CONSECUTIVE_FAILURES: u64 = 5;
+// a failing address is tried again after this delay
+const FAILED_ADDRESS_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Default)]
+struct AddressHealth {
+    consecutive_failures: u64,
+    last_failure: Option<Instant>,
+}
+
+impl AddressHealth {
+    fn is_failing(&self) -> bool {
+        self.consecutive_failures >= MAX_CONSECUTIVE_FAILURES
+            && self
+                .last_failure
+                .is_some_and(|last_failure| last_failure.elapsed() < FAILED_ADDRESS_RETRY_AFTER)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct TpuAddressHealth {
+    addresses: Arc<DashMap<(Pubkey, SocketAddr), AddressHealth>>,
+}
+
+impl TpuAddressHealth {
+    pub fn record_success(&self, identity: Pubkey, address: SocketAddr) {
+        self.addresses.remove(&(identity, address));
+    }
+
+    pub fn record_failure(&self, identity: Pubkey, address: SocketAddr) {
+        NB_TPU_ADDRESS_FAILURES.inc();
+        let mut health = self.addresses.entry((identity, address)).or_default();
+        health.consecutive_failures += 1;
+        health.last_failure = Some(Instant::now());
+    }
+
+    /// forgets the addresses of the leaders which left the fanout
+    pub fn retain_leaders(&self, is_in_fanout: impl Fn(&Pubkey) -> bool) {
+        self.addresses
+            .retain(|(identity, _), _| is_in_fanout(identity));
+    }
+
+    /// first address of the leader which is not failing, the preferred one if they all fail
+    pub fn select(&self, identity: Pubkey, addresses: &[SocketAddr]) -> Option<SocketAddr> {
+        let healthy = addresses.iter().position(|address| {
+            self.addresses
+                .get(&(identity, *address))
+                .map_or(true, |health| !health.is_failing())
+        });
+        match healthy {
+            Some(0) | None => addresses.first().copied(),
+            Some(index) => {
+                NB_TPU_ADDRESS_FALLBACKS.inc();
+                Some(addresses[index])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn falls_back_to_forwards_after_failures() {
+        let addresses = vec![
+            SocketAddr::from_str("10.0.0.1:8009").unwrap(),
+            SocketAddr::from_str("10.0.0.1:8010").unwrap(),
+        ];
+
+        let identity = Pubkey::new_unique();
+        let health = TpuAddressHealth::default();
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            assert_eq!(health.select(identity, &addresses), Some(addresses[0]));
+            health.record_failure(identity, addresses[0]);
+        }
+        assert_eq!(health.select(identity, &addresses), Some(addresses[1]));
+
+        // every address fails, back to the preferred one
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            health.record_failure(identity, addresses[1]);
+        }
+        assert_eq!(health.select(identity, &addresses), Some(addresses[0]));
+
+        health.record_success(identity, addresses[0]);
+        assert_eq!(health.select(identity, &addresses), Some(addresses[0]));
+
+        health.record_failure(identity, addresses[0]);
+        health.retain_leaders(|leader| *leader != identity);
+        assert!(health.addresses.is_empty());
+    }
+}
@@ -1,27 +1,34 @@
 use dashmap::DashMap;
 use log::{error, trace};
 use prometheus::{
-    core::GenericGauge, histogram_opts, opts, register_histogram, register_int_gauge, Histogram,
+    core::{Collector, GenericGauge},
+    histogram_opts, opts, register_histogram, register_histogram_vec, register_int_gauge,
+    Histogram, HistogramVec,
 };
-use quinn::Endpoint;
 use solana_lite_rpc_core::{
     stores::data_cache::DataCache,
     structures::{
         identity_stakes::IdentityStakesData, prioritization_fee_heap::PrioritizationFeesHeap,
-        rotating_queue::RotatingQueue, transaction_sent_info::SentTransactionInfo,
+        transaction_sent_info::TransactionToSend,
     },
 };
 use solana_sdk::pubkey::Pubkey;
 use solana_streamer::nonblocking::quic::compute_max_allowed_uni_streams;
-use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::Arc,
+    time::Duration,
+};
 use tokio::sync::{
     broadcast::{self, Receiver, Sender},
     Notify,
 };
 
 use crate::{
-    quic_connection::{PooledConnection, QuicConnectionPool},
-    quic_connection_utils::{QuicConnectionParameters, QuicConnectionUtils},
+    quic_connection::{EndpointPool, PooledConnection, QuicConnection, QuicConnectionPool},
+    quic_connection_utils::{QuicBindConfig, QuicConnectionParameters},
+    tpu_utils::tpu_address_health::TpuAddressHealth,
 };
 
 lazy_static::lazy_static! {
@@ -39,27 +46,66 @@ lazy_static::lazy_static! {
 
     static ref TRANSACTIONS_IN_HEAP: GenericGauge<prometheus::core::AtomicI64> =
         register_int_gauge!(opts!("literpc_transactions_in_priority_heap", "Number of transactions in priority heap")).unwrap();
+
+    static ref NB_PREWARMED_CONNECTIONS: GenericGauge<prometheus::core::AtomicI64> =
+        register_int_gauge!(opts!("literpc_prewarmed_connections", "Number of connections opened ahead of the leader window")).unwrap();
+    static ref PREWARM_HANDSHAKE_TIME: HistogramVec = register_histogram_vec!(histogram_opts!(
+            "literpc_prewarm_handshake_time",
+            "Time to open a connection ahead of the leader window",
+        ), &["identity"])
+        .unwrap();
+}
+
+/// drops the prewarm handshake times of the leaders which are not in the `scheduled` leaders anymore
+pub fn retain_scheduled_prewarm_metrics(scheduled: &HashSet<Pubkey>) {
+    let identities = PREWARM_HANDSHAKE_TIME
+        .collect()
+        .iter()
+        .flat_map(|family| family.get_metric())
+        .flat_map(|metric| metric.get_label())
+        .map(|label| label.get_value().to_string())
+        .collect::<Vec<_>>();
+    for identity in identities {
+        if !identity
+            .parse()
+            .is_ok_and(|identity| scheduled.contains(&identity))
+        {
+            let _ = PREWARM_HANDSHAKE_TIME.remove_label_values(&[&identity]);
+        }
+    }
 }
 
+// time both connections are used when the identity is changed
+const IDENTITY_SWAP_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
 #[derive(Clone)]
 struct ActiveConnection {
-    endpoints: RotatingQueue<Endpoint>,
+    endpoints: EndpointPool,
     identity: Pubkey,
     tpu_address: SocketAddr,
     data_cache: DataCache,
     connection_parameters: QuicConnectionParameters,
     exit_notifier: broadcast::Sender<()>,
+    prewarmed_connection: Option<QuicConnection>,
+    address_health: TpuAddressHealth,
 }
 
 impl ActiveConnection {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        endpoints: RotatingQueue<Endpoint>,
+        endpoints: EndpointPool,
         tpu_address: SocketAddr,
         identity: Pubkey,
         data_cache: DataCache,
         connection_parameters: QuicConnectionParameters,
+        prewarmed_connection: Option<PrewarmedConnection>,
+        address_health: TpuAddressHealth,
     ) -> Self {
-        let (exit_notifier, _) = broadcast::channel(1);
+        // the exit notifier of a prewarmed connection also stops its handshake
+        let (prewarmed_connection, exit_notifier) = match prewarmed_connection {
+            Some(prewarmed) => (Some(prewarmed.connection), prewarmed.exit_notifier),
+            None => (None, broadcast::channel(1).0),
+        };
         Self {
             endpoints,
             tpu_address,
@@ -67,13 +113,15 @@ impl ActiveConnection {
             data_cache,
             connection_parameters,
             exit_notifier,
+            prewarmed_connection,
+            address_health,
         }
     }
 
     #[allow(clippy::too_many_arguments)]
     async fn listen(
         &self,
-        mut transaction_reciever: Receiver<SentTransactionInfo>,
+        mut transaction_reciever: Receiver<TransactionToSend>,
         addr: SocketAddr,
         identity_stakes: IdentityStakesData,
     ) {
@@ -96,12 +144,21 @@ impl ActiveConnection {
             self.connection_parameters,
             max_number_of_connections,
             max_uni_stream_connections,
+            self.prewarmed_connection.clone(),
         );
         let prioritization_heap_size = self
             .connection_parameters
             .prioritization_heap_size
             .unwrap_or(2 * max_uni_stream_connections);
-        let priorization_heap = PrioritizationFeesHeap::new(prioritization_heap_size);
+        let max_transactions_per_sender = (prioritization_heap_size
+            * self
+                .connection_parameters
+                .prioritization_heap_share_per_sender_in_percentage
+                .min(100) as usize
+            / 100)
+            .max(1);
+        let priorization_heap =
+            PrioritizationFeesHeap::new(prioritization_heap_size, max_transactions_per_sender);
 
         let heap_filler_task = {
             let priorization_heap = priorization_heap.clone();
@@ -122,9 +179,7 @@ impl ActiveConnection {
                     };
                     match tx {
                         Ok(transaction_sent_info) => {
-                            if data_cache
-                                .check_if_confirmed_or_expired_blockheight(&transaction_sent_info)
-                            {
+                            if data_cache.check_if_confirmed_or_expired(&transaction_sent_info) {
                                 // transactions is confirmed or expired
                                 continue;
                             }
@@ -182,8 +237,12 @@ impl ActiveConnection {
                         };
                         TRANSACTIONS_IN_HEAP.dec();
 
-                        // check if transaction is already confirmed
-                        if self.data_cache.txs.is_transaction_confirmed(&tx.signature) {
+                        // check if transaction (or the whole bundle) is already confirmed
+                        if tx
+                            .transactions()
+                            .iter()
+                            .all(|tx| self.data_cache.txs.is_transaction_confirmed(&tx.signature))
+                        {
                             continue;
                         }
 
@@ -198,6 +257,7 @@ impl ActiveConnection {
                             },
                         };
                         let exit_notifier = self.exit_notifier.subscribe();
+                        let address_health = self.address_health.clone();
 
                         tokio::spawn(async move {
                             // permit will be used to send all the transaction and then destroyed
@@ -206,7 +266,17 @@ impl ActiveConnection {
 
                             NB_QUIC_TASKS.inc();
 
-                            connection.send_transaction(tx.transaction.as_ref(), exit_notifier).await;
+                            // transactions of a bundle are sent in order over the same connection
+                            for tx in tx.transactions() {
+                                match connection
+                                    .send_transaction(tx.transaction.as_ref(), exit_notifier.resubscribe())
+                                    .await
+                                {
+                                    Some(true) => address_health.record_success(identity, addr),
+                                    Some(false) => address_health.record_failure(identity, addr),
+                                    None => break,
+                                }
+                            }
                             timer.observe_duration();
                             NB_QUIC_TASKS.dec();
                         });
@@ -227,7 +297,7 @@ impl ActiveConnection {
 
     pub fn start_listening(
         &self,
-        transaction_reciever: Receiver<SentTransactionInfo>,
+        transaction_reciever: Receiver<TransactionToSend>,
         identity_stakes: IdentityStakesData,
     ) {
         let addr = self.tpu_address;
@@ -239,9 +309,27 @@ impl ActiveConnection {
     }
 }
 
+struct PrewarmedConnection {
+    connection: QuicConnection,
+    exit_notifier: broadcast::Sender<()>,
+}
+
+impl PrewarmedConnection {
+    fn close(&self) {
+        let _ = self.exit_notifier.send(());
+        let connection = self.connection.clone();
+        tokio::spawn(async move { connection.close().await });
+    }
+}
+
 pub struct TpuConnectionManager {
-    endpoints: RotatingQueue<Endpoint>,
+    // replaced when the identity changes
+    endpoints: std::sync::RwLock<EndpointPool>,
+    bind_config: QuicBindConfig,
+    number_of_endpoints: usize,
+    address_health: TpuAddressHealth,
     identity_to_active_connection: Arc<DashMap<Pubkey, ActiveConnection>>,
+    identity_to_prewarmed_connection: Arc<DashMap<Pubkey, PrewarmedConnection>>,
 }
 
 impl TpuConnectionManager {
@@ -249,40 +337,138 @@ impl TpuConnectionManager {
         certificate: rustls::Certificate,
         key: rustls::PrivateKey,
         _fanout: usize,
+        bind_config: QuicBindConfig,
     ) -> Self {
         let number_of_clients = 1; // fanout * 4;
         Self {
-            endpoints: RotatingQueue::new(number_of_clients, || {
-                QuicConnectionUtils::create_endpoint(certificate.clone(), key.clone())
-            }),
+            endpoints: std::sync::RwLock::new(EndpointPool::new(
+                certificate,
+                key,
+                &bind_config,
+                number_of_clients,
+            )),
+            bind_config,
+            number_of_endpoints: number_of_clients,
+            address_health: TpuAddressHealth::default(),
             identity_to_active_connection: Arc::new(DashMap::new()),
+            identity_to_prewarmed_connection: Arc::new(DashMap::new()),
+        }
+    }
+
+    fn endpoints(&self) -> EndpointPool {
+        self.endpoints.read().unwrap().clone()
+    }
+
+    pub fn address_health(&self) -> &TpuAddressHealth {
+        &self.address_health
+    }
+
+    /// use a new client certificate, the active connections are replaced by connections with the new identity
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_identity(
+        &self,
+        certificate: rustls::Certificate,
+        key: rustls::PrivateKey,
+        broadcast_sender: Arc<Sender<TransactionToSend>>,
+        identity_stakes: IdentityStakesData,
+        data_cache: DataCache,
+        connection_parameters: QuicConnectionParameters,
+    ) {
+        *self.endpoints.write().unwrap() = EndpointPool::new(
+            certificate,
+            key,
+            &self.bind_config,
+            self.number_of_endpoints,
+        );
+
+        self.identity_to_prewarmed_connection
+            .retain(|_, prewarmed| {
+                prewarmed.close();
+                false
+            });
+        NB_PREWARMED_CONNECTIONS.set(0);
+
+        let connections = self
+            .identity_to_active_connection
+            .iter()
+            .map(|entry| (*entry.key(), entry.tpu_address))
+            .collect::<Vec<_>>();
+        for (identity, socket_addr) in connections {
+            let active_connection = ActiveConnection::new(
+                self.endpoints(),
+                socket_addr,
+                identity,
+                data_cache.clone(),
+                connection_parameters,
+                None,
+                self.address_health.clone(),
+            );
+            active_connection.start_listening(broadcast_sender.subscribe(), identity_stakes);
+            if let Some(previous_connection) = self
+                .identity_to_active_connection
+                .insert(identity, active_connection)
+            {
+                // the previous connection keeps sending while the new one is established
+                tokio::spawn(async move {
+                    tokio::time::sleep(IDENTITY_SWAP_GRACE_PERIOD).await;
+                    let _ = previous_connection.exit_notifier.send(());
+                });
+            }
         }
     }
 
     pub async fn update_connections(
         &self,
-        broadcast_sender: Arc<Sender<SentTransactionInfo>>,
+        broadcast_sender: Arc<Sender<TransactionToSend>>,
         connections_to_keep: HashMap<Pubkey, SocketAddr>,
         identity_stakes: IdentityStakesData,
         data_cache: DataCache,
         connection_parameters: QuicConnectionParameters,
     ) {
         NB_CONNECTIONS_TO_KEEP.set(connections_to_keep.len() as i64);
+        let endpoints = self.endpoints();
         for (identity, socket_addr) in &connections_to_keep {
-            if self.identity_to_active_connection.get(identity).is_none() {
+            if !endpoints.can_reach(socket_addr) {
+                trace!("no local address can reach {}, {}", identity, socket_addr);
+                continue;
+            }
+            // the address of a leader changes when it falls back to another tpu port
+            let has_connection = self
+                .identity_to_active_connection
+                .get(identity)
+                .is_some_and(|connection| connection.tpu_address == *socket_addr);
+            if !has_connection {
                 trace!("added a connection for {}, {}", identity, socket_addr);
+                // reuse the connection opened ahead of the leader window
+                let prewarmed_connection = self
+                    .identity_to_prewarmed_connection
+                    .remove(identity)
+                    .map(|(_, prewarmed)| prewarmed)
+                    .filter(|prewarmed| {
+                        let same_address = prewarmed.connection.socket_address() == *socket_addr;
+                        if !same_address {
+                            prewarmed.close();
+                        }
+                        same_address
+                    });
                 let active_connection = ActiveConnection::new(
-                    self.endpoints.clone(),
+                    endpoints.clone(),
                     *socket_addr,
                     *identity,
                     data_cache.clone(),
                     connection_parameters,
+                    prewarmed_connection,
+                    self.address_health.clone(),
                 );
                 // using mpsc as a oneshot channel/ because with one shot channel we cannot reuse the reciever
                 let broadcast_receiver = broadcast_sender.subscribe();
                 active_connection.start_listening(broadcast_receiver, identity_stakes);
-                self.identity_to_active_connection
-                    .insert(*identity, active_connection);
+                if let Some(previous_connection) = self
+                    .identity_to_active_connection
+                    .insert(*identity, active_connection)
+                {
+                    let _ = previous_connection.exit_notifier.send(());
+                }
             }
         }
 
@@ -297,5 +483,63 @@ impl TpuConnectionManager {
                 true
             }
         });
+        self.address_health
+            .retain_leaders(|identity| connections_to_keep.contains_key(identity));
+    }
+
+    /// open connections to the leaders which are about to enter the fanout
+    pub fn prewarm_connections(
+        &self,
+        connections_to_prewarm: HashMap<Pubkey, SocketAddr>,
+        connection_parameters: QuicConnectionParameters,
+    ) {
+        for (identity, socket_addr) in &connections_to_prewarm {
+            if self.identity_to_active_connection.contains_key(identity)
+                || self.identity_to_prewarmed_connection.contains_key(identity)
+            {
+                continue;
+            }
+            trace!("prewarming a connection for {}, {}", identity, socket_addr);
+            let Some(endpoint) = self.endpoints().get(socket_addr) else {
+                continue;
+            };
+            let connection =
+                QuicConnection::new(*identity, endpoint, *socket_addr, connection_parameters);
+            let (exit_notifier, _) = broadcast::channel(1);
+            let exit_notify = exit_notifier.subscribe();
+            let identity = *identity;
+            {
+                let connection = connection.clone();
+                tokio::spawn(async move {
+                    let timer = PREWARM_HANDSHAKE_TIME
+                        .with_label_values(&[&identity.to_string()])
+                        .start_timer();
+                    if connection.prewarm(exit_notify).await {
+                        timer.observe_duration();
+                    } else {
+                        timer.stop_and_discard();
+                        trace!("could not prewarm a connection for {}", identity);
+                    }
+                });
+            }
+            self.identity_to_prewarmed_connection.insert(
+                identity,
+                PrewarmedConnection {
+                    connection,
+                    exit_notifier,
+                },
+            );
+        }
+
+        // close connections of leaders which left the prewarm window without being used
+        self.identity_to_prewarmed_connection
+            .retain(|identity, prewarmed| {
+                if connections_to_prewarm.contains_key(identity) {
+                    return true;
+                }
+                prewarmed.close();
+                false
+            });
+        NB_PREWARMED_CONNECTIONS.set(self.identity_to_prewarmed_connection.len() as i64);
     }
 }
@@ -1,10 +1,49 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
 use std::fmt::Display;
 use std::net::SocketAddr;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum TpuConnectionPath {
     QuicDirectPath,
-    QuicForwardProxyPath { forward_proxy_address: SocketAddr },
+    QuicForwardProxyPath {
+        // at least one proxy
+        forward_proxies: Vec<ForwardProxy>,
+        balancing: ProxyBalancing,
+        // send directly to the tpus while no proxy is healthy
+        fallback_to_direct: bool,
+        // the proxies report the delivery of the transactions to each tpu
+        delivery_reports: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ForwardProxy {
+    pub address: SocketAddr,
+    // identity the proxy must authenticate with, not verified if not set
+    pub identity: Option<Pubkey>,
+    // share of the transactions with weighted round-robin
+    pub weight: u32,
+}
+
+/// How the transactions are spread over the proxies of the group
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProxyBalancing {
+    /// each batch goes to the next proxy, proportionally to the weights
+    #[default]
+    WeightedRoundRobin,
+    /// the transactions for a leader always go through the same proxy
+    ShardByLeader,
+}
+
+impl Display for ForwardProxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.identity {
+            Some(identity) => write!(f, "{} on {}", identity, self.address),
+            None => write!(f, "{}", self.address),
+        }
+    }
 }
 
 impl Display for TpuConnectionPath {
@@ -12,9 +51,20 @@ impl Display for TpuConnectionPath {
         match self {
             TpuConnectionPath::QuicDirectPath => write!(f, "Direct QUIC connection to TPU"),
             TpuConnectionPath::QuicForwardProxyPath {
-                forward_proxy_address,
+                forward_proxies,
+                balancing,
+                ..
             } => {
-                write!(f, "QUIC Forward Proxy on {}", forward_proxy_address)
+                write!(
+                    f,
+                    "QUIC Forward Proxy {} ({:?})",
+                    forward_proxies
+                        .iter()
+                        .map(|proxy| proxy.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    balancing
+                )
             }
         }
     }
@@ -1,25 +1,35 @@
-use anyhow::Context;
+use anyhow::{bail, Context};
+use log::info;
 use prometheus::{core::GenericGauge, opts, register_int_gauge};
 
-use super::tpu_connection_manager::TpuConnectionManager;
-use crate::quic_connection_utils::QuicConnectionParameters;
+use super::leader_scores::{LeaderScore, LeaderScoreTracker};
+use super::proxy_delivery_reports::ProxyDeliveryReports;
+use super::slot_clock::{SlotClock, SlotEstimate, SlotTiming};
+use super::tpu_connection_manager::{retain_scheduled_prewarm_metrics, TpuConnectionManager};
+use crate::quic_connection_utils::{QuicBindConfig, QuicConnectionParameters};
 use crate::tpu_utils::quic_proxy_connection_manager::QuicProxyConnectionManager;
 use crate::tpu_utils::tpu_connection_path::TpuConnectionPath;
 use crate::tpu_utils::tpu_service::ConnectionManager::{DirectTpu, QuicProxy};
 
-use solana_lite_rpc_core::network_utils::log_gso_workaround;
+use solana_lite_rpc_core::network_utils::{log_gso_workaround, tpu_quic_addresses};
 use solana_lite_rpc_core::stores::data_cache::DataCache;
-use solana_lite_rpc_core::structures::transaction_sent_info::SentTransactionInfo;
+use solana_lite_rpc_core::structures::leader_data::LeaderData;
+use solana_lite_rpc_core::structures::transaction_sent_info::TransactionToSend;
 use solana_lite_rpc_core::traits::leaders_fetcher_interface::LeaderFetcherInterface;
-use solana_lite_rpc_core::types::SlotStream;
+use solana_lite_rpc_core::types::{BlockStream, SlotStream};
 use solana_lite_rpc_core::AnyhowJoinHandle;
-use solana_sdk::{quic::QUIC_PORT_OFFSET, signature::Keypair, slot_history::Slot};
+use solana_sdk::{
+    clock::NUM_CONSECUTIVE_LEADER_SLOTS, pubkey::Pubkey, signature::Keypair, signer::Signer,
+    slot_history::Slot,
+};
 use solana_streamer::tls_certificates::new_self_signed_tls_certificate;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 use std::{
-    net::{IpAddr, Ipv4Addr},
-    sync::Arc,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
 };
+use tokio::sync::mpsc::UnboundedReceiver;
 
 lazy_static::lazy_static! {
     static ref NB_CLUSTER_NODES: GenericGauge<prometheus::core::AtomicI64> =
@@ -30,23 +40,32 @@ lazy_static::lazy_static! {
 
     static ref ESTIMATED_SLOT: GenericGauge<prometheus::core::AtomicI64> =
     register_int_gauge!(opts!("literpc_estimated_slot", "Estimated slot seen by last rpc")).unwrap();
+
+    static ref FANOUT_EXTRA_SLOTS: GenericGauge<prometheus::core::AtomicI64> =
+    register_int_gauge!(opts!("literpc_fanout_extra_slots", "Slots added to the fanout to cover unreliable leaders")).unwrap();
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct TpuServiceConfig {
     pub fanout_slots: u64,
     pub maximum_transaction_in_queue: usize,
     pub quic_connection_params: QuicConnectionParameters,
     pub tpu_connection_path: TpuConnectionPath,
+    // local addresses of the outgoing quic endpoints, the proxy client binds to any port if not set
+    pub quic_bind_config: Option<QuicBindConfig>,
 }
 
 #[derive(Clone)]
 pub struct TpuService {
-    broadcast_sender: Arc<tokio::sync::broadcast::Sender<SentTransactionInfo>>,
+    broadcast_sender: Arc<tokio::sync::broadcast::Sender<TransactionToSend>>,
     connection_manager: ConnectionManager,
     leader_schedule: Arc<dyn LeaderFetcherInterface>,
     config: TpuServiceConfig,
     data_cache: DataCache,
+    leader_scores: LeaderScoreTracker,
+    slot_clock: SlotClock,
+    // leaders fetched on the last fanout update, read without awaiting the leader schedule
+    cached_leaders: Arc<RwLock<Vec<LeaderData>>>,
 }
 
 #[derive(Clone)]
@@ -56,6 +75,8 @@ enum ConnectionManager {
     },
     QuicProxy {
         quic_proxy_connection_manager: Arc<QuicProxyConnectionManager>,
+        // reaches the tpus while no proxy is healthy
+        direct_fallback: Option<Arc<TpuConnectionManager>>,
     },
 }
 
@@ -67,88 +88,293 @@ impl TpuService {
         data_cache: DataCache,
     ) -> anyhow::Result<Self> {
         let (sender, _) = tokio::sync::broadcast::channel(config.maximum_transaction_in_queue);
-        let (certificate, key) = new_self_signed_tls_certificate(
-            identity.as_ref(),
-            IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
-        )
-        .expect("Failed to initialize QUIC client certificates");
+        let broadcast_sender = Arc::new(sender);
+        let bind_config = config.quic_bind_config.clone().unwrap_or_default();
+        let (certificate, key) =
+            new_self_signed_tls_certificate(identity.as_ref(), bind_config.certificate_address())
+                .expect("Failed to initialize QUIC client certificates");
 
         log_gso_workaround();
 
-        let connection_manager = match config.tpu_connection_path {
+        let connection_manager = match &config.tpu_connection_path {
             TpuConnectionPath::QuicDirectPath => {
-                let tpu_connection_manager =
-                    TpuConnectionManager::new(certificate, key, config.fanout_slots as usize).await;
+                let tpu_connection_manager = TpuConnectionManager::new(
+                    certificate,
+                    key,
+                    config.fanout_slots as usize,
+                    bind_config,
+                )
+                .await;
                 DirectTpu {
                     tpu_connection_manager: Arc::new(tpu_connection_manager),
                 }
             }
             TpuConnectionPath::QuicForwardProxyPath {
-                forward_proxy_address,
+                forward_proxies,
+                balancing,
+                fallback_to_direct,
+                delivery_reports,
             } => {
-                let quic_proxy_connection_manager =
-                    QuicProxyConnectionManager::new(certificate, key, forward_proxy_address).await;
+                let direct_fallback = if *fallback_to_direct {
+                    Some(Arc::new(
+                        TpuConnectionManager::new(
+                            certificate.clone(),
+                            key.clone(),
+                            config.fanout_slots as usize,
+                            bind_config,
+                        )
+                        .await,
+                    ))
+                } else {
+                    None
+                };
+                let (fallback_sender, fallback_receiver) = tokio::sync::mpsc::unbounded_channel();
+                let quic_proxy_connection_manager = Arc::new(
+                    QuicProxyConnectionManager::new(
+                        certificate,
+                        key,
+                        forward_proxies,
+                        *balancing,
+                        direct_fallback.is_some().then_some(fallback_sender),
+                        *delivery_reports,
+                        config.quic_bind_config.as_ref(),
+                    )
+                    .await,
+                );
+                if let Some(direct_fallback) = &direct_fallback {
+                    tokio::spawn(Self::send_directly_when_proxies_fail(
+                        fallback_receiver,
+                        quic_proxy_connection_manager.clone(),
+                        direct_fallback.clone(),
+                        broadcast_sender.clone(),
+                        data_cache.clone(),
+                        config.quic_connection_params,
+                    ));
+                }
 
                 QuicProxy {
-                    quic_proxy_connection_manager: Arc::new(quic_proxy_connection_manager),
+                    quic_proxy_connection_manager,
+                    direct_fallback,
                 }
             }
         };
 
         Ok(Self {
             leader_schedule,
-            broadcast_sender: Arc::new(sender),
+            broadcast_sender,
             connection_manager,
             config,
             data_cache,
+            leader_scores: LeaderScoreTracker::default(),
+            slot_clock: SlotClock::default(),
+            cached_leaders: Arc::new(RwLock::new(vec![])),
         })
     }
 
-    pub fn send_transaction(&self, transaction: &SentTransactionInfo) -> anyhow::Result<()> {
+    // opens the direct connections as soon as the last proxy fails and resends the batches it failed on
+    async fn send_directly_when_proxies_fail(
+        mut failed_batches: UnboundedReceiver<Vec<TransactionToSend>>,
+        quic_proxy_connection_manager: Arc<QuicProxyConnectionManager>,
+        direct_fallback: Arc<TpuConnectionManager>,
+        broadcast_sender: Arc<tokio::sync::broadcast::Sender<TransactionToSend>>,
+        data_cache: DataCache,
+        connection_parameters: QuicConnectionParameters,
+    ) {
+        while let Some(batch) = failed_batches.recv().await {
+            if !quic_proxy_connection_manager.has_healthy_proxy() {
+                direct_fallback
+                    .update_connections(
+                        broadcast_sender.clone(),
+                        quic_proxy_connection_manager.current_connections().await,
+                        data_cache.identity_stakes.get_stakes().await,
+                        data_cache.clone(),
+                        connection_parameters,
+                    )
+                    .await;
+            }
+            // picked up by the direct connections, or by the proxies if one recovered meanwhile
+            for transaction in batch {
+                let _ = broadcast_sender.send(transaction);
+            }
+        }
+    }
+
+    /// rotate the identity used for the quic connections with the leaders
+    pub async fn update_identity(&self, identity: &Keypair) -> anyhow::Result<()> {
+        let DirectTpu {
+            tpu_connection_manager,
+        } = &self.connection_manager
+        else {
+            bail!("identity cannot be changed when sending through the quic forward proxy");
+        };
+        let (certificate, key) = new_self_signed_tls_certificate(
+            identity,
+            self.config
+                .quic_bind_config
+                .clone()
+                .unwrap_or_default()
+                .certificate_address(),
+        )
+        .context("Failed to initialize QUIC client certificates")?;
+
+        let identity_stakes = self
+            .data_cache
+            .identity_stakes
+            .set_identity(identity.pubkey())
+            .await;
+        tpu_connection_manager
+            .update_identity(
+                certificate,
+                key,
+                self.broadcast_sender.clone(),
+                identity_stakes,
+                self.data_cache.clone(),
+                self.config.quic_connection_params,
+            )
+            .await;
+        info!(
+            "Tpu service identity changed to {} ({:?})",
+            identity.pubkey(),
+            identity_stakes.peer_type
+        );
+        Ok(())
+    }
+
+    pub fn send_transaction(&self, transaction: &TransactionToSend) -> anyhow::Result<()> {
+        if let Some(delivery_reports) = self.proxy_delivery_reports() {
+            delivery_reports.forwarded(transaction);
+        }
         self.broadcast_sender.send(transaction.clone())?;
+        let current_slot = self.data_cache.slot_cache.get_current_slot();
+        self.leader_scores.record_sent(
+            transaction,
+            current_slot..=current_slot + self.config.fanout_slots,
+        );
         Ok(())
     }
 
+    /// delivery results of the quic proxy, if it is asked for them
+    pub fn proxy_delivery_reports(&self) -> Option<ProxyDeliveryReports> {
+        match &self.connection_manager {
+            DirectTpu { .. } => None,
+            QuicProxy {
+                quic_proxy_connection_manager,
+                ..
+            } => quic_proxy_connection_manager.delivery_reports(),
+        }
+    }
+
+    pub fn get_leader_scores(&self) -> Vec<LeaderScore> {
+        self.leader_scores.scores()
+    }
+
+    /// estimated current slot and the time until the next leaders take over
+    pub async fn get_slot_timing(&self, nb_leaders: usize) -> anyhow::Result<SlotTiming> {
+        let now = Instant::now();
+        let estimate = self.slot_clock.estimate(now);
+        let leaders = self
+            .leader_schedule
+            .get_slot_leaders(
+                estimate.earliest_slot,
+                estimate.latest_slot + nb_leaders as u64 * NUM_CONSECUTIVE_LEADER_SLOTS,
+            )
+            .await?;
+        let identities = leaders
+            .iter()
+            .map(|x| x.pubkey)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let unreliable_leaders = self.leader_scores.unreliable_leaders(&identities);
+        let mut timing = self.slot_clock.timing(now, &leaders, &unreliable_leaders);
+        timing.upcoming_leaders.truncate(nb_leaders);
+        Ok(timing)
+    }
+
+    // first slot after `slot` which belongs to a different leader
+    /// first slot after `slot` led by another leader, from the leaders cached on the last fanout update
+    pub fn get_next_leader_change(&self, slot: Slot) -> Option<Slot> {
+        let leaders = self.cached_leaders.read().unwrap();
+        let current_leader = leaders.iter().rev().find(|x| x.leader_slot <= slot)?;
+        leaders
+            .iter()
+            .find(|x| x.leader_slot > slot && x.pubkey != current_leader.pubkey)
+            .map(|x| x.leader_slot)
+    }
+
+    // get the leaders with their tpu quic address
+    fn get_tpu_addresses(&self, leaders: &[LeaderData]) -> HashMap<Pubkey, SocketAddr> {
+        let cluster_info = &self.data_cache.cluster_info;
+        leaders
+            .iter()
+            .filter_map(|x| {
+                let contact_info = cluster_info.cluster_nodes.get(&x.pubkey)?;
+                let tpu_forwards_quic = cluster_info
+                    .tpu_forwards_quic
+                    .get(&x.pubkey)
+                    .map(|address| *address);
+                let addresses = tpu_quic_addresses(&contact_info, tpu_forwards_quic);
+                let address = match &self.connection_manager {
+                    // skip the addresses of the leader which keep failing
+                    DirectTpu {
+                        tpu_connection_manager,
+                    } => tpu_connection_manager
+                        .address_health()
+                        .select(x.pubkey, &addresses),
+                    QuicProxy { .. } => addresses.first().copied(),
+                }?;
+                Some((x.pubkey, address))
+            })
+            .collect()
+    }
+
     // update/reconfigure connections on slot change
     async fn update_quic_connections(
         &self,
         current_slot: Slot,
-        estimated_slot: Slot,
+        estimate: SlotEstimate,
     ) -> anyhow::Result<()> {
         let fanout = self.config.fanout_slots;
-        let last_slot = estimated_slot + fanout;
+        let prewarm_slots = self.config.quic_connection_params.prewarm_slots;
+        // the cluster may be as far as the latest estimated slot
+        let last_slot = estimate.latest_slot + fanout;
         let current_slot = current_slot.saturating_sub(4);
 
-        let cluster_nodes = self.data_cache.cluster_info.cluster_nodes.clone();
-
+        // fetch twice the fanout so it can be extended past unreliable leaders
         let next_leaders = self
             .leader_schedule
-            .get_slot_leaders(current_slot, last_slot)
+            .get_slot_leaders(current_slot, last_slot + fanout + prewarm_slots)
             .await?;
-        // get next leader with its tpu port
-        let connections_to_keep: HashMap<_, _> = next_leaders
+        self.leader_scores.record_schedule(&next_leaders);
+        *self.cached_leaders.write().unwrap() = next_leaders.clone();
+
+        let upcoming_leaders = next_leaders
             .iter()
-            .map(|x| {
-                let contact_info = cluster_nodes.get(&x.pubkey);
-                let tpu_port = match contact_info {
-                    Some(info) => info.tpu,
-                    _ => None,
-                };
-                (x.pubkey, tpu_port)
-            })
-            .filter(|x| x.1.is_some())
-            .map(|x| {
-                let mut addr = x.1.unwrap();
-                // add quic port offset
-                addr.set_port(addr.port() + QUIC_PORT_OFFSET);
-                (x.0, addr)
-            })
-            .collect();
+            .filter(|x| x.leader_slot >= estimate.earliest_slot && x.leader_slot <= last_slot)
+            .map(|x| x.pubkey)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let nb_unreliable_leaders = self
+            .leader_scores
+            .unreliable_leaders(&upcoming_leaders)
+            .len();
+        let extra_slots = (nb_unreliable_leaders as u64 * NUM_CONSECUTIVE_LEADER_SLOTS).min(fanout);
+        FANOUT_EXTRA_SLOTS.set(extra_slots as i64);
+        let fanout_last_slot = last_slot + extra_slots;
+        let (next_leaders, leaders_to_prewarm): (Vec<_>, Vec<_>) = next_leaders
+            .into_iter()
+            .filter(|x| x.leader_slot <= fanout_last_slot + prewarm_slots)
+            .partition(|x| x.leader_slot <= fanout_last_slot);
+        let connections_to_keep = self.get_tpu_addresses(&next_leaders);
 
         match &self.connection_manager {
             DirectTpu {
                 tpu_connection_manager,
             } => {
+                let mut connections_to_prewarm = self.get_tpu_addresses(&leaders_to_prewarm);
+                connections_to_prewarm
+                    .retain(|identity, _| !connections_to_keep.contains_key(identity));
                 tpu_connection_manager
                     .update_connections(
                         self.broadcast_sender.clone(),
@@ -158,10 +384,32 @@ impl TpuService {
                         self.config.quic_connection_params,
                     )
                     .await;
+                tpu_connection_manager.prewarm_connections(
+                    connections_to_prewarm,
+                    self.config.quic_connection_params,
+                );
             }
             QuicProxy {
                 quic_proxy_connection_manager,
+                direct_fallback,
             } => {
+                if let Some(direct_fallback) = direct_fallback {
+                    // the direct connections are closed again once a proxy is healthy
+                    let direct_connections = if quic_proxy_connection_manager.has_healthy_proxy() {
+                        HashMap::new()
+                    } else {
+                        connections_to_keep.clone()
+                    };
+                    direct_fallback
+                        .update_connections(
+                            self.broadcast_sender.clone(),
+                            direct_connections,
+                            self.data_cache.identity_stakes.get_stakes().await,
+                            self.data_cache.clone(),
+                            self.config.quic_connection_params,
+                        )
+                        .await;
+                }
                 let transaction_receiver = self.broadcast_sender.subscribe();
                 quic_proxy_connection_manager
                     .update_connection(
@@ -175,21 +423,64 @@ impl TpuService {
         Ok(())
     }
 
+    // drops the state kept per leader for the leaders out of the current and next epoch schedules
+    async fn prune_departed_leaders(&self, pruned_epochs: &mut Option<(u64, Option<u64>)>) {
+        let schedule = self.data_cache.leader_schedule.read().await;
+        let Some(current) = &schedule.current else {
+            return;
+        };
+        let epochs = Some((current.epoch, schedule.next.as_ref().map(|next| next.epoch)));
+        if epochs == *pruned_epochs {
+            return;
+        }
+        *pruned_epochs = epochs;
+        let scheduled = [&schedule.current, &schedule.next]
+            .into_iter()
+            .flatten()
+            .flat_map(|schedule| schedule.schedule_by_node.keys())
+            .filter_map(|identity| identity.parse().ok())
+            .collect::<HashSet<Pubkey>>();
+        drop(schedule);
+        self.leader_scores.retain_scheduled(&scheduled);
+        retain_scheduled_prewarm_metrics(&scheduled);
+    }
+
     pub fn start(&self, slot_notifications: SlotStream) -> AnyhowJoinHandle {
         let this = self.clone();
         tokio::spawn(async move {
             let mut slot_notifications = slot_notifications;
+            let mut current_slot = None;
+            let mut last_estimate = SlotEstimate::default();
+            let mut pruned_epochs = None;
             loop {
-                let notification = slot_notifications
-                    .recv()
-                    .await
-                    .context("Tpu service cannot get slot notification")?;
-                this.update_quic_connections(
-                    notification.processed_slot,
-                    notification.estimated_processed_slot,
-                )
-                .await?;
+                let slot_duration =
+                    Duration::from_millis(this.slot_clock.slot_durations().mean_ms as u64);
+                // without a slot notification for a slot the clock moves the fanout on
+                if let Ok(notification) =
+                    tokio::time::timeout(slot_duration, slot_notifications.recv()).await
+                {
+                    let notification =
+                        notification.context("Tpu service cannot get slot notification")?;
+                    current_slot = Some(notification.processed_slot);
+                    // the clock is fed observed slots only, not the estimates of the slot stream
+                    this.slot_clock
+                        .record_processed_slot(notification.processed_slot, Instant::now());
+                }
+                let Some(current_slot) = current_slot else {
+                    continue;
+                };
+                let estimate = this.slot_clock.estimate(Instant::now());
+                if estimate == last_estimate {
+                    continue;
+                }
+                last_estimate = estimate;
+                this.update_quic_connections(current_slot, estimate).await?;
+                this.prune_departed_leaders(&mut pruned_epochs).await;
             }
         })
     }
+
+    pub fn start_leader_scoring(&self, blocks: BlockStream) -> AnyhowJoinHandle {
+        self.leader_scores.start(blocks)
+    }
 }
@@ -1,11 +1,13 @@
-use crate::tpu_utils::tpu_service::TpuService;
+use crate::{tpu_utils::tpu_service::TpuService, transaction_wal::TransactionWal};
 use anyhow::{bail, Context};
 use log::error;
 use prometheus::{core::GenericGauge, opts, register_int_gauge};
+use serde::{Deserialize, Serialize};
 use solana_lite_rpc_core::{
-    stores::data_cache::DataCache, structures::transaction_sent_info::SentTransactionInfo,
+    stores::data_cache::DataCache, structures::transaction_sent_info::TransactionToSend,
     AnyhowJoinHandle,
 };
+use solana_sdk::clock::{DEFAULT_MS_PER_SLOT, NUM_CONSECUTIVE_LEADER_SLOTS};
 use std::time::Duration;
 use tokio::{
     sync::mpsc::{UnboundedReceiver, UnboundedSender},
@@ -17,18 +19,48 @@ lazy_static::lazy_static! {
         register_int_gauge!(opts!("literpc_messages_in_replay_queue", "Number of transactions waiting for replay")).unwrap();
 }
 
+const SLOT_DURATION: Duration = Duration::from_millis(DEFAULT_MS_PER_SLOT);
+// caps the exponential backoff to retry_offset * 2^6
+const MAX_BACKOFF_EXPONENT: usize = 6;
+
+/// How a transaction is replayed until it is confirmed or its blockhash expires
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReplayStrategy {
+    /// replay after retry_offset, retry_offset*2, retry_offset*3 ...
+    #[default]
+    Linear,
+    /// replay after retry_offset, retry_offset, retry_offset*2, retry_offset*4 ... up to retry_offset*64
+    ExponentialBackoff,
+    /// replay every slot until the blockhash expires, max_replay is ignored
+    EverySlot,
+    /// replay when the window of the next distinct leader starts
+    LeaderAware,
+    /// same schedule as linear but stop as soon as the transaction is processed
+    UntilProcessed,
+    /// same schedule as linear but keep replaying a processed transaction until it is confirmed
+    UntilConfirmed,
+}
+
+// the first replay waits retry_offset, then the delay doubles with every replay
+fn exponential_backoff(retry_offset: Duration, replay_count: usize) -> Duration {
+    let exponent = replay_count.saturating_sub(1).min(MAX_BACKOFF_EXPONENT);
+    retry_offset * (1u32 << exponent)
+}
+
 #[derive(Debug, Clone)]
 pub struct TransactionReplay {
-    pub transaction: SentTransactionInfo,
+    pub transaction: TransactionToSend,
     pub replay_count: usize,
     pub max_replay: usize,
     pub replay_at: Instant,
+    pub strategy: ReplayStrategy,
 }
 
 /// Transaction Replayer
 /// It will replay transaction sent to the cluster if they are not confirmed
-/// They will be replayed max_replay times
-/// The replay time will be linearly increasing by after count * replay after
+/// They will be replayed max_replay times following the ReplayStrategy of the transaction
+/// With the default linear strategy the replay time will be linearly increasing by after count * replay after
 /// So the transasctions will be replayed like retry_after, retry_after*2, retry_after*3 ...
 
 #[derive(Clone)]
@@ -36,6 +68,7 @@ pub struct TransactionReplayer {
     pub tpu_service: TpuService,
     pub data_cache: DataCache,
     pub retry_offset: Duration,
+    pub transaction_wal: Option<TransactionWal>,
 }
 
 impl TransactionReplayer {
@@ -44,6 +77,50 @@ impl TransactionReplayer {
             tpu_service,
             data_cache,
             retry_offset,
+            transaction_wal: None,
+        }
+    }
+
+    /// persist the replay state of the transactions in a write-ahead log
+    pub fn with_transaction_wal(mut self, transaction_wal: TransactionWal) -> Self {
+        self.transaction_wal = Some(transaction_wal);
+        self
+    }
+
+    /// time of the next replay once a transaction has been replayed `replay_count` times
+    pub fn get_replay_at(&self, strategy: ReplayStrategy, replay_count: usize) -> Instant {
+        let now = Instant::now();
+        match strategy {
+            ReplayStrategy::Linear
+            | ReplayStrategy::UntilProcessed
+            | ReplayStrategy::UntilConfirmed => {
+                now + self.retry_offset.mul_f32(replay_count.max(1) as f32)
+            }
+            ReplayStrategy::ExponentialBackoff => {
+                now + exponential_backoff(self.retry_offset, replay_count)
+            }
+            ReplayStrategy::EverySlot => now + SLOT_DURATION,
+            ReplayStrategy::LeaderAware => {
+                now + SLOT_DURATION * self.get_slots_until_next_leader() as u32
+            }
+        }
+    }
+
+    fn get_slots_until_next_leader(&self) -> u64 {
+        let current_slot = self.data_cache.slot_cache.get_estimated_slot();
+        let next_leader_slot = match self.tpu_service.get_next_leader_change(current_slot) {
+            Some(slot) => slot,
+            // fallback on the next leader window boundary
+            None => {
+                (current_slot / NUM_CONSECUTIVE_LEADER_SLOTS + 1) * NUM_CONSECUTIVE_LEADER_SLOTS
+            }
+        };
+        next_leader_slot.saturating_sub(current_slot).max(1)
+    }
+
+    fn record_done(&self, tx_replay: &TransactionReplay) {
+        if let Some(transaction_wal) = &self.transaction_wal {
+            transaction_wal.done(tx_replay.transaction.id());
         }
     }
 
@@ -52,16 +129,22 @@ impl TransactionReplayer {
         sender: UnboundedSender<TransactionReplay>,
         mut reciever: UnboundedReceiver<TransactionReplay>,
     ) -> AnyhowJoinHandle {
-        let tpu_service = self.tpu_service.clone();
-        let data_cache = self.data_cache.clone();
-        let retry_offset = self.retry_offset;
+        let this = self.clone();
 
         tokio::spawn(async move {
             while let Some(mut tx_replay) = reciever.recv().await {
                 MESSAGES_IN_REPLAY_QUEUE.dec();
                 let now = Instant::now();
+                // the quic proxy could not deliver it, no need to wait for the usual replay time
+                if this
+                    .tpu_service
+                    .proxy_delivery_reports()
+                    .is_some_and(|reports| reports.is_undelivered(&tx_replay.transaction))
+                {
+                    tx_replay.replay_at = tx_replay.replay_at.min(now + SLOT_DURATION);
+                }
                 if now < tx_replay.replay_at {
-                    if tx_replay.replay_at > now + retry_offset {
+                    if tx_replay.replay_at > now + this.retry_offset {
                         // requeue the transactions will be replayed after retry_after duration
                         sender.send(tx_replay).context("replay channel closed")?;
                         MESSAGES_IN_REPLAY_QUEUE.inc();
@@ -69,19 +152,38 @@ impl TransactionReplayer {
                     }
                     tokio::time::sleep_until(tx_replay.replay_at).await;
                 }
-                if data_cache.check_if_confirmed_or_expired_blockheight(&tx_replay.transaction) {
+                let done = match tx_replay.strategy {
+                    ReplayStrategy::UntilConfirmed => this
+                        .data_cache
+                        .check_if_confirmed_or_finalized_or_expired(&tx_replay.transaction),
+                    // any status counts as processed
+                    _ => this
+                        .data_cache
+                        .check_if_confirmed_or_expired(&tx_replay.transaction),
+                };
+                if done {
                     // transaction has already expired or confirmed
+                    this.record_done(&tx_replay);
                     continue;
                 }
                 // ignore reset error
-                let _ = tpu_service.send_transaction(&tx_replay.transaction);
+                let _ = this.tpu_service.send_transaction(&tx_replay.transaction);
 
-                if tx_replay.replay_count < tx_replay.max_replay {
+                // replaying every slot is bounded by the blockhash expiry only
+                if tx_replay.strategy == ReplayStrategy::EverySlot
+                    || tx_replay.replay_count < tx_replay.max_replay
+                {
                     tx_replay.replay_count += 1;
                     tx_replay.replay_at =
-                        Instant::now() + retry_offset.mul_f32(tx_replay.replay_count as f32);
+                        this.get_replay_at(tx_replay.strategy, tx_replay.replay_count);
+                    if let Some(transaction_wal) = &this.transaction_wal {
+                        transaction_wal
+                            .replayed(tx_replay.transaction.id(), tx_replay.replay_count);
+                    }
                     sender.send(tx_replay).context("replay channel closed")?;
                     MESSAGES_IN_REPLAY_QUEUE.inc();
+                } else {
+                    this.record_done(&tx_replay);
                 }
             }
             error!("transaction replay channel broken");
@@ -89,3 +191,20 @@ impl TransactionReplayer {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_backoff_sequence() {
+        let retry_offset = Duration::from_millis(100);
+        let delays = (0..10)
+            .map(|replay_count| exponential_backoff(retry_offset, replay_count).as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            vec![100, 100, 200, 400, 800, 1600, 3200, 6400, 6400, 6400]
+        );
+    }
+}
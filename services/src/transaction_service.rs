@@ -1,17 +1,26 @@
 // This class will manage the lifecycle for a transaction
 // It will send, replay if necessary and confirm by listening to blocks
 
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
 use crate::{
     tpu_utils::tpu_service::TpuService,
-    transaction_replayer::{TransactionReplay, TransactionReplayer, MESSAGES_IN_REPLAY_QUEUE},
+    transaction_replayer::{
+        ReplayStrategy, TransactionReplay, TransactionReplayer, MESSAGES_IN_REPLAY_QUEUE,
+    },
+    transaction_wal::PendingTransaction,
     tx_sender::TxSender,
 };
 use anyhow::bail;
 use prometheus::{histogram_opts, register_histogram, Histogram};
 use solana_lite_rpc_core::{
-    solana_utils::SerializableTransaction, structures::transaction_sent_info::SentTransactionInfo,
+    solana_utils::SerializableTransaction,
+    structures::{
+        transaction_priority::TransactionPriority,
+        transaction_sent_info::{
+            SentBundleInfo, SentTransactionInfo, TransactionToSend, MAX_TRANSACTIONS_PER_BUNDLE,
+        },
+    },
     types::SlotStream,
 };
 use solana_lite_rpc_core::{
@@ -19,14 +28,13 @@ use solana_lite_rpc_core::{
     structures::notifications::NotificationSender,
     AnyhowJoinHandle,
 };
-use solana_sdk::{
-    compute_budget::{self, ComputeBudgetInstruction},
-    transaction::VersionedTransaction,
-};
-use tokio::{
-    sync::mpsc::{self, Sender, UnboundedSender},
-    time::Instant,
-};
+use solana_sdk::transaction::VersionedTransaction;
+use tokio::sync::mpsc::{self, Sender, UnboundedSender};
+
+/// the transaction sent by the client cannot be forwarded as it is
+#[derive(thiserror::Error, Debug)]
+#[error("Invalid transaction: {0}")]
+pub struct InvalidTransaction(&'static str);
 
 lazy_static::lazy_static! {
     static ref PRIORITY_FEES_HISTOGRAM: Histogram = register_histogram!(histogram_opts!(
@@ -104,6 +112,7 @@ impl TransactionServiceBuilder {
                 block_information_store,
                 max_retries,
                 replay_offset: self.tx_replayer.retry_offset,
+                tx_replayer: self.tx_replayer,
             },
             jh_services,
         )
@@ -112,11 +121,12 @@ impl TransactionServiceBuilder {
 
 #[derive(Clone)]
 pub struct TransactionService {
-    pub transaction_channel: Sender<SentTransactionInfo>,
+    pub transaction_channel: Sender<TransactionToSend>,
     pub replay_channel: UnboundedSender<TransactionReplay>,
     pub block_information_store: BlockInformationStore,
     pub max_retries: usize,
     pub replay_offset: Duration,
+    pub tx_replayer: TransactionReplayer,
 }
 
 impl TransactionService {
@@ -124,23 +134,117 @@ impl TransactionService {
         &self,
         tx: VersionedTransaction,
         max_retries: Option<u16>,
+        replay_strategy: ReplayStrategy,
     ) -> anyhow::Result<String> {
         let raw_tx = bincode::serialize(&tx)?;
-        self.send_wire_transaction(raw_tx, max_retries).await
+        self.send_wire_transaction(raw_tx, max_retries, replay_strategy)
+            .await
     }
 
     pub async fn send_wire_transaction(
         &self,
         raw_tx: Vec<u8>,
         max_retries: Option<u16>,
+        replay_strategy: ReplayStrategy,
     ) -> anyhow::Result<String> {
+        let transaction_info = self.get_transaction_info(raw_tx)?;
+        let signature = transaction_info.signature;
+        self.enqueue(transaction_info.into(), max_retries, replay_strategy)
+            .await?;
+        Ok(signature.to_string())
+    }
+
+    /// transactions of a bundle are forwarded together in the given order, the bundle is identified by the first signature
+    pub async fn send_wire_bundle(
+        &self,
+        raw_txs: Vec<Vec<u8>>,
+        max_retries: Option<u16>,
+        replay_strategy: ReplayStrategy,
+    ) -> anyhow::Result<String> {
+        if raw_txs.is_empty() || raw_txs.len() > MAX_TRANSACTIONS_PER_BUNDLE {
+            bail!(
+                "Bundle should contain between 1 and {} transactions",
+                MAX_TRANSACTIONS_PER_BUNDLE
+            );
+        }
+
+        let mut signatures = HashSet::new();
+        let mut transactions = Vec::with_capacity(raw_txs.len());
+        for raw_tx in raw_txs {
+            let transaction_info = self.get_transaction_info(raw_tx)?;
+            if !signatures.insert(transaction_info.signature) {
+                bail!(
+                    "Duplicate transaction {} in bundle",
+                    transaction_info.signature
+                );
+            }
+            transactions.push(transaction_info);
+        }
+
+        let bundle = SentBundleInfo::new(transactions);
+        let bundle_id = bundle.bundle_id;
+        self.enqueue(bundle.into(), max_retries, replay_strategy)
+            .await?;
+        Ok(bundle_id.to_string())
+    }
+
+    /// send again the transactions restored from the write-ahead log, expired ones are dropped
+    pub async fn restore_pending_transactions(
+        &self,
+        pending_transactions: Vec<PendingTransaction>,
+    ) -> anyhow::Result<()> {
+        let current_blockheight = self.block_information_store.get_last_blockheight();
+        let mut nb_restored = 0;
+        for pending in pending_transactions {
+            let transaction = pending.transaction;
+            if current_blockheight > transaction.last_valid_block_height() {
+                if let Some(transaction_wal) = &self.tx_replayer.transaction_wal {
+                    transaction_wal.done(transaction.id());
+                }
+                continue;
+            }
+
+            if let Err(e) = self.transaction_channel.send(transaction.clone()).await {
+                bail!(
+                    "Internal error sending transaction on send channel error {}",
+                    e
+                );
+            }
+            let replay_at = self
+                .tx_replayer
+                .get_replay_at(pending.strategy, pending.replay_count);
+            if self
+                .replay_channel
+                .send(TransactionReplay {
+                    transaction,
+                    replay_count: pending.replay_count,
+                    max_replay: pending.max_replay,
+                    replay_at,
+                    strategy: pending.strategy,
+                })
+                .is_ok()
+            {
+                MESSAGES_IN_REPLAY_QUEUE.inc();
+            }
+            nb_restored += 1;
+        }
+        log::info!("Restored {nb_restored} pending transactions");
+        Ok(())
+    }
+
+    fn get_transaction_info(&self, raw_tx: Vec<u8>) -> anyhow::Result<SentTransactionInfo> {
         let tx = match bincode::deserialize::<VersionedTransaction>(&raw_tx) {
             Ok(tx) => tx,
             Err(err) => {
                 bail!(err.to_string());
             }
         };
-        let signature = tx.signatures[0];
+        let Some(signature) = tx.signatures.first().copied() else {
+            return Err(InvalidTransaction("no signature").into());
+        };
+        let Some(fee_payer) = tx.message.static_account_keys().first().copied() else {
+            return Err(InvalidTransaction("no account keys").into());
+        };
 
         let Some(BlockInformation {
             slot,
@@ -157,59 +261,56 @@ impl TransactionService {
             bail!("Blockhash is expired");
         }
 
-        let prioritization_fee = {
-            let mut prioritization_fee = 0;
-            for ix in tx.message.instructions() {
-                if ix
-                    .program_id(tx.message.static_account_keys())
-                    .eq(&compute_budget::id())
-                {
-                    let ix_which = solana_sdk::borsh1::try_from_slice_unchecked::<
-                        ComputeBudgetInstruction,
-                    >(ix.data.as_slice());
-                    if let Ok(ComputeBudgetInstruction::SetComputeUnitPrice(fees)) = ix_which {
-                        prioritization_fee = fees;
-                    }
-                }
-            }
-            prioritization_fee
-        };
+        let priority = TransactionPriority::from_transaction(&tx);
 
-        PRIORITY_FEES_HISTOGRAM.observe(prioritization_fee as f64);
+        PRIORITY_FEES_HISTOGRAM.observe(priority.cu_price as f64);
 
-        let max_replay = max_retries.map_or(self.max_retries, |x| x as usize);
-        let transaction_info = SentTransactionInfo {
+        Ok(SentTransactionInfo {
             signature,
             last_valid_block_height: last_valid_blockheight,
             slot,
             transaction: Arc::new(raw_tx),
-            prioritization_fee,
-        };
-        if let Err(e) = self
-            .transaction_channel
-            .send(transaction_info.clone())
-            .await
-        {
+            prioritization_fee: priority.cu_price,
+            priority_score: priority.score(),
+            fee_payer,
+        })
+    }
+
+    async fn enqueue(
+        &self,
+        transaction: TransactionToSend,
+        max_retries: Option<u16>,
+        replay_strategy: ReplayStrategy,
+    ) -> anyhow::Result<()> {
+        let max_replay = max_retries.map_or(self.max_retries, |x| x as usize);
+        // the transaction is durable before it is sent and its signature returned
+        if let Some(transaction_wal) = &self.tx_replayer.transaction_wal {
+            transaction_wal
+                .accepted(&transaction, max_replay, replay_strategy)
+                .await?;
+        }
+        if let Err(e) = self.transaction_channel.send(transaction.clone()).await {
             bail!(
                 "Internal error sending transaction on send channel error {}",
                 e
             );
         }
-        let replay_at = Instant::now() + self.replay_offset;
+        let replay_at = self.tx_replayer.get_replay_at(replay_strategy, 0);
         // ignore error for replay service
         if self
             .replay_channel
             .send(TransactionReplay {
-                transaction: transaction_info,
+                transaction,
                 replay_count: 0,
                 max_replay,
                 replay_at,
+                strategy: replay_strategy,
             })
             .is_ok()
         {
             MESSAGES_IN_REPLAY_QUEUE.inc();
         }
-        Ok(signature.to_string())
+        Ok(())
     }
 }
 
@@ -0,0 +1,425 @@
+// Write-ahead log of the transactions accepted by lite-rpc
+// It allows to replay the transactions which were not yet confirmed when lite-rpc was restarted
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+};
+
+use anyhow::Context;
+use log::{error, info, warn};
+use prometheus::{core::GenericGauge, opts, register_int_gauge};
+use serde::{Deserialize, Serialize};
+use solana_lite_rpc_core::structures::transaction_sent_info::{
+    SentBundleInfo, SentTransactionInfo, TransactionToSend, MAX_TRANSACTIONS_PER_BUNDLE,
+};
+use solana_sdk::{
+    packet::PACKET_DATA_SIZE, pubkey::Pubkey, signature::Signature, slot_history::Slot,
+};
+use tokio::sync::oneshot;
+
+use crate::transaction_replayer::ReplayStrategy;
+
+lazy_static::lazy_static! {
+    static ref TXS_IN_WAL: GenericGauge<prometheus::core::AtomicI64> =
+        register_int_gauge!(opts!("literpc_txs_in_wal", "Number of pending transactions in the write-ahead log")).unwrap();
+}
+
+// rewrite the log when it contains that many records more than pending transactions
+const COMPACTION_THRESHOLD: usize = 100_000;
+// a record holds at most a full bundle and its metadata, a longer length prefix is corrupted
+const MAX_RECORD_LEN: usize = MAX_TRANSACTIONS_PER_BUNDLE * (PACKET_DATA_SIZE + 1024);
+
+#[derive(Serialize, Deserialize, Clone)]
+struct PersistedTransactionInfo {
+    signature: Signature,
+    slot: Slot,
+    transaction: Vec<u8>,
+    last_valid_block_height: u64,
+    prioritization_fee: u64,
+    priority_score: u64,
+    fee_payer: Pubkey,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct PersistedTransaction {
+    transactions: Vec<PersistedTransactionInfo>,
+    is_bundle: bool,
+    replay_count: usize,
+    max_replay: usize,
+    strategy: ReplayStrategy,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+enum WalRecord {
+    Accepted(PersistedTransaction),
+    Replayed { id: Signature, replay_count: usize },
+    Done { id: Signature },
+}
+
+enum WalMessage {
+    Record(WalRecord),
+    // acknowledged once the record is written and synced to the disk, dropped on error
+    Synced(WalRecord, oneshot::Sender<()>),
+    // acknowledged once the records sent before are written to the file
+    Flush(Sender<()>),
+}
+
+/// Transaction restored from the log with its replay state
+#[derive(Debug, Clone)]
+pub struct PendingTransaction {
+    pub transaction: TransactionToSend,
+    pub replay_count: usize,
+    pub max_replay: usize,
+    pub strategy: ReplayStrategy,
+}
+
+#[derive(Clone)]
+pub struct TransactionWal {
+    sender: Sender<WalMessage>,
+}
+
+impl TransactionWal {
+    /// opens or creates the log at `path`, returns the transactions which were still pending
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<(Self, Vec<PendingTransaction>)> {
+        let path = path.as_ref().to_path_buf();
+        let pending = if path.exists() {
+            read_pending(&path)?
+        } else {
+            HashMap::new()
+        };
+        // start with a compacted log
+        let writer = rewrite(&path, &pending)?;
+        info!(
+            "Transaction write-ahead log {} opened with {} pending transactions",
+            path.display(),
+            pending.len()
+        );
+        TXS_IN_WAL.set(pending.len() as i64);
+
+        let restored = pending
+            .values()
+            .map(|persisted| PendingTransaction {
+                transaction: to_transaction(persisted),
+                replay_count: persisted.replay_count,
+                max_replay: persisted.max_replay,
+                strategy: persisted.strategy,
+            })
+            .collect();
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("transaction-wal".to_string())
+            .spawn(move || run_writer(path, writer, pending, receiver))
+            .context("spawn transaction wal writer")?;
+
+        Ok((Self { sender }, restored))
+    }
+
+    /// returns once the transaction is synced to the disk
+    pub async fn accepted(
+        &self,
+        transaction: &TransactionToSend,
+        max_replay: usize,
+        strategy: ReplayStrategy,
+    ) -> anyhow::Result<()> {
+        let transactions = transaction
+            .transactions()
+            .iter()
+            .map(|tx| PersistedTransactionInfo {
+                signature: tx.signature,
+                slot: tx.slot,
+                transaction: tx.transaction.as_ref().clone(),
+                last_valid_block_height: tx.last_valid_block_height,
+                prioritization_fee: tx.prioritization_fee,
+                priority_score: tx.priority_score,
+                fee_payer: tx.fee_payer,
+            })
+            .collect();
+        let record = WalRecord::Accepted(PersistedTransaction {
+            transactions,
+            is_bundle: transaction.is_bundle(),
+            replay_count: 0,
+            max_replay,
+            strategy,
+        });
+        let (ack_sender, ack_receiver) = oneshot::channel();
+        self.sender
+            .send(WalMessage::Synced(record, ack_sender))
+            .context("transaction wal writer stopped")?;
+        ack_receiver
+            .await
+            .context("transaction could not be written to the wal")
+    }
+
+    pub fn replayed(&self, id: Signature, replay_count: usize) {
+        self.send(WalRecord::Replayed { id, replay_count });
+    }
+
+    pub fn done(&self, id: Signature) {
+        self.send(WalRecord::Done { id });
+    }
+
+    /// blocks until the records sent before are written to the file
+    pub fn flush(&self) {
+        let (ack_sender, ack_receiver) = mpsc::channel();
+        if self.sender.send(WalMessage::Flush(ack_sender)).is_err() || ack_receiver.recv().is_err()
+        {
+            warn!("Transaction write-ahead log writer stopped");
+        }
+    }
+
+    fn send(&self, record: WalRecord) {
+        if self.sender.send(WalMessage::Record(record)).is_err() {
+            warn!("Transaction write-ahead log writer stopped");
+        }
+    }
+}
+
+fn id_of(persisted: &PersistedTransaction) -> Signature {
+    persisted.transactions[0].signature
+}
+
+fn to_transaction(persisted: &PersistedTransaction) -> TransactionToSend {
+    let transactions = persisted
+        .transactions
+        .iter()
+        .map(|tx| SentTransactionInfo {
+            signature: tx.signature,
+            slot: tx.slot,
+            transaction: Arc::new(tx.transaction.clone()),
+            last_valid_block_height: tx.last_valid_block_height,
+            prioritization_fee: tx.prioritization_fee,
+            priority_score: tx.priority_score,
+            fee_payer: tx.fee_payer,
+        })
+        .collect::<Vec<_>>();
+    if persisted.is_bundle {
+        SentBundleInfo::new(transactions).into()
+    } else {
+        transactions.into_iter().next().unwrap().into()
+    }
+}
+
+fn apply(pending: &mut HashMap<Signature, PersistedTransaction>, record: WalRecord) {
+    match record {
+        WalRecord::Accepted(persisted) => {
+            if !persisted.transactions.is_empty() {
+                pending.insert(id_of(&persisted), persisted);
+            }
+        }
+        WalRecord::Replayed { id, replay_count } => {
+            if let Some(persisted) = pending.get_mut(&id) {
+                persisted.replay_count = replay_count;
+            }
+        }
+        WalRecord::Done { id } => {
+            pending.remove(&id);
+        }
+    }
+}
+
+fn read_pending(path: &Path) -> anyhow::Result<HashMap<Signature, PersistedTransaction>> {
+    let mut reader = BufReader::new(File::open(path).context("open transaction wal")?);
+    let mut pending = HashMap::new();
+    loop {
+        let mut len = [0u8; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).context("read transaction wal"),
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_RECORD_LEN {
+            warn!("Ignoring corrupted record of {len} bytes in the transaction wal");
+            break;
+        }
+        let mut buf = vec![0u8; len];
+        if reader.read_exact(&mut buf).is_err() {
+            warn!("Ignoring truncated record at the end of the transaction wal");
+            break;
+        }
+        match bincode::deserialize::<WalRecord>(&buf) {
+            Ok(record) => apply(&mut pending, record),
+            Err(e) => {
+                warn!("Ignoring corrupted record in the transaction wal: {e}");
+                break;
+            }
+        }
+    }
+    Ok(pending)
+}
+
+fn write_record(writer: &mut BufWriter<File>, record: &WalRecord) -> anyhow::Result<()> {
+    let buf = bincode::serialize(record)?;
+    writer.write_all(&(buf.len() as u32).to_le_bytes())?;
+    writer.write_all(&buf)?;
+    Ok(())
+}
+
+// atomically replace the log by one containing only the pending transactions
+fn rewrite(
+    path: &Path,
+    pending: &HashMap<Signature, PersistedTransaction>,
+) -> anyhow::Result<BufWriter<File>> {
+    let tmp_path = PathBuf::from(format!("{}.tmp", path.display()));
+    {
+        let mut writer = BufWriter::new(File::create(&tmp_path).context("create transaction wal")?);
+        for persisted in pending.values() {
+            write_record(&mut writer, &WalRecord::Accepted(persisted.clone()))?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+    }
+    std::fs::rename(&tmp_path, path).context("replace transaction wal")?;
+    let file = OpenOptions::new()
+        .append(true)
+        .open(path)
+        .context("open transaction wal")?;
+    Ok(BufWriter::new(file))
+}
+
+fn run_writer(
+    path: PathBuf,
+    mut writer: BufWriter<File>,
+    mut pending: HashMap<Signature, PersistedTransaction>,
+    receiver: Receiver<WalMessage>,
+) {
+    let mut nb_records = pending.len();
+    while let Ok(message) = receiver.recv() {
+        // batch all the records available before flushing
+        let messages = std::iter::once(message)
+            .chain(receiver.try_iter())
+            .collect::<Vec<_>>();
+        let mut flush_acks = vec![];
+        let mut sync_acks = vec![];
+        for message in messages {
+            let record = match message {
+                WalMessage::Record(record) => record,
+                WalMessage::Synced(record, ack_sender) => {
+                    sync_acks.push(ack_sender);
+                    record
+                }
+                WalMessage::Flush(ack_sender) => {
+                    flush_acks.push(ack_sender);
+                    continue;
+                }
+            };
+            if let Err(e) = write_record(&mut writer, &record) {
+                error!("Error writing to the transaction wal: {e:?}");
+                // the record may be partially written, do not acknowledge it
+                sync_acks.clear();
+            }
+            apply(&mut pending, record);
+            nb_records += 1;
+        }
+        let mut written = match writer.flush() {
+            Ok(()) => true,
+            Err(e) => {
+                error!("Error flushing the transaction wal: {e:?}");
+                false
+            }
+        };
+        // one sync for the whole batch
+        if written && !sync_acks.is_empty() {
+            if let Err(e) = writer.get_ref().sync_data() {
+                error!("Error syncing the transaction wal: {e:?}");
+                written = false;
+            }
+        }
+        if written {
+            for ack_sender in sync_acks {
+                let _ = ack_sender.send(());
+            }
+        }
+        for ack_sender in flush_acks {
+            let _ = ack_sender.send(());
+        }
+        TXS_IN_WAL.set(pending.len() as i64);
+
+        if nb_records > pending.len() + COMPACTION_THRESHOLD {
+            match rewrite(&path, &pending) {
+                Ok(new_writer) => {
+                    writer = new_writer;
+                    nb_records = pending.len();
+                }
+                Err(e) => error!("Error compacting the transaction wal: {e:?}"),
+            }
+        }
+    }
+    warn!("Transaction write-ahead log closed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx_creator(last_valid_block_height: u64) -> SentTransactionInfo {
+        SentTransactionInfo {
+            signature: Signature::new_unique(),
+            slot: 1,
+            transaction: Arc::new(vec![1, 2, 3]),
+            last_valid_block_height,
+            prioritization_fee: 10,
+            priority_score: 10,
+            fee_payer: Pubkey::new_unique(),
+        }
+    }
+
+    #[tokio::test]
+    async fn restores_pending_transactions() {
+        let path = std::env::temp_dir().join(format!("lite-rpc-wal-{}", Signature::new_unique()));
+
+        let single: TransactionToSend = tx_creator(100).into();
+        let bundle: TransactionToSend =
+            SentBundleInfo::new(vec![tx_creator(100), tx_creator(90)]).into();
+        let done: TransactionToSend = tx_creator(100).into();
+        {
+            let (wal, pending) = TransactionWal::open(&path).unwrap();
+            assert!(pending.is_empty());
+            wal.accepted(&single, 5, ReplayStrategy::Linear)
+                .await
+                .unwrap();
+            wal.accepted(&bundle, 3, ReplayStrategy::EverySlot)
+                .await
+                .unwrap();
+            wal.accepted(&done, 5, ReplayStrategy::Linear)
+                .await
+                .unwrap();
+            wal.replayed(single.id(), 2);
+            wal.done(done.id());
+            wal.flush();
+        }
+
+        let (_wal, mut pending) = TransactionWal::open(&path).unwrap();
+        pending.sort_by_key(|p| p.transaction.is_bundle());
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].transaction, single);
+        assert_eq!(pending[0].replay_count, 2);
+        assert_eq!(pending[1].transaction, bundle);
+        assert_eq!(pending[1].max_replay, 3);
+        assert_eq!(pending[1].strategy, ReplayStrategy::EverySlot);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn stops_at_oversized_record() {
+        let path = std::env::temp_dir().join(format!("lite-rpc-wal-{}", Signature::new_unique()));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        file.write_all(&[0u8; 16]).unwrap();
+        drop(file);
+
+        let (_wal, pending) = TransactionWal::open(&path).unwrap();
+        assert!(pending.is_empty());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -2,7 +2,6 @@ use std::time::Duration;
 
 use anyhow::bail;
 use chrono::Utc;
-use itertools::Itertools;
 use log::{trace, warn};
 
 use prometheus::{core::GenericGauge, opts, register_int_counter, register_int_gauge, IntCounter};
@@ -10,10 +9,10 @@ use tokio::sync::mpsc::Receiver;
 
 use crate::tpu_utils::tpu_service::TpuService;
 use solana_lite_rpc_core::{
-    stores::{data_cache::DataCache, tx_store::TxProps},
+    stores::{bundle_store::BundleProps, data_cache::DataCache, tx_store::TxProps},
     structures::{
         notifications::{NotificationMsg, NotificationSender, TransactionNotification},
-        transaction_sent_info::SentTransactionInfo,
+        transaction_sent_info::TransactionToSend,
     },
     AnyhowJoinHandle,
 };
@@ -29,6 +28,8 @@ lazy_static::lazy_static! {
 }
 
 const INTERVAL_PER_BATCH_IN_MS: Duration = Duration::from_millis(400);
+// notifications wait that long for the delivery report of the quic proxy
+const DELIVERY_REPORT_WAIT_MS: i64 = 10_000;
 
 /// Retry transactions to a maximum of `u16` times, keep a track of confirmed transactions
 #[derive(Clone)]
@@ -47,16 +48,27 @@ impl TxSender {
     }
 
     /// retry enqued_tx(s)
-    async fn forward_txs(&self, transaction_info: &SentTransactionInfo) {
-        trace!("sending transaction {}", transaction_info.signature);
-        self.data_cache.txs.insert(
-            transaction_info.signature,
-            TxProps {
-                status: None,
-                last_valid_blockheight: transaction_info.last_valid_block_height,
-                sent_by_lite_rpc: true,
-            },
-        );
+    async fn forward_txs(&self, transaction_info: &TransactionToSend) {
+        trace!("sending transaction {}", transaction_info.id());
+        for tx in transaction_info.transactions() {
+            self.data_cache.txs.insert(
+                tx.signature,
+                TxProps {
+                    status: None,
+                    last_valid_blockheight: tx.last_valid_block_height,
+                    sent_by_lite_rpc: true,
+                },
+            );
+        }
+        if let TransactionToSend::Bundle(bundle) = transaction_info {
+            self.data_cache.bundles.insert(
+                bundle.bundle_id,
+                BundleProps {
+                    signatures: bundle.transactions.iter().map(|tx| tx.signature).collect(),
+                    last_valid_blockheight: transaction_info.last_valid_block_height(),
+                },
+            );
+        }
 
         match self.tpu_service.send_transaction(transaction_info) {
             Ok(_) => {
@@ -74,24 +86,45 @@ impl TxSender {
     /// retry and confirm transactions every 2ms (avg time to confirm tx)
     pub fn execute(
         self,
-        mut recv: Receiver<SentTransactionInfo>,
+        mut recv: Receiver<TransactionToSend>,
         notifier: Option<NotificationSender>,
     ) -> AnyhowJoinHandle {
         tokio::spawn(async move {
             let mut notifications = vec![];
             let mut interval = tokio::time::interval(INTERVAL_PER_BATCH_IN_MS);
-            let notify_transaction_messages = |notifications: &mut Vec<TransactionNotification>| {
-                if notifications.is_empty() {
-                    // no notifications to send
-                    return;
-                }
-                if let Some(notifier) = &notifier {
-                    // send notification for sent transactions
-                    let _ = notifier.send(NotificationMsg::TxNotificationMsg(
-                        notifications.drain(..).collect_vec(),
-                    ));
-                }
-            };
+            let delivery_reports = self.tpu_service.proxy_delivery_reports();
+            let notify_transaction_messages =
+                |notifications: &mut Vec<TransactionNotification>, flush: bool| {
+                    if notifications.is_empty() {
+                        // no notifications to send
+                        return;
+                    }
+                    if let Some(delivery_reports) = &delivery_reports {
+                        for notification in notifications.iter_mut() {
+                            if let Some(status) = delivery_reports.get(&notification.signature) {
+                                notification.quic_response = status.quic_response_code();
+                            }
+                        }
+                    }
+                    // keep the notifications still waiting for their delivery report
+                    let deadline =
+                        Utc::now() - chrono::Duration::milliseconds(DELIVERY_REPORT_WAIT_MS);
+                    let (ready, pending): (Vec<_>, Vec<_>) =
+                        notifications.drain(..).partition(|notification| {
+                            flush
+                                || delivery_reports.is_none()
+                                || notification.quic_response != 0
+                                || notification.forwarded_local_time < deadline
+                        });
+                    *notifications = pending;
+                    if ready.is_empty() {
+                        return;
+                    }
+                    if let Some(notifier) = &notifier {
+                        // send notification for sent transactions
+                        let _ = notifier.send(NotificationMsg::TxNotificationMsg(ready));
+                    }
+                };
 
             loop {
                 tokio::select! {
@@ -102,27 +135,29 @@ impl TxSender {
                             if notifier.is_some() {
                                 let forwarded_slot = self.data_cache.slot_cache.get_current_slot();
                                 let forwarded_local_time = Utc::now();
-                                let tx_notification = TransactionNotification {
-                                    signature: transaction_info.signature,
-                                    recent_slot: transaction_info.slot,
-                                    forwarded_slot,
-                                    forwarded_local_time,
-                                    processed_slot: None,
-                                    cu_consumed: None,
-                                    cu_requested: None,
-                                    quic_response: 0,
-                                };
-                                notifications.push(tx_notification);
+                                for tx in transaction_info.transactions() {
+                                    let tx_notification = TransactionNotification {
+                                        signature: tx.signature,
+                                        recent_slot: tx.slot,
+                                        forwarded_slot,
+                                        forwarded_local_time,
+                                        processed_slot: None,
+                                        cu_consumed: None,
+                                        cu_requested: None,
+                                        quic_response: 0,
+                                    };
+                                    notifications.push(tx_notification);
+                                }
                             }
                         } else {
-                            notify_transaction_messages(&mut notifications);
+                            notify_transaction_messages(&mut notifications, true);
                             log::warn!("TxSender reciever broken");
                             break;
                         }
 
                     },
                     _ = interval.tick() => {
-                        notify_transaction_messages(&mut notifications);
+                        notify_transaction_messages(&mut notifications, false);
                     }
                 }
             }
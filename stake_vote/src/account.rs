@@ -5,6 +5,7 @@ use solana_sdk::stake::state::Delegation;
 use solana_sdk::stake::state::StakeState;
 use solana_sdk::stake_history::StakeHistory;
 use solana_sdk::vote::state::VoteState;
+#[cfg(feature = "geyser")]
 use yellowstone_grpc_proto::prelude::SubscribeUpdateAccount;
 
 #[derive(Debug)]
@@ -23,6 +24,7 @@ pub struct AccountPretty {
 }
 
 impl AccountPretty {
+    #[cfg(feature = "geyser")]
     pub fn new_from_geyser(
         geyser_account: SubscribeUpdateAccount,
         current_slot: u64,
@@ -7,6 +7,7 @@ use crate::vote::EpochVoteStakes;
 use crate::vote::EpochVoteStakesCache;
 use crate::vote::VoteMap;
 use crate::vote::VoteStore;
+use crate::Slot;
 use anyhow::bail;
 use futures::future::join_all;
 use futures_util::stream::FuturesUnordered;
@@ -54,7 +55,7 @@ pub fn bootstrap_leaderschedule_from_files(
     current_epoch_of_loading: u64,
     slots_in_epoch: u64,
 ) -> Option<(CalculatedSchedule, RpcVoteAccountStatus)> {
-    bootstrap_current_leader_schedule(slots_in_epoch, current_epoch_of_loading)
+    bootstrap_current_leader_schedule(current_epoch_of_loading, slots_in_epoch)
         .map(|(leader_schedule, current_epoch_stakes, _)| {
             let vote_acccounts = crate::vote::get_rpc_vote_account_info_from_current_epoch_stakes(
                 &current_epoch_stakes,
@@ -108,6 +109,7 @@ pub fn bootstrap_leaderschedule_from_rpc(
             })?;
 
     Ok(CalculatedSchedule {
+        previous: None,
         current: Some(LeaderScheduleData {
             schedule_by_node: current_schedule_by_node.clone(),
             schedule_by_slot: current_schedule_by_slot.clone(),
@@ -474,6 +476,7 @@ pub fn bootstrap_current_leader_schedule(
 
     Ok((
         CalculatedSchedule {
+            previous: None,
             current: Some(LeaderScheduleData {
                 schedule_by_node: LeaderScheduleGeneratedData::get_schedule_by_nodes(
                     &current_schedule,
@@ -260,7 +260,7 @@ fn calculate_epoch_stakes(
                 let delegation = stake_account.stake;
                 let entry = delegated_stakes.entry(delegation.voter_pubkey).or_default();
                 *entry +=
-                    delegation.stake(new_epoch, Some(stake_history), new_rate_activation_epoch);
+                    delegation.stake(new_epoch, stake_history, new_rate_activation_epoch);
                 delegated_stakes
             });
 
@@ -0,0 +1,439 @@
+// Keeps the leader schedules of the previous, current and next epoch
+// the next epoch can be computed from the vote account stakes, the stakes activated in epoch N give the leader schedule of epoch N+1
+// limitation: the stakes are taken from getVoteAccounts, the stake store of the geyser feature
+// (start_stakes_and_votes_loop) is not used, the rpc schedule stays the reference
+
+use crate::bootstrap::{CURRENT_EPOCH_VOTE_STAKES_FILE, NEXT_EPOCH_VOTE_STAKES_FILE};
+use crate::leader_schedule::LeaderScheduleGeneratedData;
+use crate::vote::StoredVote;
+use anyhow::bail;
+use log::{debug, info, warn};
+use prometheus::{opts, register_int_gauge, IntGauge};
+use solana_client::rpc_response::RpcVoteAccountStatus;
+use solana_lite_rpc_core::stores::data_cache::DataCache;
+use solana_lite_rpc_core::structures::leaderschedule::LeaderScheduleData;
+use solana_lite_rpc_core::types::{SlotStream, VoteAccountStream};
+use solana_lite_rpc_core::AnyhowJoinHandle;
+use solana_program::vote::state::{VoteInit, VoteState};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::config::RpcLeaderScheduleConfig;
+use solana_sdk::clock::Clock;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+
+lazy_static::lazy_static! {
+    static ref LOCAL_LEADER_SCHEDULE_EPOCH: IntGauge =
+        register_int_gauge!(opts!("literpc_local_leader_schedule_epoch", "Last epoch the leader schedule was computed locally for")).unwrap();
+    static ref LOCAL_LEADER_SCHEDULE_RPC_MISMATCHES: IntGauge =
+        register_int_gauge!(opts!("literpc_local_leader_schedule_rpc_mismatches", "Slots where the locally computed leader schedule differs from the rpc")).unwrap();
+}
+
+const RPC_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+type VoteStakes = HashMap<Pubkey, (u64, Arc<StoredVote>)>;
+
+/// keeps the previous, current and next epoch of the data cache leader schedule from the rpc
+pub fn start_rpc_leader_schedule_task(
+    data_cache: DataCache,
+    rpc_client: Arc<RpcClient>,
+    mut slots: SlotStream,
+) -> AnyhowJoinHandle {
+    tokio::spawn(async move {
+        let mut schedule = LocalLeaderSchedule::new(data_cache, rpc_client, false);
+        loop {
+            match slots.recv().await {
+                Ok(slot_notification) => {
+                    schedule
+                        .update_epoch(slot_notification.processed_slot)
+                        .await
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => bail!("Slot stream has been closed - abort"),
+            }
+            schedule.update_schedule().await;
+        }
+    })
+}
+
+/// keeps the previous, current and next epoch of the data cache leader schedule
+/// the next epoch schedule is computed from the stakes of the vote account stream
+pub fn start_local_leader_schedule_task(
+    data_cache: DataCache,
+    rpc_client: Arc<RpcClient>,
+    mut vote_accounts: VoteAccountStream,
+    mut slots: SlotStream,
+) -> AnyhowJoinHandle {
+    tokio::spawn(async move {
+        let mut schedule = LocalLeaderSchedule::new(data_cache, rpc_client, true);
+        loop {
+            tokio::select! {
+                vote_account_status = vote_accounts.recv() => match vote_account_status {
+                    Ok(vote_account_status) => schedule.update_vote_stakes(vote_account_status).await,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => bail!("Vote account stream has been closed - abort"),
+                },
+                slot_notification = slots.recv() => match slot_notification {
+                    Ok(slot_notification) => schedule.update_epoch(slot_notification.processed_slot).await,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => bail!("Slot stream has been closed - abort"),
+                },
+            }
+            schedule.update_schedule().await;
+        }
+    })
+}
+
+struct LocalLeaderSchedule {
+    data_cache: DataCache,
+    rpc_client: Arc<RpcClient>,
+    compute_next_epoch: bool,
+    current_epoch: Option<u64>,
+    // stakes with the epoch they were activated in
+    vote_stakes: Option<(u64, VoteStakes)>,
+    last_rpc_attempt: Option<Instant>,
+}
+
+impl LocalLeaderSchedule {
+    fn new(data_cache: DataCache, rpc_client: Arc<RpcClient>, compute_next_epoch: bool) -> Self {
+        Self {
+            data_cache,
+            rpc_client,
+            compute_next_epoch,
+            current_epoch: None,
+            vote_stakes: None,
+            last_rpc_attempt: None,
+        }
+    }
+
+    async fn update_epoch(&mut self, processed_slot: u64) {
+        let epoch = self.data_cache.epoch_data.get_epoch_at_slot(processed_slot);
+        if self.current_epoch == Some(epoch.epoch) {
+            return;
+        }
+        if self.current_epoch.is_none() {
+            if self.compute_next_epoch {
+                self.bootstrap_from_files(epoch.epoch, epoch.slots_in_epoch)
+                    .await;
+            }
+            self.bootstrap_previous_epoch(epoch.epoch).await;
+        }
+        self.current_epoch = Some(epoch.epoch);
+
+        if self
+            .data_cache
+            .leader_schedule
+            .write()
+            .await
+            .rotate_to_epoch(epoch.epoch)
+        {
+            info!("Leader schedule of epoch {} is now current", epoch.epoch);
+        }
+    }
+
+    // later previous epochs come from the rotation
+    async fn bootstrap_previous_epoch(&self, epoch: u64) {
+        let Some(previous_epoch) = epoch.checked_sub(1) else {
+            return;
+        };
+        match self.get_rpc_schedule(previous_epoch).await {
+            Ok(previous) => self.data_cache.leader_schedule.write().await.previous = Some(previous),
+            Err(err) => debug!("No rpc leader schedule for previous epoch {previous_epoch}: {err}"),
+        }
+    }
+
+    async fn bootstrap_from_files(&self, epoch: u64, slots_in_epoch: u64) {
+        match crate::bootstrap::bootstrap_leaderschedule_from_files(epoch, slots_in_epoch) {
+            Some((leader_schedule, vote_stakes)) => {
+                info!("Leader schedule of epoch {} bootstrapped from files", epoch);
+                self.data_cache
+                    .identity_stakes
+                    .update_stakes_for_identity(vote_stakes)
+                    .await;
+                *self.data_cache.leader_schedule.write().await = leader_schedule;
+            }
+            None => info!("No leader schedule files for epoch {}", epoch),
+        }
+    }
+
+    async fn update_vote_stakes(&mut self, vote_account_status: RpcVoteAccountStatus) {
+        // the vote accounts are polled with finalized commitment
+        let epoch = self
+            .data_cache
+            .get_current_epoch(CommitmentConfig::finalized())
+            .await
+            .epoch;
+        debug!("Vote account stakes received for epoch {}", epoch);
+        self.vote_stakes = Some((epoch, vote_stakes_from_status(&vote_account_status)));
+    }
+
+    async fn update_schedule(&mut self) {
+        let Some(epoch) = self.current_epoch else {
+            return;
+        };
+        let (has_current, has_next) = {
+            let leader_schedule = self.data_cache.leader_schedule.read().await;
+            (
+                leader_schedule
+                    .current
+                    .as_ref()
+                    .is_some_and(|current| current.epoch == epoch),
+                leader_schedule
+                    .next
+                    .as_ref()
+                    .is_some_and(|next| next.epoch == epoch + 1),
+            )
+        };
+        if has_current && has_next {
+            return;
+        }
+        let has_vote_stakes = self
+            .vote_stakes
+            .as_ref()
+            .is_some_and(|(stakes_epoch, _)| *stakes_epoch == epoch);
+        let next_from_stakes = self.compute_next_epoch && has_vote_stakes;
+        let needs_rpc = !has_current || !(has_next || next_from_stakes);
+        if needs_rpc && !self.rpc_attempt_allowed() {
+            return;
+        }
+
+        if !has_current {
+            match self.get_rpc_schedule(epoch).await {
+                Ok(current) => {
+                    self.data_cache.leader_schedule.write().await.current = Some(current)
+                }
+                Err(err) => warn!(
+                    "Leader schedule of epoch {} unavailable from rpc: {err}",
+                    epoch
+                ),
+            }
+        }
+
+        if !has_next && !self.compute_next_epoch {
+            match self.get_rpc_schedule(epoch + 1).await {
+                Ok(next) => self.data_cache.leader_schedule.write().await.next = Some(next),
+                Err(err) => warn!(
+                    "Leader schedule of epoch {} unavailable from rpc: {err}",
+                    epoch + 1
+                ),
+            }
+        } else if !has_next {
+            let vote_stakes = match self.vote_stakes.take() {
+                Some((stakes_epoch, vote_stakes)) if stakes_epoch == epoch => vote_stakes,
+                _ => match self.get_rpc_vote_stakes(epoch).await {
+                    Ok(vote_stakes) => vote_stakes,
+                    Err(err) => {
+                        warn!("Vote account stakes of epoch {} unavailable: {err}", epoch);
+                        return;
+                    }
+                },
+            };
+            self.compute_next_schedule(epoch, vote_stakes).await;
+        }
+    }
+
+    async fn compute_next_schedule(&self, epoch: u64, vote_stakes: VoteStakes) {
+        let next_epoch = epoch + 1;
+        let slots_in_epoch = self.data_cache.epoch_data.get_slots_in_epoch(next_epoch);
+        let computed = tokio::task::spawn_blocking(move || {
+            let schedule = crate::leader_schedule::calculate_leader_schedule(
+                &vote_stakes,
+                next_epoch,
+                slots_in_epoch,
+            );
+            if let Err(err) = save_vote_stakes(&vote_stakes, epoch, next_epoch) {
+                warn!(
+                    "Failed to save the vote stakes of epoch {}: {err}",
+                    next_epoch
+                );
+            }
+            LeaderScheduleData {
+                schedule_by_node: LeaderScheduleGeneratedData::get_schedule_by_nodes(&schedule),
+                schedule_by_slot: schedule.get_slot_leaders().to_vec(),
+                epoch: next_epoch,
+            }
+        })
+        .await;
+        let mut next = match computed {
+            Ok(next) => next,
+            Err(err) => {
+                warn!(
+                    "Leader schedule computation of epoch {} failed: {err}",
+                    next_epoch
+                );
+                return;
+            }
+        };
+        info!("Leader schedule of epoch {} computed locally", next_epoch);
+        LOCAL_LEADER_SCHEDULE_EPOCH.set(next_epoch as i64);
+
+        // the rpc is the reference when it is available
+        match self.get_rpc_schedule(next_epoch).await {
+            Ok(rpc_schedule) => {
+                let mismatches =
+                    count_mismatches(&next.schedule_by_slot, &rpc_schedule.schedule_by_slot);
+                LOCAL_LEADER_SCHEDULE_RPC_MISMATCHES.set(mismatches as i64);
+                if mismatches > 0 {
+                    warn!(
+                        "Local leader schedule of epoch {} differs from rpc in {} slots, using rpc",
+                        next_epoch, mismatches
+                    );
+                    next = rpc_schedule;
+                }
+            }
+            Err(err) => debug!("No rpc leader schedule to cross check epoch {next_epoch}: {err}"),
+        }
+        self.data_cache.leader_schedule.write().await.next = Some(next);
+    }
+
+    fn rpc_attempt_allowed(&mut self) -> bool {
+        if self
+            .last_rpc_attempt
+            .is_some_and(|last| last.elapsed() < RPC_RETRY_INTERVAL)
+        {
+            return false;
+        }
+        self.last_rpc_attempt = Some(Instant::now());
+        true
+    }
+
+    async fn get_rpc_schedule(&self, epoch: u64) -> anyhow::Result<LeaderScheduleData> {
+        let first_slot = self.data_cache.epoch_data.get_first_slot_in_epoch(epoch);
+        let Some(schedule_by_node) = self
+            .rpc_client
+            .get_leader_schedule_with_config(
+                Some(first_slot),
+                RpcLeaderScheduleConfig {
+                    identity: None,
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await?
+        else {
+            bail!("rpc returned no leader schedule for epoch {epoch}");
+        };
+        let schedule_by_slot =
+            crate::leader_schedule::calculate_slot_leaders_from_schedule(&schedule_by_node)
+                .map_err(anyhow::Error::msg)?;
+        Ok(LeaderScheduleData {
+            schedule_by_node,
+            schedule_by_slot,
+            epoch,
+        })
+    }
+
+    async fn get_rpc_vote_stakes(&self, epoch: u64) -> anyhow::Result<VoteStakes> {
+        // right after the epoch boundary the finalized bank is still in the previous epoch
+        let finalized_epoch = self
+            .data_cache
+            .get_current_epoch(CommitmentConfig::finalized())
+            .await
+            .epoch;
+        if finalized_epoch != epoch {
+            bail!("finalized epoch is still {finalized_epoch}");
+        }
+        let vote_account_status = self
+            .rpc_client
+            .get_vote_accounts_with_commitment(CommitmentConfig::finalized())
+            .await?;
+        Ok(vote_stakes_from_status(&vote_account_status))
+    }
+}
+
+// synthetic vote states from getVoteAccounts, only the node pubkey is used by the schedule computation
+fn vote_stakes_from_status(vote_account_status: &RpcVoteAccountStatus) -> VoteStakes {
+    vote_account_status
+        .current
+        .iter()
+        .chain(vote_account_status.delinquent.iter())
+        .filter_map(|vote_account| {
+            let vote_pubkey = Pubkey::from_str(&vote_account.vote_pubkey).ok()?;
+            let node_pubkey = Pubkey::from_str(&vote_account.node_pubkey).ok()?;
+            let stored_vote = StoredVote {
+                pubkey: vote_pubkey,
+                vote_data: VoteState::new(
+                    &VoteInit {
+                        node_pubkey,
+                        commission: vote_account.commission,
+                        ..VoteInit::default()
+                    },
+                    &Clock::default(),
+                ),
+                ..StoredVote::default()
+            };
+            Some((
+                vote_pubkey,
+                (vote_account.activated_stake, Arc::new(stored_vote)),
+            ))
+        })
+        .collect()
+}
+
+// the previous next epoch file becomes the current one
+fn save_vote_stakes(vote_stakes: &VoteStakes, epoch: u64, next_epoch: u64) -> anyhow::Result<()> {
+    if let Ok((saved_epoch, _)) =
+        crate::utils::read_schedule_vote_stakes(NEXT_EPOCH_VOTE_STAKES_FILE)
+    {
+        if saved_epoch == epoch {
+            std::fs::rename(NEXT_EPOCH_VOTE_STAKES_FILE, CURRENT_EPOCH_VOTE_STAKES_FILE)?;
+        }
+    }
+    crate::utils::save_schedule_vote_stakes(NEXT_EPOCH_VOTE_STAKES_FILE, vote_stakes, next_epoch)
+}
+
+fn count_mismatches(local: &[Pubkey], rpc: &[Pubkey]) -> usize {
+    let differing = local.iter().zip(rpc).filter(|(l, r)| l != r).count();
+    differing + local.len().abs_diff(rpc.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::rpc_response::RpcVoteAccountInfo;
+
+    fn vote_account(node_pubkey: &Pubkey, activated_stake: u64) -> RpcVoteAccountInfo {
+        RpcVoteAccountInfo {
+            vote_pubkey: Pubkey::new_unique().to_string(),
+            node_pubkey: node_pubkey.to_string(),
+            activated_stake,
+            commission: 0,
+            epoch_vote_account: true,
+            epoch_credits: vec![],
+            last_vote: 0,
+            root_slot: 0,
+        }
+    }
+
+    #[test]
+    fn schedule_from_vote_account_stakes() {
+        let (big, small) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let vote_account_status = RpcVoteAccountStatus {
+            current: vec![vote_account(&big, 900), vote_account(&big, 100)],
+            delinquent: vec![
+                vote_account(&small, 1),
+                vote_account(&Pubkey::new_unique(), 0),
+            ],
+        };
+        let vote_stakes = vote_stakes_from_status(&vote_account_status);
+        assert_eq!(vote_stakes.len(), 4);
+
+        let schedule = crate::leader_schedule::calculate_leader_schedule(&vote_stakes, 7, 4000);
+        let slot_leaders = schedule.get_slot_leaders();
+        assert_eq!(slot_leaders.len(), 4000);
+        // stakes of the vote accounts add up per node, unstaked nodes never lead
+        let big_slots = slot_leaders.iter().filter(|leader| **leader == big).count();
+        assert!(big_slots > 3900);
+        assert!(slot_leaders
+            .iter()
+            .all(|leader| *leader == big || *leader == small));
+        assert_eq!(count_mismatches(slot_leaders, slot_leaders), 0);
+        let mut rpc_leaders = slot_leaders.to_vec();
+        rpc_leaders[0] = Pubkey::new_unique();
+        rpc_leaders.pop();
+        assert_eq!(count_mismatches(slot_leaders, &rpc_leaders), 2);
+    }
+}
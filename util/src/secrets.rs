@@ -1,5 +1,8 @@
 #![allow(dead_code)]
 
+use std::borrow::Cow;
+use url::Url;
+
 pub fn obfuscate_rpcurl(rpc_addr: &str) -> String {
     if rpc_addr.contains("rpcpool.com") {
         return rpc_addr.replacen(char::is_numeric, "X", 99);
@@ -18,3 +21,17 @@ pub fn obfuscate_token(token: &Option<String>) -> String {
         }
     }
 }
+
+/// obfuscate urls with api token like http://mango.rpcpool.com/a991fba00fagbad
+pub fn url_obfuscate_api_token(url: &str) -> Cow<'_, str> {
+    if let Ok(mut parsed) = Url::parse(url) {
+        if parsed.path() == "/" {
+            Cow::Borrowed(url)
+        } else {
+            parsed.set_path("omitted-secret");
+            Cow::Owned(parsed.to_string())
+        }
+    } else {
+        Cow::Borrowed(url)
+    }
+}